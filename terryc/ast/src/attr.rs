@@ -0,0 +1,171 @@
+//! Attributes, and the registry of the ones the compiler knows about.
+
+use terryc_base::ast::*;
+use terryc_base::errors::{make_diag, msg, Code, ErrorReported};
+use terryc_base::lex::TokenKind as T;
+use terryc_base::sym::{self, Symbol};
+
+use crate::Parser;
+
+/// What an attribute is written in front of.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Target {
+    Fn,
+    Struct,
+    Enum,
+    Mod,
+    Stmt,
+}
+
+impl Target {
+    pub(crate) fn of_item(kind: &ItemKind) -> Target {
+        match kind {
+            ItemKind::Fn(_) => Target::Fn,
+            ItemKind::Struct(_) => Target::Struct,
+            ItemKind::Enum(_) => Target::Enum,
+            ItemKind::Mod { .. } => Target::Mod,
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Target::Fn => "a function",
+            Target::Struct => "a struct",
+            Target::Enum => "an enum",
+            Target::Mod => "a module",
+            Target::Stmt => "a statement",
+        }
+    }
+}
+
+/// The arguments an attribute takes.
+enum Args {
+    /// `#[name]`
+    None,
+    /// `#[name]`, or `#[name(word)]` with one of the words.
+    OptionalWord(&'static [Symbol]),
+    /// `#[name(key)]` or `#[name(key = "value")]`
+    KeyValue,
+}
+
+struct BuiltinAttr {
+    name: Symbol,
+    targets: &'static [Target],
+    args: Args,
+    /// How the attribute is written, for the error about malformed ones.
+    template: &'static str,
+}
+
+/// Every attribute the compiler knows about. Any other attribute is an
+/// error.
+const BUILTIN_ATTRS: &[BuiltinAttr] = &[
+    BuiltinAttr {
+        name: sym::cfg,
+        targets: &[Target::Fn, Target::Struct, Target::Enum, Target::Mod, Target::Stmt],
+        args: Args::KeyValue,
+        template: "`#[cfg(name)]` or `#[cfg(name = \"value\")]`",
+    },
+    BuiltinAttr {
+        name: sym::inline,
+        targets: &[Target::Fn],
+        args: Args::OptionalWord(&[sym::always, sym::never]),
+        template: "`#[inline]`, `#[inline(always)]` or `#[inline(never)]`",
+    },
+    BuiltinAttr {
+        name: sym::test,
+        targets: &[Target::Fn],
+        args: Args::None,
+        template: "`#[test]`",
+    },
+    BuiltinAttr {
+        name: sym::bench,
+        targets: &[Target::Fn],
+        args: Args::None,
+        template: "`#[bench]`",
+    },
+];
+
+impl BuiltinAttr {
+    fn accepts(&self, args: &[AttrArg]) -> bool {
+        match (&self.args, args) {
+            (Args::None, []) | (Args::OptionalWord(_), []) => true,
+            (Args::OptionalWord(words), [arg]) => {
+                arg.value.is_none() && words.contains(&arg.name.symbol)
+            }
+            (Args::KeyValue, [_]) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Parser<'_> {
+    /// Parses the attributes in front of an item or a statement. They are
+    /// checked by [`Parser::check_attrs`] once it is known what they are
+    /// in front of.
+    pub(crate) fn parse_attrs(&mut self) -> Result<Vec<Attr>, ErrorReported> {
+        let mut attrs = vec![];
+        while self.eat(T::Pound) {
+            let lo = self.prev_token.span;
+            self.expect(T::LeftBracket)?;
+            let name = self.expect_ident()?;
+            let args = if self.eat(T::LeftParen) {
+                self.parse_comma_list(T::RightParen, Parser::parse_attr_arg)?
+            } else {
+                vec![]
+            };
+            self.expect(T::RightBracket)?;
+            attrs.push(Attr { name, args, span: lo.to(self.prev_token.span) });
+        }
+        Ok(attrs)
+    }
+
+    fn parse_attr_arg(&mut self) -> Result<AttrArg, ErrorReported> {
+        let name = self.expect_ident()?;
+        let value = if self.eat(T::Eq) {
+            let T::String(value) = self.peek().kind else {
+                return Err(self.error(Code::E0109, &["a string".into()]));
+            };
+            self.bump();
+            Some(value)
+        } else {
+            None
+        };
+        Ok(AttrArg { name, value })
+    }
+
+    /// Reports the attributes that are unknown, used on the wrong `target` or
+    /// given the wrong arguments, and leaves them out.
+    pub(crate) fn check_attrs(&mut self, attrs: Vec<Attr>, target: Target) -> Vec<Attr> {
+        attrs
+            .into_iter()
+            .filter(|attr| {
+                let Some(builtin) = BUILTIN_ATTRS.iter().find(|b| b.name == attr.name.symbol)
+                else {
+                    let known: Vec<_> =
+                        BUILTIN_ATTRS.iter().map(|b| format!("`{}`", b.name)).collect();
+                    make_diag!(Error, attr.name.span, E0114, attr.name.symbol)
+                        .note(msg!(N0114, known.join(", ")))
+                        .emit();
+                    self.has_errors = true;
+                    return false;
+                };
+                if !builtin.targets.contains(&target) {
+                    let allowed: Vec<_> = builtin.targets.iter().map(|t| t.describe()).collect();
+                    make_diag!(Error, attr.span, E0115, attr.name.symbol, target.describe())
+                        .note(msg!(N0115, allowed.join(", ")))
+                        .emit();
+                    self.has_errors = true;
+                    return false;
+                }
+                if !builtin.accepts(&attr.args) {
+                    make_diag!(Error, attr.span, E0116, attr.name.symbol)
+                        .note(msg!(N0116, builtin.template))
+                        .emit();
+                    self.has_errors = true;
+                    return false;
+                }
+                true
+            })
+            .collect()
+    }
+}
@@ -1,11 +1,75 @@
+use terryc_base::Span;
 use terryc_base::ast::*;
-use terryc_base::errors::ErrorReported;
+use terryc_base::errors::{Code, ErrorReported, make_diag, msg};
 use terryc_base::lex::{Ident, TokenKind as T};
-use terryc_base::sym::kw;
-use terryc_base::Span;
+use terryc_base::sym::{kw, Symbol};
 
 use super::Parser;
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Assoc {
+    Left,
+    /// Chaining, as in `a < b < c`, is an error.
+    None,
+}
+
+/// Binary operators, from the loosest to the tightest binding.
+///
+/// Assignment binds looser than all of these and is right associative, `as`
+/// binds tighter than all of these but looser than unary operators.
+const PRECEDENCE: &[(Assoc, &[(T, BinOpKind)])] = &[
+    (Assoc::Left, &[(T::OrOr, BinOpKind::Or)]),
+    (Assoc::Left, &[(T::AndAnd, BinOpKind::And)]),
+    (
+        Assoc::None,
+        &[(T::EqEq, BinOpKind::Equal), (T::NotEq, BinOpKind::NotEqual)],
+    ),
+    (
+        Assoc::None,
+        &[
+            (T::Greater, BinOpKind::Greater),
+            (T::GreaterEq, BinOpKind::GreaterEqual),
+            (T::Less, BinOpKind::Less),
+            (T::LessEq, BinOpKind::LessEqual),
+        ],
+    ),
+    (Assoc::Left, &[(T::Or, BinOpKind::BitOr)]),
+    (Assoc::Left, &[(T::Caret, BinOpKind::BitXor)]),
+    (Assoc::Left, &[(T::And, BinOpKind::BitAnd)]),
+    (
+        Assoc::Left,
+        &[(T::Shl, BinOpKind::Shl), (T::Shr, BinOpKind::Shr)],
+    ),
+    (
+        Assoc::Left,
+        &[(T::Plus, BinOpKind::Add), (T::Minus, BinOpKind::Sub)],
+    ),
+    (
+        Assoc::Left,
+        &[
+            (T::Star, BinOpKind::Mul),
+            (T::Slash, BinOpKind::Div),
+            (T::Percent, BinOpKind::Mod),
+        ],
+    ),
+];
+
+/// Whether `outer` having an unparenthesized `inner` operand is easy to misread,
+/// like `a << b + c` or `a | b & c`.
+fn is_surprising_mix(outer: BinOpKind, inner: BinOpKind) -> bool {
+    use BinOpKind::*;
+    let arithmetic = |op| matches!(op, Add | Sub | Mul | Div | Mod);
+    let shift = |op| matches!(op, Shl | Shr);
+    let bitwise = |op| matches!(op, BitAnd | BitOr | BitXor);
+    if shift(outer) {
+        arithmetic(inner)
+    } else if bitwise(outer) {
+        arithmetic(inner) || shift(inner) || (bitwise(inner) && inner != outer)
+    } else {
+        false
+    }
+}
+
 impl<'a> Parser<'a> {
     pub fn parse_expr(&mut self) -> Result<Expr, ErrorReported> {
         self.expression().ok_or(ErrorReported)
@@ -15,28 +79,33 @@ impl<'a> Parser<'a> {
         self.return_()
     }
 
+    /// An expression that is followed by a block, like the condition of `if`.
+    fn condition(&mut self) -> Option<Expr> {
+        let prev = std::mem::replace(&mut self.no_struct_literal, true);
+        let expr = self.expression();
+        self.no_struct_literal = prev;
+        expr
+    }
+
     fn return_(&mut self) -> Option<Expr> {
-        let mut layers = vec![];
-        while self.eat_kw(kw::Return) {
-            layers.push(self.prev_token.span);
+        if !self.eat_kw(kw::Return) {
+            return self.assignment();
         }
-        let e = self.assignment();
-        if let Some(mut e) = e {
-            for s in layers.into_iter().rev() {
-                let span = s.to(e.span);
-                e = Expr {
-                    kind: ExprKind::Return(Box::new(e), s),
-                    span,
-                };
-            }
-            Some(e)
-        } else {
-            None
+        let kw = self.prev_token.span;
+        // nothing can follow a bare `return` but the end of the statement
+        // or of the block.
+        if matches!(self.peek().kind, T::Semicolon | T::RightBrace) {
+            return Some(Expr { kind: ExprKind::Return(None, kw), span: kw });
         }
+        let e = self.return_()?;
+        Some(Expr {
+            span: kw.to(e.span),
+            kind: ExprKind::Return(Some(Box::new(e)), kw),
+        })
     }
 
     fn assignment(&mut self) -> Option<Expr> {
-        let expr = self.equality()?;
+        let (expr, _) = self.binary(0)?;
         if self.eat(T::Eq) {
             let expr2 = self.expression()?;
             let span = expr.span.to(expr2.span);
@@ -47,86 +116,98 @@ impl<'a> Parser<'a> {
                 },
                 span,
             })
-        } else {
-            Some(expr)
-        }
-    }
-
-    fn equality(&mut self) -> Option<Expr> {
-        let mut expr = self.comparison()?;
-        while self.eat_any(&[T::EqEq, T::NotEq]) {
-            let token = &self.prev_token;
-            let op = match token.kind {
-                T::EqEq => BinOpKind::Equal,
-                T::NotEq => BinOpKind::NotEqual,
+        } else if self.eat_any(&[T::PlusEq, T::MinusEq, T::StarEq, T::SlashEq, T::PercentEq]) {
+            let op = match self.prev_token.kind {
+                T::PlusEq => BinOpKind::Add,
+                T::MinusEq => BinOpKind::Sub,
+                T::StarEq => BinOpKind::Mul,
+                T::SlashEq => BinOpKind::Div,
+                T::PercentEq => BinOpKind::Mod,
                 _ => unreachable!(),
             };
-            let right = self.comparison()?;
-            let span = expr.span.to(right.span);
-            expr = Expr {
-                kind: ExprKind::BinOp(op, Box::new(expr), Box::new(right)),
+            let expr2 = self.expression()?;
+            let span = expr.span.to(expr2.span);
+            Some(Expr {
+                kind: ExprKind::AssignOp(op, Box::new(expr), Box::new(expr2)),
                 span,
-            };
+            })
+        } else {
+            Some(expr)
         }
-        Some(expr)
     }
 
-    fn comparison(&mut self) -> Option<Expr> {
-        let mut expr = self.term()?;
-        while self.eat_any(&[T::Greater, T::GreaterEq, T::Less, T::LessEq]) {
-            let token = &self.prev_token;
-            let op = match token.kind {
-                T::Greater => BinOpKind::Greater,
-                T::GreaterEq => BinOpKind::GreaterEqual,
-                T::Less => BinOpKind::Less,
-                T::LessEq => BinOpKind::LessEqual,
-                _ => unreachable!(),
-            };
-            let right = self.term()?;
+    /// Parses binary operators binding at least as tightly as `PRECEDENCE[level]`.
+    ///
+    /// Also returns the operator at the root of the parsed expression, unless
+    /// it came from a single operand, so that mixes like `a << b + c` can be
+    /// told apart from `a << (b + c)`.
+    fn binary(&mut self, level: usize) -> Option<(Expr, Option<BinOpKind>)> {
+        let Some(&(assoc, ops)) = PRECEDENCE.get(level) else {
+            return self.cast().map(|expr| (expr, None));
+        };
+
+        let (mut expr, mut root) = self.binary(level + 1)?;
+        let mut chained = false;
+        while let Some(op) = self.eat_filter_map(|kind| {
+            ops.iter()
+                .find(|(token, _)| token == kind)
+                .map(|&(_, op)| op)
+        }) {
+            let op_span = self.prev_token.span;
+            if assoc == Assoc::None && chained {
+                self.has_errors = true;
+                make_diag! {
+                    Error,
+                    op_span,
+                    E0111,
+                }
+                .note(msg!(N0111))
+                .emit();
+            }
+
+            let (right, right_root) = self.binary(level + 1)?;
+            self.check_mixed_precedence(op, &expr, root);
+            self.check_mixed_precedence(op, &right, right_root);
+
             let span = expr.span.to(right.span);
             expr = Expr {
                 kind: ExprKind::BinOp(op, Box::new(expr), Box::new(right)),
                 span,
             };
+            root = Some(op);
+            chained = true;
         }
-        Some(expr)
+        Some((expr, root))
     }
 
-    fn term(&mut self) -> Option<Expr> {
-        let mut expr = self.factor()?;
-
-        while self.eat_any(&[T::Minus, T::Plus]) {
-            let token = &self.prev_token;
-            let op = match token.kind {
-                T::Minus => BinOpKind::Sub,
-                T::Plus => BinOpKind::Add,
-                _ => unreachable!(),
-            };
-            let right = self.factor()?;
-            let span = expr.span.to(right.span);
-            expr = Expr {
-                kind: ExprKind::BinOp(op, Box::new(expr), Box::new(right)),
-                span,
-            };
+    /// Warns when `operand` is an unparenthesized `inner` expression used with
+    /// `op`, and the grouping of the two commonly surprises.
+    fn check_mixed_precedence(&self, op: BinOpKind, operand: &Expr, inner: Option<BinOpKind>) {
+        let Some(inner) = inner else { return };
+        if !is_surprising_mix(op, inner) {
+            return;
         }
 
-        Some(expr)
+        let suggestion = match self.cx.get_file(self.current_file.into()) {
+            Some(src) => format!("`({})`", &src[operand.span.lo()..operand.span.hi()]),
+            None => msg!(N0113),
+        };
+        make_diag! {
+            Warning,
+            operand.span,
+            W0112,
+        }
+        .note(msg!(N0112, inner.as_str(), op.as_str(), suggestion))
+        .emit();
     }
 
-    fn factor(&mut self) -> Option<Expr> {
+    fn cast(&mut self) -> Option<Expr> {
         let mut expr = self.unary()?;
-        while self.eat_any(&[T::Star, T::Slash, T::Percent]) {
-            let token = &self.prev_token;
-            let op = match token.kind {
-                T::Star => BinOpKind::Mul,
-                T::Slash => BinOpKind::Div,
-                T::Percent => BinOpKind::Mod,
-                _ => unreachable!(),
-            };
-            let right = self.unary()?;
-            let span = expr.span.to(right.span);
+        while self.eat_kw(kw::As) {
+            let ty = self.parse_ty().ok()?;
+            let span = expr.span.to(ty.span);
             expr = Expr {
-                kind: ExprKind::BinOp(op, Box::new(expr), Box::new(right)),
+                kind: ExprKind::Cast(Box::new(expr), ty),
                 span,
             };
         }
@@ -152,73 +233,61 @@ impl<'a> Parser<'a> {
     }
 
     fn finish_call(&mut self, expr: Expr) -> Option<Expr> {
-        let mut args = vec![];
-        let span = expr.span;
-        if self.eat(T::Comma) {
-            if self.eat(T::RightParen) {
-                let span = span.to(self.prev_token.span);
-                return Some(Expr {
-                    kind: ExprKind::Call {
-                        callee: Box::new(expr),
-                        args,
-                    },
-                    span,
-                });
-            } else {
-                self.error("expected `)`");
-                return None;
-            }
-        } else if self.eat(T::RightParen) {
-            let span = span.to(self.prev_token.span);
-            return Some(Expr {
-                kind: ExprKind::Call {
-                    callee: Box::new(expr),
-                    args,
-                },
-                span,
-            });
-        }
+        let args = self
+            .parse_comma_list(T::RightParen, Parser::parse_expr)
+            .ok()?;
+        let span = expr.span.to(self.prev_token.span);
+        Some(Expr {
+            kind: ExprKind::Call {
+                callee: Box::new(expr),
+                args,
+            },
+            span,
+        })
+    }
 
+    fn finish_index(&mut self, expr: Expr) -> Option<Expr> {
+        let index = self.expression()?;
+        self.expect(T::RightBracket).ok()?;
+        let span = expr.span.to(self.prev_token.span);
+        Some(Expr {
+            kind: ExprKind::Index(Box::new(expr), Box::new(index)),
+            span,
+        })
+    }
+
+    fn call(&mut self) -> Option<Expr> {
+        let mut expr = self.primary()?;
         loop {
-            args.push(self.expression()?);
-            if self.eat(T::Comma) {
-                if self.eat(T::RightParen) {
-                    let span = span.to(self.prev_token.span);
-                    return Some(Expr {
-                        kind: ExprKind::Call {
-                            callee: Box::new(expr),
-                            args,
-                        },
-                        span,
-                    });
-                }
-            } else if self.eat(T::RightParen) {
-                let span = span.to(self.prev_token.span);
-                return Some(Expr {
-                    kind: ExprKind::Call {
-                        callee: Box::new(expr),
-                        args,
-                    },
+            if self.eat(T::LeftParen) {
+                expr = self.finish_call(expr)?;
+            } else if self.eat(T::LeftBracket) {
+                expr = self.finish_index(expr)?;
+            } else if self.eat(T::Dot) {
+                let field = if let T::Integer(index) = self.peek().kind {
+                    self.bump();
+                    Ident {
+                        symbol: Symbol::new(&index.to_string()),
+                        span: self.prev_token.span,
+                    }
+                } else {
+                    self.expect_ident().ok()?
+                };
+                let span = expr.span.to(field.span);
+                expr = Expr {
+                    kind: ExprKind::Field(Box::new(expr), field),
                     span,
-                });
+                };
             } else {
-                self.error("expected `)` or `,`");
-                return None;
+                return Some(expr);
             }
         }
     }
-    fn call(&mut self) -> Option<Expr> {
-        let mut expr = self.primary()?;
-        while self.eat(T::LeftParen) {
-            expr = self.finish_call(expr)?;
-        }
-        Some(expr)
-    }
 
     fn while_(&mut self) -> Option<Expr> {
         if self.eat_kw(kw::While) {
             let span = self.prev_token.span;
-            let expr = self.expression()?;
+            let expr = self.condition()?;
             let block = self.parse_block().ok()?;
             let span = span.to(block.span);
             Some(Expr {
@@ -234,6 +303,36 @@ impl<'a> Parser<'a> {
         }
     }
 
+    fn for_(&mut self) -> Option<Expr> {
+        if self.eat_kw(kw::For) {
+            let for_ = self.prev_token.span;
+            let binding = self.expect_ident().ok()?;
+            if !self.eat_kw(kw::In) {
+                self.error(Code::E0103, &[]);
+                return None;
+            }
+            let start = self.condition()?;
+            self.expect(T::DotDot).ok()?;
+            let end = self.condition()?;
+            let block = self.parse_block().ok()?;
+            let span = for_.to(block.span);
+            Some(Expr {
+                kind: ExprKind::For(ExprFor {
+                    for_,
+                    id: self.mk_id(),
+                    binding,
+                    start: Box::new(start),
+                    end_id: self.mk_id(),
+                    end: Box::new(end),
+                    block,
+                }),
+                span,
+            })
+        } else {
+            None
+        }
+    }
+
     fn if_(&mut self) -> Option<Expr> {
         self.opt_if().map(|(if_, span)| Expr {
             kind: ExprKind::If(if_),
@@ -244,7 +343,7 @@ impl<'a> Parser<'a> {
     fn opt_if(&mut self) -> Option<(ExprIf, Span)> {
         if self.eat_kw(kw::If) {
             let prev = self.prev_token.span;
-            let expr = self.expression()?;
+            let expr = self.condition()?;
             let block = self.parse_block().ok()?;
             let else_ = self.opt_else();
             let span = prev.to(self.prev_token.span);
@@ -273,6 +372,84 @@ impl<'a> Parser<'a> {
         }
     }
 
+    fn match_(&mut self) -> Option<Expr> {
+        self.eat_kw(kw::Match);
+        let lo = self.prev_token.span;
+        let expr = self.condition()?;
+        self.expect(T::LeftBrace).ok()?;
+        let mut arms = vec![];
+        while !self.eat(T::RightBrace) {
+            if self.is_end() {
+                self.error(Code::E0109, &["`}`".into()]);
+                return None;
+            }
+            let pat = self.parse_pat().ok()?;
+            self.expect(T::FatArrow).ok()?;
+            let body = self.expression()?;
+            // like statements, arms that end with a block need no comma.
+            let needs_comma = !body.kind.has_block();
+            arms.push(Arm { pat, body });
+            if !self.eat(T::Comma) && needs_comma && !self.check(T::RightBrace) {
+                self.error(Code::E0110, &["`}`".into()]);
+            }
+        }
+        Some(Expr {
+            kind: ExprKind::Match(ExprMatch {
+                expr: Box::new(expr),
+                arms,
+            }),
+            span: lo.to(self.prev_token.span),
+        })
+    }
+
+    /// The body of a closure extends as far as an expression can, so
+    /// `|x: i32| x + 1` returns `x + 1`. With a return type, it is a block.
+    fn closure(&mut self) -> Option<Expr> {
+        let lo = self.peek().span;
+        let args = if self.eat(T::OrOr) {
+            vec![]
+        } else {
+            self.expect(T::Or).ok()?;
+            self.parse_comma_list(T::Or, Parser::parse_arg).ok()?
+        };
+        let (ret, body) = if self.eat(T::RArrow) {
+            let ret = self.parse_ty().ok()?;
+            let block = self.parse_block().ok()?;
+            let span = block.span;
+            (Some(ret), Expr { kind: ExprKind::Block(block), span })
+        } else {
+            (None, self.expression()?)
+        };
+        Some(Expr {
+            span: lo.to(body.span),
+            kind: ExprKind::Closure(ExprClosure {
+                id: self.mk_id(),
+                args,
+                ret,
+                body: Box::new(body),
+            }),
+        })
+    }
+
+    /// `(expr)` or the tuple `(a, b)`, after the `(`. An expression followed
+    /// by a comma, like `(a,)`, is a tuple with a single element.
+    fn paren_or_tuple(&mut self, lo: Span) -> Option<Expr> {
+        let expr = self.expression()?;
+        if !self.eat(T::Comma) {
+            if self.peek().kind != T::RightParen {
+                self.error(Code::E0104, &[]);
+            }
+            self.bump();
+            return Some(expr);
+        }
+        let mut elems = vec![expr];
+        elems.extend(self.parse_comma_list(T::RightParen, Parser::parse_expr).ok()?);
+        Some(Expr {
+            kind: ExprKind::Tuple(elems),
+            span: lo.to(self.prev_token.span),
+        })
+    }
+
     fn primary(&mut self) -> Option<Expr> {
         let tok = self.peek();
         let span = tok.span;
@@ -307,24 +484,66 @@ impl<'a> Parser<'a> {
                 }),
                 span,
             },
-            /*T::Decimal(f) => Expr {
+            T::Decimal(f) => Expr {
                 kind: ExprKind::Literal(Literal {
                     kind: LiteralKind::Float(f),
                 }),
                 span,
-            },*/
+            },
             T::LeftParen => {
                 self.bump();
-                let expr = self.expression()?;
-                if self.peek().kind != T::RightParen {
-                    self.error("expected ')'");
-                }
-                expr
+                // parentheses end the condition the expression might be in.
+                let prev = std::mem::replace(&mut self.no_struct_literal, false);
+                let expr = self.paren_or_tuple(span);
+                self.no_struct_literal = prev;
+                return expr;
+            }
+            T::Ident(enum_) if self.look_ahead_is(T::ColonColon) => {
+                self.bump();
+                self.bump();
+                let variant = self.expect_ident().ok()?;
+                return Some(Expr {
+                    kind: ExprKind::Path(enum_, variant),
+                    span: span.to(variant.span),
+                });
+            }
+            T::Ident(name) if !self.no_struct_literal && self.look_ahead_is(T::LeftBrace) => {
+                self.bump();
+                self.bump();
+                let fields = self
+                    .parse_comma_list(T::RightBrace, |this| {
+                        let name = this.expect_ident()?;
+                        this.expect(T::Colon)?;
+                        let value = this.parse_expr()?;
+                        Ok(ExprField { name, value })
+                    })
+                    .ok()?;
+                return Some(Expr {
+                    kind: ExprKind::Struct { name, fields },
+                    span: span.to(self.prev_token.span),
+                });
             }
             T::Ident(sym) => Expr {
                 kind: ExprKind::Ident(sym.symbol),
                 span,
             },
+            T::Keyword(Ident {
+                symbol: kw::Underscore,
+                span,
+            }) => Expr {
+                kind: ExprKind::Underscore,
+                span,
+            },
+            T::LeftBracket => {
+                self.bump();
+                let elems = self
+                    .parse_comma_list(T::RightBracket, Parser::parse_expr)
+                    .ok()?;
+                return Some(Expr {
+                    kind: ExprKind::Array(elems),
+                    span: span.to(self.prev_token.span),
+                });
+            }
             T::LeftBrace => {
                 let block = self.parse_block().ok()?;
                 let span = block.span;
@@ -336,10 +555,16 @@ impl<'a> Parser<'a> {
             T::Keyword(Ident {
                 symbol: kw::While, ..
             }) => return self.while_(),
+            T::Keyword(Ident {
+                symbol: kw::For, ..
+            }) => return self.for_(),
             T::Keyword(Ident { symbol: kw::If, .. }) => return self.if_(),
-            T::Eof => return None,
+            T::Keyword(Ident {
+                symbol: kw::Match, ..
+            }) => return self.match_(),
+            T::Or | T::OrOr => return self.closure(),
             _ => {
-                self.error("expected expression");
+                self.error(Code::E0102, &[]);
                 return None;
             }
         };
@@ -1,27 +1,69 @@
 use terryc_base::{ast::*, ContextExt};
-use terryc_base::errors::ErrorReported;
-use terryc_base::lex::{Ident, TokenKind as T};
+use terryc_base::errors::{Code, ErrorReported};
+use terryc_base::lex::TokenKind as T;
 use terryc_base::sym::kw;
 
+use crate::attr::Target;
 use crate::Parser;
 
 impl Parser<'_> {
+    /// Whether an item starts at the current token.
+    pub(crate) fn check_item(&mut self) -> bool {
+        self.check(T::Pound)
+            || [kw::Fn, kw::Struct, kw::Enum, kw::Mod].into_iter().any(|kw| self.check_kw(kw))
+    }
+
     pub(crate) fn parse_item(&mut self) -> Result<Item, ErrorReported> {
-        if self.eat_kw(kw::Fn) {
+        let attrs = self.parse_attrs()?;
+        let lo = self.peek().span;
+        let kind = if self.eat_kw(kw::Fn) {
             let name = self.expect_ident()?;
             let args = self.parse_args()?;
-            self.expect(T::RArrow)?;
-            let ret = self.parse_ty()?;
+            // without `-> ty`, the function returns `unit`.
+            let ret = if self.eat(T::RArrow) {
+                self.parse_ty()?
+            } else {
+                Ty { kind: TyKind::Unit, span: self.prev_token.span }
+            };
             let body = self.parse_block()?;
 
-            Ok(Item {
-                kind: ItemKind::Fn(ItemFn {
-                    name,
-                    id: self.mk_id(),
-                    args,
-                    ret,
-                    body,
-                }),
+            ItemKind::Fn(ItemFn {
+                name,
+                id: self.mk_id(),
+                args,
+                ret,
+                body,
+            })
+        } else if self.eat_kw(kw::Struct) {
+            let name = self.expect_ident()?;
+            self.expect(T::LeftBrace)?;
+            let fields = self.parse_comma_list(T::RightBrace, |this| {
+                let name = this.expect_ident()?;
+                this.expect(T::Colon)?;
+                let ty = this.parse_ty()?;
+                Ok(FieldDef { name, ty })
+            })?;
+            ItemKind::Struct(ItemStruct {
+                name,
+                id: self.mk_id(),
+                fields,
+            })
+        } else if self.eat_kw(kw::Enum) {
+            let name = self.expect_ident()?;
+            self.expect(T::LeftBrace)?;
+            let variants = self.parse_comma_list(T::RightBrace, |this| {
+                let name = this.expect_ident()?;
+                let fields = if this.eat(T::LeftParen) {
+                    this.parse_comma_list(T::RightParen, Parser::parse_ty)?
+                } else {
+                    vec![]
+                };
+                Ok(Variant { name, fields })
+            })?;
+            ItemKind::Enum(ItemEnum {
+                name,
+                id: self.mk_id(),
+                variants,
             })
         } else if self.eat_kw(kw::Mod) {
             let name = self.expect_ident()?;
@@ -30,41 +72,28 @@ impl Parser<'_> {
             let tree = Parser::enter(self.cx, id, |nested| {
                 nested.parse()
             })??;
-            Ok(Item { kind: ItemKind::Mod { name, tree } })
+            ItemKind::Mod { name, tree }
         } else {
-            Err(self.error("expected item"))
-        }
+            return Err(self.error(Code::E0101, &[]));
+        };
+        let attrs = self.check_attrs(attrs, Target::of_item(&kind));
+        Ok(Item { kind, attrs, span: lo.to(self.prev_token.span) })
     }
 
-    fn parse_args(&mut self) -> Result<Vec<(Ident, Ty)>, ErrorReported> {
-        let mut args = Vec::new();
+    fn parse_args(&mut self) -> Result<Vec<FnArg>, ErrorReported> {
         self.expect(T::LeftParen)?;
+        self.parse_comma_list(T::RightParen, Parser::parse_arg)
+    }
 
-        if self.eat(T::Comma) {
-            if self.eat(T::RightParen) {
-                return Ok(args);
-            } else {
-                return Err(self.error("expected `)`"));
-            }
-        } else if self.eat(T::RightParen) {
-            return Ok(args);
-        }
-
-        loop {
-            let name = self.expect_ident()?;
-            self.expect(T::Colon)?;
-            let ty = self.parse_ty()?;
-            args.push((name, ty));
-
-            if self.eat(T::Comma) {
-                if self.eat(T::RightParen) {
-                    break;
-                }
-            } else if self.eat(T::RightParen) {
-                break;
-            }
-        }
-
-        Ok(args)
+    /// `name: ty`, in a function or a closure.
+    pub(crate) fn parse_arg(&mut self) -> Result<FnArg, ErrorReported> {
+        let name = self.expect_ident()?;
+        self.expect(T::Colon)?;
+        let ty = self.parse_ty()?;
+        Ok(FnArg {
+            id: self.mk_id(),
+            name,
+            ty,
+        })
     }
 }
@@ -1,15 +1,19 @@
 #![feature(let_chains)]
 
 pub use terryc_base::ast::*;
-use terryc_base::errors::{DiagnosticBuilder, DiagnosticSeverity, ErrorReported};
+use terryc_base::errors::{message, Code, DiagnosticBuilder, DiagnosticSeverity, ErrorReported};
 use terryc_base::lex::TokenKind::{self, self as T};
 use terryc_base::lex::{Ident, Token};
 use terryc_base::sym::{kw, Symbol};
 use terryc_base::{Context, FileId, Id, IdMaker, Providers};
 
+mod attr;
 mod expr;
 mod item;
+mod pat;
 mod stmt;
+#[cfg(test)]
+mod tests;
 mod ty;
 
 pub struct Parser<'a> {
@@ -20,6 +24,9 @@ pub struct Parser<'a> {
     pub prev_token: Token,
     pub has_errors: bool,
     maker: IdMaker,
+    /// Set while parsing the condition of `if`, `while` or `for`, where `{`
+    /// after a name starts the block instead of a struct literal.
+    no_struct_literal: bool,
 }
 
 impl<'a> Parser<'a> {
@@ -36,17 +43,18 @@ impl<'a> Parser<'a> {
             current: 0,
             prev_token: Token::dummy(),
             has_errors: false,
-            maker: IdMaker::new(),
+            maker: IdMaker::new(current_file),
+            no_struct_literal: false,
         }
     }
 
     pub fn parse(mut self) -> Result<Tree, ErrorReported> {
         let mut items = vec![];
-        while self.check_kw(kw::Fn) {
+        while self.check_item() {
             items.push(self.parse_item()?);
         }
         if !self.is_end() {
-            return Err(self.error("expected item"));
+            return Err(self.error(Code::E0101, &[]));
         }
         Ok(Tree {
             items: items.into_iter().collect(),
@@ -57,11 +65,13 @@ impl<'a> Parser<'a> {
         self.maker.make()
     }
 
-    fn error(&mut self, message: &str) -> ErrorReported {
+    fn error(&mut self, code: Code, args: &[String]) -> ErrorReported {
         self.has_errors = true;
         let tok = self.peek();
 
-        DiagnosticBuilder::new(DiagnosticSeverity::Error, message, tok.span).emit();
+        DiagnosticBuilder::new(DiagnosticSeverity::Error, message(code, args), tok.span)
+            .code(code)
+            .emit();
 
         ErrorReported
     }
@@ -74,6 +84,11 @@ impl<'a> Parser<'a> {
         self.tokens.get(self.current).unwrap()
     }
 
+    /// Whether the token after the current one is `kind`.
+    fn look_ahead_is(&self, kind: TokenKind) -> bool {
+        self.tokens.get(self.current + 1).is_some_and(|tok| tok.kind == kind)
+    }
+
     fn bump(&mut self) -> &Token {
         if !self.is_end() {
             self.prev_token = self.peek().clone();
@@ -117,14 +132,35 @@ impl<'a> Parser<'a> {
         if self.eat(kind.clone()) {
             Ok(())
         } else {
-            Err(self.error(&format!("expected {kind:?}")))
+            Err(self.error(Code::E0109, &[format!("`{kind}`")]))
         }
     }
 
     /// like eat but does not consume
-    /*fn check(&mut self, kind: TokenKind) -> bool {
+    fn check(&mut self, kind: TokenKind) -> bool {
         !self.is_end() && self.peek().kind == kind
-    }*/
+    }
+
+    /// Parses a comma separated list up to and including `close`, allowing a
+    /// trailing comma. A missing comma is reported, and parsing continues as
+    /// if it was there.
+    fn parse_comma_list<I>(
+        &mut self,
+        close: TokenKind,
+        mut item: impl FnMut(&mut Self) -> Result<I, ErrorReported>,
+    ) -> Result<Vec<I>, ErrorReported> {
+        let mut items = vec![];
+        while !self.eat(close.clone()) {
+            if self.is_end() {
+                return Err(self.error(Code::E0109, &[format!("`{close}`")]));
+            }
+            items.push(item(self)?);
+            if !self.eat(T::Comma) && !self.check(close.clone()) {
+                self.error(Code::E0110, &[format!("`{close}`")]);
+            }
+        }
+        Ok(items)
+    }
 
     fn eat_filter_map<F: FnOnce(&TokenKind) -> Option<O>, O>(&mut self, f: F) -> Option<O> {
         if !self.is_end() &&  let Some(o) = f(&self.peek().kind) {
@@ -143,7 +179,7 @@ impl<'a> Parser<'a> {
         if let Some(ident) = self.eat_ident() {
             Ok(ident)
         } else {
-            Err(self.error("expected identifier"))
+            Err(self.error(Code::E0105, &[]))
         }
     }
 
@@ -166,7 +202,15 @@ impl<'a> Parser<'a> {
             }
             match self.peek().kind {
                 T::Keyword(Ident {
-                    symbol: kw::Fn | kw::Let | kw::For | kw::If | kw::While | kw::Return,
+                    symbol:
+                        kw::Fn
+                        | kw::Struct
+                        | kw::Enum
+                        | kw::Let
+                        | kw::For
+                        | kw::If
+                        | kw::While
+                        | kw::Return,
                     ..
                 }) => {
                     return;
@@ -0,0 +1,40 @@
+use terryc_base::ast::{Literal, LiteralKind, Pat, PatKind};
+use terryc_base::errors::{Code, ErrorReported};
+use terryc_base::lex::{Ident, TokenKind as T};
+use terryc_base::sym::kw;
+
+use super::Parser;
+
+impl Parser<'_> {
+    pub fn parse_pat(&mut self) -> Result<Pat, ErrorReported> {
+        let span = self.peek().span;
+        let kind = match self.peek().kind {
+            T::Keyword(Ident { symbol: kw::Underscore, .. }) => PatKind::Wild,
+            T::Keyword(Ident { symbol: kw::True, .. }) => {
+                PatKind::Literal(Literal { kind: LiteralKind::Bool(true) })
+            }
+            T::Keyword(Ident { symbol: kw::False, .. }) => {
+                PatKind::Literal(Literal { kind: LiteralKind::Bool(false) })
+            }
+            T::Integer(n) => PatKind::Literal(Literal { kind: LiteralKind::Int(n) }),
+            T::Ident(enum_) if self.look_ahead_is(T::ColonColon) => {
+                self.bump();
+                self.bump();
+                let variant = self.expect_ident()?;
+                let fields = if self.eat(T::LeftParen) {
+                    self.parse_comma_list(T::RightParen, Parser::parse_pat)?
+                } else {
+                    vec![]
+                };
+                return Ok(Pat {
+                    kind: PatKind::Variant { enum_, variant, fields },
+                    span: span.to(self.prev_token.span),
+                });
+            }
+            T::Ident(name) => PatKind::Binding { id: self.mk_id(), name },
+            _ => return Err(self.error(Code::E0109, &["pattern".into()])),
+        };
+        self.bump();
+        Ok(Pat { kind, span })
+    }
+}
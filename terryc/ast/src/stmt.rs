@@ -1,8 +1,9 @@
 use terryc_base::ast::*;
-use terryc_base::errors::ErrorReported;
+use terryc_base::errors::{Code, ErrorReported};
 use terryc_base::lex::TokenKind as T;
 use terryc_base::sym::kw;
 
+use crate::attr::Target;
 use crate::Parser;
 
 impl<'a> Parser<'a> {
@@ -26,7 +27,7 @@ impl<'a> Parser<'a> {
 
     pub fn parse_block(&mut self) -> Result<Block, ErrorReported> {
         if !self.eat(T::LeftBrace) {
-            return Err(self.error("expected block"));
+            return Err(self.error(Code::E0106, &[]));
         }
 
         let lbrace = self.prev_token.span;
@@ -35,7 +36,11 @@ impl<'a> Parser<'a> {
         let mut trailing = None;
         while !self.eat(T::RightBrace) && !self.is_end() {
             if let Ok(stmt) = self.stmt() {
-                if self.eat(T::RightBrace) && let StmtKind::Expr(e) = stmt.kind {
+                // a statement with attributes is never the value of the block.
+                if stmt.attrs.is_empty()
+                    && self.eat(T::RightBrace)
+                    && let StmtKind::Expr(e) = stmt.kind
+                {
                     trailing = Some(Box::new(e));
                     break;
                 }
@@ -77,36 +82,45 @@ impl<'a> Parser<'a> {
     }
     fn stmt_end(&mut self, stmt: &Stmt) {
         if Self::needs_semicolon(stmt) && !self.eat(T::Semicolon) {
-            self.error("expected semicolon");
+            self.error(Code::E0107, &[]);
             self.synchronize();
         }
     }
 
-    fn var(&mut self) -> Result<Stmt, ErrorReported> {
+    fn var(&mut self) -> Result<StmtKind, ErrorReported> {
+        let mutable = self.eat_kw(kw::Mut);
         let name = self.expect_ident()?;
 
         let user_ty = self.eat(T::Colon).then(|| self.parse_ty()).transpose()?;
 
         let value = self.eat(T::Eq).then(|| self.parse_expr()).transpose()?;
 
-        let kind = StmtKind::Let {
+        Ok(StmtKind::Let {
             id: self.mk_id(),
+            mutable,
             user_ty,
             name,
             value,
-        };
-        Ok(Stmt { kind })
+        })
     }
 
     fn stmt(&mut self) -> Result<Stmt, ErrorReported> {
+        let attrs = self.parse_attrs()?;
+        let lo = self.peek().span;
         let kind = if self.eat_kw(kw::Let) {
-            return self.var();
-        } else if self.check_kw(kw::Fn) {
+            self.var()?
+        } else if self.check_item() {
             self.parse_item().map(StmtKind::Item)?
         } else {
             StmtKind::Expr(self.parse_expr()?)
         };
 
-        Ok(Stmt { kind })
+        let target = match &kind {
+            StmtKind::Item(item) => Target::of_item(&item.kind),
+            _ => Target::Stmt,
+        };
+        let attrs = self.check_attrs(attrs, target);
+        let span = lo.to(self.prev_token.span);
+        Ok(Stmt { kind, attrs, span })
     }
 }
@@ -0,0 +1,50 @@
+use terryc_base::errors::Code;
+use terryc_base::{Context, FileId, GlobalCtxt, Options, Providers};
+use terryc_lex::Lexer;
+
+use crate::Parser;
+
+fn init() {
+    GlobalCtxt::create_and_then(Options::default(), |mut gcx| {
+        let mut providers = Providers::default();
+        terryc_lex::provide(&mut providers);
+        crate::provide(&mut providers);
+        gcx.set_providers(terryc_base::leak(providers));
+        gcx.capture_diagnostics();
+        gcx
+    });
+}
+
+fn parse_expr(src: &str) -> String {
+    let tokens = Lexer::new(src, FileId::Main).scan_tokens().unwrap();
+    GlobalCtxt::with(|cx| {
+        let expr = Parser::new_with_tokens(cx, FileId::Main, &tokens).parse_expr();
+        format!("{:?}", expr.unwrap())
+    })
+}
+
+#[test]
+fn trailing_commas_in_calls() {
+    init();
+    assert_eq!(
+        parse_expr("f(1, 2,)"),
+        "Call { callee: Ident(f), args: [Literal(1), Literal(2)] }"
+    );
+    assert_eq!(parse_expr("f(1, 2,)"), parse_expr("f(1, 2)"));
+}
+
+#[test]
+fn missing_commas_are_reported_and_parsing_continues() {
+    init();
+    assert_eq!(parse_expr("f(1 2, [3 4] 5)"), parse_expr("f(1, 2, [3, 4], 5)"));
+    let diagnostics = GlobalCtxt::with(GlobalCtxt::take_diagnostics);
+    let errors: Vec<_> = diagnostics.iter().map(|d| (d.code, &*d.message)).collect();
+    assert_eq!(
+        errors,
+        [
+            (Some(Code::E0110), "expected `,` or `)`"),
+            (Some(Code::E0110), "expected `,` or `]`"),
+            (Some(Code::E0110), "expected `,` or `)`"),
+        ]
+    );
+}
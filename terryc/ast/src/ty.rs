@@ -1,5 +1,6 @@
 use terryc_base::ast::{Ty, TyKind};
-use terryc_base::errors::ErrorReported;
+use terryc_base::errors::{Code, ErrorReported};
+use terryc_base::lex::TokenKind as T;
 use terryc_base::sym;
 
 use super::Parser;
@@ -18,8 +19,43 @@ impl<'a> Parser<'a> {
             kind = TyKind::F32;
         } else if self.eat_sym(sym::string) {
             kind = TyKind::String;
+        } else if self.eat(T::LeftBracket) {
+            // `[elem; len]`
+            let lo = self.prev_token.span;
+            let elem = self.parse_ty()?;
+            self.expect(T::Semicolon)?;
+            let T::Integer(len) = self.peek().kind else {
+                return Err(self.error(Code::E0109, &["array length".into()]));
+            };
+            let Ok(len) = u32::try_from(len) else {
+                return Err(self.error(Code::E0005, &[]));
+            };
+            self.bump();
+            self.expect(T::RightBracket)?;
+            let span = lo.to(self.prev_token.span);
+            return Ok(Ty {
+                span,
+                kind: TyKind::array(elem.kind, len),
+            });
+        } else if self.eat(T::LeftParen) {
+            // `(a, b)` or `(a,)`, while `(a)` is just `a`.
+            let lo = self.prev_token.span;
+            let first = self.parse_ty()?;
+            if !self.eat(T::Comma) {
+                self.expect(T::RightParen)?;
+                return Ok(first);
+            }
+            let mut elems = vec![first.kind];
+            elems.extend(self.parse_comma_list(T::RightParen, |this| Ok(this.parse_ty()?.kind))?);
+            let span = lo.to(self.prev_token.span);
+            return Ok(Ty {
+                span,
+                kind: TyKind::tuple(elems),
+            });
+        } else if let Some(name) = self.eat_ident() {
+            kind = TyKind::Named(name.symbol);
         } else {
-            return Err(self.error("expected type"));
+            return Err(self.error(Code::E0108, &[]));
         }
 
         let span = self.prev_token.span;
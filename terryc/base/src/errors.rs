@@ -2,119 +2,195 @@ use std::fmt::{self, Display};
 
 use ariadne::{Label, ReportKind, Source};
 
-use crate::{Context, FileId, GlobalCtxt};
+use crate::render::RenderConfig;
+use crate::{Context, FileId, GlobalCtxt, Span};
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct ErrorReported;
-
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
-pub struct Span {
-    lo: usize,
-    hi: usize,
-    file: FileId,
-}
-
-impl Span {
-    pub const fn new(lo: usize, hi: usize, file: FileId) -> Self {
-        Self { lo, hi, file }
-    }
+mod catalog;
 
-    pub const fn lo(&self) -> usize {
-        self.lo
-    }
+pub use catalog::{message, Code};
 
-    pub const fn hi(&self) -> usize {
-        self.hi
-    }
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ErrorReported;
 
-    pub const fn file(&self) -> FileId {
-        self.file
-    }
+/// A file as ariadne names it, by its path.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+struct SourceFile(FileId);
 
-    pub fn to(self, other: Span) -> Span {
-        assert_eq!(self.file, other.file);
-        Span::new(self.lo.min(other.lo), self.hi.max(other.hi), self.file)
+impl Display for SourceFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        GlobalCtxt::with(|cx| {
+            let path = cx.file_path(self.0);
+            if cx.options().dont_print_path {
+                write!(
+                    f,
+                    "DIR/{}",
+                    path.file_name().expect("file name").to_string_lossy()
+                )
+            } else {
+                path.display().fmt(f)
+            }
+        })
     }
 }
 
-impl fmt::Debug for Span {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        (self.lo..self.hi).fmt(f)
+/// A [`Span`] as ariadne sees it.
+struct ReportSpan(SourceFile, Span);
+
+impl From<Span> for ReportSpan {
+    fn from(span: Span) -> Self {
+        ReportSpan(SourceFile(span.file()), span)
     }
 }
 
-impl ariadne::Span for Span {
-    type SourceId = FileId;
+impl ariadne::Span for ReportSpan {
+    type SourceId = SourceFile;
 
     fn source(&self) -> &Self::SourceId {
-        &self.file
+        &self.0
     }
 
     fn start(&self) -> usize {
-        self.lo
+        self.1.lo()
     }
 
     fn end(&self) -> usize {
-        self.hi
+        self.1.hi()
+    }
+}
+
+/// A diagnostic that was emitted, in a form tools can inspect.
+/// See [`GlobalCtxt::capture_diagnostics`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: DiagnosticSeverity,
+    /// The catalog entry of `message`, `None` for messages from plugins.
+    pub code: Option<Code>,
+    pub message: String,
+    pub span: Span,
+    pub note: Option<String>,
+    /// The explanation of `code`, with `--teaching-diagnostics`.
+    pub help: Option<&'static str>,
+    pub span_notes: Vec<(Span, String)>,
+}
+
+/// How far the lines after the first of a wrapped note or help are indented,
+/// past the margin ariadne draws.
+const NOTE_INDENT: usize = 4;
+
+impl Diagnostic {
+    fn eprint(&self, file: String) {
+        let render = RenderConfig::diagnostics();
+        let file_id = SourceFile(self.span.file());
+        let mut builder = ariadne::Report::build(self.severity.into(), file_id, self.span.lo())
+            .with_config(render.ariadne())
+            .with_message(&self.message)
+            .with_label(Label::new(ReportSpan::from(self.span)));
+        if let Some(note) = &self.note {
+            builder.set_note(render.wrap(note, NOTE_INDENT));
+        }
+        if let Some(help) = self.help {
+            builder.set_help(render.wrap(help, NOTE_INDENT));
+        }
+        for (span, note) in &self.span_notes {
+            let label = Label::new(ReportSpan::from(*span));
+            builder.add_label(label.with_message(format!("note: {note}")));
+        }
+        builder
+            .finish()
+            .eprint((file_id, Source::from(file)))
+            .unwrap();
     }
 }
 
 pub struct DiagnosticBuilder {
-    builder: ariadne::ReportBuilder<Span>,
-    main_span: Span,
+    diagnostic: Diagnostic,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum DiagnosticSeverity {
     Error,
+    Warning,
 }
 
 impl From<DiagnosticSeverity> for ariadne::ReportKind {
     fn from(s: DiagnosticSeverity) -> Self {
         match s {
             DiagnosticSeverity::Error => ReportKind::Error,
+            DiagnosticSeverity::Warning => ReportKind::Warning,
         }
     }
 }
 
 impl DiagnosticBuilder {
     pub fn new(severity: DiagnosticSeverity, message: impl ToString, span: Span) -> Self {
-        let builder = ariadne::Report::build(severity.into(), span.file(), span.lo())
-            .with_config(crate::ariadne_config())
-            .with_message(message)
-            .with_label(Label::new(span));
         Self {
-            builder,
-            main_span: span,
+            diagnostic: Diagnostic {
+                severity,
+                code: None,
+                message: message.to_string(),
+                span,
+                note: None,
+                help: None,
+                span_notes: vec![],
+            },
         }
     }
 
+    pub fn code(mut self, code: Code) -> Self {
+        self.diagnostic.code = Some(code);
+        self
+    }
+
     pub fn note(mut self, note: impl ToString) -> Self {
-        self.builder.set_note(note);
+        self.diagnostic.note = Some(note.to_string());
         self
     }
 
     pub fn span_note(mut self, span: Span, note: impl Display) -> Self {
-        self.builder
-            .add_label(Label::new(span).with_message(format!("note: {note}")));
+        self.diagnostic.span_notes.push((span, note.to_string()));
         self
     }
 
     pub fn emit(self) -> ErrorReported {
         GlobalCtxt::with(|gcx| {
-            let id = self.main_span.file();
-            let Some(file) = gcx.get_file(id.into()) else { return };
-            self.builder
-                .finish()
-                .eprint((id, Source::from(file)))
-                .unwrap();
+            let mut diagnostic = self.diagnostic;
+            diagnostic.help = diagnostic.code.and_then(catalog::teaching);
+            gcx.record_diagnostic(diagnostic.severity);
+            // a cached result would not report this again, so the query that
+            // reports it runs again in the next revision.
+            gcx.storage.salsa_runtime().report_untracked_read();
+            if let Some(captured) = &mut *gcx.captured_diagnostics.borrow_mut() {
+                captured.push(diagnostic);
+                return;
+            }
+            let Some(file) = gcx.get_file(diagnostic.span.file().into()) else { return };
+            gcx.providers().observers.iter().for_each(|o| o.before_diagnostic());
+            diagnostic.eprint(file);
         });
         ErrorReported
     }
 }
 
+/// Creates a diagnostic with the message `$code` from the catalog, or with a
+/// message that is not translated when given a format string.
 pub macro make_diag {
     (
-        Error,
+        $severity:ident,
+        $span:expr,
+        $code:ident
+        $(,
+            $($arg:expr),*$(,)?
+        )?
+    ) => {
+        $crate::errors::DiagnosticBuilder::new(
+            $crate::errors::DiagnosticSeverity::$severity,
+            $crate::errors::msg!($code $(, $($arg),*)?),
+            $span,
+        )
+        .code($crate::errors::Code::$code)
+    },
+    (
+        $severity:ident,
         $span:expr,
         $fmt:literal
         $(,
@@ -122,9 +198,14 @@ pub macro make_diag {
         )?
     ) => {
         $crate::errors::DiagnosticBuilder::new(
-            $crate::errors::DiagnosticSeverity::Error,
+            $crate::errors::DiagnosticSeverity::$severity,
             format!($fmt, $($($arg),*)?),
             $span,
         )
     }
 }
+
+/// Renders the message `$code` from the catalog with the given arguments.
+pub macro msg($code:ident $(, $($arg:expr),* $(,)?)?) {
+    $crate::errors::message($crate::errors::Code::$code, &[$($($arg.to_string()),*)?])
+}
@@ -0,0 +1,323 @@
+//! Every user-facing diagnostic message, keyed by its code.
+//!
+//! Messages are templates: `{}` is replaced by the next argument and `{0}`,
+//! `{1}`, ... by the argument at that position, so translations can reorder
+//! them. A message without a translation for the selected `--locale` falls
+//! back to English.
+
+use std::fmt;
+
+use crate::{Context, GlobalCtxt};
+
+// `macro_rules!` so that `Code` is visible outside of the expansion.
+macro_rules! catalog {
+    ($(
+        $code:ident $en:literal $({ $($locale:ident: $text:literal),* $(,)? })?;
+    )*) => {
+        /// A message in the catalog. `E` codes are errors, `W` codes warnings and
+        /// `N` codes notes attached to either.
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+        pub enum Code {
+            $($code,)*
+        }
+
+        impl Code {
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(Code::$code => stringify!($code),)*
+                }
+            }
+
+            /// The template for `locale`, which is a language like `de`.
+            pub fn template(self, locale: &str) -> &'static str {
+                match self {
+                    $(Code::$code => match locale {
+                        $($(l if l == stringify!($locale) => $text,)*)?
+                        _ => $en,
+                    },)*
+                }
+            }
+        }
+
+        impl std::str::FromStr for Code {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Code, String> {
+                match s {
+                    $(stringify!($code) => Ok(Code::$code),)*
+                    _ => Err(format!("unknown diagnostic code `{s}`")),
+                }
+            }
+        }
+    };
+}
+
+catalog! {
+    // lexer
+    E0001 "unexpected character `{}`" { de: "unerwartetes Zeichen `{}`" };
+    E0002 "unterminated string literal" { de: "nicht abgeschlossenes Zeichenkettenliteral" };
+    E0003 "unclosed block comment" { de: "nicht geschlossener Blockkommentar" };
+    E0004 "invalid float literal" { de: "ungültiges Gleitkommaliteral" };
+    E0005 "integer literal is too large" { de: "Ganzzahlliteral ist zu groß" };
+    E0006 "ASCII escape literal must be in range of [0x00, 0x7F]" {
+        de: "ASCII-Escape-Literal muss im Bereich [0x00, 0x7F] liegen",
+    };
+    E0007 "ASCII escape literal must be a hexadecimal" {
+        de: "ASCII-Escape-Literal muss hexadezimal sein",
+    };
+    E0008 "ASCII escape literal must be followed by exactly two hexadecimal digits" {
+        de: "auf ein ASCII-Escape-Literal müssen genau zwei Hexadezimalziffern folgen",
+    };
+    E0009 "unknown escape sequence" { de: "unbekannte Escape-Sequenz" };
+    E0010 "invalid digit for a base {} literal" {
+        de: "ungültige Ziffer für ein Literal zur Basis {}",
+    };
+    E0011 "integer literal has no digits" { de: "Ganzzahlliteral hat keine Ziffern" };
+    E0012 "source files larger than 4 GiB are not supported" {
+        de: "Quelldateien über 4 GiB werden nicht unterstützt",
+    };
+
+    // parser
+    E0101 "expected item" { de: "Element erwartet" };
+    E0102 "expected expression" { de: "Ausdruck erwartet" };
+    E0103 "expected `in`" { de: "`in` erwartet" };
+    E0104 "expected ')'" { de: "')' erwartet" };
+    E0105 "expected identifier" { de: "Bezeichner erwartet" };
+    E0106 "expected block" { de: "Block erwartet" };
+    E0107 "expected semicolon" { de: "Semikolon erwartet" };
+    E0108 "expected type" { de: "Typ erwartet" };
+    E0109 "expected {}" { de: "{} erwartet" };
+    E0110 "expected `,` or {}" { de: "`,` oder {} erwartet" };
+    E0111 "comparison operators cannot be chained" {
+        de: "Vergleichsoperatoren können nicht verkettet werden",
+    };
+    N0111 "use `&&` to combine the comparisons, or parentheses to compare the result" {
+        de: "verbinde die Vergleiche mit `&&` oder vergleiche das Ergebnis in Klammern",
+    };
+    W0112 "operator precedence might not be obvious" {
+        de: "die Operatorrangfolge ist möglicherweise nicht offensichtlich",
+    };
+    N0112 "`{}` is evaluated before `{}`, consider writing {}" {
+        de: "`{0}` wird vor `{1}` ausgewertet, schreibe stattdessen {2}",
+    };
+    N0113 "parentheses" { de: "Klammern" };
+    E0114 "unknown attribute `{}`" { de: "unbekanntes Attribut `{}`" };
+    N0114 "the known attributes are {}" { de: "die bekannten Attribute sind {}" };
+    E0115 "`#[{}]` cannot be used on {}" {
+        de: "`#[{}]` kann nicht an {} verwendet werden",
+    };
+    N0115 "it can only be used on {}" { de: "es kann nur an {} verwendet werden" };
+    E0116 "malformed `#[{}]` attribute" { de: "fehlerhaftes Attribut `#[{}]`" };
+    N0116 "expected {}" { de: "{} erwartet" };
+
+    // name resolution and type checking
+    E0201 "mismatched types" { de: "nicht übereinstimmende Typen" };
+    N0201 "expected `{}`, found `{}`" { de: "`{}` erwartet, `{}` gefunden" };
+    N0202 "expected one of {}, found `{}`" { de: "eines von {} erwartet, `{}` gefunden" };
+    N0203 "expected because of this" { de: "deswegen erwartet" };
+    E0202 "function clashes with variable" { de: "Funktion kollidiert mit Variable" };
+    W0203 "unused result of expression" { de: "unbenutztes Ergebnis eines Ausdrucks" };
+    N0204 "use `_ = ...;` to ignore the value" {
+        de: "verwende `_ = ...;`, um den Wert zu ignorieren",
+    };
+    E0204 "missing type annotation for `{}`" { de: "fehlende Typangabe für `{}`" };
+    N0205 "annotate its type, or assign it a value" {
+        de: "gib seinen Typ an oder weise ihm einen Wert zu",
+    };
+    E0205 "`{}` clashes with a previous function declaration" {
+        de: "`{}` kollidiert mit einer vorherigen Funktionsdeklaration",
+    };
+    E0206 "conflicting types" { de: "widersprüchliche Typen" };
+    E0207 "`{}` is not supported yet" { de: "`{}` wird noch nicht unterstützt" };
+    E0208 "cannot compare two values of different types" {
+        de: "zwei Werte unterschiedlicher Typen können nicht verglichen werden",
+    };
+    E0209 "cannot cast `{}` as `{}`" { de: "`{}` kann nicht in `{}` umgewandelt werden" };
+    N0209 "`as` converts between `i32` and `f32`, and from `bool` to `i32`" {
+        de: "`as` wandelt zwischen `i32` und `f32` und von `bool` in `i32` um",
+    };
+    E0210 "`_` can only be used on the left-hand side of an assignment" {
+        de: "`_` kann nur auf der linken Seite einer Zuweisung stehen",
+    };
+    E0211 "unknown identifier" { de: "unbekannter Bezeichner" };
+    E0212 "`{}` cannot be printed" { de: "`{}` kann nicht ausgegeben werden" };
+    E0213 "`println` takes exact one argument" { de: "`println` nimmt genau ein Argument" };
+    E0214 "unresolved function call" { de: "unaufgelöster Funktionsaufruf" };
+    E0215 "cannot infer the type of an empty array" {
+        de: "der Typ eines leeren Arrays kann nicht bestimmt werden",
+    };
+    E0216 "cannot index into a value of type `{}`" {
+        de: "ein Wert vom Typ `{}` kann nicht indiziert werden",
+    };
+    E0217 "cannot find type `{}`" { de: "Typ `{}` nicht gefunden" };
+    E0218 "`{}` is defined more than once" { de: "`{}` ist mehrfach definiert" };
+    E0219 "no field `{}` on type `{}`" { de: "kein Feld `{}` im Typ `{}`" };
+    E0220 "missing field `{}` in `{}`" { de: "fehlendes Feld `{}` in `{}`" };
+    E0221 "field `{}` is initialized more than once" {
+        de: "Feld `{}` wird mehrfach initialisiert",
+    };
+    E0222 "only variables can be assigned to" { de: "nur Variablen können zugewiesen werden" };
+    E0223 "no variant `{}` in enum `{}`" { de: "keine Variante `{}` im Enum `{}`" };
+    E0224 "`{}` has {} fields, but {} were given" {
+        de: "`{}` hat {} Felder, aber {} wurden angegeben",
+    };
+    E0225 "non-exhaustive patterns: {} not covered" {
+        de: "nicht erschöpfende Muster: {} nicht abgedeckt",
+    };
+    W0226 "unreachable pattern" { de: "unerreichbares Muster" };
+    E0227 "only bindings and `_` are supported inside of variant patterns" {
+        de: "in Variantenmustern werden nur Bindungen und `_` unterstützt",
+    };
+    E0228 "cannot match `{}` values against literals" {
+        de: "`{}`-Werte können nicht mit Literalen verglichen werden",
+    };
+    E0229 "`main` function not found" { de: "Funktion `main` nicht gefunden" };
+    N0229 "add a `fn main() -> unit`, or pass `--lib` to build a library" {
+        de: "füge ein `fn main() -> unit` hinzu, oder übergib `--lib` für eine Bibliothek",
+    };
+    E0230 "`main` is defined more than once" { de: "`main` ist mehrfach definiert" };
+    N0230 "`main` is first defined here" { de: "`main` ist zuerst hier definiert" };
+    E0231 "`main` has the wrong signature" { de: "`main` hat die falsche Signatur" };
+    N0231 "expected `fn main() -> unit`, found `{}`" {
+        de: "`fn main() -> unit` erwartet, `{}` gefunden",
+    };
+    E0232 "cannot assign to `{}`, which is not declared as mutable" {
+        de: "`{}` kann nicht zugewiesen werden, da es nicht als veränderbar deklariert ist",
+    };
+    N0232 "declare it with `let mut {}` to allow assignments" {
+        de: "deklariere es mit `let mut {}`, um Zuweisungen zu erlauben",
+    };
+    E0233 "test and benchmark functions cannot take arguments or return a value" {
+        de: "Test- und Benchmarkfunktionen können keine Argumente nehmen oder einen Wert \
+             zurückgeben",
+    };
+    N0233 "expected `fn {}() -> unit`, found `{}`" {
+        de: "`fn {}() -> unit` erwartet, `{}` gefunden",
+    };
+    E0234 "`assert` takes exactly one argument" { de: "`assert` nimmt genau ein Argument" };
+    E0235 "cannot read the library `{}` from `{}`: {}" {
+        de: "die Bibliothek `{}` kann nicht aus `{}` gelesen werden: {}",
+    };
+    E0236 "`{}` is not a terry library, line {} is malformed" {
+        de: "`{}` ist keine terry-Bibliothek, Zeile {} ist fehlerhaft",
+    };
+    E0237 "no function `{}` in the library `{}`" {
+        de: "keine Funktion `{}` in der Bibliothek `{}`",
+    };
+    E0238 "the type of `{}` is not known yet" { de: "der Typ von `{}` ist noch nicht bekannt" };
+    N0238 "`{}` is declared here without a type or a value" {
+        de: "`{}` wird hier ohne Typ und ohne Wert deklariert",
+    };
+    E0239 "a closure can only be the value of a `let` without `mut` or a type" {
+        de: "ein Closure kann nur der Wert eines `let` ohne `mut` oder Typ sein",
+    };
+    E0240 "`{}` is a closure, which can only be called" {
+        de: "`{}` ist ein Closure, der nur aufgerufen werden kann",
+    };
+    E0241 "closures cannot capture the mutable local `{}`" {
+        de: "Closures können die veränderbare Variable `{}` nicht erfassen",
+    };
+    N0241 "a closure gets the values of the locals it uses when it is called" {
+        de: "ein Closure bekommt die Werte der Variablen, die er benutzt, wenn er aufgerufen wird",
+    };
+    E0242 "`return` in a closure needs its return type after the arguments, like `-> i32`" {
+        de: "`return` in einem Closure braucht dessen Rückgabetyp nach den Argumenten, \
+             wie `-> i32`",
+    };
+    E0243 "the closure `{}` takes {} arguments but {} were given" {
+        de: "der Closure `{}` nimmt {} Argumente, aber {} wurden übergeben",
+    };
+    E0244 "arrays and tuples cannot hold values of type `unit`" {
+        de: "Arrays und Tupel können keine Werte vom Typ `unit` enthalten",
+    };
+
+    // MIR
+    W0301 "function `{}` is never called" { de: "Funktion `{}` wird nie aufgerufen" };
+}
+
+impl Code {
+    /// A longer explanation with an example for beginners, shown after the
+    /// message with `--teaching-diagnostics`.
+    pub fn teaching(self, locale: &str) -> Option<&'static str> {
+        Some(match (self, locale) {
+            (Code::E0201, "de") => {
+                "Jeder Wert hat einen Typ, und an dieser Stelle wird ein Wert eines anderen \
+                 Typs erwartet. Zum Beispiel passt `let x: bool = 1;` nicht, weil `1` eine \
+                 Zahl (`i32`) ist. Schreibe `let x: bool = true;` oder `let x: i32 = 1;`."
+            }
+            (Code::E0201, _) => {
+                "Every value has a type, and a value of a different type is expected here. \
+                 For example, `let x: bool = 1;` does not work because `1` is a number \
+                 (`i32`). Write `let x: bool = true;` or `let x: i32 = 1;` instead."
+            }
+            (Code::E0211 | Code::E0214, "de") => {
+                "Namen müssen deklariert werden, bevor sie benutzt werden. Prüfe die \
+                 Schreibweise und ob es ein `let x = ...;` oder `fn x() ...` weiter oben \
+                 gibt. Eine Variable ist nur in dem Block `{ ... }` sichtbar, in dem sie \
+                 deklariert wurde."
+            }
+            (Code::E0211 | Code::E0214, _) => {
+                "Names have to be declared before they are used. Check the spelling, and \
+                 that there is a `let x = ...;` or `fn x() ...` above this line. A variable \
+                 can only be used inside the block `{ ... }` it was declared in."
+            }
+            (Code::E0107, "de") => {
+                "Anweisungen enden mit einem Semikolon. In `let x = 1 let y = 2;` fehlt es \
+                 nach der `1`: `let x = 1; let y = 2;`."
+            }
+            (Code::E0107, _) => {
+                "Statements end with a semicolon. In `let x = 1 let y = 2;` it is missing \
+                 after the `1`: `let x = 1; let y = 2;`."
+            }
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Renders the message `code` in the language of `--locale`.
+pub fn message(code: Code, args: &[String]) -> String {
+    fill(code.template(language()), args)
+}
+
+/// The explanation of `code` for `--teaching-diagnostics`, if enabled.
+pub(super) fn teaching(code: Code) -> Option<&'static str> {
+    let options = GlobalCtxt::with(|gcx| gcx.options());
+    if !options.teaching_diagnostics {
+        return None;
+    }
+    code.teaching(language())
+}
+
+fn language() -> &'static str {
+    let options = GlobalCtxt::with(|gcx| gcx.options());
+    // `de_DE.UTF-8` uses the `de` messages.
+    let locale = options.locale.as_deref().unwrap_or("en");
+    locale.split(['_', '-', '.']).next().unwrap_or_default()
+}
+
+fn fill(template: &str, args: &[String]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut next = 0;
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let close = open + rest[open..].find('}').expect("unclosed `{` in message");
+        let index = match &rest[open + 1..close] {
+            "" => {
+                next += 1;
+                next - 1
+            }
+            index => index.parse().expect("invalid argument in message"),
+        };
+        out.push_str(&args[index]);
+        rest = &rest[close + 1..];
+    }
+    out.push_str(rest);
+    out
+}
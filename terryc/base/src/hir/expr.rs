@@ -20,6 +20,8 @@ pub struct Block {
 pub enum Expr {
     BinOp(BinOpKind, Box<Expr>, Box<Expr>, TyKind),
     UnOp(UnOpKind, Box<Expr>, TyKind),
+    /// `expr as ty`, with the type of `expr` and `ty`.
+    Cast(Box<Expr>, TyKind, TyKind),
     Block(Block),
     Call {
         callee: Resolution,
@@ -29,6 +31,7 @@ pub enum Expr {
     If {
         cond: Box<Expr>,
         then: Block,
+        else_: Option<Block>,
     },
     While {
         cond: Box<Expr>,
@@ -42,6 +45,130 @@ pub enum Expr {
     Group(Box<Expr>),
     Return(Box<Expr>, TyKind),
     Resolved(Resolution),
+    /// `[a, b, c]`, with the type of the array.
+    Array(Vec<Expr>, TyKind),
+    /// `(a, b)`, with the type of the tuple.
+    Tuple(Vec<Expr>, TyKind),
+    /// `array[index]`, with the type of `array`.
+    Index(Box<Expr>, Box<Expr>, TyKind),
+    /// `Name { field: value }`, with the index of each field in the order they
+    /// are written, and the type of the struct.
+    Struct(Vec<(u32, Expr)>, TyKind),
+    /// `expr.field` or `expr.0`, with the index of the field and the type of
+    /// `expr`, which is a struct or a tuple.
+    Field(Box<Expr>, u32, TyKind),
+    /// `Enum::Variant(fields)`, with the index of the variant and the type of
+    /// the enum.
+    Variant(u32, Vec<Expr>, TyKind),
+    Match {
+        scrutinee: Box<Expr>,
+        scrutinee_ty: TyKind,
+        arms: Vec<Arm>,
+        /// The type of the whole `match`, which every arm evaluates to.
+        ty: TyKind,
+    },
+}
+
+#[derive(PartialEq, Eq, Hash, Debug)]
+pub struct Arm {
+    pub pat: Pat,
+    pub body: Expr,
+}
+
+#[derive(PartialEq, Eq, Hash, Debug)]
+pub enum Pat {
+    Wild,
+    Binding(LocalDecl),
+    Literal(Literal),
+    /// A variant with the index, and patterns for its fields, which are
+    /// bindings or `_`.
+    Variant(u32, Vec<Pat>),
+}
+
+impl Expr {
+    /// Whether evaluating this expression may have side effects.
+    pub fn has_effects(&self) -> bool {
+        match self {
+            Expr::BinOp(_, lhs, rhs, _) => lhs.has_effects() || rhs.has_effects(),
+            Expr::UnOp(_, e, _)
+            | Expr::Cast(e, _, _)
+            | Expr::Group(e)
+            | Expr::Field(e, _, _) => e.has_effects(),
+            Expr::Block(block) => block.has_effects(),
+            Expr::Call { callee, args, .. } => {
+                callee.has_effects() || args.iter().any(|(arg, _)| arg.has_effects())
+            }
+            Expr::If { cond, then, else_ } => {
+                cond.has_effects()
+                    || then.has_effects()
+                    || else_.as_ref().is_some_and(Block::has_effects)
+            }
+            Expr::Array(elems, _) | Expr::Tuple(elems, _) => {
+                elems.iter().any(Expr::has_effects)
+            }
+            Expr::Index(array, index, _) => array.has_effects() || index.has_effects(),
+            Expr::Struct(fields, _) => fields.iter().any(|(_, e)| e.has_effects()),
+            Expr::Variant(_, fields, _) => fields.iter().any(Expr::has_effects),
+            Expr::Match { scrutinee, arms, .. } => {
+                scrutinee.has_effects() || arms.iter().any(|arm| arm.body.has_effects())
+            }
+            Expr::While { .. } | Expr::Assign { .. } | Expr::Return(..) => true,
+            Expr::Literal(_) | Expr::Resolved(_) => false,
+        }
+    }
+
+    /// Whether this is a non-unit value that is computed for nothing when used
+    /// as a statement, like `a + b;`.
+    pub fn is_unused_value(&self) -> bool {
+        match self {
+            Expr::Group(e) => e.is_unused_value(),
+            Expr::Literal(Literal::Unit)
+            | Expr::Block(_)
+            | Expr::If { .. }
+            | Expr::Match { .. } => false,
+            _ => !self.has_effects(),
+        }
+    }
+
+    /// Whether evaluating this expression returns from the function on every
+    /// path, so that nothing after it runs.
+    pub fn always_returns(&self) -> bool {
+        match self {
+            Expr::Return(..) => true,
+            Expr::Group(e) => e.always_returns(),
+            Expr::Block(block) => block.always_returns(),
+            _ => false,
+        }
+    }
+}
+
+impl Pat {
+    /// The value a `match` switches on to pick this arm, `None` if the arm
+    /// matches any value.
+    pub fn switch_value(&self) -> Option<i32> {
+        match self {
+            Pat::Wild | Pat::Binding(_) => None,
+            Pat::Literal(Literal::Bool(b)) => Some(*b as i32),
+            Pat::Literal(Literal::Int(n)) => Some(*n as i32),
+            Pat::Literal(_) => unreachable!("only `bool` and `i32` are matched on"),
+            Pat::Variant(index, _) => Some(*index as i32),
+        }
+    }
+}
+
+impl Block {
+    pub fn has_effects(&self) -> bool {
+        self.statements.iter().any(|stmt| match stmt {
+            Stmt::Local(decl) => decl.initializer.as_ref().is_some_and(Expr::has_effects),
+            Stmt::Expr(e) => e.has_effects(),
+            Stmt::Item(_) => false,
+        }) || self.expr.as_ref().is_some_and(|e| e.has_effects())
+    }
+
+    pub fn always_returns(&self) -> bool {
+        self.statements.iter().any(|stmt| matches!(stmt, Stmt::Expr(e) if e.always_returns()))
+            || self.expr.as_ref().is_some_and(|e| e.always_returns())
+    }
 }
 
 #[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
@@ -53,6 +180,18 @@ pub enum Literal {
     Unit,
 }
 
+impl Literal {
+    pub fn ty(self) -> TyKind {
+        match self {
+            Literal::Int(_) => TyKind::I32,
+            Literal::String(_) => TyKind::String,
+            Literal::Float(_) => TyKind::F32,
+            Literal::Bool(_) => TyKind::Bool,
+            Literal::Unit => TyKind::Unit,
+        }
+    }
+}
+
 #[derive(PartialEq, Eq, Hash, Debug)]
 pub enum Stmt {
     Local(LocalDecl),
@@ -63,6 +202,9 @@ pub enum Stmt {
 #[derive(PartialEq, Eq, Hash, Debug)]
 pub struct LocalDecl {
     pub id: Id,
+    pub name: Ident,
+    /// Declared with `let mut`.
+    pub mutable: bool,
     pub ty: TyKind,
     pub initializer: Option<Expr>,
 }
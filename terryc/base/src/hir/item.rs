@@ -1,5 +1,5 @@
 use super::{Block, HirTree};
-use crate::ast::TyKind;
+use crate::ast::{EnumDef, StructDef, TyKind};
 use crate::lex::Ident;
 use crate::sym::Symbol;
 use crate::{Id, FileId};
@@ -7,6 +7,8 @@ use crate::{Id, FileId};
 #[derive(PartialEq, Eq, Hash, Debug)]
 pub enum Item {
     Fn(ItemFn),
+    Struct(&'static StructDef),
+    Enum(&'static EnumDef),
     Mod {
         name: Ident,
         tree: HirTree,
@@ -20,6 +22,35 @@ pub struct ItemFn {
     pub args: Vec<FnArg>,
     pub ret: TyKind,
     pub block: Block,
+    /// Marked `#[test]` or `#[bench]`.
+    pub test: Option<TestKind>,
+    pub inline: Option<InlineKind>,
+}
+
+/// Whether a function asked to be inlined into its callers.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum InlineKind {
+    /// `#[inline]`, which allows inlining bigger functions.
+    Hint,
+    /// `#[inline(always)]`
+    Always,
+    /// `#[inline(never)]`
+    Never,
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum TestKind {
+    Test,
+    Bench,
+}
+
+/// A `#[test]` or `#[bench]` function, named by the path to it like
+/// `math::adds`.
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct TestFn {
+    pub id: Id,
+    pub name: String,
+    pub kind: TestKind,
 }
 
 #[derive(PartialEq, Eq, Hash, Debug)]
@@ -1,15 +1,18 @@
 mod expr;
 mod item;
+mod typed;
+pub mod visit;
 use std::hash::Hash;
 use std::rc::Rc;
 
 pub use expr::*;
 pub use item::*;
-use rustc_hash::FxHashMap;
+pub use typed::*;
 
 use crate::ast::{Ty, TyKind};
+use crate::data::FxIndexMap;
 use crate::lex::Ident;
-use crate::sym::Symbol;
+use crate::sym::{self, Symbol};
 use crate::Id;
 
 #[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
@@ -17,6 +20,21 @@ pub enum Resolution {
     Builtin(Symbol),
     Fn(Id),
     Local(Id),
+    /// A function of a library given with `--extern`, by the name of the
+    /// library and of the function.
+    Extern(Symbol, Symbol),
+}
+
+impl Resolution {
+    /// Whether calling this may have side effects.
+    pub fn has_effects(self) -> bool {
+        match self {
+            Resolution::Builtin(sym::println | sym::assert) => true,
+            Resolution::Builtin(_) => false,
+            // we do not analyze user functions yet
+            Resolution::Fn(_) | Resolution::Local(_) | Resolution::Extern(..) => true,
+        }
+    }
 }
 
 #[derive(PartialEq, Eq, Hash, Debug, Clone)]
@@ -28,7 +46,7 @@ pub struct Func {
 
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub struct HirTree {
-    pub functions: FxHashMap<Id, Func>,
+    pub functions: FxIndexMap<Id, Func>,
     pub items: Rc<[Item]>,
 }
 
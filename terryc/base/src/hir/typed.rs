@@ -0,0 +1,367 @@
+//! A dump of HIR that shows the type of every expression, for
+//! `-Z unpretty=hir-typed`. Each expression is on its own line with its
+//! operands indented below it.
+
+use std::fmt::{self, Write};
+
+use super::{
+    Block, Expr, Func, HirTree, InlineKind, Item, ItemFn, Literal, Pat, Resolution, Stmt, TestKind,
+};
+use crate::ast::{TyKind, UnOpKind};
+use crate::data::{FxHashMap, FxIndexMap};
+use crate::sym::Symbol;
+use crate::Id;
+
+const INDENT: &str = "    ";
+
+/// Renders `tree` with the type inferred for each expression.
+pub fn typed_dump(tree: &HirTree) -> String {
+    let mut printer = TypedPrinter {
+        functions: &tree.functions,
+        locals: FxHashMap::default(),
+        out: String::new(),
+        depth: 0,
+    };
+    printer
+        .items(&tree.items)
+        .expect("writing to a string cannot fail");
+    printer.out
+}
+
+struct TypedPrinter<'a> {
+    functions: &'a FxIndexMap<Id, Func>,
+    locals: FxHashMap<Id, (Symbol, TyKind)>,
+    out: String,
+    depth: usize,
+}
+
+impl TypedPrinter<'_> {
+    fn line(&mut self, text: impl fmt::Display) -> fmt::Result {
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+        writeln!(self.out, "{text}")
+    }
+
+    fn nested(&mut self, f: impl FnOnce(&mut Self) -> fmt::Result) -> fmt::Result {
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn items(&mut self, items: &[Item]) -> fmt::Result {
+        for item in items {
+            match item {
+                Item::Fn(f) => self.item_fn(f)?,
+                Item::Struct(def) => {
+                    self.line(format_args!("struct {}", def.name))?;
+                    self.nested(|this| {
+                        def.fields
+                            .iter()
+                            .try_for_each(|(name, ty)| this.line(format_args!("{name}: {ty}")))
+                    })?;
+                }
+                Item::Enum(def) => {
+                    self.line(format_args!("enum {}", def.name))?;
+                    self.nested(|this| {
+                        def.variants.iter().try_for_each(|variant| {
+                            this.line(format_args!("{}{}", variant.name, fields(&variant.fields)))
+                        })
+                    })?;
+                }
+                Item::Mod { name, tree } => {
+                    self.line(format_args!("mod {}", name.symbol))?;
+                    // the module has functions of its own.
+                    let mut printer = TypedPrinter {
+                        functions: &tree.functions,
+                        locals: FxHashMap::default(),
+                        out: String::new(),
+                        depth: self.depth + 1,
+                    };
+                    printer.items(&tree.items)?;
+                    self.out.push_str(&printer.out);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn item_fn(&mut self, f: &ItemFn) -> fmt::Result {
+        let mut args = String::new();
+        for (i, arg) in f.args.iter().enumerate() {
+            if i > 0 {
+                args.push_str(", ");
+            }
+            write!(args, "{}: {}", arg.name.symbol, arg.ty)?;
+            self.locals.insert(arg.id, (arg.name.symbol, arg.ty));
+        }
+        match f.test {
+            Some(TestKind::Test) => self.line(format_args!("#[test]"))?,
+            Some(TestKind::Bench) => self.line(format_args!("#[bench]"))?,
+            None => {}
+        }
+        match f.inline {
+            Some(InlineKind::Hint) => self.line(format_args!("#[inline]"))?,
+            Some(InlineKind::Always) => self.line(format_args!("#[inline(always)]"))?,
+            Some(InlineKind::Never) => self.line(format_args!("#[inline(never)]"))?,
+            None => {}
+        }
+        self.line(format_args!("fn {}({args}) -> {}", f.name, f.ret))?;
+        self.nested(|this| this.block(&f.block))
+    }
+
+    fn block(&mut self, block: &Block) -> fmt::Result {
+        for stmt in &block.statements {
+            match stmt {
+                Stmt::Local(decl) => {
+                    self.locals.insert(decl.id, (decl.name.symbol, decl.ty));
+                    let mutable = if decl.mutable { "mut " } else { "" };
+                    self.line(format_args!("let {mutable}{}: {}", decl.name.symbol, decl.ty))?;
+                    if let Some(init) = &decl.initializer {
+                        self.nested(|this| this.expr(init))?;
+                    }
+                }
+                Stmt::Expr(e) => self.expr(e)?,
+                Stmt::Item(item) => self.items(std::slice::from_ref(item))?,
+            }
+        }
+        if let Some(e) = &block.expr {
+            self.expr(e)?;
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self, e: &Expr) -> fmt::Result {
+        let ty = self.expr_ty(e);
+        let typed = |label: String| match ty {
+            Some(ty) => format!("{label}: {ty}"),
+            None => label,
+        };
+        match e {
+            Expr::BinOp(kind, lhs, rhs, _) => {
+                self.line(typed(format!("binary {}", kind.as_str())))?;
+                self.nested(|this| {
+                    this.expr(lhs)?;
+                    this.expr(rhs)
+                })
+            }
+            Expr::UnOp(kind, operand, _) => {
+                let op = match kind {
+                    UnOpKind::Minus => "-",
+                    UnOpKind::Not => "!",
+                };
+                self.line(typed(format!("unary {op}")))?;
+                self.nested(|this| this.expr(operand))
+            }
+            Expr::Cast(operand, _, _) => {
+                self.line(typed("cast".into()))?;
+                self.nested(|this| this.expr(operand))
+            }
+            Expr::Block(block) => {
+                self.line(typed("block".into()))?;
+                self.nested(|this| this.block(block))
+            }
+            Expr::Call { callee, args, .. } => {
+                let name = self.resolution_name(*callee);
+                self.line(typed(format!("call {name}")))?;
+                self.nested(|this| args.iter().try_for_each(|(arg, _)| this.expr(arg)))
+            }
+            Expr::If { cond, then, else_ } => {
+                self.line(typed("if".into()))?;
+                self.nested(|this| {
+                    this.expr(cond)?;
+                    this.line("then")?;
+                    this.nested(|this| this.block(then))?;
+                    if let Some(else_) = else_ {
+                        this.line("else")?;
+                        this.nested(|this| this.block(else_))?;
+                    }
+                    Ok(())
+                })
+            }
+            Expr::While { cond, body } => {
+                self.line(typed("while".into()))?;
+                self.nested(|this| {
+                    this.expr(cond)?;
+                    this.line("do")?;
+                    this.nested(|this| this.block(body))
+                })
+            }
+            Expr::Assign { to, rvalue } => {
+                let name = self.resolution_name(*to);
+                self.line(typed(format!("assign {name}")))?;
+                self.nested(|this| this.expr(rvalue))
+            }
+            Expr::Literal(lit) => self.line(typed(format!("literal {}", literal(*lit)))),
+            Expr::Group(inner) => {
+                self.line(typed("group".into()))?;
+                self.nested(|this| this.expr(inner))
+            }
+            Expr::Return(value, _) => {
+                self.line(typed("return".into()))?;
+                self.nested(|this| this.expr(value))
+            }
+            Expr::Array(elems, _) => {
+                self.line(typed("array".into()))?;
+                self.nested(|this| elems.iter().try_for_each(|elem| this.expr(elem)))
+            }
+            Expr::Tuple(elems, _) => {
+                self.line(typed("tuple".into()))?;
+                self.nested(|this| elems.iter().try_for_each(|elem| this.expr(elem)))
+            }
+            Expr::Index(array, index, _) => {
+                self.line(typed("index".into()))?;
+                self.nested(|this| {
+                    this.expr(array)?;
+                    this.expr(index)
+                })
+            }
+            Expr::Struct(fields, _) => {
+                self.line(typed("struct".into()))?;
+                self.nested(|this| fields.iter().try_for_each(|(_, e)| this.expr(e)))
+            }
+            Expr::Field(base, index, ty) => {
+                match ty {
+                    TyKind::Struct(def) => {
+                        let name = def.fields[*index as usize].0;
+                        self.line(typed(format!("field {name}")))?;
+                    }
+                    _ => self.line(typed(format!("field {index}")))?,
+                }
+                self.nested(|this| this.expr(base))
+            }
+            Expr::Variant(index, fields, ty) => {
+                let TyKind::Enum(def) = ty else { unreachable!("variant of a non-enum") };
+                let name = def.variants[*index as usize].name;
+                self.line(typed(format!("variant {name}")))?;
+                self.nested(|this| fields.iter().try_for_each(|e| this.expr(e)))
+            }
+            Expr::Match { scrutinee, scrutinee_ty, arms, .. } => {
+                self.line(typed("match".into()))?;
+                self.nested(|this| {
+                    this.expr(scrutinee)?;
+                    for arm in arms {
+                        let pat = this.pat(&arm.pat, *scrutinee_ty);
+                        this.line(format_args!("arm {pat}"))?;
+                        this.nested(|this| this.expr(&arm.body))?;
+                    }
+                    Ok(())
+                })
+            }
+            Expr::Resolved(res) => {
+                let kind = match res {
+                    Resolution::Local(_) => "local",
+                    Resolution::Fn(_) | Resolution::Builtin(_) | Resolution::Extern(..) => "fn",
+                };
+                let name = self.resolution_name(*res);
+                self.line(typed(format!("{kind} {name}")))
+            }
+        }
+    }
+
+    /// The type `e` evaluates to, `None` for functions, which are not values.
+    fn expr_ty(&mut self, e: &Expr) -> Option<TyKind> {
+        Some(match e {
+            Expr::BinOp(kind, _, _, operand_ty) => {
+                if kind.is_comparison() {
+                    TyKind::Bool
+                } else {
+                    *operand_ty
+                }
+            }
+            Expr::UnOp(_, _, ty)
+            | Expr::Cast(_, _, ty)
+            | Expr::Call { ret: ty, .. }
+            | Expr::Array(_, ty)
+            | Expr::Tuple(_, ty)
+            | Expr::Struct(_, ty)
+            | Expr::Variant(_, _, ty)
+            | Expr::Match { ty, .. } => *ty,
+            Expr::Index(_, _, ty) => ty.element().expect("indexed a non-array"),
+            Expr::Field(_, index, ty) => ty.field_ty(*index),
+            Expr::Block(block)
+            | Expr::If {
+                then: block,
+                else_: Some(_),
+                ..
+            } => return self.block_ty(block),
+            Expr::If { .. } | Expr::While { .. } | Expr::Assign { .. } | Expr::Return(..) => {
+                TyKind::Unit
+            }
+            Expr::Literal(lit) => lit.ty(),
+            Expr::Group(e) => return self.expr_ty(e),
+            Expr::Resolved(Resolution::Local(id)) => self.locals[id].1,
+            Expr::Resolved(Resolution::Fn(_) | Resolution::Builtin(_) | Resolution::Extern(..)) => {
+                return None
+            }
+        })
+    }
+
+    fn block_ty(&mut self, block: &Block) -> Option<TyKind> {
+        // the value can use locals of the block, which are not printed yet.
+        for stmt in &block.statements {
+            if let Stmt::Local(decl) = stmt {
+                self.locals.insert(decl.id, (decl.name.symbol, decl.ty));
+            }
+        }
+        match &block.expr {
+            Some(e) => self.expr_ty(e),
+            None => Some(TyKind::Unit),
+        }
+    }
+
+    /// Renders `pat`, declaring the locals it binds.
+    fn pat(&mut self, pat: &Pat, ty: TyKind) -> String {
+        match pat {
+            Pat::Wild => "_".into(),
+            Pat::Binding(decl) => {
+                self.locals.insert(decl.id, (decl.name.symbol, decl.ty));
+                format!("{}: {}", decl.name.symbol, decl.ty)
+            }
+            Pat::Literal(lit) => literal(*lit),
+            Pat::Variant(index, fields) => {
+                let TyKind::Enum(def) = ty else { unreachable!("variant of a non-enum") };
+                let variant = &def.variants[*index as usize];
+                let fields: Vec<_> = fields
+                    .iter()
+                    .zip(&variant.fields)
+                    .map(|(pat, ty)| self.pat(pat, *ty))
+                    .collect();
+                if fields.is_empty() {
+                    format!("{}::{}", def.name, variant.name)
+                } else {
+                    format!("{}::{}({})", def.name, variant.name, fields.join(", "))
+                }
+            }
+        }
+    }
+
+    fn resolution_name(&self, res: Resolution) -> Symbol {
+        match res {
+            Resolution::Builtin(name) => name,
+            Resolution::Fn(id) => self.functions[&id].name.symbol,
+            Resolution::Local(id) => self.locals[&id].0,
+            Resolution::Extern(krate, name) => Symbol::new(&format!("{krate}::{name}")),
+        }
+    }
+}
+
+fn literal(lit: Literal) -> String {
+    match lit {
+        Literal::Int(i) => i.to_string(),
+        Literal::String(s) => format!("{:?}", s.get_str()),
+        Literal::Float(f) => format!("{f:?}"),
+        Literal::Bool(b) => b.to_string(),
+        Literal::Unit => "()".into(),
+    }
+}
+
+/// The fields of a variant, like `(i32, bool)`, or nothing without fields.
+fn fields(tys: &[TyKind]) -> String {
+    if tys.is_empty() {
+        return String::new();
+    }
+    let tys: Vec<_> = tys.iter().map(ToString::to_string).collect();
+    format!("({})", tys.join(", "))
+}
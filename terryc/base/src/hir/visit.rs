@@ -0,0 +1,105 @@
+//! Walking the HIR. A visitor overrides the `visit_*` methods for what it is
+//! interested in, and calls the matching `walk_*` function to keep going into
+//! what is nested.
+
+use super::{Arm, Block, Expr, HirTree, Item, ItemFn, LocalDecl, Stmt};
+
+pub trait Visitor: Sized {
+    fn visit_tree(&mut self, tree: &HirTree) {
+        walk_tree(self, tree)
+    }
+
+    fn visit_item(&mut self, item: &Item) {
+        walk_item(self, item)
+    }
+
+    fn visit_fn(&mut self, f: &ItemFn) {
+        self.visit_block(&f.block)
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        walk_block(self, block)
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        walk_stmt(self, stmt)
+    }
+
+    fn visit_local(&mut self, local: &LocalDecl) {
+        if let Some(init) = &local.initializer {
+            self.visit_expr(init);
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr)
+    }
+
+    fn visit_arm(&mut self, arm: &Arm) {
+        self.visit_expr(&arm.body)
+    }
+}
+
+pub fn walk_tree<V: Visitor>(v: &mut V, tree: &HirTree) {
+    tree.items.iter().for_each(|item| v.visit_item(item));
+}
+
+pub fn walk_item<V: Visitor>(v: &mut V, item: &Item) {
+    match item {
+        Item::Fn(f) => v.visit_fn(f),
+        Item::Mod { tree, .. } => v.visit_tree(tree),
+        Item::Struct(_) | Item::Enum(_) => {}
+    }
+}
+
+pub fn walk_block<V: Visitor>(v: &mut V, block: &Block) {
+    block.statements.iter().for_each(|stmt| v.visit_stmt(stmt));
+    if let Some(expr) = &block.expr {
+        v.visit_expr(expr);
+    }
+}
+
+pub fn walk_stmt<V: Visitor>(v: &mut V, stmt: &Stmt) {
+    match stmt {
+        Stmt::Local(local) => v.visit_local(local),
+        Stmt::Expr(expr) => v.visit_expr(expr),
+        Stmt::Item(item) => v.visit_item(item),
+    }
+}
+
+pub fn walk_expr<V: Visitor>(v: &mut V, expr: &Expr) {
+    match expr {
+        Expr::BinOp(_, lhs, rhs, _) | Expr::Index(lhs, rhs, _) => {
+            v.visit_expr(lhs);
+            v.visit_expr(rhs);
+        }
+        Expr::UnOp(_, e, _)
+        | Expr::Cast(e, _, _)
+        | Expr::Group(e)
+        | Expr::Return(e, _)
+        | Expr::Field(e, _, _)
+        | Expr::Assign { rvalue: e, .. } => v.visit_expr(e),
+        Expr::Block(block) => v.visit_block(block),
+        Expr::Call { args, .. } => args.iter().for_each(|(arg, _)| v.visit_expr(arg)),
+        Expr::If { cond, then, else_ } => {
+            v.visit_expr(cond);
+            v.visit_block(then);
+            if let Some(else_) = else_ {
+                v.visit_block(else_);
+            }
+        }
+        Expr::While { cond, body } => {
+            v.visit_expr(cond);
+            v.visit_block(body);
+        }
+        Expr::Array(elems, _) | Expr::Tuple(elems, _) | Expr::Variant(_, elems, _) => {
+            elems.iter().for_each(|e| v.visit_expr(e));
+        }
+        Expr::Struct(fields, _) => fields.iter().for_each(|(_, e)| v.visit_expr(e)),
+        Expr::Match { scrutinee, arms, .. } => {
+            v.visit_expr(scrutinee);
+            arms.iter().for_each(|arm| v.visit_arm(arm));
+        }
+        Expr::Literal(_) | Expr::Resolved(_) => {}
+    }
+}
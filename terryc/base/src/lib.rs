@@ -1,58 +1,38 @@
 #![feature(once_cell, decl_macro)]
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::fmt;
 use std::hash::Hash;
-use std::ops::Deref;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::OnceLock;
 
 use ast::{Tree, TyKind};
-use errors::ErrorReported;
+use errors::{make_diag, Diagnostic, DiagnosticSeverity, ErrorReported};
 use hir::HirTree;
 use lex::Token;
 
-pub mod ast;
 pub mod errors;
 pub mod hir;
-pub mod lex;
+pub mod manifest;
+pub mod metadata;
 pub mod mir;
-pub mod sym;
+pub mod render;
+pub mod rewrite;
 
-pub use errors::Span;
 use rustc_hash::FxHashMap;
+pub use terryc_syntax::{ast, lex, sym, FileId, Id, IdMaker, Interned, Span};
 
 pub mod data {
-    pub use rustc_hash::FxHashMap;
-}
-
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
-pub struct Id(u32);
+    use std::hash::BuildHasherDefault;
 
-#[derive(Default)]
-pub struct IdMaker {
-    curr: u32,
-}
-
-impl IdMaker {
-    pub fn new() -> Self {
-        Self::default()
-    }
-
-    pub fn make(&mut self) -> Id {
-        let id = Id(self.curr);
-        self.curr += 1;
-        id
-    }
-}
-
-impl Iterator for IdMaker {
-    type Item = Id;
+    pub use rustc_hash::FxHashMap;
+    use rustc_hash::FxHasher;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        Some(self.make())
-    }
+    /// A map that iterates in insertion order, for anything whose iteration
+    /// order ends up in the output.
+    pub type FxIndexMap<K, V> = indexmap::IndexMap<K, V, BuildHasherDefault<FxHasher>>;
+    pub type FxIndexSet<T> = indexmap::IndexSet<T, BuildHasherDefault<FxHasher>>;
 }
 
 #[non_exhaustive]
@@ -62,35 +42,99 @@ pub struct SessionGlobals {
 }
 
 thread_local! { // TODO use something else than thread local once we have multithreading
-    static GLOBAL_CTXT: OnceLock<GlobalCtxt> = OnceLock::new();
+    static GLOBAL_CTXT: RefCell<Option<GlobalCtxt>> = RefCell::new(None);
+    static INTERNERS: OnceLock<&'static Interners> = OnceLock::new();
 }
 
-pub fn ariadne_config() -> ariadne::Config {
-    fn use_ascii() -> bool {
-        GlobalCtxt::with(|gcx| gcx.options().use_ascii)
-    }
-    ariadne::Config::default()
-        .with_char_set(if use_ascii() {
-            ariadne::CharSet::Ascii
-        } else {
-            ariadne::CharSet::Unicode
-        })
-        .with_color(!use_ascii())
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub enum Mode {
+    Unpretty(Unpretty),
+    CallgraphDot,
+    #[default]
+    Gen,
 }
 
+/// An intermediate representation to print instead of compiling, selected
+/// with `-Z unpretty` or `--emit`.
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
-pub enum Mode {
-    PrintAst,
-    PrintMir,
-    Gen,
+pub enum Unpretty {
+    Tokens,
+    Ast,
+    Hir,
+    /// HIR with the type of every expression.
+    HirTyped,
+    Mir,
+    /// The control flow graph of every MIR body, in the Graphviz `dot` format.
+    MirCfg,
+}
+
+impl Unpretty {
+    /// Runs the compiler as far as needed and renders the result.
+    pub fn render(self, cx: &dyn Context, file: FileId) -> Result<String, ErrorReported> {
+        Ok(match self {
+            Unpretty::Tokens => format!("{:#?}", cx.lex(file)?),
+            Unpretty::Ast => format!("{:#?}", cx.parse(file)?),
+            Unpretty::Hir => format!("{:#?}", cx.hir(file)?),
+            Unpretty::HirTyped => hir::typed_dump(&cx.hir(file)?),
+            Unpretty::Mir => format!("{:#?}", cx.mir(file)?),
+            Unpretty::MirCfg => cx.mir(file)?.to_dot(),
+        })
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Options {
     pub use_ascii: bool,
+    pub color: render::ColorChoice,
     pub dont_print_path: bool,
     pub path: PathBuf,
     pub mode: Mode,
+    /// Run optimizations on MIR.
+    pub optimize: bool,
+    /// Keep the bounds checks that optimizations prove to always pass, to
+    /// verify that removing them is right.
+    pub keep_bounds_checks: bool,
+    /// Make the generated program count how often each block runs and write
+    /// the counts to `lcov.info` when it exits.
+    pub coverage: bool,
+    /// The language of diagnostics, like `de`. English is used for messages
+    /// that are not translated.
+    pub locale: Option<String>,
+    /// Explain common errors at length, with examples, for beginners.
+    pub teaching_diagnostics: bool,
+    /// Build a library: no `main` is required, and the object file is kept
+    /// instead of being linked into a program.
+    pub lib: bool,
+    /// Build a test runner for the `#[test]` and `#[bench]` functions instead
+    /// of a program. Its `main` runs the test whose index is the first
+    /// argument as many times as the second one says.
+    pub test: bool,
+    /// What `#[cfg(...)]` attributes are enabled, like `debug` or
+    /// `feature=debug`.
+    pub cfg: Vec<String>,
+    /// The libraries given with `--extern name=path`, by name and the path to
+    /// their metadata.
+    pub externs: Vec<(String, PathBuf)>,
+}
+
+/// Where source files come from. The default reads them from disk, embedders
+/// without a file system (like the playground) can provide their own.
+pub trait FileLoader {
+    fn load(&self, path: &Path) -> Option<String>;
+}
+
+impl fmt::Debug for dyn FileLoader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileLoader").finish_non_exhaustive()
+    }
+}
+
+pub struct DiskLoader;
+
+impl FileLoader for DiskLoader {
+    fn load(&self, path: &Path) -> Option<String> {
+        std::fs::read_to_string(path).ok()
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -100,12 +144,6 @@ pub enum FileLocator {
     Resolved(u32),
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
-pub enum FileId {
-    Main,
-    Other(u32),
-}
-
 impl From<FileId> for FileLocator {
     fn from(id: FileId) -> Self {
         match id {
@@ -115,40 +153,27 @@ impl From<FileId> for FileLocator {
     }
 }
 
-impl fmt::Display for FileId {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        GlobalCtxt::with(|cx| {
-            let path = cx.file_path(*self);
-            if cx.options().dont_print_path {
-                write!(
-                    f,
-                    "DIR/{}",
-                    path.file_name().expect("file name").to_string_lossy()
-                )
-            } else {
-                path.display().fmt(f)
-            }
-        })
-    }
-}
-
 pub fn run() {
     GlobalCtxt::with(|cx| match cx.mode() {
-        Mode::PrintAst => {
-            if let Ok(ast) = cx.parse(FileId::Main) {
-                eprintln!("{ast:#?}");
+        Mode::Unpretty(unpretty) => {
+            if let Ok(output) = unpretty.render(cx, FileId::Main) {
+                eprintln!("{}", output.trim_end());
             }
         }
-        Mode::PrintMir => {
-            let mir = cx.mir(FileId::Main);
-            eprintln!("{mir:#?}");
+        Mode::CallgraphDot => {
+            if let Ok(graph) = cx.call_graph(FileId::Main) {
+                print!("{}", graph.to_dot());
+            }
         }
         Mode::Gen => {
+            // reports functions that are never called
+            let _ = cx.call_graph(FileId::Main);
             /* let class = */
             let _ = cx.codegen(FileId::Main);
             // fs::write("Main.class", &*class).unwrap();
         }
     });
+    GlobalCtxt::with(|cx| cx.providers().observers.iter().for_each(|o| o.finished()));
 }
 
 #[derive(PartialEq, Eq, Debug)]
@@ -156,48 +181,148 @@ pub struct DefTree {
     pub defs: FxHashMap<Id, Definition>,
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
 pub enum DefKind {
     Fn,
+    Struct,
+    Enum,
+    Arg,
+    Local,
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
 pub struct Definition {
     pub kind: DefKind,
+    /// The span of the name of the definition.
     pub span: Span,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
-pub struct TyList(&'static [TyKind]);
+/// Collects every definition of a single file. Nested module files
+/// are skipped since they have a `DefTree` of their own.
+struct DefCollector {
+    defs: FxHashMap<Id, Definition>,
+}
 
-impl fmt::Debug for TyList {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.0.fmt(f)
+impl DefCollector {
+    fn def(&mut self, id: Id, kind: DefKind, span: Span) {
+        self.defs.insert(id, Definition { kind, span });
     }
-}
 
-impl Deref for TyList {
-    type Target = [TyKind];
-    fn deref(&self) -> &Self::Target {
-        self.0
+    fn item(&mut self, item: &ast::Item) {
+        match &item.kind {
+            ast::ItemKind::Fn(f) => {
+                self.def(f.id, DefKind::Fn, f.name.span);
+                for arg in &f.args {
+                    self.def(arg.id, DefKind::Arg, arg.name.span);
+                }
+                self.block(&f.body);
+            }
+            ast::ItemKind::Struct(s) => self.def(s.id, DefKind::Struct, s.name.span),
+            ast::ItemKind::Enum(e) => self.def(e.id, DefKind::Enum, e.name.span),
+            ast::ItemKind::Mod { .. } => {}
+        }
     }
-}
-// FIXME replace this with a unique arena instead to help faster comparisons
-/*
-impl PartialEq for TyList {
-    fn eq(&self, other: &Self) -> bool {
 
-        std::ptr::eq(self.0, other.0)
+    fn block(&mut self, block: &ast::Block) {
+        for stmt in &block.stmts {
+            match &stmt.kind {
+                ast::StmtKind::Expr(e) => self.expr(e),
+                ast::StmtKind::Let {
+                    id, name, value, ..
+                } => {
+                    if let Some(value) = value {
+                        self.expr(value);
+                    }
+                    self.def(*id, DefKind::Local, name.span);
+                }
+                ast::StmtKind::Item(item) => self.item(item),
+            }
+        }
+        if let Some(e) = &block.expr {
+            self.expr(e);
+        }
     }
-}
 
-impl Eq for TyList {}
+    fn if_(&mut self, if_: &ast::ExprIf) {
+        self.expr(&if_.expr);
+        self.block(&if_.block);
+        match &if_.else_ {
+            Some(ast::Else::ElseIf(if_, _)) => self.if_(if_),
+            Some(ast::Else::Else(block)) => self.block(block),
+            None => {}
+        }
+    }
 
-impl Hash for TyList {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.0.as_ptr().hash(state)
+    fn expr(&mut self, e: &ast::Expr) {
+        match &e.kind {
+            ast::ExprKind::BinOp(_, lhs, rhs)
+            | ast::ExprKind::AssignOp(_, lhs, rhs)
+            | ast::ExprKind::Assignment { lhs, rhs }
+            | ast::ExprKind::Index(lhs, rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            ast::ExprKind::UnOp(_, e)
+            | ast::ExprKind::Cast(e, _)
+            | ast::ExprKind::Group(e, _)
+            | ast::ExprKind::Return(Some(e), _)
+            | ast::ExprKind::Field(e, _) => self.expr(e),
+            ast::ExprKind::Block(block) => self.block(block),
+            ast::ExprKind::If(if_) => self.if_(if_),
+            ast::ExprKind::While(while_) => {
+                self.expr(&while_.expr);
+                self.block(&while_.block);
+            }
+            ast::ExprKind::For(for_) => {
+                self.expr(&for_.start);
+                self.expr(&for_.end);
+                self.def(for_.id, DefKind::Local, for_.binding.span);
+                self.def(for_.end_id, DefKind::Local, for_.end.span);
+                self.block(&for_.block);
+            }
+            ast::ExprKind::Call { callee, args } => {
+                self.expr(callee);
+                args.iter().for_each(|arg| self.expr(arg));
+            }
+            ast::ExprKind::Array(elems) | ast::ExprKind::Tuple(elems) => {
+                elems.iter().for_each(|elem| self.expr(elem))
+            }
+            ast::ExprKind::Struct { fields, .. } => {
+                fields.iter().for_each(|field| self.expr(&field.value))
+            }
+            ast::ExprKind::Match(match_) => {
+                self.expr(&match_.expr);
+                for arm in &match_.arms {
+                    self.pat(&arm.pat);
+                    self.expr(&arm.body);
+                }
+            }
+            ast::ExprKind::Closure(closure) => {
+                self.def(closure.id, DefKind::Fn, e.span);
+                for arg in &closure.args {
+                    self.def(arg.id, DefKind::Arg, arg.name.span);
+                }
+                self.expr(&closure.body);
+            }
+            ast::ExprKind::Literal(_)
+            | ast::ExprKind::Ident(_)
+            | ast::ExprKind::Underscore
+            | ast::ExprKind::Return(None, _)
+            | ast::ExprKind::Path(..) => {}
+        }
     }
-} */
+
+    fn pat(&mut self, pat: &ast::Pat) {
+        match &pat.kind {
+            ast::PatKind::Binding { id, name } => self.def(*id, DefKind::Local, name.span),
+            ast::PatKind::Variant { fields, .. } => fields.iter().for_each(|pat| self.pat(pat)),
+            ast::PatKind::Wild | ast::PatKind::Literal(_) => {}
+        }
+    }
+}
+
+/// A list of types, compared by address like the types themselves.
+pub type TyList = Interned<[TyKind]>;
 
 #[derive(Default)]
 pub struct PathResolver {
@@ -212,6 +337,10 @@ impl PathResolver {
             FileLocator::Main => FileId::Main,
             FileLocator::Resolved(x) => FileId::Other(x),
             FileLocator::Unresolved(path) => {
+                // a module reached twice is the same file, with the same ids.
+                if let Some(id) = self.resolutions.get(&*path) {
+                    return *id;
+                }
                 let path = &*Box::leak(path.into_boxed_path());
                 // cur_id should never be zero.
                 self.cur_id += 1;
@@ -225,8 +354,8 @@ impl PathResolver {
 }
 
 pub struct Interners {
-    pub symbols: sym::Interner,
-    pub types: typed_arena::Arena<TyKind>,
+    /// Those of the syntax tree, installed on the thread of the context.
+    pub syntax: &'static terryc_syntax::Interners,
     pub paths: RefCell<PathResolver>,
 }
 
@@ -239,8 +368,7 @@ impl fmt::Debug for Interners {
 impl Interners {
     pub fn fresh() -> Self {
         Self {
-            symbols: sym::Interner::fresh(),
-            types: Default::default(),
+            syntax: leak(terryc_syntax::Interners::fresh()),
             paths: Default::default(),
         }
     }
@@ -256,25 +384,44 @@ pub trait Context {
     #[salsa::input]
     fn providers(&self) -> &'static Providers;
     fn locate(&self, locator: FileLocator) -> FileId;
-    #[salsa::dependencies]
+    #[salsa::input]
+    fn file_loader(&self) -> &'static dyn FileLoader;
+    /// Contents of files that are used instead of what the loader returns.
+    #[salsa::input]
+    fn file_overrides(&self) -> Rc<FxHashMap<FileId, String>>;
     fn get_file(&self, id: FileLocator) -> Option<String>;
     // fn file_list(&self) -> &'static [PathBuf];
     fn file_path(&self, id: FileId) -> &'static Path;
     fn lex(&self, id: FileId) -> Result<Rc<[Token]>, ErrorReported>;
     fn parse(&self, id: FileId) -> Result<Tree, ErrorReported>;
+    /// The AST after surface constructs have been rewritten into core constructs.
+    fn desugar(&self, id: FileId) -> Result<Tree, ErrorReported>;
     fn hir(&self, id: FileId) -> Result<HirTree, ErrorReported>;
-    fn def_tree(&self) -> Result<Rc<DefTree>, ErrorReported>;
+    /// The `main` function of the program, checked to exist exactly once with
+    /// no arguments and a `unit` return type.
+    fn entry_point(&self, id: FileId) -> Result<Id, ErrorReported>;
+    /// The `#[test]` and `#[bench]` functions of the program, in the order
+    /// they are defined.
+    fn tests(&self, id: FileId) -> Result<Rc<[hir::TestFn]>, ErrorReported>;
+    fn def_tree(&self, id: FileId) -> Result<Rc<DefTree>, ErrorReported>;
+    /// The libraries given with `--extern`, read from their metadata.
+    fn externs(&self) -> Result<Rc<[metadata::ExternCrate]>, ErrorReported>;
+    /// The span of the name of the definition `id` refers to, or `None` if
+    /// `id` does not refer to a definition.
+    fn def_span(&self, id: Id) -> Option<Span>;
+    fn def_kind(&self, id: Id) -> Option<DefKind>;
     fn mir(&self, id: FileId) -> Result<mir::MirTree, ErrorReported>;
+    fn call_graph(&self, id: FileId) -> Result<Rc<mir::CallGraph>, ErrorReported>;
     fn codegen(&self, id: FileId) -> Result<(), ErrorReported>;
 }
 
 pub trait ContextExt: Context {
     fn intern_types(&self, x: impl IntoIterator<Item = TyKind>) -> TyList {
-        TyList(self.interners().types.alloc_extend(x))
+        self.interners().syntax.types.intern_slice(x)
     }
     fn resolve_mod(&self, current_file: FileId, mod_name: &str) -> FileId {
         let cur_path = self.file_path(current_file);
-        let mod_file = cur_path.parent().unwrap().join(mod_name).join("mod.rs");
+        let mod_file = cur_path.parent().unwrap().join(mod_name).join("mod.terry");
         self.locate(FileLocator::Unresolved(mod_file))
     }
 }
@@ -289,22 +436,80 @@ fn locate(cx: &dyn Context, locator: FileLocator) -> FileId {
     cx.interners().paths.borrow_mut().locate(locator)
 }
 
-fn def_tree(cx: &dyn Context) -> Result<Rc<DefTree>, ErrorReported> {
-    todo!()
+fn def_tree(cx: &dyn Context, id: FileId) -> Result<Rc<DefTree>, ErrorReported> {
+    let tree = cx.parse(id)?;
+    let mut collector = DefCollector {
+        defs: FxHashMap::default(),
+    };
+    tree.items.iter().for_each(|item| collector.item(item));
+    Ok(Rc::new(DefTree {
+        defs: collector.defs,
+    }))
+}
+
+fn externs(cx: &dyn Context) -> Result<Rc<[metadata::ExternCrate]>, ErrorReported> {
+    // there is no place in the source to point at.
+    let span = Span::new(0, 0, FileId::Main);
+    let crates = cx
+        .options()
+        .externs
+        .iter()
+        .map(|(name, path)| {
+            let text = std::fs::read_to_string(path).map_err(|e| {
+                make_diag!(Error, span, E0235, name, path.display(), e).emit()
+            })?;
+            let dir = path.parent().unwrap_or(Path::new(""));
+            let metadata = metadata::Metadata::parse(&text, dir)
+                .map_err(|line| make_diag!(Error, span, E0236, path.display(), line).emit())?;
+            Ok(metadata::ExternCrate {
+                name: sym::Symbol::new(name),
+                metadata,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(crates.into())
+}
+
+fn definition(cx: &dyn Context, id: Id) -> Option<Definition> {
+    // expressions, locals and the like have ids too, but no definition.
+    cx.def_tree(id.file()).ok()?.defs.get(&id).copied()
+}
+
+fn def_span(cx: &dyn Context, id: Id) -> Option<Span> {
+    definition(cx, id).map(|def| def.span)
+}
+
+fn def_kind(cx: &dyn Context, id: Id) -> Option<DefKind> {
+    definition(cx, id).map(|def| def.kind)
 }
 
 dynamic_queries! {
     Providers ->
     fn lex(&self, id: FileId) -> Result<Rc<[Token]>, ErrorReported>;
     fn parse(&self, id: FileId) -> Result<Tree, ErrorReported>;
+    fn desugar(&self, id: FileId) -> Result<Tree, ErrorReported>;
     fn hir(&self, id: FileId) -> Result<HirTree, ErrorReported>;
+    fn entry_point(&self, id: FileId) -> Result<Id, ErrorReported>;
+    fn tests(&self, id: FileId) -> Result<Rc<[hir::TestFn]>, ErrorReported>;
     fn mir(&self, id: FileId) -> Result<mir::MirTree, ErrorReported>;
+    fn call_graph(&self, id: FileId) -> Result<Rc<mir::CallGraph>, ErrorReported>;
     fn codegen(&self, id: FileId) -> Result<(), ErrorReported>;
+    hooks {
+        /// Run on every MIR body after the built-in passes.
+        mir_passes: &'static [MirPass],
+        /// Run on the HIR and the MIR of every file once they are built.
+        lints: &'static [&'static dyn Lint],
+        /// Told what the compiler is working on, like to show progress.
+        observers: &'static [&'static dyn Observer],
+    }
 }
 
 macro dynamic_queries(
     $Providers:ident ->
     $(fn $name:ident(&self, $($ident:ident: $ty:ty),*$(,)?) $( -> $retty:ty )?;)*
+    hooks {
+        $($(#[$hook_meta:meta])* $hook:ident: $hook_ty:ty,)*
+    }
 ) {
     #[derive(Clone, Copy)]
     #[allow(unused_parens)]
@@ -312,6 +517,10 @@ macro dynamic_queries(
         $(
             pub $name: fn(&dyn Context, $($ty),*) -> ($($retty)?),
         )*
+        $(
+            $(#[$hook_meta])*
+            pub $hook: $hook_ty,
+        )*
     }
 
     impl Default for $Providers {
@@ -320,6 +529,9 @@ macro dynamic_queries(
                 $(
                     $name: |_cx, $(_: $ty),*| panic!(concat!(stringify!($name), " is not supported/initialized")),
                 )*
+                $(
+                    $hook: Default::default(),
+                )*
             }
         }
     }
@@ -327,11 +539,138 @@ macro dynamic_queries(
     $(
         #[allow(unused_parens)]
         fn $name(cx: &dyn Context, $($ident: $ty,)*) -> ($($retty)?) {
-            (cx.providers().$name)(cx, $($ident,)*)
+            // only entered when salsa has no cached result, so the time the
+            // span is open is the time it took to compute the query.
+            let _span = tracing::debug_span!(stringify!($name), $($ident = ?$ident),*).entered();
+            // every query is computed per file.
+            $(query_started(cx, stringify!($name), $ident);)*
+            let result = (cx.providers().$name)(cx, $($ident,)*);
+            audit_error_reported(stringify!($name), &result);
+            result
         }
     )*
 }
 
+fn query_started(cx: &dyn Context, query: &'static str, file: FileId) {
+    for observer in cx.providers().observers {
+        observer.query_started(query, file);
+    }
+}
+
+/// `ErrorReported` should only be returned after a diagnostic was emitted,
+/// otherwise compilation fails without telling the user why.
+fn audit_error_reported<T>(query: &str, result: &Result<T, ErrorReported>) {
+    if result.is_err() {
+        debug_assert!(
+            GlobalCtxt::with(GlobalCtxt::has_errors),
+            "`{query}` returned `ErrorReported` without emitting an error"
+        );
+    }
+}
+
+/// A custom pass over the MIR body of the function `Id`, registered with
+/// [`Providers::add_mir_pass`].
+pub type MirPass = fn(&dyn Context, Id, &mut mir::Function);
+
+/// A custom check, registered with [`Providers::add_lint`]. Lints are
+/// compiled into the driver, like the style rules of a course, and report
+/// problems with [`errors::DiagnosticBuilder`]. [`hir::visit::Visitor`]
+/// helps finding expressions in the HIR.
+///
+/// Functions taking the MIR are lints too.
+pub trait Lint {
+    /// What the lint is called, like `while_true`.
+    fn name(&self) -> &'static str;
+
+    /// Runs on the HIR of every file once it is built.
+    fn check_hir(&self, _cx: &dyn Context, _tree: &HirTree) {}
+
+    /// Runs on the MIR of every file once it is built.
+    fn check_mir(&self, _cx: &dyn Context, _tree: &mir::MirTree) {}
+}
+
+impl Lint for &'static dyn Lint {
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
+
+    fn check_hir(&self, cx: &dyn Context, tree: &HirTree) {
+        (**self).check_hir(cx, tree)
+    }
+
+    fn check_mir(&self, cx: &dyn Context, tree: &mir::MirTree) {
+        (**self).check_mir(cx, tree)
+    }
+}
+
+/// Events from the compiler as it works, registered with
+/// [`Providers::add_observer`]. Observers only watch: they are called in the
+/// middle of queries and must not call queries themselves.
+pub trait Observer {
+    /// A query started computing `file`. Queries that are cached do not start.
+    fn query_started(&self, _query: &'static str, _file: FileId) {}
+
+    /// The MIR body of the function `id` was built.
+    fn function_lowered(&self, _id: Id) {}
+
+    /// A diagnostic is about to be printed to stderr.
+    fn before_diagnostic(&self) {}
+
+    /// The compiler is done, after all diagnostics were printed.
+    fn finished(&self) {}
+}
+
+impl Observer for &'static dyn Observer {
+    fn query_started(&self, query: &'static str, file: FileId) {
+        (**self).query_started(query, file)
+    }
+
+    fn function_lowered(&self, id: Id) {
+        (**self).function_lowered(id)
+    }
+
+    fn before_diagnostic(&self) {
+        (**self).before_diagnostic()
+    }
+
+    fn finished(&self) {
+        (**self).finished()
+    }
+}
+
+impl<F: Fn(&dyn Context, &mir::MirTree)> Lint for F {
+    fn name(&self) -> &'static str {
+        std::any::type_name::<F>()
+    }
+
+    fn check_mir(&self, cx: &dyn Context, tree: &mir::MirTree) {
+        self(cx, tree)
+    }
+}
+
+impl Providers {
+    /// Runs `pass` after the passes that are already registered. This is how
+    /// embedders extend the compiler without changing it.
+    pub fn add_mir_pass(&mut self, pass: MirPass) {
+        self.mir_passes = append(self.mir_passes, pass);
+    }
+
+    /// Runs `lint` after the lints that are already registered.
+    pub fn add_lint(&mut self, lint: impl Lint + 'static) {
+        self.lints = append(self.lints, leak(lint) as &dyn Lint);
+    }
+
+    /// Tells `observer` about what the compiler is doing, after the
+    /// observers that are already registered.
+    pub fn add_observer(&mut self, observer: impl Observer + 'static) {
+        self.observers = append(self.observers, leak(observer) as &dyn Observer);
+    }
+}
+
+fn append<T: Copy>(list: &'static [T], x: T) -> &'static [T] {
+    Box::leak([list, &[x]].concat().into_boxed_slice())
+}
+
 impl fmt::Debug for Providers {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Providers").finish_non_exhaustive()
@@ -341,22 +680,120 @@ impl fmt::Debug for Providers {
 #[salsa::database(ContextStorage)]
 pub struct GlobalCtxt {
     storage: salsa::Storage<GlobalCtxt>,
+    errors: Cell<usize>,
+    warnings: Cell<usize>,
+    captured_diagnostics: RefCell<Option<Vec<Diagnostic>>>,
 }
 
 impl GlobalCtxt {
     pub fn create_and_then(options: Options, f: impl FnOnce(GlobalCtxt) -> GlobalCtxt) {
         let mut ctxt = Self {
             storage: Default::default(),
+            errors: Cell::new(0),
+            warnings: Cell::new(0),
+            captured_diagnostics: RefCell::new(None),
         };
 
         ctxt.set_options(Box::leak(Box::new(options)));
-        ctxt.set_interners(Box::leak(Box::new(Interners::fresh())));
-
-        GLOBAL_CTXT.with(|cell| cell.set(f(ctxt)).ok().expect("`create` called twice"))
+        let interners = leak(Interners::fresh());
+        ctxt.set_interners(interners);
+        ctxt.set_file_loader(&DiskLoader);
+        ctxt.set_file_overrides(Default::default());
+        INTERNERS.with(|cell| assert!(cell.set(interners).is_ok(), "`create` called twice"));
+        interners.syntax.install().expect("`create` called twice");
+
+        let ctxt = f(ctxt);
+        GLOBAL_CTXT.with(|cell| {
+            let mut cell = cell.borrow_mut();
+            assert!(cell.is_none(), "`create` called twice");
+            *cell = Some(ctxt);
+        })
     }
 
     pub fn with<T>(f: impl FnOnce(&GlobalCtxt) -> T) -> T {
-        GLOBAL_CTXT.with(|cell| f(cell.get().expect("`with` called before `create`")))
+        GLOBAL_CTXT.with(|cell| {
+            f(cell.borrow().as_ref().expect("`with` called before `create`"))
+        })
+    }
+
+    /// Like [`GlobalCtxt::with`], for changing inputs between compilations.
+    /// Panics when called while a query is running.
+    pub fn with_mut<T>(f: impl FnOnce(&mut GlobalCtxt) -> T) -> T {
+        GLOBAL_CTXT.with(|cell| {
+            f(cell
+                .borrow_mut()
+                .as_mut()
+                .expect("`with_mut` called before `create`"))
+        })
+    }
+
+    /// The number of errors emitted since the diagnostics were last taken
+    /// with [`GlobalCtxt::take_diagnostics`].
+    pub fn error_count(&self) -> usize {
+        self.errors.get()
+    }
+
+    pub fn warning_count(&self) -> usize {
+        self.warnings.get()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.error_count() != 0
+    }
+
+    /// Collects emitted diagnostics instead of printing them, until they
+    /// are taken with [`GlobalCtxt::take_diagnostics`].
+    pub fn capture_diagnostics(&self) {
+        self.captured_diagnostics.borrow_mut().get_or_insert_with(Vec::new);
+    }
+
+    /// Takes the captured diagnostics and starts counting errors and warnings
+    /// from zero again.
+    pub fn take_diagnostics(&self) -> Vec<Diagnostic> {
+        self.errors.set(0);
+        self.warnings.set(0);
+        self.captured_diagnostics
+            .borrow_mut()
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn record_diagnostic(&self, severity: DiagnosticSeverity) {
+        let count = match severity {
+            DiagnosticSeverity::Error => &self.errors,
+            DiagnosticSeverity::Warning => &self.warnings,
+        };
+        count.set(count.get() + 1);
+    }
+
+    /// Makes `id` have `contents` instead of what is on disk. Queries that
+    /// depend on the file are recomputed the next time they are requested.
+    pub fn set_file_contents(&mut self, id: FileId, contents: String) {
+        let mut overrides = (*self.file_overrides()).clone();
+        overrides.insert(id, contents);
+        self.set_file_overrides(Rc::new(overrides));
+    }
+
+    /// Starts a new revision. Files are read from the loader again the next
+    /// time they are needed, and queries that reported diagnostics run again
+    /// to report them again. Overridden contents are kept.
+    pub fn new_revision(&mut self) {
+        self.storage.salsa_runtime_mut().synthetic_write(salsa::Durability::LOW);
+    }
+
+    /// Adds a file that only exists in memory. `name` is used in diagnostics
+    /// and to resolve modules declared in the file.
+    pub fn add_virtual_file(&mut self, name: impl Into<PathBuf>, contents: String) -> FileId {
+        let id = self.locate(FileLocator::Unresolved(name.into()));
+        self.set_file_contents(id, contents);
+        id
+    }
+
+    /// The same as [`Context::interners`], but without going through the query system.
+    /// Symbols are interned for every identifier, so this needs to be fast.
+    pub fn interners_untracked() -> &'static Interners {
+        INTERNERS.with(|cell| *cell.get().expect("`interners_untracked` called before `create`"))
     }
 }
 
@@ -367,16 +804,19 @@ pub fn leak<T>(x: T) -> &'static T {
 }
 
 fn get_file(gcx: &dyn Context, locator: FileLocator) -> Option<String> {
-    if locator == FileLocator::Main {
-        let p = &gcx.options().path;
-        let res = std::fs::read_to_string(p).ok();
-        if res.is_none() {
-            eprintln!("ERROR: failed to read file `{}`", p.display());
-        }
-        res
-    } else {
-        todo!()
+    let id = gcx.locate(locator);
+    if let Some(contents) = gcx.file_overrides().get(&id) {
+        return Some(contents.clone());
+    }
+    // files on disk can change at any time, see `GlobalCtxt::new_revision`.
+    gcx.salsa_runtime().report_untracked_read();
+    let p = gcx.file_path(id);
+    let res = gcx.file_loader().load(p);
+    if res.is_none() {
+        GlobalCtxt::with(|gcx| gcx.record_diagnostic(DiagnosticSeverity::Error));
+        eprintln!("ERROR: failed to read file `{}`", p.display());
     }
+    res
 }
 
 /*
@@ -389,6 +829,6 @@ fn file_list(cx: &dyn Context) -> &'static [PathBuf] {
 fn file_path(cx: &dyn Context, id: FileId) -> &'static Path {
     match id {
         FileId::Main => Box::leak(cx.options().path.clone().into_boxed_path()),
-        FileId::Other(_) => todo!(),
+        FileId::Other(_) => cx.interners().paths.borrow().paths[&id],
     }
 }
@@ -0,0 +1,105 @@
+//! `terryc-output.json`, a list of the files a compilation produced so that
+//! build systems can track them without knowing where terryc puts things.
+
+use std::fmt::Write;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::Options;
+
+/// The name of the manifest, which is written next to the artifacts it lists.
+pub const MANIFEST_PATH: &str = "terryc-output.json";
+/// Where the linked program is written.
+pub const EXECUTABLE_PATH: &str = "out";
+/// Where the object file is written with `--lib`, which skips linking.
+pub const OBJECT_PATH: &str = "out.o";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArtifactKind {
+    Executable,
+    /// An object file, for `--lib`.
+    Object,
+    /// The functions of the object file, for `--extern`.
+    Metadata,
+    /// Written by the compiled program when it exits, so it has no hash yet.
+    CoverageReport,
+}
+
+impl ArtifactKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            ArtifactKind::Executable => "executable",
+            ArtifactKind::Object => "object",
+            ArtifactKind::Metadata => "metadata",
+            ArtifactKind::CoverageReport => "coverage-report",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Manifest {
+    artifacts: Vec<(PathBuf, ArtifactKind)>,
+}
+
+impl Manifest {
+    pub fn add(&mut self, path: impl Into<PathBuf>, kind: ArtifactKind) {
+        self.artifacts.push((path.into(), kind));
+    }
+
+    /// Renders the manifest, hashing every artifact that exists on disk.
+    pub fn to_json(&self, options: &Options) -> io::Result<String> {
+        let mut out = String::from("{\n");
+        writeln!(out, "  \"options\": {{").unwrap();
+        writeln!(out, "    \"path\": {},", json_path(&options.path)).unwrap();
+        writeln!(out, "    \"optimize\": {},", options.optimize).unwrap();
+        writeln!(out, "    \"coverage\": {},", options.coverage).unwrap();
+        writeln!(out, "    \"lib\": {},", options.lib).unwrap();
+        writeln!(out, "    \"test\": {}", options.test).unwrap();
+        writeln!(out, "  }},").unwrap();
+        writeln!(out, "  \"artifacts\": [").unwrap();
+        for (i, (path, kind)) in self.artifacts.iter().enumerate() {
+            let sha256 = match std::fs::read(path) {
+                Ok(contents) => format!("\"{:x}\"", Sha256::digest(&contents)),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => "null".into(),
+                Err(e) => return Err(e),
+            };
+            let comma = if i + 1 < self.artifacts.len() { "," } else { "" };
+            writeln!(
+                out,
+                "    {{ \"path\": {}, \"kind\": \"{}\", \"sha256\": {sha256} }}{comma}",
+                json_path(path),
+                kind.as_str(),
+            )
+            .unwrap();
+        }
+        writeln!(out, "  ]").unwrap();
+        out.push_str("}\n");
+        Ok(out)
+    }
+
+    /// Where the manifest goes, in the directory of the first artifact.
+    pub fn path(&self) -> PathBuf {
+        let dir = self.artifacts.first().and_then(|(path, _)| path.parent());
+        dir.unwrap_or(Path::new("")).join(MANIFEST_PATH)
+    }
+
+    pub fn write(&self, options: &Options) -> io::Result<()> {
+        std::fs::write(self.path(), self.to_json(options)?)
+    }
+}
+
+fn json_path(path: &Path) -> String {
+    let mut out = String::from("\"");
+    for c in path.to_string_lossy().chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
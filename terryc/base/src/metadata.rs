@@ -0,0 +1,140 @@
+//! `out.tmeta`, written next to the object file of a `--lib` build. It lists
+//! the functions of the library so that a program can call them as
+//! `name::function(...)` after `--extern name=path/to/out.tmeta`, without
+//! compiling the library again.
+//!
+//! The format is text, one entry per line:
+//!
+//! ```text
+//! terry library 1
+//! object out.o
+//! fn add(i32, i32) -> i32
+//! ```
+
+use std::fmt::Write;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::ast::TyKind;
+use crate::hir::{HirTree, Item};
+use crate::sym::Symbol;
+
+/// Where the metadata is written, next to the object file.
+pub const METADATA_PATH: &str = "out.tmeta";
+
+/// The first line, which changes whenever the format does.
+const HEADER: &str = "terry library 1";
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ExternFn {
+    pub name: Symbol,
+    pub args: Vec<TyKind>,
+    pub ret: TyKind,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Metadata {
+    /// The object file with the code of the functions.
+    pub object: PathBuf,
+    pub functions: Vec<ExternFn>,
+}
+
+/// A library given with `--extern`.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ExternCrate {
+    /// What calls to its functions are prefixed with.
+    pub name: Symbol,
+    pub metadata: Metadata,
+}
+
+impl ExternCrate {
+    pub fn function(&self, name: Symbol) -> Option<&ExternFn> {
+        self.metadata.functions.iter().find(|f| f.name == name)
+    }
+}
+
+impl Metadata {
+    /// The functions at the top level of `tree`, whose code is in `object`.
+    /// Functions that take or return arrays, structs or enums are left out,
+    /// as their layout is not part of the metadata.
+    pub fn of_library(tree: &HirTree, object: impl Into<PathBuf>) -> Metadata {
+        let functions = tree
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Fn(f) => Some(ExternFn {
+                    name: f.name,
+                    args: f.args.iter().map(|arg| arg.ty).collect(),
+                    ret: f.ret,
+                }),
+                _ => None,
+            })
+            .filter(|f| f.args.iter().chain([&f.ret]).all(|&ty| is_primitive(ty)))
+            .collect();
+        Metadata {
+            object: object.into(),
+            functions,
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = format!("{HEADER}\nobject {}\n", self.object.display());
+        for f in &self.functions {
+            let args: Vec<_> = f.args.iter().map(TyKind::to_string).collect();
+            writeln!(out, "fn {}({}) -> {}", f.name, args.join(", "), f.ret).unwrap();
+        }
+        out
+    }
+
+    /// Reads what [`Metadata::to_text`] wrote, with the object file relative
+    /// to `dir`. Fails with the number of the first malformed line.
+    pub fn parse(text: &str, dir: &Path) -> Result<Metadata, usize> {
+        let mut lines = text.lines().zip(1usize..);
+        if lines.next().map(|(line, _)| line) != Some(HEADER) {
+            return Err(1);
+        }
+        let object = match lines.next() {
+            Some((line, n)) => dir.join(line.strip_prefix("object ").ok_or(n)?),
+            None => return Err(2),
+        };
+        let functions = lines
+            .map(|(line, n)| parse_fn(line).ok_or(n))
+            .collect::<Result<_, _>>()?;
+        Ok(Metadata { object, functions })
+    }
+
+    pub fn write(&self) -> io::Result<()> {
+        std::fs::write(METADATA_PATH, self.to_text())
+    }
+}
+
+/// Parses `fn name(i32, bool) -> i32`.
+fn parse_fn(line: &str) -> Option<ExternFn> {
+    let (name, rest) = line.strip_prefix("fn ")?.split_once('(')?;
+    let (args, ret) = rest.split_once(") -> ")?;
+    let args = match args {
+        "" => vec![],
+        args => args.split(", ").map(parse_ty).collect::<Option<_>>()?,
+    };
+    Some(ExternFn {
+        name: Symbol::new(name),
+        args,
+        ret: parse_ty(ret)?,
+    })
+}
+
+fn is_primitive(ty: TyKind) -> bool {
+    matches!(ty, TyKind::I32 | TyKind::F32 | TyKind::Bool | TyKind::String | TyKind::Unit)
+}
+
+/// Parses one of the types [`is_primitive`] accepts.
+fn parse_ty(ty: &str) -> Option<TyKind> {
+    Some(match ty {
+        "i32" => TyKind::I32,
+        "f32" => TyKind::F32,
+        "bool" => TyKind::Bool,
+        "string" => TyKind::String,
+        "unit" => TyKind::Unit,
+        _ => return None,
+    })
+}
@@ -0,0 +1,135 @@
+use std::fmt::{self, Write};
+
+use crate::data::{FxIndexMap, FxIndexSet};
+use crate::sym::Symbol;
+use crate::Id;
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct CallGraphNode {
+    pub name: Symbol,
+    /// Functions called from this function, in order of first call.
+    pub callees: FxIndexSet<Id>,
+}
+
+/// Which functions call which, built from the call terminators in MIR.
+/// Calls to builtins are not included.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct CallGraph {
+    pub nodes: FxIndexMap<Id, CallGraphNode>,
+}
+
+impl CallGraph {
+    /// All functions, with callees before their callers where possible, which
+    /// is the order an inliner wants to visit them in. Functions in a cycle
+    /// are visited in the order they were reached.
+    pub fn bottom_up(&self) -> Vec<Id> {
+        let mut visited = FxIndexSet::default();
+        let mut order = Vec::with_capacity(self.nodes.len());
+        for &id in self.nodes.keys() {
+            self.post_order(id, &mut visited, &mut order);
+        }
+        order
+    }
+
+    fn post_order(&self, id: Id, visited: &mut FxIndexSet<Id>, order: &mut Vec<Id>) {
+        if !visited.insert(id) {
+            return;
+        }
+        for &callee in &self.nodes[&id].callees {
+            self.post_order(callee, visited, order);
+        }
+        order.push(id);
+    }
+
+    /// Functions that can be called, directly or indirectly, from `root`.
+    pub fn reachable_from(&self, root: Id) -> FxIndexSet<Id> {
+        let mut reachable = FxIndexSet::default();
+        let mut stack = vec![root];
+        while let Some(id) = stack.pop() {
+            if reachable.insert(id) {
+                stack.extend(self.nodes[&id].callees.iter().copied());
+            }
+        }
+        reachable
+    }
+
+    /// Groups of functions that call each other, directly or through other
+    /// functions in the group, including functions that call themselves.
+    /// Inlining or evaluating such calls at compile time would never finish.
+    pub fn cycles(&self) -> Vec<Vec<Id>> {
+        let mut tarjan = Tarjan {
+            graph: self,
+            index: FxIndexMap::default(),
+            low_link: FxIndexMap::default(),
+            stack: vec![],
+            cycles: vec![],
+        };
+        for &id in self.nodes.keys() {
+            if !tarjan.index.contains_key(&id) {
+                tarjan.visit(id);
+            }
+        }
+        tarjan.cycles
+    }
+
+    /// Whether `id` can end up calling itself.
+    pub fn is_recursive(&self, id: Id) -> bool {
+        self.cycles().iter().any(|cycle| cycle.contains(&id))
+    }
+
+    /// Renders the graph in the Graphviz `dot` format.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph callgraph {\n");
+        self.write_dot(&mut out).expect("writing to a string cannot fail");
+        out.push_str("}\n");
+        out
+    }
+
+    fn write_dot(&self, out: &mut String) -> fmt::Result {
+        for (i, node) in self.nodes.values().enumerate() {
+            writeln!(out, "    n{i} [label={:?}];", node.name.get_str())?;
+        }
+        for (i, node) in self.nodes.values().enumerate() {
+            for callee in &node.callees {
+                writeln!(out, "    n{i} -> n{};", self.nodes.get_index_of(callee).unwrap())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Tarjan's strongly connected components algorithm.
+struct Tarjan<'a> {
+    graph: &'a CallGraph,
+    index: FxIndexMap<Id, usize>,
+    low_link: FxIndexMap<Id, usize>,
+    stack: Vec<Id>,
+    cycles: Vec<Vec<Id>>,
+}
+
+impl Tarjan<'_> {
+    fn visit(&mut self, id: Id) {
+        let index = self.index.len();
+        self.index.insert(id, index);
+        self.low_link.insert(id, index);
+        self.stack.push(id);
+
+        for &callee in &self.graph.nodes[&id].callees {
+            if !self.index.contains_key(&callee) {
+                self.visit(callee);
+                self.low_link[&id] = self.low_link[&id].min(self.low_link[&callee]);
+            } else if self.stack.contains(&callee) {
+                self.low_link[&id] = self.low_link[&id].min(self.index[&callee]);
+            }
+        }
+
+        if self.low_link[&id] == index {
+            let start = self.stack.iter().rposition(|&x| x == id).unwrap();
+            let component = self.stack.split_off(start);
+            let calls_itself = self.graph.nodes[&id].callees.contains(&id);
+            if component.len() > 1 || calls_itself {
+                self.cycles.push(component);
+            }
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! Control flow graph utilities shared by MIR passes.
+
+use std::fmt::{self, Write};
+
+use index_vec::IndexVec;
+
+use super::{BasicBlock, Body, MirTree, Terminator};
+
+impl Terminator {
+    /// The blocks control can continue to. For `SwitchInt` these are in the
+    /// order of its targets, the `else` target last.
+    pub fn successors(&self) -> Vec<BasicBlock> {
+        match self {
+            Terminator::Goto(target)
+            | Terminator::Call {
+                destination: (_, target),
+                ..
+            }
+            | Terminator::Assert { target, .. } => vec![*target],
+            Terminator::SwitchInt(_, targets) => targets.targets.clone(),
+            Terminator::Return(_) | Terminator::Unreachable => vec![],
+            Terminator::ReplacedAfterConstruction => unreachable!(),
+        }
+    }
+
+    /// Like [`Terminator::successors`], for redirecting edges.
+    pub fn successors_mut(&mut self) -> Vec<&mut BasicBlock> {
+        match self {
+            Terminator::Goto(target)
+            | Terminator::Call {
+                destination: (_, target),
+                ..
+            }
+            | Terminator::Assert { target, .. } => vec![target],
+            Terminator::SwitchInt(_, targets) => targets.targets.iter_mut().collect(),
+            Terminator::Return(_) | Terminator::Unreachable => vec![],
+            Terminator::ReplacedAfterConstruction => unreachable!(),
+        }
+    }
+}
+
+impl Body {
+    /// The blocks that can jump to each block. Compute this once per pass
+    /// rather than per block, it has to look at the whole body.
+    pub fn predecessors(&self) -> IndexVec<BasicBlock, Vec<BasicBlock>> {
+        let mut preds = IndexVec::from_vec(vec![vec![]; self.blocks.len()]);
+        for (bb, data) in self.blocks.iter_enumerated() {
+            for succ in data.terminator.successors() {
+                preds[succ].push(bb);
+            }
+        }
+        preds
+    }
+
+    /// The blocks reachable from the entry, each one before its successors
+    /// unless it is the target of a back edge.
+    pub fn reverse_post_order(&self) -> Vec<BasicBlock> {
+        let mut visited = IndexVec::from_vec(vec![false; self.blocks.len()]);
+        let mut post_order = Vec::with_capacity(self.blocks.len());
+        // a block together with how many of its successors were visited
+        let start = BasicBlock::from_usize(0);
+        let mut stack = vec![(start, 0)];
+        visited[start] = true;
+        while let Some((bb, next)) = stack.last_mut() {
+            let succs = self.blocks[*bb].terminator.successors();
+            if let Some(&succ) = succs.get(*next) {
+                *next += 1;
+                if !visited[succ] {
+                    visited[succ] = true;
+                    stack.push((succ, 0));
+                }
+            } else {
+                post_order.push(*bb);
+                stack.pop();
+            }
+        }
+        post_order.reverse();
+        post_order
+    }
+
+    /// The dominator tree of the blocks reachable from the entry.
+    pub fn dominators(&self) -> Dominators {
+        // "A Simple, Fast Dominance Algorithm" by Cooper, Harvey and Kennedy.
+        let rpo = self.reverse_post_order();
+        let mut rpo_index: IndexVec<BasicBlock, usize> =
+            IndexVec::from_vec(vec![usize::MAX; self.blocks.len()]);
+        for (i, &bb) in rpo.iter().enumerate() {
+            rpo_index[bb] = i;
+        }
+        let preds = self.predecessors();
+        let mut idom: IndexVec<BasicBlock, Option<BasicBlock>> =
+            IndexVec::from_vec(vec![None; self.blocks.len()]);
+        let start = rpo[0];
+        idom[start] = Some(start);
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &bb in &rpo[1..] {
+                let new_idom = preds[bb]
+                    .iter()
+                    .copied()
+                    .filter(|&pred| idom[pred].is_some())
+                    .reduce(|a, b| intersect(&idom, &rpo_index, a, b));
+                if new_idom.is_some() && new_idom != idom[bb] {
+                    idom[bb] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+        Dominators { idom }
+    }
+}
+
+/// The closest common dominator of `a` and `b`, found by walking up the
+/// (partial) dominator tree.
+fn intersect(
+    idom: &IndexVec<BasicBlock, Option<BasicBlock>>,
+    rpo_index: &IndexVec<BasicBlock, usize>,
+    mut a: BasicBlock,
+    mut b: BasicBlock,
+) -> BasicBlock {
+    while a != b {
+        while rpo_index[a] > rpo_index[b] {
+            a = idom[a].unwrap();
+        }
+        while rpo_index[b] > rpo_index[a] {
+            b = idom[b].unwrap();
+        }
+    }
+    a
+}
+
+#[derive(Debug, Clone)]
+pub struct Dominators {
+    /// The entry block is its own immediate dominator, unreachable blocks
+    /// have none.
+    idom: IndexVec<BasicBlock, Option<BasicBlock>>,
+}
+
+impl Dominators {
+    /// The closest block every path from the entry to `bb` goes through, or
+    /// `None` for the entry itself and unreachable blocks.
+    pub fn immediate_dominator(&self, bb: BasicBlock) -> Option<BasicBlock> {
+        self.idom[bb].filter(|&idom| idom != bb)
+    }
+
+    pub fn is_reachable(&self, bb: BasicBlock) -> bool {
+        self.idom[bb].is_some()
+    }
+
+    /// Whether every path from the entry to `b` goes through `a`. Every
+    /// block dominates itself.
+    pub fn dominates(&self, a: BasicBlock, mut b: BasicBlock) -> bool {
+        if !self.is_reachable(b) {
+            return false;
+        }
+        loop {
+            if a == b {
+                return true;
+            }
+            match self.immediate_dominator(b) {
+                Some(idom) => b = idom,
+                None => return false,
+            }
+        }
+    }
+}
+
+impl MirTree {
+    /// Renders the blocks of every function in the Graphviz `dot` format,
+    /// one cluster per function.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph mir {\n");
+        self.write_dot(&mut out)
+            .expect("writing to a string cannot fail");
+        out.push_str("}\n");
+        out
+    }
+
+    fn write_dot(&self, out: &mut String) -> fmt::Result {
+        for (i, f) in self.functions.values().enumerate() {
+            writeln!(out, "    subgraph cluster_{i} {{")?;
+            writeln!(out, "        label={:?};", f.name.get_str())?;
+            for (bb, data) in f.body.blocks.iter_enumerated() {
+                let mut label = format!("{bb:?}\n");
+                for stmt in &data.statements {
+                    writeln!(label, "{stmt:?}")?;
+                }
+                writeln!(label, "{:?}", data.terminator)?;
+                writeln!(out, "        f{i}_{bb:?} [shape=box, label={label:?}];")?;
+            }
+            for (bb, data) in f.body.blocks.iter_enumerated() {
+                for succ in data.terminator.successors() {
+                    writeln!(out, "        f{i}_{bb:?} -> f{i}_{succ:?};")?;
+                }
+            }
+            writeln!(out, "    }}")?;
+        }
+        Ok(())
+    }
+}
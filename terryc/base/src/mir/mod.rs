@@ -1,27 +1,105 @@
+mod call_graph;
+mod cfg;
+
 use core::fmt;
 use std::fmt::Debug;
 use std::rc::Rc;
 
-use rustc_hash::FxHashMap;
-
 use crate::ast::{BinOpKind, TyKind, UnOpKind};
-use crate::hir::{Literal, Resolution};
+use crate::data::FxIndexMap;
+use crate::hir::{InlineKind, Literal, Resolution};
 use crate::sym::Symbol;
 use crate::{Id, TyList};
 
+pub use call_graph::*;
+pub use cfg::*;
+
 index_vec::define_index_type! {
     pub struct Local = u32;
     DEBUG_FORMAT = "_{}";
+    DEFAULT = Local::from_raw(0);
 }
 
 #[derive(PartialEq, Eq, Hash, Debug, Clone)]
 pub struct LocalData {
     pub ty: TyKind,
+    /// Declared with `let mut`. Arguments and temporaries are not.
+    pub mutable: bool,
+}
+
+impl LocalData {
+    /// An argument or a temporary, which the program cannot assign to.
+    pub fn new(ty: TyKind) -> Self {
+        LocalData { ty, mutable: false }
+    }
+}
+
+/// A step from a place into a part of it.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum ProjectionElem {
+    /// A field of a struct, counted in declaration order, or an element of a
+    /// tuple.
+    Field(u32),
+    /// An element, at the index stored in the local.
+    Index(Local),
+    /// A field of a variant of an enum, which has to be the active one.
+    Variant(u32, u32),
+}
+
+/// A memory location: a local, or a part of one like `a[i].x`.
+#[derive(PartialEq, Eq, Hash, Clone)]
+pub struct Place {
+    pub local: Local,
+    pub projection: Vec<ProjectionElem>,
+}
+
+impl ProjectionElem {
+    /// The type of this part of a value of type `base`.
+    pub fn ty(self, base: TyKind) -> TyKind {
+        match (self, base) {
+            (ProjectionElem::Index(_), _) => base.element().expect("indexed a non-array"),
+            (ProjectionElem::Field(field), _) => base.field_ty(field),
+            (ProjectionElem::Variant(variant, field), TyKind::Enum(def)) => {
+                def.variants[variant as usize].fields[field as usize]
+            }
+            (ProjectionElem::Variant(..), _) => unreachable!("variant of a non-enum"),
+        }
+    }
+}
+
+impl Place {
+    /// The local this place is, unless it refers to a part of it.
+    pub fn as_local(&self) -> Option<Local> {
+        self.projection.is_empty().then_some(self.local)
+    }
+}
+
+impl From<Local> for Place {
+    fn from(local: Local) -> Self {
+        Place {
+            local,
+            projection: vec![],
+        }
+    }
+}
+
+impl Debug for Place {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.local)?;
+        for elem in &self.projection {
+            match elem {
+                ProjectionElem::Field(field) => write!(f, ".{field}")?,
+                ProjectionElem::Index(index) => write!(f, "[{index:?}]")?,
+                ProjectionElem::Variant(variant, field) => write!(f, "@{variant}.{field}")?,
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(PartialEq, Eq, Hash, Debug, Clone)]
 pub enum Operand {
-    Copy(Local),
+    Copy(Place),
     Const(Literal),
 }
 
@@ -50,23 +128,42 @@ pub enum Rvalue {
     Use(Operand),
     BinaryOp(BinOpKind, Operand, Operand),
     UnaryOp(UnOpKind, Operand),
+    /// The operand converted to `TyKind`, which is `i32` or `f32`. Floats are
+    /// truncated towards zero when converted to integers, saturating at the
+    /// bounds of `i32`, and `NaN` becomes `0`.
+    Cast(Operand, TyKind),
+    /// An array, tuple or struct of type `TyKind` with the operands as its
+    /// elements or fields.
+    Aggregate(TyKind, Vec<Operand>),
+    /// The variant of the enum in the place, as an `i32`.
+    Discriminant(Place),
+    /// The variant with the index of the enum `TyKind`, with the operands as
+    /// its fields.
+    Variant(TyKind, u32, Vec<Operand>),
 }
 
 #[derive(PartialEq, Eq, Hash, Clone)]
 pub enum Statement {
-    Assign(Local, Rvalue),
+    Assign(Place, Rvalue),
+    /// The local starts being used. Its value is undefined until assigned.
+    StorageLive(Local),
+    /// The local goes out of scope and its storage can be reused.
+    StorageDead(Local),
 }
 
 impl Debug for Statement {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Assign(local, rvalue) => write!(f, "{local:?} = {rvalue:?}"),
+            Self::Assign(place, rvalue) => write!(f, "{place:?} = {rvalue:?}"),
+            Self::StorageLive(local) => write!(f, "StorageLive({local:?})"),
+            Self::StorageDead(local) => write!(f, "StorageDead({local:?})"),
         }
     }
 }
 
 #[derive(PartialEq, Eq, Hash, Debug, Clone)]
 pub struct Targets {
+    /// Bools are switched on as `0` for `false` and `1` for `true`.
     pub values: Vec<i32>,
     // last: else
     pub targets: Vec<BasicBlock>,
@@ -95,6 +192,15 @@ pub enum Terminator {
         args: Vec<Rvalue>,
         destination: (Local, BasicBlock),
     },
+    /// Continues to `target` if `cond` is true, otherwise stops the program
+    /// after printing `msg`.
+    Assert {
+        cond: Operand,
+        msg: Symbol,
+        target: BasicBlock,
+    },
+    /// Marks code that can never be reached.
+    Unreachable,
     ReplacedAfterConstruction,
 }
 
@@ -112,6 +218,10 @@ impl fmt::Debug for Terminator {
                 types: _,
                 destination: (local, bb),
             } => write!(f, "{local:?} = {callee:?}({args:?}); goto {bb:?}"),
+            Self::Assert { cond, msg, target } => {
+                write!(f, "assert({cond:?}, {msg:?}); goto {target:?}")
+            }
+            Self::Unreachable => write!(f, "unreachable"),
             Self::ReplacedAfterConstruction => unreachable!(),
         }
     }
@@ -134,21 +244,35 @@ pub struct Function {
     pub name: Symbol,
     pub args: TyList,
     pub ret: TyKind,
+    pub inline: Option<InlineKind>,
 }
 
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub struct MirTree {
-    pub functions: Rc<FxHashMap<Id, Function>>,
+    pub functions: Rc<FxIndexMap<Id, Rc<Function>>>,
+    /// The calls written in the program, from before any of them were
+    /// inlined.
+    pub call_graph: Rc<CallGraph>,
 }
 
 #[derive(PartialEq, Eq, Hash, Debug, Default, Clone)]
 pub struct Body {
     pub blocks: index_vec::IndexVec<BasicBlock, BasicBlockData>,
     pub locals: index_vec::IndexVec<Local, LocalData>,
+    /// The local right after the arguments, which every `return` writes the
+    /// value of the function to.
+    pub return_place: Local,
 }
 
 impl Body {
     pub fn expect_last_mut(&mut self) -> &mut BasicBlockData {
         self.blocks.last_mut().expect("expected last basic block")
     }
+
+    pub fn place_ty(&self, place: &Place) -> TyKind {
+        place
+            .projection
+            .iter()
+            .fold(self.locals[place.local].ty, |ty, elem| elem.ty(ty))
+    }
 }
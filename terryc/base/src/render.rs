@@ -0,0 +1,127 @@
+//! How output for people is drawn: diagnostics, and the results of
+//! `terryc test` and `terryc bench`. They all ask a [`RenderConfig`] instead of
+//! the options, so that `--use-ascii` and `--color` apply everywhere.
+
+use std::io::{self, IsTerminal};
+
+use crate::{Context, GlobalCtxt, Options};
+
+/// When to color the output, selected with `--color`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Color output that goes to a terminal, unless `NO_COLOR` is set or
+    /// `--use-ascii` is given.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Error,
+    Warning,
+    Success,
+}
+
+impl Style {
+    fn ansi(self) -> &'static str {
+        match self {
+            Style::Error => "\x1b[1;31m",
+            Style::Warning => "\x1b[1;33m",
+            Style::Success => "\x1b[32m",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderConfig {
+    /// Draw with ASCII characters only.
+    pub ascii: bool,
+    pub color: bool,
+    /// The width of the terminal, `None` if the output does not go to one and
+    /// is not wrapped.
+    pub width: Option<usize>,
+}
+
+impl RenderConfig {
+    /// The config for output to a stream, which is a terminal if `terminal`.
+    pub fn new(options: &Options, terminal: bool) -> Self {
+        let color = match options.color {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                terminal && !options.use_ascii && std::env::var_os("NO_COLOR").is_none()
+            }
+        };
+        Self {
+            ascii: options.use_ascii,
+            color,
+            width: terminal.then(terminal_width),
+        }
+    }
+
+    /// The config for diagnostics, which are written to stderr.
+    pub fn diagnostics() -> Self {
+        GlobalCtxt::with(|gcx| Self::new(gcx.options(), io::stderr().is_terminal()))
+    }
+
+    /// The config for results, which are written to stdout.
+    pub fn stdout() -> Self {
+        GlobalCtxt::with(|gcx| Self::new(gcx.options(), io::stdout().is_terminal()))
+    }
+
+    pub fn ariadne(self) -> ariadne::Config {
+        let char_set = if self.ascii { ariadne::CharSet::Ascii } else { ariadne::CharSet::Unicode };
+        ariadne::Config::default().with_char_set(char_set).with_color(self.color)
+    }
+
+    /// `text` in `style`, if colors are on.
+    pub fn paint(self, text: &str, style: Style) -> String {
+        if self.color {
+            format!("{}{text}\x1b[0m", style.ansi())
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// Breaks the lines of `text` between words to fit the terminal, indenting
+    /// the lines after the first by `indent` columns. Words longer than a line
+    /// are not broken.
+    pub fn wrap(self, text: &str, indent: usize) -> String {
+        let Some(width) = self.width else { return text.to_string() };
+        let width = width.saturating_sub(indent).max(20);
+        let pad = " ".repeat(indent);
+        let mut out = String::with_capacity(text.len());
+        for (i, line) in text.lines().enumerate() {
+            if i > 0 {
+                out.push('\n');
+                out.push_str(&pad);
+            }
+            let mut column = 0;
+            for word in line.split(' ') {
+                let len = word.chars().count();
+                if column > 0 && column + 1 + len > width {
+                    out.push('\n');
+                    out.push_str(&pad);
+                    column = 0;
+                } else if column > 0 {
+                    out.push(' ');
+                    column += 1;
+                }
+                out.push_str(word);
+                column += len;
+            }
+        }
+        out
+    }
+}
+
+/// The width the shell exports as `COLUMNS`, 80 columns otherwise.
+fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .filter(|&columns| columns > 0)
+        .unwrap_or(80)
+}
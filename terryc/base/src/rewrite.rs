@@ -0,0 +1,89 @@
+//! Changing source text by replacing spans of it, for tools that edit code
+//! like the minimizer. Every span refers to the original text, so the
+//! replacements do not have to account for each other.
+
+use std::fmt;
+
+use crate::{FileId, Span};
+
+/// Replacements of spans of one file, applied together by
+/// [`SourceRewriter::rewrite`].
+#[derive(Debug, Clone)]
+pub struct SourceRewriter {
+    file: FileId,
+    /// Sorted by position and never overlapping.
+    edits: Vec<(Span, String)>,
+}
+
+/// A replacement that was rejected because it overlaps one that was added
+/// before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overlap {
+    pub existing: Span,
+    pub new: Span,
+}
+
+impl fmt::Display for Overlap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "replacement of {:?} overlaps {:?}", self.new, self.existing)
+    }
+}
+
+impl SourceRewriter {
+    pub fn new(file: FileId) -> Self {
+        Self { file, edits: vec![] }
+    }
+
+    /// Replaces the text at `span` with `text`. Insertions, which have an
+    /// empty span, only overlap replacements they are strictly inside of,
+    /// and several at the same position are made in the order they were
+    /// added.
+    pub fn replace(&mut self, span: Span, text: impl Into<String>) -> Result<(), Overlap> {
+        assert_eq!(span.file(), self.file, "replacement in another file");
+        let key = |span: Span| (span.lo(), span.hi());
+        let index = self.edits.partition_point(|(existing, _)| key(*existing) <= key(span));
+        let neighbors = [index.checked_sub(1), Some(index)];
+        for existing in neighbors.into_iter().flatten().filter_map(|i| self.edits.get(i)) {
+            let existing = existing.0;
+            if existing.lo() < span.hi() && span.lo() < existing.hi() {
+                return Err(Overlap { existing, new: span });
+            }
+        }
+        self.edits.insert(index, (span, text.into()));
+        Ok(())
+    }
+
+    pub fn insert_before(&mut self, span: Span, text: impl Into<String>) -> Result<(), Overlap> {
+        self.replace(Span::new(span.lo(), span.lo(), span.file()), text)
+    }
+
+    pub fn insert_after(&mut self, span: Span, text: impl Into<String>) -> Result<(), Overlap> {
+        self.replace(Span::new(span.hi(), span.hi(), span.file()), text)
+    }
+
+    pub fn remove(&mut self, span: Span) -> Result<(), Overlap> {
+        self.replace(span, "")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// The replacements in source order.
+    pub fn edits(&self) -> impl Iterator<Item = (Span, &str)> {
+        self.edits.iter().map(|(span, text)| (*span, &**text))
+    }
+
+    /// `source` with every replacement made.
+    pub fn rewrite(&self, source: &str) -> String {
+        let mut result = String::with_capacity(source.len());
+        let mut end = 0;
+        for (span, text) in &self.edits {
+            result.push_str(&source[end..span.lo()]);
+            result.push_str(text);
+            end = span.hi();
+        }
+        result.push_str(&source[end..]);
+        result
+    }
+}
@@ -0,0 +1,147 @@
+//! Counters for `--coverage`.
+//!
+//! Every function gets a global array with one counter per MIR block, bumped
+//! whenever the block is entered. When `main` returns the counts are written
+//! to `lcov.info`. MIR does not know which lines a block came from, so all of
+//! a function's blocks are reported as branches on the line it is defined on.
+
+use std::fmt::Write;
+
+use inkwell::values::{BasicMetadataValueEnum, FunctionValue, GlobalValue};
+use inkwell::AddressSpace;
+use terryc_base::mir::{BasicBlock, Function};
+use terryc_base::{FileId, Id};
+
+use crate::LlvmCodegen;
+
+/// Where the generated program writes its report.
+pub(crate) const REPORT_PATH: &str = "lcov.info";
+
+impl<'a, 'cx> LlvmCodegen<'a, 'cx> {
+    /// Creates the zeroed counters for the blocks of `f`.
+    pub(crate) fn add_counters(&mut self, id: Id, f: &Function) -> GlobalValue<'a> {
+        let ty = self.llcx.i64_type().array_type(f.body.blocks.len() as u32);
+        let name = format!("__coverage_{}", f.name.get_str());
+        let counters = self.module.add_global(ty, None, &name);
+        counters.set_initializer(&ty.const_zero());
+        self.coverage_counters.insert(id, counters);
+        counters
+    }
+
+    /// Adds one to the counter of `bb` at the current position.
+    pub(crate) fn bump_counter(&mut self, counters: GlobalValue<'a>, bb: BasicBlock) {
+        let i64_ty = self.llcx.i64_type();
+        let counter = unsafe {
+            self.builder.build_in_bounds_gep(
+                counters.as_pointer_value(),
+                &[
+                    i64_ty.const_zero(),
+                    i64_ty.const_int(bb.index() as u64, false),
+                ],
+                "",
+            )
+        };
+        let count = self.builder.build_load(counter, "").into_int_value();
+        let count = self
+            .builder
+            .build_int_add(count, i64_ty.const_int(1, false), "");
+        self.builder.build_store(counter, count);
+    }
+
+    /// Writes the counters of every function to [`REPORT_PATH`], in the
+    /// lcov tracefile format.
+    pub(crate) fn write_coverage_report(&mut self, file: FileId) {
+        let str_ty = self.llcx.i8_type().ptr_type(AddressSpace::Generic);
+        let fopen = self.module.add_function(
+            "fopen",
+            str_ty.fn_type(&[str_ty.into(), str_ty.into()], false),
+            None,
+        );
+        let fprintf = self.module.add_function(
+            "fprintf",
+            self.llcx
+                .i32_type()
+                .fn_type(&[str_ty.into(), str_ty.into()], true),
+            None,
+        );
+        let fclose = self.module.add_function(
+            "fclose",
+            self.llcx.i32_type().fn_type(&[str_ty.into()], false),
+            None,
+        );
+
+        let path = self
+            .builder
+            .build_global_string_ptr(REPORT_PATH, "coverage_path");
+        let mode = self.builder.build_global_string_ptr("w", "coverage_mode");
+        let stream = self
+            .builder
+            .build_call(
+                fopen,
+                &[
+                    path.as_pointer_value().into(),
+                    mode.as_pointer_value().into(),
+                ],
+                "",
+            )
+            .try_as_basic_value()
+            .left()
+            .unwrap();
+
+        let source = self.cx.get_file(file.into()).unwrap_or_default();
+        let path = self.cx.file_path(file).display().to_string();
+        let header = format!("TN:\nSF:{}\n", path.replace('%', "%%"));
+        self.fprintf(fprintf, stream.into(), &header, vec![]);
+        for (id, f) in &*self.mir.functions.clone() {
+            let span = self.cx.def_span(*id).expect("functions are definitions");
+            let line = source[..span.lo()].matches('\n').count() + 1;
+            let name = f.name.get_str();
+            let counters = self.coverage_counters[id];
+            let counts = self.load_counters(counters, f.body.blocks.len());
+
+            // the entry block runs exactly once per call.
+            let mut fmt = format!("FN:{line},{name}\nFNDA:%lu,{name}\nDA:{line},%lu\n");
+            let mut args = vec![counts[0], counts[0]];
+            for (bb, count) in counts.into_iter().enumerate() {
+                writeln!(fmt, "BRDA:{line},0,{bb},%lu").unwrap();
+                args.push(count);
+            }
+            self.fprintf(fprintf, stream.into(), &fmt, args);
+        }
+        self.fprintf(fprintf, stream.into(), "end_of_record\n", vec![]);
+        self.builder.build_call(fclose, &[stream.into()], "");
+    }
+
+    fn load_counters(
+        &mut self,
+        counters: GlobalValue<'a>,
+        len: usize,
+    ) -> Vec<BasicMetadataValueEnum<'a>> {
+        let i64_ty = self.llcx.i64_type();
+        (0..len)
+            .map(|i| {
+                let counter = unsafe {
+                    self.builder.build_in_bounds_gep(
+                        counters.as_pointer_value(),
+                        &[i64_ty.const_zero(), i64_ty.const_int(i as u64, false)],
+                        "",
+                    )
+                };
+                self.builder.build_load(counter, "").into()
+            })
+            .collect()
+    }
+
+    fn fprintf(
+        &mut self,
+        fprintf: FunctionValue<'a>,
+        stream: BasicMetadataValueEnum<'a>,
+        fmt: &str,
+        args: Vec<BasicMetadataValueEnum<'a>>,
+    ) {
+        let fmt = self.builder.build_global_string_ptr(fmt, "coverage_fmt");
+        let mut all_args = vec![stream, fmt.as_pointer_value().into()];
+        all_args.extend(args);
+        self.builder.build_call(fprintf, &all_args, "");
+    }
+}
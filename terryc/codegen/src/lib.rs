@@ -1,35 +1,53 @@
 #![deny(rust_2018_idioms)]
 #![feature(exit_status_error)]
 
+mod coverage;
+
 use std::path::Path;
 use std::process::Command;
+use std::rc::Rc;
 
+use inkwell::attributes::{Attribute, AttributeLoc};
 use inkwell::builder::Builder;
 use inkwell::context::Context as LLCxt;
-use inkwell::module::Module;
+use inkwell::module::{Linkage, Module};
 use inkwell::targets::{
     CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine,
 };
 use inkwell::types::{BasicType, BasicTypeEnum, FunctionType};
-use inkwell::values::{BasicValueEnum, FunctionValue, PointerValue};
-use inkwell::{AddressSpace, IntPredicate, OptimizationLevel};
-use terryc_base::ast::{BinOpKind, TyKind, UnOpKind};
+use inkwell::values::{
+    AggregateValueEnum, BasicMetadataValueEnum, BasicValueEnum, FunctionValue, GlobalValue,
+    IntValue, PointerValue,
+};
+use inkwell::{AddressSpace, FloatPredicate, IntPredicate, OptimizationLevel};
+use terryc_base::ast::{BinOpKind, EnumDef, TyKind, UnOpKind};
 use terryc_base::data::FxHashMap;
 use terryc_base::errors::ErrorReported;
-use terryc_base::hir::{Literal, Resolution};
-use terryc_base::mir::{self, Function, Local, Operand, Rvalue, Statement, Terminator};
+use terryc_base::hir::{InlineKind, Literal, Resolution, TestFn};
+use terryc_base::manifest::{ArtifactKind, Manifest, EXECUTABLE_PATH, OBJECT_PATH};
+use terryc_base::metadata::{Metadata, METADATA_PATH};
+use terryc_base::mir::{
+    self, Function, Local, Operand, Place, ProjectionElem, Rvalue, Statement, Terminator,
+};
 use terryc_base::sym::{self, Symbol};
 use terryc_base::{Context, FileId, Id, Providers, TyList};
 
 fn codegen(cx: &dyn Context, id: FileId) -> Result<(), ErrorReported> {
     let llcx = LLCxt::create();
+    let lib = cx.options().lib;
+    let test = cx.options().test;
+    let entry = if lib || test { None } else { Some(cx.entry_point(id)?) };
+    let tests = if test { Some(cx.tests(id)?) } else { None };
     let mut codegen = LlvmCodegen::new(cx, &llcx, cx.mir(id)?);
-    codegen.gen();
+    codegen.entry = entry;
+    codegen.tests = tests;
+    tracing::debug_span!("gen_module").in_scope(|| codegen.gen(id));
     codegen.module.print_to_stderr();
-    codegen
-        .module
-        .verify()
-        .unwrap_or_else(|x| println!("{x:?}"));
+    tracing::debug_span!("verify").in_scope(|| {
+        if let Err(e) = codegen.module.verify() {
+            tracing::error!("generated an invalid module: {e:?}");
+        }
+    });
     Target::initialize_native(&InitializationConfig::default()).unwrap();
     let triple = TargetMachine::get_default_triple();
     let target = Target::from_triple(&triple).unwrap();
@@ -43,17 +61,41 @@ fn codegen(cx: &dyn Context, id: FileId) -> Result<(), ErrorReported> {
             CodeModel::Default,
         )
         .unwrap();
-    machine
-        .write_to_file(&codegen.module, FileType::Object, Path::new("/tmp/a"))
-        .unwrap();
-    let mut cmd = Command::new("cc")
-        .arg("-fPIE")
-        .arg("-o")
-        .arg("out")
-        .arg("/tmp/a")
-        .spawn()
-        .unwrap();
-    cmd.wait().unwrap().exit_ok().unwrap();
+    let object = if lib { OBJECT_PATH } else { "/tmp/a" };
+    tracing::debug_span!("emit_object").in_scope(|| {
+        machine
+            .write_to_file(&codegen.module, FileType::Object, Path::new(object))
+            .unwrap();
+    });
+    let mut manifest = Manifest::default();
+    if lib {
+        manifest.add(OBJECT_PATH, ArtifactKind::Object);
+        Metadata::of_library(&cx.hir(id)?, OBJECT_PATH)
+            .write()
+            .unwrap_or_else(|e| panic!("failed to write `{METADATA_PATH}`: {e}"));
+        manifest.add(METADATA_PATH, ArtifactKind::Metadata);
+    } else {
+        let externs = cx.externs()?;
+        tracing::debug_span!("link").in_scope(|| {
+            let mut cmd = Command::new("cc")
+                .arg("-fPIE")
+                .arg("-o")
+                .arg(EXECUTABLE_PATH)
+                .arg(object)
+                .args(externs.iter().map(|lib| &lib.metadata.object))
+                .spawn()
+                .unwrap();
+            cmd.wait().unwrap().exit_ok().unwrap();
+        });
+        manifest.add(EXECUTABLE_PATH, ArtifactKind::Executable);
+    }
+    // the report is written by the C `main`, which libraries do not have.
+    if cx.options().coverage && !lib {
+        manifest.add(coverage::REPORT_PATH, ArtifactKind::CoverageReport);
+    }
+    manifest
+        .write(cx.options())
+        .unwrap_or_else(|e| panic!("failed to write `{}`: {e}", manifest.path().display()));
     Ok(())
 }
 
@@ -65,9 +107,22 @@ pub struct LlvmCodegen<'a, 'cx> {
     pub module: Module<'a>,
     pub fun: Option<FunctionValue<'a>>,
     pub locals: FxHashMap<Local, PointerValue<'a>>,
+    pub local_tys: FxHashMap<Local, TyKind>,
     pub genned_functions: FxHashMap<Id, FunctionValue<'a>>,
     pub builtins: FxHashMap<(Symbol, TyList), FunctionValue<'a>>,
     pub c_printf: Option<FunctionValue<'a>>,
+    pub c_strcmp: Option<FunctionValue<'a>>,
+    pub c_strlen: Option<FunctionValue<'a>>,
+    pub c_malloc: Option<FunctionValue<'a>>,
+    pub c_atoi: Option<FunctionValue<'a>>,
+    pub c_abort: Option<FunctionValue<'a>>,
+    /// The block counters of each function, with `--coverage`.
+    pub coverage_counters: FxHashMap<Id, GlobalValue<'a>>,
+    /// The `main` function, which the C `main` calls. `None` with `--lib`.
+    pub entry: Option<Id>,
+    /// The `#[test]` functions, one of which the C `main` calls instead of
+    /// `entry` when building a test runner.
+    pub tests: Option<Rc<[TestFn]>>,
 }
 
 macro_rules! cached {
@@ -96,9 +151,18 @@ impl<'a, 'cx> LlvmCodegen<'a, 'cx> {
             module,
             fun: None,
             locals: Default::default(),
+            local_tys: Default::default(),
             genned_functions: Default::default(),
             builtins: Default::default(),
             c_printf: None,
+            c_strcmp: None,
+            c_strlen: None,
+            c_malloc: None,
+            c_atoi: None,
+            c_abort: None,
+            coverage_counters: Default::default(),
+            entry: None,
+            tests: None,
         }
     }
 
@@ -107,29 +171,45 @@ impl<'a, 'cx> LlvmCodegen<'a, 'cx> {
             TyKind::Bool => self.llcx.bool_type().into(),
             TyKind::F32 => self.llcx.f32_type().into(),
             TyKind::I32 => self.llcx.i32_type().into(),
-            TyKind::Unit => unreachable!("unit types should not be visible to codegen"),
+            TyKind::Unit | TyKind::Never => {
+                unreachable!("zero-sized types should not be visible to codegen")
+            }
             TyKind::String => self.llcx.i8_type().ptr_type(AddressSpace::Generic).into(),
+            TyKind::Array(elem, len) => self.basic_ty(*elem).array_type(len).into(),
+            TyKind::Struct(def) => {
+                let fields: Vec<_> = def.fields.iter().map(|&(_, ty)| self.basic_ty(ty)).collect();
+                self.llcx.struct_type(&fields, false).into()
+            }
+            TyKind::Tuple(elems) => {
+                let elems: Vec<_> = elems.iter().map(|&ty| self.basic_ty(ty)).collect();
+                self.llcx.struct_type(&elems, false).into()
+            }
+            TyKind::Enum(def) => {
+                // the discriminant, followed by the fields of every variant.
+                let mut fields = vec![self.llcx.i32_type().into()];
+                for variant in &def.variants {
+                    fields.extend(variant.fields.iter().map(|&ty| self.basic_ty(ty)));
+                }
+                self.llcx.struct_type(&fields, false).into()
+            }
+            TyKind::Named(_) => unreachable!("names of types are resolved in HIR"),
             // x => todo!("{x:?}"),
         }
     }
 
-    pub fn func_ty(&mut self, f: &Function) -> FunctionType<'a> {
-        let args: Vec<_> = f
-            .args
-            .iter()
-            .copied()
-            .map(|x| self.basic_ty(x).into())
-            .collect();
-        if f.ret == TyKind::Unit {
+    pub fn func_ty(&mut self, args: &[TyKind], ret: TyKind) -> FunctionType<'a> {
+        let args: Vec<_> = args.iter().copied().map(|x| self.basic_ty(x).into()).collect();
+        if ret == TyKind::Unit {
             self.llcx.void_type().fn_type(&args, false)
         } else {
-            self.basic_ty(f.ret).fn_type(&args, false)
+            self.basic_ty(ret).fn_type(&args, false)
         }
     }
     pub fn literal(&mut self, c: &Literal) -> BasicValueEnum<'a> {
         match c {
             Literal::Bool(b) => self.llcx.bool_type().const_int(*b as u64, false).into(),
             Literal::Int(i) => self.llcx.i32_type().const_int(*i as u64, false).into(),
+            Literal::Float(f) => self.llcx.f32_type().const_float(f.0).into(),
             Literal::String(s) => self
                 .builder
                 .build_global_string_ptr(s.get_str(), "global")
@@ -141,15 +221,70 @@ impl<'a, 'cx> LlvmCodegen<'a, 'cx> {
     pub fn operand(&mut self, op: &Operand) -> BasicValueEnum<'a> {
         match op {
             Operand::Const(c) => self.literal(c),
-            Operand::Copy(local) => self.local(*local),
+            Operand::Copy(place) => match place.as_local() {
+                Some(local) => self.local(local),
+                None => {
+                    let ptr = self.place(place);
+                    self.builder.build_load(ptr, "")
+                }
+            },
         }
     }
+    /// A pointer to the memory of `place`.
+    pub fn place(&mut self, place: &Place) -> PointerValue<'a> {
+        let mut ptr = self.locals[&place.local];
+        let mut ty = self.local_tys[&place.local];
+        for elem in &place.projection {
+            ptr = match elem {
+                ProjectionElem::Index(local) => {
+                    let index = self.local(*local).into_int_value();
+                    let zero = self.llcx.i32_type().const_zero();
+                    // bounds were checked by an `Assert` in MIR.
+                    unsafe { self.builder.build_in_bounds_gep(ptr, &[zero, index], "") }
+                }
+                ProjectionElem::Field(field) => self
+                    .builder
+                    .build_struct_gep(ptr, *field, "")
+                    .expect("field of a non-struct or tuple"),
+                ProjectionElem::Variant(variant, field) => {
+                    let TyKind::Enum(def) = ty else { unreachable!("variant of a non-enum") };
+                    self.builder
+                        .build_struct_gep(ptr, variant_offset(def, *variant) + field, "")
+                        .expect("variant of a non-enum")
+                }
+            };
+            ty = elem.ty(ty);
+        }
+        ptr
+    }
     pub fn binop(
         &mut self,
         binop: BinOpKind,
         a: BasicValueEnum<'a>,
         b: BasicValueEnum<'a>,
     ) -> BasicValueEnum<'a> {
+        if let (BasicValueEnum::PointerValue(a), BasicValueEnum::PointerValue(b)) = (a, b) {
+            return match binop {
+                BinOpKind::Add => self.str_concat(a, b),
+                _ => self.str_eq(binop, a, b),
+            };
+        }
+        if let (
+            BinOpKind::Shl | BinOpKind::Shr,
+            BasicValueEnum::IntValue(a),
+            BasicValueEnum::IntValue(b),
+        ) = (binop, a, b)
+        {
+            // mask the amount to the width so that shifting too far is not poison
+            let width = a.get_type().get_bit_width();
+            let mask = b.get_type().const_int(u64::from(width - 1), false);
+            let amount = self.builder.build_and(b, mask, "");
+            return match binop {
+                BinOpKind::Shl => self.builder.build_left_shift(a, amount, ""),
+                _ => self.builder.build_right_shift(a, amount, true, ""),
+            }
+            .into();
+        }
         macro_rules! gen_match {
             (
                 $($binop: ident => {
@@ -172,21 +307,52 @@ impl<'a, 'cx> LlvmCodegen<'a, 'cx> {
             }
             Sub => {
                 IntValue => [build_int_sub]
+                FloatValue => [build_float_sub]
             }
             Mul => {
                 IntValue => [build_int_mul]
+                FloatValue => [build_float_mul]
             }
             Div => {
                 IntValue => [build_int_signed_div]
+                FloatValue => [build_float_div]
             }
             Mod => {
                 IntValue => [build_int_signed_rem]
+                FloatValue => [build_float_rem]
             }
             Equal => {
                 IntValue => [build_int_compare, IntPredicate::EQ]
+                FloatValue => [build_float_compare, FloatPredicate::OEQ]
+            }
+            NotEqual => {
+                IntValue => [build_int_compare, IntPredicate::NE]
+                FloatValue => [build_float_compare, FloatPredicate::UNE]
+            }
+            Less => {
+                IntValue => [build_int_compare, IntPredicate::SLT]
+                FloatValue => [build_float_compare, FloatPredicate::OLT]
+            }
+            LessEqual => {
+                IntValue => [build_int_compare, IntPredicate::SLE]
+                FloatValue => [build_float_compare, FloatPredicate::OLE]
             }
             Greater => {
                 IntValue => [build_int_compare, IntPredicate::SGT]
+                FloatValue => [build_float_compare, FloatPredicate::OGT]
+            }
+            GreaterEqual => {
+                IntValue => [build_int_compare, IntPredicate::SGE]
+                FloatValue => [build_float_compare, FloatPredicate::OGE]
+            }
+            BitAnd => {
+                IntValue => [build_and]
+            }
+            BitOr => {
+                IntValue => [build_or]
+            }
+            BitXor => {
+                IntValue => [build_xor]
             }
         }
     }
@@ -200,12 +366,193 @@ impl<'a, 'cx> LlvmCodegen<'a, 'cx> {
             }
             Rvalue::UnaryOp(UnOpKind::Minus, a) => match self.operand(a) {
                 BasicValueEnum::IntValue(x) => self.builder.build_int_neg(x, "").into(),
+                BasicValueEnum::FloatValue(x) => self.builder.build_float_neg(x, "").into(),
                 _ => todo!(),
             },
-            x => todo!("{x:?}"),
+            Rvalue::UnaryOp(UnOpKind::Not, a) => {
+                let a = self.operand(a).into_int_value();
+                self.builder.build_not(a, "").into()
+            }
+            Rvalue::Cast(op, ty) => {
+                let value = self.operand(op);
+                self.cast(value, *ty)
+            }
+            Rvalue::Aggregate(ty, elems) => {
+                let mut aggregate: AggregateValueEnum<'a> = match self.basic_ty(*ty) {
+                    BasicTypeEnum::ArrayType(ty) => ty.get_undef().into(),
+                    BasicTypeEnum::StructType(ty) => ty.get_undef().into(),
+                    ty => unreachable!("`{ty:?}` is not an aggregate"),
+                };
+                for (i, elem) in elems.iter().enumerate() {
+                    let elem = self.operand(elem);
+                    aggregate = self
+                        .builder
+                        .build_insert_value(aggregate, elem, i as u32, "")
+                        .unwrap();
+                }
+                match aggregate {
+                    AggregateValueEnum::ArrayValue(array) => array.into(),
+                    AggregateValueEnum::StructValue(value) => value.into(),
+                }
+            }
+            Rvalue::Discriminant(place) => {
+                let ptr = self.place(place);
+                let tag = self.builder.build_struct_gep(ptr, 0, "").expect("not an enum");
+                self.builder.build_load(tag, "")
+            }
+            Rvalue::Variant(ty, variant, fields) => {
+                let TyKind::Enum(def) = ty else { unreachable!("variants belong to enums") };
+                let BasicTypeEnum::StructType(llty) = self.basic_ty(*ty) else {
+                    unreachable!("enums are structs")
+                };
+                let tag = self.llcx.i32_type().const_int(*variant as u64, false);
+                let mut value = self
+                    .builder
+                    .build_insert_value(llty.get_undef(), tag, 0, "")
+                    .unwrap();
+                let offset = variant_offset(def, *variant);
+                for (i, field) in fields.iter().enumerate() {
+                    let field = self.operand(field);
+                    value = self
+                        .builder
+                        .build_insert_value(value, field, offset + i as u32, "")
+                        .unwrap();
+                }
+                value.into_struct_value().into()
+            }
         }
     }
 
+    /// `value as ty`, for the casts that typeck allows.
+    fn cast(&mut self, value: BasicValueEnum<'a>, ty: TyKind) -> BasicValueEnum<'a> {
+        let i32_ty = self.llcx.i32_type();
+        match (value, ty) {
+            // `bool` is the only integer that is not an `i32`.
+            (BasicValueEnum::IntValue(int), TyKind::I32) => {
+                self.builder.build_int_z_extend(int, i32_ty, "").into()
+            }
+            (BasicValueEnum::IntValue(int), TyKind::F32) => self
+                .builder
+                .build_signed_int_to_float(int, self.llcx.f32_type(), "")
+                .into(),
+            (BasicValueEnum::FloatValue(float), TyKind::I32) => {
+                // unlike `fptosi`, saturates instead of being undefined for
+                // values that do not fit, like const propagation does.
+                let name = "llvm.fptosi.sat.i32.f32";
+                let fptosi_sat = self.module.get_function(name).unwrap_or_else(|| {
+                    let fn_ty = i32_ty.fn_type(&[self.llcx.f32_type().into()], false);
+                    self.module.add_function(name, fn_ty, None)
+                });
+                self.builder
+                    .build_call(fptosi_sat, &[float.into()], "")
+                    .try_as_basic_value()
+                    .left()
+                    .expect("returns an `i32`")
+            }
+            (value, ty) => unreachable!("cannot cast `{value:?}` as `{ty}`"),
+        }
+    }
+
+    /// Strings are compared by contents, like `strcmp` does.
+    fn str_eq(
+        &mut self,
+        binop: BinOpKind,
+        a: PointerValue<'a>,
+        b: PointerValue<'a>,
+    ) -> BasicValueEnum<'a> {
+        let predicate = match binop {
+            BinOpKind::Equal => IntPredicate::EQ,
+            BinOpKind::NotEqual => IntPredicate::NE,
+            x => unreachable!("`{x:?}` on strings"),
+        };
+        let strcmp = self.c_strcmp();
+        let ordering = self
+            .builder
+            .build_call(strcmp, &[a.into(), b.into()], "")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let zero = self.llcx.i32_type().const_zero();
+        self.builder
+            .build_int_compare(predicate, ordering, zero, "")
+            .into()
+    }
+
+    /// Copies both strings into a new one on the heap. Strings are never
+    /// freed.
+    fn str_concat(&mut self, a: PointerValue<'a>, b: PointerValue<'a>) -> BasicValueEnum<'a> {
+        let strlen = self.c_strlen();
+        let malloc = self.c_malloc();
+        let len = |this: &mut Self, s: PointerValue<'a>| {
+            this.builder
+                .build_call(strlen, &[s.into()], "")
+                .try_as_basic_value()
+                .left()
+                .unwrap()
+                .into_int_value()
+        };
+        let a_len = len(self, a);
+        let b_len = len(self, b);
+        // the nul terminator is copied along with `b`.
+        let one = self.llcx.i64_type().const_int(1, false);
+        let b_size = self.builder.build_int_add(b_len, one, "");
+        let size = self.builder.build_int_add(a_len, b_size, "");
+        let ptr = self
+            .builder
+            .build_call(malloc, &[size.into()], "")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value();
+        self.builder.build_memcpy(ptr, 1, a, 1, a_len).unwrap();
+        let rest = unsafe { self.builder.build_in_bounds_gep(ptr, &[a_len], "") };
+        self.builder.build_memcpy(rest, 1, b, 1, b_size).unwrap();
+        ptr.into()
+    }
+
+    fn c_strlen(&mut self) -> FunctionValue<'a> {
+        *self.c_strlen.get_or_insert_with(|| {
+            let str_ty = self.llcx.i8_type().ptr_type(AddressSpace::Generic);
+            self.module.add_function(
+                "strlen",
+                self.llcx.i64_type().fn_type(&[str_ty.into()], false),
+                None,
+            )
+        })
+    }
+
+    fn c_malloc(&mut self) -> FunctionValue<'a> {
+        *self.c_malloc.get_or_insert_with(|| {
+            let str_ty = self.llcx.i8_type().ptr_type(AddressSpace::Generic);
+            self.module.add_function(
+                "malloc",
+                str_ty.fn_type(&[self.llcx.i64_type().into()], false),
+                None,
+            )
+        })
+    }
+
+    fn c_strcmp(&mut self) -> FunctionValue<'a> {
+        *self.c_strcmp.get_or_insert_with(|| {
+            let str_ty = self.llcx.i8_type().ptr_type(AddressSpace::Generic);
+            self.module.add_function(
+                "strcmp",
+                self.llcx
+                    .i32_type()
+                    .fn_type(&[str_ty.into(), str_ty.into()], false),
+                None,
+            )
+        })
+    }
+
+    fn c_abort(&mut self) -> FunctionValue<'a> {
+        *self.c_abort.get_or_insert_with(|| {
+            self.module
+                .add_function("abort", self.llcx.void_type().fn_type(&[], false), None)
+        })
+    }
+
     fn c_printf(&mut self) -> FunctionValue<'a> {
         *self.c_printf.get_or_insert_with(|| {
             self.module.add_function(
@@ -218,6 +565,98 @@ impl<'a, 'cx> LlvmCodegen<'a, 'cx> {
             )
         })
     }
+    /// Adds the arguments to `printf` that print `value` of type `ty` to
+    /// `args`, one for every conversion of [`TyKind::print_format`].
+    fn print_args(
+        &mut self,
+        builder: &Builder<'a>,
+        ty: TyKind,
+        value: BasicValueEnum<'a>,
+        args: &mut Vec<BasicMetadataValueEnum<'a>>,
+    ) {
+        match ty {
+            TyKind::Bool => {
+                let names = self.names(builder, ty, &["false", "true"]);
+                let index =
+                    builder.build_int_z_extend(value.into_int_value(), self.llcx.i32_type(), "");
+                args.push(self.load_name(builder, names, index).into());
+            }
+            TyKind::Enum(def) => {
+                let variants: Vec<_> = def.variants.iter().map(|v| v.name.get_str()).collect();
+                let names = self.names(builder, ty, &variants);
+                let discriminant = builder
+                    .build_extract_value(value.into_struct_value(), 0, "")
+                    .expect("enums start with their discriminant")
+                    .into_int_value();
+                args.push(self.load_name(builder, names, discriminant).into());
+            }
+            // variadic arguments are promoted to `double`.
+            TyKind::F32 => args.push(
+                builder
+                    .build_float_ext(value.into_float_value(), self.llcx.f64_type(), "")
+                    .into(),
+            ),
+            TyKind::Tuple(elems) => {
+                for (i, &elem) in elems.iter().enumerate() {
+                    let value = builder
+                        .build_extract_value(value.into_struct_value(), i as u32, "")
+                        .expect("tuples are structs");
+                    self.print_args(builder, elem, value, args);
+                }
+            }
+            TyKind::Struct(def) => {
+                for (i, &(_, field)) in def.fields.iter().enumerate() {
+                    let value = builder
+                        .build_extract_value(value.into_struct_value(), i as u32, "")
+                        .expect("field of the struct");
+                    self.print_args(builder, field, value, args);
+                }
+            }
+            TyKind::Array(&elem, len) => {
+                for i in 0..len {
+                    let value = builder
+                        .build_extract_value(value.into_array_value(), i, "")
+                        .expect("element of the array");
+                    self.print_args(builder, elem, value, args);
+                }
+            }
+            _ => args.push(value.into()),
+        }
+    }
+
+    /// The global array of pointers to `names`, which `println` indexes to
+    /// print the values of `ty`. It is created the first time it is used.
+    fn names(&mut self, builder: &Builder<'a>, ty: TyKind, names: &[&str]) -> GlobalValue<'a> {
+        let global = format!("names_{ty:?}");
+        if let Some(global) = self.module.get_global(&global) {
+            return global;
+        }
+        let names: Vec<_> = names
+            .iter()
+            .map(|name| builder.build_global_string_ptr(name, "").as_pointer_value())
+            .collect();
+        let ptr_ty = self.llcx.i8_type().ptr_type(AddressSpace::Generic);
+        let table = self
+            .module
+            .add_global(ptr_ty.array_type(names.len() as u32), None, &global);
+        table.set_constant(true);
+        table.set_initializer(&ptr_ty.const_array(&names));
+        table
+    }
+
+    /// Loads the name at `index` of a table made by [`Self::names`].
+    fn load_name(
+        &self,
+        builder: &Builder<'a>,
+        names: GlobalValue<'a>,
+        index: IntValue<'a>,
+    ) -> BasicValueEnum<'a> {
+        let zero = self.llcx.i32_type().const_zero();
+        let ptr =
+            unsafe { builder.build_in_bounds_gep(names.as_pointer_value(), &[zero, index], "") };
+        builder.build_load(ptr, "")
+    }
+
     fn get_builtin_raw(&mut self, sym: Symbol, types: TyList) -> FunctionValue<'a> {
         match (sym, &*types) {
             (sym::println, &[ty]) => {
@@ -235,47 +674,15 @@ impl<'a, 'cx> LlvmCodegen<'a, 'cx> {
                 let fmt_global = if let Some(g) = self.module.get_global(&fmt_global) {
                     g
                 } else {
-                    let fmt_value = match ty {
-                        TyKind::I32 => "%d\n",
-                        TyKind::F32 => "%f\n",
-                        TyKind::String => "%s\n",
-                        TyKind::Unit => "()\n",
-                        TyKind::Bool => "%s\n",
-                    };
-                    builder.build_global_string_ptr(fmt_value, &fmt_global)
+                    let fmt_value = ty.print_format().expect("checked by typeck");
+                    builder.build_global_string_ptr(&format!("{fmt_value}\n"), &fmt_global)
                 };
 
+                let mut args = vec![fmt_global.as_pointer_value().into()];
                 let p = func.get_first_param().unwrap();
-                let val = if ty == TyKind::Bool {
-                    let ty = self.llcx.i8_type().ptr_type(AddressSpace::Generic);
-                    let s = ty.const_array(&[
-                        self.builder
-                            .build_global_string_ptr("false", "false_value")
-                            .as_pointer_value(),
-                        self.builder
-                            .build_global_string_ptr("true", "true_value")
-                            .as_pointer_value(),
-                    ]);
-                    let global = self.module.add_global(ty.array_type(2), None, "bool");
-                    global.set_initializer(&s);
-                    unsafe {
-                        builder.build_in_bounds_gep(
-                            global.as_pointer_value(),
-                            &[p.into_int_value()],
-                            "",
-                        )
-                    }
-                    .into()
-                } else {
-                    p
-                };
-
+                self.print_args(&builder, ty, p, &mut args);
                 let printf = self.c_printf();
-                builder.build_call(
-                    printf,
-                    &[fmt_global.as_pointer_value().into(), val.into()],
-                    "",
-                );
+                builder.build_call(printf, &args, "");
                 builder.build_return(None);
 
                 func
@@ -289,30 +696,61 @@ impl<'a, 'cx> LlvmCodegen<'a, 'cx> {
     pub fn get_fn(&mut self, resolution: Resolution, types: TyList) -> FunctionValue<'a> {
         match resolution {
             Resolution::Builtin(s) => self.get_builtin(s, types),
-            Resolution::Fn(id) => self.gen_function(id, &self.mir.functions.clone()[&id]),
+            Resolution::Fn(id) => self.genned_functions[&id],
+            Resolution::Extern(krate, name) => self.get_extern(krate, name),
             Resolution::Local(_) => unreachable!(),
         }
     }
+    /// Declares a function of a library given with `--extern`. Its code is
+    /// linked in from the object file of the library.
+    fn get_extern(&mut self, krate: Symbol, name: Symbol) -> FunctionValue<'a> {
+        if let Some(fun) = self.module.get_function(name.get_str()) {
+            return fun;
+        }
+        let externs = self.cx.externs().expect("loaded by HIR lowering");
+        let f = externs
+            .iter()
+            .find(|lib| lib.name == krate)
+            .and_then(|lib| lib.function(name))
+            .expect("checked by HIR lowering");
+        let func_ty = self.func_ty(&f.args, f.ret);
+        self.module.add_function(name.get_str(), func_ty, Some(Linkage::External))
+    }
     fn local(&mut self, l: Local) -> BasicValueEnum<'a> {
-        if l.index() < self.fun.unwrap().count_params() as usize {
-            self.fun.unwrap().get_nth_param(l.index() as u32).unwrap()
+        if let Some(&ptr) = self.locals.get(&l) {
+            self.builder.build_load(ptr, "")
         } else {
-            self.builder.build_load(self.locals[&l], "")
+            self.fun.unwrap().get_nth_param(l.index() as u32).unwrap()
         }
     }
-    fn gen_function(&mut self, id: Id, f: &Function) -> FunctionValue<'a> {
-        if let Some(val) = self.genned_functions.get(&id) {
-            return *val;
-        }
-        let name = if f.name == sym::main {
+    /// Adds a function to the module without its body, so that it can be
+    /// called before it is generated.
+    fn declare_function(&mut self, id: Id, f: &Function) {
+        // a test runner has a C `main` of its own, so `main` is renamed there too.
+        let name = if Some(id) == self.entry || (self.tests.is_some() && f.name == sym::main) {
             "__entrypoint_actual"
         } else {
             f.name.get_str()
         };
-        let func_ty = self.func_ty(f);
+        let func_ty = self.func_ty(&f.args, f.ret);
         let fun = self.module.add_function(name, func_ty, None);
+        // LLVM inlines on its own, so it is told about the attribute as well.
+        let inline = match f.inline {
+            Some(InlineKind::Always) => Some("alwaysinline"),
+            Some(InlineKind::Never) => Some("noinline"),
+            Some(InlineKind::Hint) | None => None,
+        };
+        if let Some(inline) = inline {
+            let kind = Attribute::get_named_enum_kind_id(inline);
+            fun.add_attribute(AttributeLoc::Function, self.llcx.create_enum_attribute(kind, 0));
+        }
         self.genned_functions.insert(id, fun);
+    }
+    fn gen_function(&mut self, id: Id, f: &Function) {
+        let _span = tracing::debug_span!("gen_function", name = %f.name).entered();
+        let fun = self.genned_functions[&id];
         self.fun = Some(fun);
+        let counters = self.cx.options().coverage.then(|| self.add_counters(id, f));
         let bb = self.llcx.append_basic_block(fun, "entry");
 
         self.builder.position_at_end(bb);
@@ -321,15 +759,28 @@ impl<'a, 'cx> LlvmCodegen<'a, 'cx> {
             .body
             .locals
             .iter_enumerated()
-            .skip(func_ty.count_param_types() as usize)
-            .filter(|(_, data)| data.ty != TyKind::Unit)
-            .map(|(local, data)| {
+            .filter(|(_, data)| !data.ty.is_zero_sized())
+            .filter_map(|(local, data)| {
+                let param = fun.get_nth_param(local.index() as u32);
+                // arrays, tuples, structs and enums are accessed through a
+                // pointer, so such arguments are copied to memory.
+                let aggregate = matches!(
+                    data.ty,
+                    TyKind::Array(..) | TyKind::Tuple(_) | TyKind::Struct(_) | TyKind::Enum(_)
+                );
+                if param.is_some() && !aggregate {
+                    return None;
+                }
                 let ty = self.basic_ty(data.ty);
                 let ptr = self.builder.build_alloca(ty, &format!("{local:?}"));
-                (local, ptr)
+                if let Some(param) = param {
+                    self.builder.build_store(ptr, param);
+                }
+                Some((local, ptr))
             })
             .collect();
         self.locals = locals;
+        self.local_tys = f.body.locals.iter_enumerated().map(|(l, data)| (l, data.ty)).collect();
 
         let basic_blocks: Vec<_> = f
             .body
@@ -343,13 +794,19 @@ impl<'a, 'cx> LlvmCodegen<'a, 'cx> {
 
         for (i, bb) in f.body.blocks.iter_enumerated() {
             self.builder.position_at_end(basic_blocks[i.index()]);
+            if let Some(counters) = counters {
+                self.bump_counter(counters, i);
+            }
             for stmt in &bb.statements {
                 match stmt {
+                    // zero-sized locals have no memory, and rvalues have no side effects.
+                    Statement::Assign(to, _) if self.local_tys[&to.local].is_zero_sized() => {}
                     Statement::Assign(to, from) => {
-                        let place = self.locals[to];
+                        let place = self.place(to);
                         let rv = self.rvalue(from);
                         self.builder.build_store(place, rv);
                     }
+                    Statement::StorageLive(_) | Statement::StorageDead(_) => {}
                 }
             }
             match &bb.terminator {
@@ -397,17 +854,45 @@ impl<'a, 'cx> LlvmCodegen<'a, 'cx> {
                             .collect::<Vec<_>>(),
                     );
                 }
+                Terminator::Assert { cond, msg, target } => {
+                    let cond = self.operand(cond).into_int_value();
+                    let failed = self.llcx.append_basic_block(fun, "assert_failed");
+                    self.builder.build_conditional_branch(
+                        cond,
+                        basic_blocks[target.index()],
+                        failed,
+                    );
+                    self.builder.position_at_end(failed);
+                    let msg = self
+                        .builder
+                        .build_global_string_ptr(&format!("{}\n", msg.get_str()), "assert_msg");
+                    let printf = self.c_printf();
+                    self.builder
+                        .build_call(printf, &[msg.as_pointer_value().into()], "");
+                    let abort = self.c_abort();
+                    self.builder.build_call(abort, &[], "");
+                    self.builder.build_unreachable();
+                }
+                Terminator::Unreachable => {
+                    self.builder.build_unreachable();
+                }
                 Terminator::ReplacedAfterConstruction => unreachable!(),
             }
         }
-
-        fun
     }
 
-    pub fn gen(&mut self) {
-        for (id, fun) in &*self.mir.functions.clone() {
+    pub fn gen(&mut self, file: FileId) {
+        let functions = self.mir.functions.clone();
+        // declared up front, as a function can call the ones after it.
+        for (id, fun) in &*functions {
+            self.declare_function(*id, fun);
+        }
+        for (id, fun) in &*functions {
             self.gen_function(*id, fun);
         }
+        if self.entry.is_none() && self.tests.is_none() {
+            return;
+        }
         let main = self.module.add_function(
             "main",
             self.llcx.i32_type().fn_type(
@@ -425,14 +910,95 @@ impl<'a, 'cx> LlvmCodegen<'a, 'cx> {
         );
         self.builder
             .position_at_end(self.llcx.append_basic_block(main, "start"));
-        self.builder.build_call(
-            self.module.get_function("__entrypoint_actual").unwrap(),
-            &[],
-            "call_main",
-        );
+        if let Some(tests) = self.tests.clone() {
+            self.call_test(main, &tests);
+        } else {
+            self.builder.build_call(
+                self.module.get_function("__entrypoint_actual").unwrap(),
+                &[],
+                "call_main",
+            );
+        }
+        if self.cx.options().coverage {
+            self.write_coverage_report(file);
+        }
         self.builder
             .build_return(Some(&self.llcx.i32_type().const_int(0, false)));
     }
+
+    /// Calls the test whose index is the first argument of the C `main` as
+    /// many times as the second argument says, or none if there is no such
+    /// test.
+    fn call_test(&mut self, main: FunctionValue<'a>, tests: &[TestFn]) {
+        let i32_ty = self.llcx.i32_type();
+        let argv = main.get_nth_param(1).unwrap().into_pointer_value();
+        let index = self.int_arg(argv, 1);
+        let iterations = self.int_arg(argv, 2);
+        let counter = self.builder.build_alloca(i32_ty, "iteration");
+        self.builder.build_store(counter, i32_ty.const_zero());
+        let header = self.llcx.append_basic_block(main, "header");
+        let body = self.llcx.append_basic_block(main, "body");
+        let latch = self.llcx.append_basic_block(main, "latch");
+        let done = self.llcx.append_basic_block(main, "done");
+        self.builder.build_unconditional_branch(header);
+
+        self.builder.position_at_end(header);
+        let i = self.builder.build_load(counter, "").into_int_value();
+        let more = self.builder.build_int_compare(IntPredicate::SLT, i, iterations, "");
+        self.builder.build_conditional_branch(more, body, done);
+
+        let cases: Vec<_> = tests
+            .iter()
+            .enumerate()
+            .map(|(i, test)| {
+                let bb = self.llcx.append_basic_block(main, &test.name);
+                self.builder.position_at_end(bb);
+                self.builder.build_call(self.genned_functions[&test.id], &[], "");
+                self.builder.build_unconditional_branch(latch);
+                (i32_ty.const_int(i as u64, false), bb)
+            })
+            .collect();
+        self.builder.position_at_end(body);
+        self.builder.build_switch(index, done, &cases);
+
+        self.builder.position_at_end(latch);
+        let next = self.builder.build_int_add(i, i32_ty.const_int(1, false), "");
+        self.builder.build_store(counter, next);
+        self.builder.build_unconditional_branch(header);
+        self.builder.position_at_end(done);
+    }
+
+    /// The `n`th command line argument, read as a number.
+    fn int_arg(&mut self, argv: PointerValue<'a>, n: u64) -> IntValue<'a> {
+        let n = self.llcx.i32_type().const_int(n, false);
+        let arg = unsafe { self.builder.build_in_bounds_gep(argv, &[n], "") };
+        let arg = self.builder.build_load(arg, "").into_pointer_value();
+        let atoi = self.c_atoi();
+        self.builder
+            .build_call(atoi, &[arg.into()], "")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value()
+    }
+
+    fn c_atoi(&mut self) -> FunctionValue<'a> {
+        *self.c_atoi.get_or_insert_with(|| {
+            let str_ty = self.llcx.i8_type().ptr_type(AddressSpace::Generic);
+            self.module.add_function(
+                "atoi",
+                self.llcx.i32_type().fn_type(&[str_ty.into()], false),
+                None,
+            )
+        })
+    }
+}
+
+/// The index of the first field of `variant` in the struct an enum is
+/// represented as.
+fn variant_offset(def: &EnumDef, variant: u32) -> u32 {
+    let before: usize = def.variants[..variant as usize].iter().map(|v| v.fields.len()).sum();
+    1 + before as u32
 }
 
 pub fn provide(providers: &mut Providers) {
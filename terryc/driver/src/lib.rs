@@ -0,0 +1,124 @@
+//! A library interface to the compiler, for tools like the LSP and tests that
+//! would otherwise have to run the `terryc` binary and read its output.
+
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use terryc_base::ast::Tree;
+pub use terryc_base::errors::Diagnostic;
+use terryc_base::hir::HirTree;
+use terryc_base::lex::Token;
+use terryc_base::mir::MirTree;
+use terryc_base::{Context, FileId, FileLoader, GlobalCtxt, Options, Providers};
+
+pub mod minimize;
+pub mod plugins;
+pub mod progress;
+#[cfg(test)]
+mod tests;
+
+/// Providers for every query, as used by the `terryc` binary.
+pub fn providers() -> Providers {
+    let mut providers = Providers::default();
+    terryc_lex::provide(&mut providers);
+    terryc_ast::provide(&mut providers);
+    terryc_mir::provide(&mut providers);
+    terryc_hir::provide(&mut providers);
+    #[cfg(feature = "codegen")]
+    terryc_codegen::provide(&mut providers);
+    providers
+}
+
+/// How far to take a file through the compiler.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Stage {
+    Lex,
+    Parse,
+    Hir,
+    Mir,
+    #[cfg(feature = "codegen")]
+    Codegen,
+}
+
+/// The result of the last stage that was run.
+#[derive(Debug)]
+pub enum Artifact {
+    Tokens(Rc<[Token]>),
+    Ast(Tree),
+    Hir(HirTree),
+    Mir(MirTree),
+    /// Code generation prints the module instead of returning it.
+    #[cfg(feature = "codegen")]
+    Codegen,
+}
+
+#[derive(Debug)]
+pub struct Output {
+    /// `None` if compilation failed before reaching the requested stage.
+    pub artifact: Option<Artifact>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// The compiler session of the current thread. Diagnostics are collected and
+/// returned from [`Compiler::run`] instead of being printed.
+pub struct Compiler {
+    // the context lives in a thread local.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Compiler {
+    /// Creates the context for this thread, which can only be done once.
+    pub fn new(options: Options) -> Self {
+        Self::with_providers(options, providers())
+    }
+
+    /// Like [`Compiler::new`], but with queries and hooks of the caller's
+    /// choosing. Start from [`providers`] to add passes or lints to the
+    /// regular pipeline.
+    pub fn with_providers(options: Options, providers: Providers) -> Self {
+        GlobalCtxt::create_and_then(options, |mut gcx| {
+            gcx.set_providers(terryc_base::leak(providers));
+            gcx.capture_diagnostics();
+            gcx
+        });
+        Compiler {
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Reads files with `loader` instead of from disk.
+    pub fn set_file_loader(&self, loader: impl FileLoader + 'static) {
+        GlobalCtxt::with_mut(|gcx| gcx.set_file_loader(terryc_base::leak(loader)))
+    }
+
+    /// Uses `contents` for `file` instead of what is on disk.
+    pub fn set_file_contents(&self, file: FileId, contents: String) {
+        GlobalCtxt::with_mut(|gcx| gcx.set_file_contents(file, contents))
+    }
+
+    /// Adds a file that only exists in memory.
+    pub fn add_file(&self, name: impl Into<PathBuf>, contents: String) -> FileId {
+        GlobalCtxt::with_mut(|gcx| gcx.add_virtual_file(name, contents))
+    }
+
+    /// Takes `file` to `stage`. Files are read again on every run, and the
+    /// diagnostics of cached results are reported again.
+    pub fn run(&self, file: FileId, stage: Stage) -> Output {
+        GlobalCtxt::with_mut(GlobalCtxt::new_revision);
+        GlobalCtxt::with(|gcx| {
+            let artifact = match stage {
+                Stage::Lex => gcx.lex(file).map(Artifact::Tokens),
+                Stage::Parse => gcx.parse(file).map(Artifact::Ast),
+                Stage::Hir => gcx.hir(file).map(Artifact::Hir),
+                Stage::Mir => gcx.mir(file).map(Artifact::Mir),
+                #[cfg(feature = "codegen")]
+                Stage::Codegen => gcx.codegen(file).map(|()| Artifact::Codegen),
+            };
+            Output {
+                artifact: artifact.ok(),
+                diagnostics: gcx.take_diagnostics(),
+            }
+        })
+    }
+}
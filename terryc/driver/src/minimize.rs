@@ -0,0 +1,313 @@
+//! Shrinks a file that makes the compiler fail into a small reproducer for
+//! bug reports, by deleting statements and expressions for as long as the
+//! failure still happens.
+
+use std::ffi::OsString;
+use std::fs;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use terryc_base::ast::{Block, Else, Expr, ExprIf, ExprKind, Item, ItemKind, StmtKind, Tree};
+use terryc_base::errors::Code;
+use terryc_base::rewrite::SourceRewriter;
+use terryc_base::{FileId, GlobalCtxt, Span};
+
+use crate::{Artifact, Compiler, Stage};
+
+/// The failure that every step of the minimization has to keep.
+#[derive(Debug, Clone)]
+pub enum Failure {
+    /// Running `command` with the path of the candidate as its last argument
+    /// panics or is killed by a signal, like `terryc` does on an ICE.
+    Crash { command: Vec<OsString> },
+    /// Compiling the candidate up to MIR emits a diagnostic with this code.
+    Diagnostic(Code),
+}
+
+/// Replaces `range` of the source with `replacement`.
+#[derive(Debug, Clone)]
+struct Edit {
+    range: Range<usize>,
+    replacement: String,
+}
+
+/// Returns the smallest source found that still shows `failure`, or `None` if
+/// `source` does not show it in the first place.
+///
+/// Candidates are parsed by `compiler`, whose main file is overwritten. For
+/// [`Failure::Crash`], they are also written to `path`, which should be next
+/// to the original file so that its modules are still found.
+pub fn minimize(
+    compiler: &Compiler,
+    source: &str,
+    path: &Path,
+    failure: &Failure,
+) -> Option<String> {
+    let tester = Tester {
+        compiler,
+        path,
+        failure,
+    };
+    if !tester.reproduces(source) {
+        return None;
+    }
+    let mut current = source.to_owned();
+    while let Some(smaller) = tester.reduce(&current) {
+        current = smaller;
+    }
+    Some(current)
+}
+
+struct Tester<'a> {
+    compiler: &'a Compiler,
+    path: &'a Path,
+    failure: &'a Failure,
+}
+
+impl Tester<'_> {
+    /// Finds a smaller source that still fails.
+    fn reduce(&self, source: &str) -> Option<String> {
+        let tree = self.parse(source)?;
+        let mut edits = Edits {
+            source,
+            removals: vec![],
+            replacements: vec![],
+        };
+        edits.tree(&tree);
+
+        // delta debugging: try to remove large chunks first, then smaller ones.
+        let mut size = edits.removals.len();
+        while size > 0 {
+            for chunk in edits.removals.chunks(size) {
+                let candidate = apply(source, chunk);
+                if self.reproduces(&candidate) {
+                    return Some(candidate);
+                }
+            }
+            size /= 2;
+        }
+
+        edits.replacements.iter().find_map(|edit| {
+            let candidate = apply(source, std::slice::from_ref(edit));
+            self.reproduces(&candidate).then_some(candidate)
+        })
+    }
+
+    fn parse(&self, source: &str) -> Option<Tree> {
+        self.compiler
+            .set_file_contents(FileId::Main, source.to_owned());
+        let output = self.silently(|| self.compiler.run(FileId::Main, Stage::Parse))?;
+        match output.artifact {
+            Some(Artifact::Ast(tree)) => Some(tree),
+            _ => None,
+        }
+    }
+
+    fn reproduces(&self, source: &str) -> bool {
+        match self.failure {
+            Failure::Crash { command } => {
+                if fs::write(self.path, source).is_err() {
+                    return false;
+                }
+                let Some((program, args)) = command.split_first() else {
+                    return false;
+                };
+                let status = Command::new(program)
+                    .args(args)
+                    .arg(self.path)
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status();
+                // a panic exits with 101, a crash has no exit code.
+                matches!(status, Ok(status) if matches!(status.code(), Some(101) | None))
+            }
+            &Failure::Diagnostic(code) => {
+                self.compiler
+                    .set_file_contents(FileId::Main, source.to_owned());
+                let output = self.silently(|| self.compiler.run(FileId::Main, Stage::Mir));
+                output.is_some_and(|output| output.diagnostics.iter().any(|d| d.code == Some(code)))
+            }
+        }
+    }
+
+    /// Runs `f` without printing its panic, if there is one. Candidates are
+    /// expected to make the compiler panic when minimizing an ICE.
+    fn silently<T>(&self, f: impl FnOnce() -> T) -> Option<T> {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(|_| {}));
+        let result = panic::catch_unwind(AssertUnwindSafe(f));
+        panic::set_hook(hook);
+        if result.is_err() {
+            // drop what was emitted before the panic.
+            GlobalCtxt::with(|gcx| gcx.take_diagnostics());
+        }
+        result.ok()
+    }
+}
+
+/// Collects the edits that can be made to the main file of a tree.
+struct Edits<'a> {
+    source: &'a str,
+    removals: Vec<Edit>,
+    /// Replacements of expressions with one of their operands.
+    replacements: Vec<Edit>,
+}
+
+impl Edits<'_> {
+    fn remove(&mut self, range: Range<usize>) {
+        if !range.is_empty() {
+            self.removals.push(Edit {
+                range,
+                replacement: String::new(),
+            });
+        }
+    }
+
+    fn replace(&mut self, span: Span, with: Span) {
+        let replacement = self.source[with.lo()..with.hi()].to_owned();
+        if replacement.len() < span.hi() - span.lo() {
+            self.replacements.push(Edit {
+                range: span.lo()..span.hi(),
+                replacement,
+            });
+        }
+    }
+
+    fn tree(&mut self, tree: &Tree) {
+        for item in tree.items.iter() {
+            self.remove(item.span.lo()..item.span.hi());
+            self.item(item);
+        }
+    }
+
+    fn item(&mut self, item: &Item) {
+        match &item.kind {
+            ItemKind::Fn(f) => self.block(&f.body),
+            ItemKind::Struct(_) | ItemKind::Enum(_) => {}
+            // modules are in other files.
+            ItemKind::Mod { .. } => {}
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        for stmt in &block.stmts {
+            self.remove(self.with_semicolon(stmt.span));
+            match &stmt.kind {
+                StmtKind::Expr(expr) => self.expr(expr),
+                StmtKind::Let { value, .. } => {
+                    if let Some(value) = value {
+                        self.expr(value);
+                    }
+                }
+                StmtKind::Item(item) => self.item(item),
+            }
+        }
+        if let Some(expr) = &block.expr {
+            self.remove(expr.span.lo()..expr.span.hi());
+            self.expr(expr);
+        }
+    }
+
+    /// The range of a statement, including the semicolon after it.
+    fn with_semicolon(&self, span: Span) -> Range<usize> {
+        let rest = &self.source[span.hi()..];
+        let whitespace = rest.len() - rest.trim_start().len();
+        if rest[whitespace..].starts_with(';') {
+            span.lo()..span.hi() + whitespace + 1
+        } else {
+            span.lo()..span.hi()
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::BinOp(_, lhs, rhs)
+            | ExprKind::Assignment { lhs, rhs }
+            | ExprKind::AssignOp(_, lhs, rhs) => {
+                if let ExprKind::BinOp(..) = expr.kind {
+                    self.replace(expr.span, lhs.span);
+                    self.replace(expr.span, rhs.span);
+                }
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            ExprKind::UnOp(_, inner) | ExprKind::Cast(inner, _) | ExprKind::Group(inner, _) => {
+                self.replace(expr.span, inner.span);
+                self.expr(inner);
+            }
+            ExprKind::Index(array, index) => {
+                self.replace(expr.span, array.span);
+                self.expr(array);
+                self.expr(index);
+            }
+            ExprKind::Return(Some(inner), _) | ExprKind::Field(inner, _) => self.expr(inner),
+            ExprKind::Closure(closure) => self.expr(&closure.body),
+            ExprKind::Block(block) => self.block(block),
+            ExprKind::If(if_) => self.if_(expr.span, if_),
+            ExprKind::While(while_) => {
+                self.expr(&while_.expr);
+                self.block(&while_.block);
+            }
+            ExprKind::For(for_) => {
+                self.expr(&for_.start);
+                self.expr(&for_.end);
+                self.block(&for_.block);
+            }
+            ExprKind::Call { callee, args } => {
+                self.expr(callee);
+                args.iter().for_each(|arg| self.expr(arg));
+            }
+            ExprKind::Array(elems) | ExprKind::Tuple(elems) => {
+                elems.iter().for_each(|elem| self.expr(elem))
+            }
+            ExprKind::Struct { fields, .. } => {
+                fields.iter().for_each(|field| self.expr(&field.value))
+            }
+            ExprKind::Match(match_) => {
+                self.expr(&match_.expr);
+                for arm in &match_.arms {
+                    self.replace(expr.span, arm.body.span);
+                    self.expr(&arm.body);
+                }
+            }
+            ExprKind::Literal(_)
+            | ExprKind::Ident(_)
+            | ExprKind::Underscore
+            | ExprKind::Return(None, _)
+            | ExprKind::Path(..) => {}
+        }
+    }
+
+    fn if_(&mut self, span: Span, if_: &ExprIf) {
+        self.replace(span, if_.block.span);
+        self.expr(&if_.expr);
+        self.block(&if_.block);
+        match &if_.else_ {
+            Some(Else::ElseIf(else_if, else_span)) => {
+                self.replace(span, *else_span);
+                self.if_(*else_span, else_if);
+            }
+            Some(Else::Else(block)) => {
+                self.replace(span, block.span);
+                self.block(block);
+            }
+            None => {}
+        }
+    }
+}
+
+/// Applies `edits` to `source`. Edits nested in an earlier edit are skipped.
+fn apply(source: &str, edits: &[Edit]) -> String {
+    let mut edits: Vec<_> = edits.iter().collect();
+    edits.sort_by_key(|edit| (edit.range.start, std::cmp::Reverse(edit.range.end)));
+
+    let mut rewriter = SourceRewriter::new(FileId::Main);
+    for edit in edits {
+        let span = Span::new(edit.range.start, edit.range.end, FileId::Main);
+        // an edit inside of another one is covered by it, and comes after it.
+        let _ = rewriter.replace(span, edit.replacement.clone());
+    }
+    rewriter.rewrite(source)
+}
@@ -0,0 +1,61 @@
+//! Lints that are compiled in but off by default, like the style rules of a
+//! course. `terryc check --lint <name>` turns them on. To ship a lint of your
+//! own, implement [`Lint`] and add it to [`PLUGINS`].
+
+use terryc_base::errors::{DiagnosticBuilder, DiagnosticSeverity};
+use terryc_base::hir::visit::{self, Visitor};
+use terryc_base::hir::{Expr, HirTree, ItemFn, Literal};
+use terryc_base::sym::Symbol;
+use terryc_base::{Context, Id, Lint};
+
+/// Every lint that can be turned on by name.
+pub const PLUGINS: &[&dyn Lint] = &[&WhileTrue];
+
+/// The plugin called `name`.
+pub fn plugin(name: &str) -> Option<&'static dyn Lint> {
+    PLUGINS.iter().copied().find(|lint| lint.name() == name)
+}
+
+/// Warns about `while true`, which hides when the loop ends in its body.
+pub struct WhileTrue;
+
+impl Lint for WhileTrue {
+    fn name(&self) -> &'static str {
+        "while_true"
+    }
+
+    fn check_hir(&self, cx: &dyn Context, tree: &HirTree) {
+        WhileTrueVisitor { cx, current_fn: None }.visit_tree(tree);
+    }
+}
+
+struct WhileTrueVisitor<'a> {
+    cx: &'a dyn Context,
+    /// The function being visited, by id and name.
+    current_fn: Option<(Id, Symbol)>,
+}
+
+impl Visitor for WhileTrueVisitor<'_> {
+    fn visit_fn(&mut self, f: &ItemFn) {
+        let outer = self.current_fn.replace((f.id, f.name));
+        self.visit_block(&f.block);
+        self.current_fn = outer;
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match (expr, self.current_fn) {
+            (Expr::While { cond, .. }, Some((id, name)))
+                if **cond == Expr::Literal(Literal::Bool(true)) =>
+            {
+                // the HIR has no spans, the function is the closest thing.
+                let message = format!("`{name}` loops with `while true`");
+                let span = self.cx.def_span(id).expect("functions are definitions");
+                DiagnosticBuilder::new(DiagnosticSeverity::Warning, message, span)
+                    .note("use a condition that says when the loop ends")
+                    .emit();
+            }
+            _ => {}
+        }
+        visit::walk_expr(self, expr)
+    }
+}
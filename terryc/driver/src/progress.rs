@@ -0,0 +1,80 @@
+//! A progress line for long builds, drawn on stderr as the compiler reports
+//! what it is doing through [`Observer`].
+
+use std::cell::Cell;
+
+use terryc_base::{FileId, Id, Observer};
+
+/// Counts files, functions and object files, and redraws a single line with
+/// the counts. The line is cleared before diagnostics are printed, so it
+/// should only be registered when stderr is a terminal.
+#[derive(Default)]
+pub struct Progress {
+    files: Cell<usize>,
+    functions: Cell<usize>,
+    objects: Cell<usize>,
+    /// The length of the line that is on the screen, 0 if none is.
+    shown: Cell<usize>,
+}
+
+impl Progress {
+    /// The counts so far, like `3 files parsed, 12 functions lowered`.
+    pub fn status(&self) -> String {
+        let mut status = plural(self.files.get(), "file") + " parsed";
+        if self.functions.get() > 0 {
+            status += &format!(", {} lowered", plural(self.functions.get(), "function"));
+        }
+        if self.objects.get() > 0 {
+            status += &format!(", {} generated", plural(self.objects.get(), "object file"));
+        }
+        status
+    }
+
+    fn draw(&self) {
+        let status = self.status();
+        // pads over the rest of a longer line that was drawn before.
+        eprint!("\r{status:<width$}", width = self.shown.get());
+        self.shown.set(status.len());
+    }
+
+    fn clear(&self) {
+        if self.shown.get() > 0 {
+            eprint!("\r{:width$}\r", "", width = self.shown.get());
+            self.shown.set(0);
+        }
+    }
+}
+
+fn plural(n: usize, what: &str) -> String {
+    if n == 1 {
+        format!("{n} {what}")
+    } else {
+        format!("{n} {what}s")
+    }
+}
+
+impl Observer for Progress {
+    fn query_started(&self, query: &'static str, _file: FileId) {
+        // nested modules are lexed and parsed without the `parse` query.
+        let counter = match query {
+            "lex" => &self.files,
+            "codegen" => &self.objects,
+            _ => return,
+        };
+        counter.set(counter.get() + 1);
+        self.draw();
+    }
+
+    fn function_lowered(&self, _id: Id) {
+        self.functions.set(self.functions.get() + 1);
+        self.draw();
+    }
+
+    fn before_diagnostic(&self) {
+        self.clear();
+    }
+
+    fn finished(&self) {
+        self.clear();
+    }
+}
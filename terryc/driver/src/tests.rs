@@ -0,0 +1,47 @@
+use terryc_base::errors::{Code, DiagnosticSeverity};
+use terryc_base::{FileId, Options};
+
+use crate::{Artifact, Compiler, Stage};
+
+#[test]
+fn driver_collects_diagnostics() {
+    let compiler = Compiler::new(Options::default());
+    let file = compiler.add_file("lib.terry", "fn f() -> i32 { 1 }".into());
+    let output = compiler.run(file, Stage::Parse);
+    assert!(matches!(output.artifact, Some(Artifact::Ast(_))));
+    assert!(output.diagnostics.is_empty());
+
+    compiler.set_file_contents(file, "fn f() -> i32 { 1 << 2 + 3 }".into());
+    let output = compiler.run(file, Stage::Parse);
+    assert!(output.artifact.is_some());
+    assert_eq!(output.diagnostics.len(), 1);
+    assert_eq!(output.diagnostics[0].severity, DiagnosticSeverity::Warning);
+
+    compiler.set_file_contents(file, "fn f() -> i32 { let }".into());
+    let output = compiler.run(file, Stage::Lex);
+    assert!(output.artifact.is_some());
+    let output = compiler.run(file, Stage::Parse);
+    assert!(output.artifact.is_none());
+    assert_eq!(output.diagnostics[0].severity, DiagnosticSeverity::Error);
+}
+
+#[test]
+fn cached_results_report_their_diagnostics_again() {
+    let compiler = Compiler::new(Options::default());
+    let file = compiler.add_file("lib.terry", "fn f() -> i32 { 1 << 2 + 3 }".into());
+    let src = "fn main() -> unit { let x: i32 = true; }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    for _ in 0..2 {
+        let output = compiler.run(FileId::Main, Stage::Hir);
+        assert!(output.artifact.is_none());
+        let codes: Vec<_> = output.diagnostics.iter().map(|d| d.code).collect();
+        assert_eq!(codes, [Some(Code::E0201)]);
+
+        let output = compiler.run(file, Stage::Parse);
+        assert!(output.artifact.is_some());
+        assert_eq!(output.diagnostics.len(), 1);
+    }
+    // the warning of the cached AST is reported when lowering it.
+    let output = compiler.run(file, Stage::Hir);
+    assert_eq!(output.diagnostics[0].severity, DiagnosticSeverity::Warning);
+}
@@ -0,0 +1,282 @@
+//! Rewrites surface syntax into the smaller set of constructs that HIR lowering
+//! understands, so that lowering (and everything after it) does not have to know
+//! about every form the user can write.
+//!
+//! * `for x in a..b { .. }` becomes a `while` loop over a local
+//! * `a op= b` becomes `a = a op b`
+//! * `else if` becomes an `else` block containing the `if`
+
+use terryc_base::ast::*;
+use terryc_base::errors::{make_diag, ErrorReported};
+use terryc_base::lex::Ident;
+use terryc_base::sym::Symbol;
+use terryc_base::{Context, FileId, Span};
+
+pub fn desugar(cx: &dyn Context, id: FileId) -> Result<Tree, ErrorReported> {
+    tree(&cx.parse(id)?)
+}
+
+fn tree(tree: &Tree) -> Result<Tree, ErrorReported> {
+    Ok(Tree {
+        items: tree.items.iter().map(item).collect::<Result<_, _>>()?,
+    })
+}
+
+fn item(i: &Item) -> Result<Item, ErrorReported> {
+    let kind = match &i.kind {
+        ItemKind::Fn(f) => ItemKind::Fn(ItemFn {
+            body: block(&f.body)?,
+            ..f.clone()
+        }),
+        ItemKind::Struct(_) | ItemKind::Enum(_) => i.kind.clone(),
+        ItemKind::Mod { name, tree: t } => ItemKind::Mod {
+            name: *name,
+            tree: tree(t)?,
+        },
+    };
+    Ok(Item {
+        kind,
+        attrs: i.attrs.clone(),
+        span: i.span,
+    })
+}
+
+fn block(b: &Block) -> Result<Block, ErrorReported> {
+    Ok(Block {
+        stmts: b.stmts.iter().map(stmt).collect::<Result<_, _>>()?,
+        expr: b.expr.as_deref().map(boxed).transpose()?,
+        span: b.span,
+    })
+}
+
+fn stmt(s: &Stmt) -> Result<Stmt, ErrorReported> {
+    let kind = match &s.kind {
+        StmtKind::Expr(e) => StmtKind::Expr(expr(e)?),
+        StmtKind::Let {
+            id,
+            mutable,
+            user_ty,
+            name,
+            value,
+        } => StmtKind::Let {
+            id: *id,
+            mutable: *mutable,
+            user_ty: *user_ty,
+            name: *name,
+            value: value.as_ref().map(expr).transpose()?,
+        },
+        StmtKind::Item(i) => StmtKind::Item(item(i)?),
+    };
+    Ok(Stmt {
+        kind,
+        attrs: s.attrs.clone(),
+        span: s.span,
+    })
+}
+
+fn boxed(e: &Expr) -> Result<Box<Expr>, ErrorReported> {
+    Ok(Box::new(expr(e)?))
+}
+
+fn exprs(es: &[Expr]) -> Result<Vec<Expr>, ErrorReported> {
+    es.iter().map(expr).collect()
+}
+
+fn if_(e: &ExprIf) -> Result<ExprIf, ErrorReported> {
+    let else_ = match &e.else_ {
+        Some(Else::ElseIf(if_expr, span)) => Some(Else::Else(Block {
+            stmts: vec![],
+            expr: Some(Box::new(Expr {
+                kind: ExprKind::If(if_(if_expr)?),
+                span: *span,
+            })),
+            span: *span,
+        })),
+        Some(Else::Else(b)) => Some(Else::Else(block(b)?)),
+        None => None,
+    };
+    Ok(ExprIf {
+        expr: boxed(&e.expr)?,
+        block: block(&e.block)?,
+        else_,
+    })
+}
+
+fn ident_expr(ident: Ident) -> Expr {
+    Expr {
+        kind: ExprKind::Ident(ident.symbol),
+        span: ident.span,
+    }
+}
+
+fn stmt_expr(kind: ExprKind, span: Span) -> Stmt {
+    Stmt {
+        kind: StmtKind::Expr(Expr { kind, span }),
+        attrs: vec![],
+        span,
+    }
+}
+
+/// ```text
+/// {
+///     let mut binding = start;
+///     let <end> = end;
+///     while binding < <end> {
+///         { block }
+///         binding = binding + 1;
+///     }
+/// }
+/// ```
+fn for_(e: &ExprFor, span: Span) -> Result<ExprKind, ErrorReported> {
+    let binding = ident_expr(e.binding);
+    let end = ident_expr(Ident {
+        // not a valid identifier, so it can't clash with anything the user wrote.
+        symbol: Symbol::new("<for end>"),
+        span: e.end.span,
+    });
+    let one = Expr {
+        kind: ExprKind::Literal(Literal {
+            kind: LiteralKind::Int(1),
+        }),
+        span: e.for_,
+    };
+    let increment = ExprKind::Assignment {
+        lhs: Box::new(binding.clone()),
+        rhs: Box::new(Expr {
+            kind: ExprKind::BinOp(BinOpKind::Add, Box::new(binding.clone()), Box::new(one)),
+            span: e.for_,
+        }),
+    };
+    let body = Block {
+        stmts: vec![
+            stmt_expr(ExprKind::Block(block(&e.block)?), e.block.span),
+            stmt_expr(increment, e.for_),
+        ],
+        expr: None,
+        span: e.block.span,
+    };
+    let cond = Expr {
+        kind: ExprKind::BinOp(BinOpKind::Less, Box::new(binding), Box::new(end.clone())),
+        span: e.binding.span.to(e.end.span),
+    };
+    let while_ = ExprKind::While(ExprWhile {
+        while_: e.for_,
+        expr: Box::new(cond),
+        block: body,
+    });
+    Ok(ExprKind::Block(Block {
+        stmts: vec![
+            Stmt {
+                kind: StmtKind::Let {
+                    id: e.id,
+                    // the increment assigns to it.
+                    mutable: true,
+                    user_ty: None,
+                    name: e.binding,
+                    value: Some(expr(&e.start)?),
+                },
+                attrs: vec![],
+                span: e.binding.span.to(e.start.span),
+            },
+            Stmt {
+                kind: StmtKind::Let {
+                    id: e.end_id,
+                    mutable: false,
+                    user_ty: None,
+                    name: Ident {
+                        symbol: Symbol::new("<for end>"),
+                        span: e.end.span,
+                    },
+                    value: Some(expr(&e.end)?),
+                },
+                attrs: vec![],
+                span: e.end.span,
+            },
+            stmt_expr(while_, span),
+        ],
+        expr: None,
+        span,
+    }))
+}
+
+/// `a op= b` becomes `a = a op b`. Only locals can be assigned to, and reading
+/// a local twice is the same as reading it once, so the place is not bound to
+/// a temporary first.
+fn assign_op(op: BinOpKind, lhs: &Expr, rhs: &Expr, span: Span) -> Result<ExprKind, ErrorReported> {
+    let ExprKind::Ident(_) = lhs.kind else {
+        raise::yeet!(make_diag!(Error, lhs.span, E0222).emit());
+    };
+    Ok(ExprKind::Assignment {
+        lhs: Box::new(lhs.clone()),
+        rhs: Box::new(Expr {
+            kind: ExprKind::BinOp(op, Box::new(lhs.clone()), boxed(rhs)?),
+            span,
+        }),
+    })
+}
+
+fn expr(e: &Expr) -> Result<Expr, ErrorReported> {
+    let kind = match &e.kind {
+        ExprKind::BinOp(op, lhs, rhs) => ExprKind::BinOp(*op, boxed(lhs)?, boxed(rhs)?),
+        ExprKind::UnOp(op, e) => ExprKind::UnOp(*op, boxed(e)?),
+        ExprKind::Cast(e, ty) => ExprKind::Cast(boxed(e)?, *ty),
+        ExprKind::Group(e, span) => ExprKind::Group(boxed(e)?, *span),
+        ExprKind::Literal(_) | ExprKind::Ident(_) | ExprKind::Underscore | ExprKind::Path(..) => {
+            e.kind.clone()
+        }
+        ExprKind::Block(b) => ExprKind::Block(block(b)?),
+        ExprKind::Return(e, span) => ExprKind::Return(e.as_deref().map(boxed).transpose()?, *span),
+        ExprKind::Assignment { lhs, rhs } => ExprKind::Assignment {
+            lhs: boxed(lhs)?,
+            rhs: boxed(rhs)?,
+        },
+        ExprKind::AssignOp(op, lhs, rhs) => assign_op(*op, lhs, rhs, e.span)?,
+        ExprKind::If(e) => ExprKind::If(if_(e)?),
+        ExprKind::While(w) => ExprKind::While(ExprWhile {
+            while_: w.while_,
+            expr: boxed(&w.expr)?,
+            block: block(&w.block)?,
+        }),
+        ExprKind::For(f) => for_(f, e.span)?,
+        ExprKind::Call { callee, args } => ExprKind::Call {
+            callee: boxed(callee)?,
+            args: exprs(args)?,
+        },
+        ExprKind::Array(elems) => ExprKind::Array(exprs(elems)?),
+        ExprKind::Tuple(elems) => ExprKind::Tuple(exprs(elems)?),
+        ExprKind::Index(array, index) => ExprKind::Index(boxed(array)?, boxed(index)?),
+        ExprKind::Struct { name, fields } => ExprKind::Struct {
+            name: *name,
+            fields: fields
+                .iter()
+                .map(|field| {
+                    Ok(ExprField {
+                        name: field.name,
+                        value: expr(&field.value)?,
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+        },
+        ExprKind::Field(base, field) => ExprKind::Field(boxed(base)?, *field),
+        ExprKind::Match(m) => ExprKind::Match(ExprMatch {
+            expr: boxed(&m.expr)?,
+            arms: m
+                .arms
+                .iter()
+                .map(|arm| {
+                    Ok(Arm {
+                        pat: arm.pat.clone(),
+                        body: expr(&arm.body)?,
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+        }),
+        ExprKind::Closure(c) => ExprKind::Closure(ExprClosure {
+            id: c.id,
+            args: c.args.clone(),
+            ret: c.ret,
+            body: boxed(&c.body)?,
+        }),
+    };
+    Ok(Expr { kind, span: e.span })
+}
@@ -0,0 +1,88 @@
+//! Finds the `main` function a program starts at, and the `#[test]`
+//! functions a test runner calls.
+
+use std::rc::Rc;
+
+use terryc_ast::TyKind;
+use terryc_base::errors::{make_diag, msg, ErrorReported};
+use terryc_base::hir::*;
+use terryc_base::{sym, Context, FileId, Id, Span};
+
+/// Checks that there is exactly one `fn main() -> unit`, in `id` or any of
+/// its modules. Functions nested in other functions are never entry points.
+pub fn entry_point(cx: &dyn Context, id: FileId) -> Result<Id, ErrorReported> {
+    let tree = cx.hir(id)?;
+    let mut mains = vec![];
+    collect_mains(&tree.items, &mut mains);
+    let Some((first, rest)) = mains.split_first() else {
+        raise::yeet!(make_diag!(Error, Span::new(0, 0, id), E0229)
+            .note(msg!(N0229))
+            .emit());
+    };
+    let def_span = |id| cx.def_span(id).expect("functions are definitions");
+    if let Some(second) = rest.first() {
+        raise::yeet!(make_diag!(Error, def_span(second.id), E0230)
+            .span_note(def_span(first.id), msg!(N0230))
+            .emit());
+    }
+    if !first.args.is_empty() || first.ret != TyKind::Unit {
+        raise::yeet!(make_diag!(Error, def_span(first.id), E0231)
+            .note(msg!(N0231, signature(first)))
+            .emit());
+    }
+    Ok(first.id)
+}
+
+/// Checks that every `#[test]` and `#[bench]` function in `id` and its
+/// modules takes no arguments and returns `unit`, so the test runner can
+/// call it.
+pub fn tests(cx: &dyn Context, id: FileId) -> Result<Rc<[TestFn]>, ErrorReported> {
+    let tree = cx.hir(id)?;
+    let mut tests = vec![];
+    collect_tests(cx, &tree.items, "", &mut tests)?;
+    Ok(tests.into())
+}
+
+fn collect_tests(
+    cx: &dyn Context,
+    items: &[Item],
+    prefix: &str,
+    tests: &mut Vec<TestFn>,
+) -> Result<(), ErrorReported> {
+    for item in items {
+        match item {
+            &Item::Fn(ref f @ ItemFn { test: Some(kind), .. }) => {
+                if !f.args.is_empty() || f.ret != TyKind::Unit {
+                    let span = cx.def_span(f.id).expect("functions are definitions");
+                    raise::yeet!(make_diag!(Error, span, E0233)
+                        .note(msg!(N0233, f.name, signature(f)))
+                        .emit());
+                }
+                let name = format!("{prefix}{}", f.name);
+                tests.push(TestFn { id: f.id, name, kind });
+            }
+            Item::Mod { name, tree } => {
+                let prefix = format!("{prefix}{}::", name.symbol);
+                collect_tests(cx, &tree.items, &prefix, tests)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Like `fn main(i32) -> i32`, for notes about the wrong signature.
+fn signature(f: &ItemFn) -> String {
+    let args: Vec<_> = f.args.iter().map(|arg| arg.ty.to_string()).collect();
+    format!("fn {}({}) -> {}", f.name, args.join(", "), f.ret)
+}
+
+fn collect_mains<'a>(items: &'a [Item], mains: &mut Vec<&'a ItemFn>) {
+    for item in items {
+        match item {
+            Item::Fn(f) if f.name == sym::main => mains.push(f),
+            Item::Mod { tree, .. } => collect_mains(&tree.items, mains),
+            _ => {}
+        }
+    }
+}
@@ -2,20 +2,39 @@
 #![warn(rust_2018_idioms)]
 
 use std::collections::hash_map::Entry;
+use std::rc::Rc;
 
 use ast::{BinOpKind, Ty};
-use rustc_hash::{FxHashMap, FxHashSet};
-use terryc_ast::{self as ast, TyKind, UnOpKind};
+use rustc_hash::FxHashMap;
+use terryc_ast::{self as ast, EnumDef, StructDef, TyKind, UnOpKind};
 use terryc_base::ast::ExprKind;
-use terryc_base::errors::{make_diag, DiagnosticBuilder, DiagnosticSeverity, ErrorReported};
+use terryc_base::data::{FxIndexMap, FxIndexSet};
+use terryc_base::errors::{make_diag, msg, ErrorReported};
+use terryc_base::lex::Ident;
+use terryc_base::metadata::{ExternCrate, ExternFn};
 pub use terryc_base::hir::*;
 use terryc_base::sym::Symbol;
-use terryc_base::{sym, Context, FileId, Id, IdMaker, Providers, Span};
+use terryc_base::{sym, Context, FileId, Id, Providers, Span};
+
+mod desugar;
+mod entry;
+mod pattern;
+#[cfg(test)]
+mod tests;
+
+/// A closure being lowered: the locals declared outside of it, and the ones of
+/// those its body uses in the order it first uses them.
+struct CaptureFrame {
+    outside: FxIndexSet<Id>,
+    captures: FxIndexMap<Id, (Symbol, ResolvedDecl)>,
+}
 
 #[derive(Clone)]
 pub struct ResolvedDecl {
     id: Id,
-    type_: TyKind,
+    mutable: bool,
+    /// The name in the declaration.
+    span: Span,
 }
 
 #[derive(Clone, Copy)]
@@ -26,7 +45,7 @@ pub enum TypeckExpectation<'a> {
         sp: Span,
     },
     AnyOf {
-        tys: &'a FxHashSet<TyKind>,
+        tys: &'a FxIndexSet<TyKind>,
         sp: Span,
     },
 }
@@ -43,27 +62,30 @@ impl From<Ty> for TypeckExpectation<'_> {
 impl TypeckExpectation<'_> {
     pub fn check(&self, result: TyKind, res: Span) -> Result<(), ErrorReported> {
         match self {
+            // `return` leaves before its value could be used.
+            _ if result == TyKind::Never => Ok(()),
             Self::Equals { ty, sp } if result != *ty => Err(make_diag! {
                 Error,
                 res,
-                "mismatched types",
+                E0201,
             }
-            .note(format_args!("expected `{ty}`, found `{result}`"))
-            .span_note(*sp, "expected because of this")
+            .note(msg!(N0201, ty, result))
+            .span_note(*sp, msg!(N0203))
             .emit()),
             Self::AnyOf { tys, sp } if !tys.contains(&result) => Err(make_diag! {
                 Error,
                 res,
-                "mismatched types",
+                E0201,
             }
-            .note(format_args!(
-                "expected one of {}, found `{result}`",
+            .note(msg!(
+                N0202,
                 tys.iter()
                     .map(|x| format!("`{x}`"))
                     .intersperse(", ".to_string())
-                    .collect::<String>()
+                    .collect::<String>(),
+                result,
             ))
-            .span_note(*sp, "expected because of this")
+            .span_note(*sp, msg!(N0203))
             .emit()),
             Self::Equals { .. } | Self::AnyOf { .. } | Self::NoExpectation => Ok(()),
         }
@@ -73,26 +95,115 @@ impl TypeckExpectation<'_> {
 #[derive(Default)]
 pub struct AstLowerer {
     fn_symbols: FxHashMap<Symbol, Id>,
+    structs: FxHashMap<Symbol, &'static StructDef>,
+    enums: FxHashMap<Symbol, &'static EnumDef>,
     scoped_syms: FxHashMap<Symbol, ResolvedDecl>,
-    functions: FxHashMap<Id, Func>,
+    /// The types of the locals, spanning what decided them: the annotation,
+    /// the initializer or the first assignment of a `let x;`. Those are
+    /// missing until the assignment is checked.
+    local_tys: FxHashMap<Id, Ty>,
+    functions: FxIndexMap<Id, Func>,
+    /// The locals each closure captures, by the id of the closure. They are
+    /// passed to it after its arguments.
+    closures: FxHashMap<Id, Vec<(Symbol, ResolvedDecl)>>,
+    /// The closures being lowered, innermost last.
+    capturing: Vec<CaptureFrame>,
     // all_items: Vec<Item>,
-    def_ids: IdMaker,
     current_func_ret_ty: Option<Ty>,
+    /// The `--cfg` options. Items and statements whose `#[cfg(...)]` is not
+    /// enabled are left out.
+    cfg: &'static [String],
+    /// The libraries given with `--extern`.
+    externs: Rc<[ExternCrate]>,
     pub had_errors: bool,
 }
 
 impl AstLowerer {
-    fn lower_ty(&mut self, ty: &Ty) -> TyKind {
-        match ty.kind {
-            ast::TyKind::I32 => TyKind::I32,
-            ast::TyKind::Unit => TyKind::Unit,
-            _ => todo!(),
+    /// Resolves the names of structs and enums in `ty`.
+    fn lower_ty(&mut self, ty: &Ty) -> Result<Ty, ErrorReported> {
+        let kind = self.lower_ty_kind(ty.kind, ty.span)?;
+        Ok(Ty {
+            kind,
+            span: ty.span,
+        })
+    }
+    fn lower_ty_kind(&mut self, kind: TyKind, span: Span) -> Result<TyKind, ErrorReported> {
+        Ok(match kind {
+            TyKind::Named(name) => match (self.structs.get(&name), self.enums.get(&name)) {
+                (Some(&def), _) => TyKind::Struct(def),
+                (None, Some(&def)) => TyKind::Enum(def),
+                (None, None) => raise::yeet!(make_diag!(Error, span, E0217, name).emit()),
+            },
+            TyKind::Array(elem, len) => TyKind::array(self.lower_ty_kind(*elem, span)?, len),
+            TyKind::Tuple(elems) => {
+                let elems: Vec<_> = elems
+                    .iter()
+                    .map(|&elem| self.lower_ty_kind(elem, span))
+                    .collect::<Result<_, _>>()?;
+                TyKind::tuple(elems)
+            }
+            kind => kind,
+        })
+    }
+    fn lower_struct(&mut self, s: &ast::ItemStruct) -> Result<Item, ErrorReported> {
+        let mut fields = Vec::with_capacity(s.fields.len());
+        for field in &s.fields {
+            let name = field.name.symbol;
+            if fields.iter().any(|&(prev, _)| prev == name) {
+                raise::yeet!(make_diag!(Error, field.name.span, E0218, name).emit());
+            }
+            fields.push((name, self.lower_ty(&field.ty)?.kind));
+        }
+        let def = StructDef {
+            id: s.id,
+            name: s.name.symbol,
+            fields,
+        }
+        .intern();
+        if self.structs.insert(s.name.symbol, def).is_some()
+            || self.enums.contains_key(&s.name.symbol)
+        {
+            raise::yeet!(make_diag!(Error, s.name.span, E0218, s.name.symbol).emit());
+        }
+        Ok(Item::Struct(def))
+    }
+    /// Checks that a literal of `def` initializes each of its fields once.
+    fn typeck_fields(
+        &mut self,
+        def: &'static StructDef,
+        fields: &[ast::ExprField],
+        sp: Span,
+    ) -> Result<(), ErrorReported> {
+        let mut indices = Vec::with_capacity(fields.len());
+        for field in fields {
+            let name = field.name;
+            let Some((index, ty)) = def.field(name.symbol) else {
+                raise::yeet!(make_diag!(Error, name.span, E0219, name.symbol, def.name).emit());
+            };
+            if indices.contains(&index) {
+                raise::yeet!(make_diag!(Error, name.span, E0221, name.symbol).emit());
+            }
+            self.typeck(&field.value, TypeckExpectation::Equals { ty, sp: name.span })?;
+            indices.push(index);
+        }
+        if let Some((missing, _)) =
+            def.fields.iter().zip(0..).find(|(_, index)| !indices.contains(index))
+        {
+            raise::yeet!(make_diag!(Error, sp, E0220, missing.0, def.name).emit());
         }
+        Ok(())
     }
     fn lower_item(&mut self, item: &ast::Item) -> Result<Item, ErrorReported> {
         match &item.kind {
+            ast::ItemKind::Struct(s) => self.lower_struct(s),
+            ast::ItemKind::Enum(e) => self.lower_enum(e),
             ast::ItemKind::Mod { name, tree } => {
-                Ok(Item::Mod { name: *name, tree: AstLowerer::default().lower_tree(tree)? })
+                let lowerer = AstLowerer {
+                    cfg: self.cfg,
+                    externs: self.externs.clone(),
+                    ..AstLowerer::default()
+                };
+                Ok(Item::Mod { name: *name, tree: lowerer.lower_tree(tree)? })
             }
             ast::ItemKind::Fn(ast::ItemFn {
                 name,
@@ -100,79 +211,239 @@ impl AstLowerer {
                 args,
                 ret,
                 body,
-            }) => match self.fn_symbols.entry(name.symbol) {
-                Entry::Occupied(_) => {
-                    raise::yeet!(
-                        make_diag!(Error, name.span, "function clashes with variable").emit()
-                    );
-                }
-                Entry::Vacant(v) => {
-                    v.insert(*id);
-                    self.functions.insert(
-                        *id,
-                        Func {
-                            name: *name,
-                            args: args.iter().map(|(_, t)| *t).collect(),
-                            ret: ret.kind,
-                        },
-                    );
-                    let mut lowered_args = Vec::with_capacity(args.len());
-                    let prev = self.scoped_syms.clone();
-                    self.current_func_ret_ty = Some(*ret);
-                    for (ident, ty) in args {
-                        let id = self.def_ids.make();
-                        let ty = self.lower_ty(ty);
-                        self.scoped_syms
-                            .insert(ident.symbol, ResolvedDecl { id, type_: ty });
-                        lowered_args.push(FnArg {
-                            name: *ident,
-                            ty,
-                            id,
-                        })
-                    }
-                    let block = self.lower_block(body, (*ret).into())?;
-                    self.scoped_syms = prev;
-                    self.current_func_ret_ty = None;
-                    Ok(Item::Fn(ItemFn {
+            }) => {
+                let ret = self.lower_ty(ret)?;
+                let arg_tys = args
+                    .iter()
+                    .map(|arg| self.lower_ty(&arg.ty))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let mut lowered_args = Vec::with_capacity(args.len());
+                let prev = self.scoped_syms.clone();
+                // functions can be declared inside of other functions.
+                let outer_ret_ty = self.current_func_ret_ty.replace(ret);
+                for (ast::FnArg { id, name, .. }, ty) in args.iter().zip(arg_tys) {
+                    let decl = ResolvedDecl {
+                        id: *id,
+                        mutable: false,
+                        span: name.span,
+                    };
+                    self.scoped_syms.insert(name.symbol, decl);
+                    self.local_tys.insert(*id, ty);
+                    lowered_args.push(FnArg {
+                        name: *name,
+                        ty: ty.kind,
                         id: *id,
-                        name: name.symbol,
-                        args: lowered_args,
-                        ret: self.lower_ty(ret),
-                        block,
-                    }))
+                    })
                 }
-            },
+                let block = self.lower_block(body, ret.into());
+                self.scoped_syms = prev;
+                self.current_func_ret_ty = outer_ret_ty;
+                let block = block?;
+                // without a value at the end, the body has to `return` one.
+                if block.expr.is_none() && !block.always_returns() {
+                    TypeckExpectation::from(ret).check(TyKind::Unit, body.span)?;
+                }
+                Ok(Item::Fn(ItemFn {
+                    id: *id,
+                    name: name.symbol,
+                    args: lowered_args,
+                    ret: ret.kind,
+                    block,
+                    test: item.attrs.iter().find_map(|attr| match attr.name.symbol {
+                        sym::test => Some(TestKind::Test),
+                        sym::bench => Some(TestKind::Bench),
+                        _ => None,
+                    }),
+                    inline: item.attrs.iter().find(|attr| attr.name.symbol == sym::inline).map(
+                        |attr| match attr.args.first().map(|arg| arg.name.symbol) {
+                            Some(sym::always) => InlineKind::Always,
+                            Some(sym::never) => InlineKind::Never,
+                            _ => InlineKind::Hint,
+                        },
+                    ),
+                }))
+            }
+        }
+    }
+    /// Makes a function callable before its item is lowered, so that functions
+    /// can call themselves and the ones declared after them.
+    fn declare_fn(&mut self, f: &ast::ItemFn) -> Result<(), ErrorReported> {
+        let Entry::Vacant(v) = self.fn_symbols.entry(f.name.symbol) else {
+            raise::yeet!(make_diag!(Error, f.name.span, E0202).emit());
+        };
+        v.insert(f.id);
+        let ret = self.lower_ty(&f.ret)?;
+        let args = f
+            .args
+            .iter()
+            .map(|arg| self.lower_ty(&arg.ty))
+            .collect::<Result<_, _>>()?;
+        self.functions.insert(f.id, Func { name: f.name, args, ret: ret.kind });
+        Ok(())
+    }
+    /// Lowers `let name = |args| body;` to a function that takes the locals
+    /// the body captures after its arguments.
+    fn lower_closure(
+        &mut self,
+        name: Ident,
+        closure: &ast::ExprClosure,
+    ) -> Result<Item, ErrorReported> {
+        let mut arg_tys = closure
+            .args
+            .iter()
+            .map(|arg| self.lower_ty(&arg.ty))
+            .collect::<Result<Vec<_>, _>>()?;
+        let ret = closure.ret.as_ref().map(|ty| self.lower_ty(ty)).transpose()?;
+        let outside = self.scoped_syms.values().map(|decl| decl.id).collect();
+        self.capturing.push(CaptureFrame { outside, captures: FxIndexMap::default() });
+        let prev = self.scoped_syms.clone();
+        let outer_ret_ty = std::mem::replace(&mut self.current_func_ret_ty, ret);
+        let mut args = Vec::with_capacity(arg_tys.len());
+        for (ast::FnArg { id, name, .. }, ty) in closure.args.iter().zip(&arg_tys) {
+            let decl = ResolvedDecl { id: *id, mutable: false, span: name.span };
+            self.scoped_syms.insert(name.symbol, decl);
+            self.local_tys.insert(*id, *ty);
+            args.push(FnArg { name: *name, ty: ty.kind, id: *id });
+        }
+        let body = self.lower_closure_body(&closure.body, ret);
+        self.scoped_syms = prev;
+        self.current_func_ret_ty = outer_ret_ty;
+        let frame = self.capturing.pop().unwrap();
+        let (body, ret) = body?;
+
+        let captures: Vec<_> = frame.captures.into_values().collect();
+        for (symbol, decl) in &captures {
+            let ty = self.local_tys[&decl.id];
+            arg_tys.push(ty);
+            let name = Ident { symbol: *symbol, span: decl.span };
+            args.push(FnArg { name, ty: ty.kind, id: decl.id });
+        }
+        if self.fn_symbols.contains_key(&name.symbol) {
+            make_diag!(Error, name.span, E0205, name.symbol).emit();
+        }
+        self.functions.insert(closure.id, Func { name, args: arg_tys, ret: ret.kind });
+        self.closures.insert(closure.id, captures);
+        let decl = ResolvedDecl { id: closure.id, mutable: false, span: name.span };
+        self.scoped_syms.insert(name.symbol, decl);
+        Ok(Item::Fn(ItemFn {
+            id: closure.id,
+            name: name.symbol,
+            args,
+            ret: ret.kind,
+            block: Block { statements: vec![], expr: Some(Box::new(body)) },
+            test: None,
+            inline: None,
+        }))
+    }
+    /// Without a return type, a closure returns the type of its body. With
+    /// one, the body is a block checked like the one of a function.
+    fn lower_closure_body(
+        &mut self,
+        body: &ast::Expr,
+        ret: Option<Ty>,
+    ) -> Result<(Expr, Ty), ErrorReported> {
+        if let Some(ret) = ret {
+            let ast::ExprKind::Block(block) = &body.kind else {
+                unreachable!("the parser only allows a block after `-> ty`")
+            };
+            let block = self.lower_block(block, ret.into())?;
+            if block.expr.is_none() && !block.always_returns() {
+                TypeckExpectation::from(ret).check(TyKind::Unit, body.span)?;
+            }
+            return Ok((Expr::Block(block), ret));
+        }
+        let kind = self.typeck(body, TypeckExpectation::NoExpectation)?;
+        let ret = Ty { kind, span: body.span };
+        Ok((self.lower_expr(body, ret.into())?, ret))
+    }
+    /// The id of the closure `name` refers to, if it is one.
+    fn closure_id(&self, name: Symbol) -> Option<Id> {
+        let decl = self.scoped_syms.get(&name)?;
+        self.closures.contains_key(&decl.id).then_some(decl.id)
+    }
+    /// Records that the closures being lowered use the local `decl`, if they
+    /// are inside of its scope. Only immutable locals can be captured, since
+    /// closures are passed their values when they are called.
+    fn capture(
+        &mut self,
+        name: Symbol,
+        decl: &ResolvedDecl,
+        span: Span,
+    ) -> Result<(), ErrorReported> {
+        for frame in &mut self.capturing {
+            if !frame.outside.contains(&decl.id) || frame.captures.contains_key(&decl.id) {
+                continue;
+            }
+            if decl.mutable {
+                raise::yeet!(make_diag!(Error, span, E0241, name).note(msg!(N0241)).emit());
+            }
+            frame.captures.insert(decl.id, (name, decl.clone()));
+        }
+        Ok(())
+    }
+    fn lower_closure_call(
+        &mut self,
+        e: &ast::Expr,
+        id: Id,
+        args: &[ast::Expr],
+        expectation: TypeckExpectation<'_>,
+    ) -> Result<Expr, ErrorReported> {
+        self.typeck(e, expectation)?;
+        let func = self.functions[&id].clone();
+        let mut lowered = Vec::with_capacity(func.args.len());
+        for (arg, &ty) in args.iter().zip(&func.args) {
+            lowered.push((self.lower_expr(arg, ty.into())?, ty.kind));
+        }
+        for (name, decl) in self.closures[&id].clone() {
+            self.capture(name, &decl, e.span)?;
+            let ty = self.local_tys[&decl.id].kind;
+            lowered.push((Expr::Resolved(Resolution::Local(decl.id)), ty));
         }
+        Ok(Expr::Call { callee: Resolution::Fn(id), args: lowered, ret: func.ret })
     }
     fn lower_stmt(&mut self, stmt: &ast::Stmt) -> Result<Stmt, ErrorReported> {
         match &stmt.kind {
-            ast::StmtKind::Expr(expr) => Ok(Stmt::Expr(
-                self.lower_expr(expr, TypeckExpectation::NoExpectation)?,
-            )),
+            ast::StmtKind::Expr(expr) => {
+                let lowered = self.lower_expr(expr, TypeckExpectation::NoExpectation)?;
+                if lowered.is_unused_value() {
+                    make_diag! {
+                        Warning,
+                        expr.span,
+                        W0203,
+                    }
+                    .note(msg!(N0204))
+                    .emit();
+                }
+                Ok(Stmt::Expr(lowered))
+            }
             ast::StmtKind::Let {
-                id: _,
+                id,
+                mutable,
                 name,
                 user_ty,
                 value,
             } => {
+                if let Some(ast::Expr { kind: ast::ExprKind::Closure(closure), span }) = value {
+                    if *mutable || user_ty.is_some() {
+                        raise::yeet!(make_diag!(Error, *span, E0239).emit());
+                    }
+                    return self.lower_closure(*name, closure).map(Stmt::Item);
+                }
+                let user_ty = user_ty.as_ref().map(|ty| self.lower_ty(ty)).transpose()?;
                 let expectation = user_ty
                     .map(|x| TypeckExpectation::Equals {
                         ty: x.kind,
                         sp: x.span,
                     })
                     .unwrap_or(TypeckExpectation::NoExpectation);
-                let ty = if let Some(val) = value {
-                    self.typeck(val, expectation)?
-                } else if let Some(user_ty) = user_ty {
-                    user_ty.kind
-                } else {
-                    raise::yeet!(make_diag! {
-                        Error,
-                        name.span,
-                        "missing type annotation for `{}`",
-                        name.symbol
-                    }
-                    .emit());
+                // without either, `let x;` takes the type of the first value
+                // assigned to `x`.
+                let ty = match value {
+                    Some(val) => Some(Ty {
+                        kind: self.typeck(val, expectation)?,
+                        span: user_ty.map_or(val.span, |ty| ty.span),
+                    }),
+                    None => user_ty,
                 };
 
                 let value = value
@@ -181,19 +452,19 @@ impl AstLowerer {
                     .transpose()?;
                 let sym = &name.symbol;
                 if self.fn_symbols.contains_key(sym) {
-                    DiagnosticBuilder::new(
-                        DiagnosticSeverity::Error,
-                        format!("`{sym}` clashes with a previous function declaration"),
-                        name.span,
-                    )
-                    .emit();
+                    make_diag!(Error, name.span, E0205, sym).emit();
+                }
+                let decl = ResolvedDecl { id: *id, mutable: *mutable, span: name.span };
+                self.scoped_syms.insert(*sym, decl);
+                if let Some(ty) = ty {
+                    self.local_tys.insert(*id, ty);
                 }
-                let id = self.def_ids.make();
-                self.scoped_syms
-                    .insert(*sym, ResolvedDecl { type_: ty, id });
                 Ok(Stmt::Local(LocalDecl {
-                    id,
-                    ty,
+                    id: *id,
+                    name: *name,
+                    mutable: *mutable,
+                    // filled in by `lower_block` once the type is known.
+                    ty: ty.map_or(TyKind::Unit, |ty| ty.kind),
                     initializer: value,
                 }))
             }
@@ -208,16 +479,36 @@ impl AstLowerer {
     ) -> Result<Block, ErrorReported> {
         let mut statements = vec![];
         let prev_env = self.scoped_syms.clone();
-        for stmt in &block.stmts {
+        let stmts: Vec<_> =
+            block.stmts.iter().filter(|stmt| is_enabled(&stmt.attrs, self.cfg)).collect();
+        for stmt in &stmts {
+            if let ast::StmtKind::Item(ast::Item { kind: ast::ItemKind::Fn(f), .. }) = &stmt.kind {
+                self.declare_fn(f)?;
+            }
+        }
+        for stmt in stmts {
             statements.push(self.lower_stmt(stmt)?);
         }
         let expr = block
             .expr
             .as_ref()
-            .map(|e| self.lower_expr(e, expectation))
+            .map(|e| {
+                self.typeck(e, expectation)?;
+                self.lower_expr(e, expectation)
+            })
             .transpose()?
             .map(Box::new);
         self.scoped_syms = prev_env;
+        for stmt in &mut statements {
+            if let Stmt::Local(local) = stmt {
+                let Some(ty) = self.local_tys.get(&local.id) else {
+                    raise::yeet!(make_diag!(Error, local.name.span, E0204, local.name.symbol)
+                        .note(msg!(N0205))
+                        .emit());
+                };
+                local.ty = ty.kind;
+            }
+        }
         Ok(Block { statements, expr })
     }
 
@@ -227,32 +518,30 @@ impl AstLowerer {
         sp: Span,
         expectation: TypeckExpectation<'_>,
     ) -> Result<TyKind, ErrorReported> {
+        self.typeck(
+            &e.expr,
+            TypeckExpectation::Equals {
+                ty: TyKind::Bool,
+                sp,
+            },
+        )?;
         let new_expectation = if e.else_.is_some() {
             expectation
         } else {
             TypeckExpectation::NoExpectation
         };
-        let ty1 = e
-            .block
-            .expr
-            .as_ref()
-            .map(|b| self.typeck(b, new_expectation))
-            .transpose()?
-            .unwrap_or(TyKind::Unit);
-        let else_expect = TypeckExpectation::Equals {
-            ty: ty1,
-            sp: e.block.span,
+        let ty1 = self.typeck_block(&e.block, new_expectation)?;
+        // a `then` block that returns leaves the type to the `else` block.
+        let else_expect = match ty1 {
+            TyKind::Never => new_expectation,
+            ty => TypeckExpectation::Equals {
+                ty,
+                sp: e.block.span,
+            },
         };
         let (ty2, sp2) = match &e.else_ {
             None => (TyKind::Unit, None),
-            Some(ast::Else::Else(b)) => (
-                b.expr
-                    .as_ref()
-                    .map(|b| self.typeck(b, else_expect))
-                    .transpose()?
-                    .unwrap_or(TyKind::Unit),
-                Some(b.span),
-            ),
+            Some(ast::Else::Else(b)) => (self.typeck_block(b, else_expect)?, Some(b.span)),
             Some(ast::Else::ElseIf(elif, sp)) => {
                 (self.typeck_if(elif, *sp, else_expect)?, Some(*sp))
             }
@@ -264,18 +553,38 @@ impl AstLowerer {
             sp = sp.to(s);
         }
 
-        if ty1 != ty2 {
-            raise::yeet!(make_diag! {
+        let ty = match (ty1, ty2) {
+            (TyKind::Never, ty) | (ty, TyKind::Never) => ty,
+            (ty1, ty2) if ty1 != ty2 => raise::yeet!(make_diag! {
                 Error,
                 sp,
-                "conflicting types",
+                E0206,
             }
-            .emit());
-        }
+            .emit()),
+            (ty, _) => ty,
+        };
 
-        expectation.check(ty1, sp)?;
+        expectation.check(ty, sp)?;
 
-        Ok(ty1)
+        Ok(ty)
+    }
+
+    /// The type of the value of `block`, which is `!` if it returns before
+    /// reaching its end.
+    fn typeck_block(
+        &mut self,
+        block: &ast::Block,
+        expectation: TypeckExpectation<'_>,
+    ) -> Result<TyKind, ErrorReported> {
+        let returns = |stmt: &&ast::Stmt| {
+            is_enabled(&stmt.attrs, self.cfg)
+                && matches!(&stmt.kind, ast::StmtKind::Expr(e) if always_returns(e))
+        };
+        match &block.expr {
+            Some(e) => self.typeck(e, expectation),
+            None if block.stmts.iter().any(|stmt| returns(&stmt)) => Ok(TyKind::Never),
+            None => Ok(TyKind::Unit),
+        }
     }
 
     fn typeck(
@@ -284,9 +593,38 @@ impl AstLowerer {
         expectation: TypeckExpectation<'_>,
     ) -> Result<TyKind, ErrorReported> {
         let ty = match &e.kind {
+            ast::ExprKind::BinOp(BinOpKind::And | BinOpKind::Or, expr1, expr2) => {
+                for expr in [expr1, expr2] {
+                    let expectation = TypeckExpectation::Equals {
+                        ty: TyKind::Bool,
+                        sp: e.span,
+                    };
+                    self.typeck(expr, expectation)?;
+                }
+                TyKind::Bool
+            }
             ast::ExprKind::BinOp(op, expr1, expr2) => {
-                let mut set = FxHashSet::default();
-                set.extend([TyKind::I32, TyKind::F32]);
+                let mut set = FxIndexSet::default();
+                match op {
+                    // bit manipulation only makes sense on integers
+                    BinOpKind::BitAnd
+                    | BinOpKind::BitOr
+                    | BinOpKind::BitXor
+                    | BinOpKind::Shl
+                    | BinOpKind::Shr => {
+                        set.insert(TyKind::I32);
+                    }
+                    BinOpKind::Equal | BinOpKind::NotEqual => {
+                        set.extend([TyKind::I32, TyKind::F32, TyKind::Bool, TyKind::String]);
+                    }
+                    // concatenation
+                    BinOpKind::Add => {
+                        set.extend([TyKind::I32, TyKind::F32, TyKind::String]);
+                    }
+                    _ => {
+                        set.extend([TyKind::I32, TyKind::F32]);
+                    }
+                }
                 let ty1 = self.typeck(
                     expr1,
                     TypeckExpectation::AnyOf {
@@ -302,25 +640,26 @@ impl AstLowerer {
                     },
                 )?;
                 if ty1 != ty2 {
-                    return Err(make_diag!(
-                        Error,
-                        expr1.span.to(expr2.span),
-                        "cannot compare two values of different types"
-                    )
-                    .emit());
+                    return Err(make_diag!(Error, expr1.span.to(expr2.span), E0208).emit());
                 }
                 match op {
                     BinOpKind::Add
                     | BinOpKind::Div
                     | BinOpKind::Mod
                     | BinOpKind::Mul
-                    | BinOpKind::Sub => ty1,
+                    | BinOpKind::Sub
+                    | BinOpKind::BitAnd
+                    | BinOpKind::BitOr
+                    | BinOpKind::BitXor
+                    | BinOpKind::Shl
+                    | BinOpKind::Shr => ty1,
                     BinOpKind::Less
                     | BinOpKind::LessEqual
                     | BinOpKind::Greater
                     | BinOpKind::GreaterEqual
                     | BinOpKind::Equal
                     | BinOpKind::NotEqual => TyKind::Bool,
+                    BinOpKind::And | BinOpKind::Or => unreachable!(),
                 }
             }
             ast::ExprKind::UnOp(UnOpKind::Not, expr) => self.typeck(
@@ -331,7 +670,7 @@ impl AstLowerer {
                 },
             )?,
             ast::ExprKind::UnOp(UnOpKind::Minus, expr) => {
-                let mut set = FxHashSet::default();
+                let mut set = FxIndexSet::default();
                 set.extend([TyKind::I32, TyKind::F32]);
                 self.typeck(
                     expr,
@@ -341,42 +680,126 @@ impl AstLowerer {
                     },
                 )?
             }
+            ast::ExprKind::Cast(expr, ty) => {
+                let from = self.typeck(expr, TypeckExpectation::NoExpectation)?;
+                let to = self.lower_ty_kind(ty.kind, ty.span)?;
+                if !is_valid_cast(from, to) {
+                    raise::yeet! {
+                        make_diag! {
+                            Error,
+                            e.span,
+                            E0209,
+                            from,
+                            to,
+                        }
+                        .note(msg!(N0209))
+                        .emit()
+                    }
+                }
+                to
+            }
             ast::ExprKind::Literal(lit) => lit.kind.ty(),
+            ast::ExprKind::Underscore => raise::yeet! {
+                make_diag! {
+                    Error,
+                    e.span,
+                    E0210,
+                }.emit()
+            },
             ast::ExprKind::Ident(ident) => {
                 if let Some(decl) = self.scoped_syms.get(ident) {
-                    decl.type_
+                    if self.closures.contains_key(&decl.id) {
+                        raise::yeet!(make_diag!(Error, e.span, E0240, ident).emit());
+                    }
+                    let Some(ty) = self.local_tys.get(&decl.id) else {
+                        raise::yeet!(make_diag!(Error, e.span, E0238, ident)
+                            .span_note(decl.span, msg!(N0238, ident))
+                            .emit());
+                    };
+                    ty.kind
                 } else {
-                    return Err(DiagnosticBuilder::new(
-                        DiagnosticSeverity::Error,
-                        "unknown identifier",
-                        e.span,
-                    )
-                    .emit());
+                    return Err(make_diag!(Error, e.span, E0211).emit());
                 }
             }
-            ast::ExprKind::Block(block) => {
-                if let Some(e) = &block.expr {
-                    self.typeck(e, expectation)?
-                } else {
-                    TyKind::Unit
+            ast::ExprKind::Block(block) => self.typeck_block(block, expectation)?,
+            ast::ExprKind::Assignment { lhs, rhs } => {
+                if let ExprKind::Ident(symbol) = lhs.kind
+                    && let Some(decl) = self.scoped_syms.get(&symbol)
+                {
+                    let id = decl.id;
+                    if let Some(&ty) = self.local_tys.get(&id) {
+                        self.typeck(rhs, ty.into())?;
+                    } else {
+                        let kind = self.typeck(rhs, TypeckExpectation::NoExpectation)?;
+                        self.local_tys.insert(id, Ty { kind, span: rhs.span });
+                    }
                 }
+                TyKind::Unit
             }
-            ast::ExprKind::Assignment { .. } => TyKind::Unit,
             ast::ExprKind::If(if_) => self.typeck_if(if_, e.span, expectation)?,
-            ast::ExprKind::While(_) => TyKind::Unit,
+            ast::ExprKind::Match(match_) => self.typeck_match(match_, expectation)?,
+            ast::ExprKind::Closure(_) => raise::yeet!(make_diag!(Error, e.span, E0239).emit()),
+            ast::ExprKind::Path(enum_, variant) => {
+                self.typeck_variant(*enum_, *variant, &[], e.span)?
+            }
+            ast::ExprKind::While(while_) => {
+                let expectation = TypeckExpectation::Equals {
+                    ty: TyKind::Bool,
+                    sp: while_.while_,
+                };
+                self.typeck(&while_.expr, expectation)?;
+                TyKind::Unit
+            }
+            ast::ExprKind::AssignOp(..) | ast::ExprKind::For(_) => {
+                unreachable!("removed by desugaring")
+            }
             ast::ExprKind::Call { callee, args } => {
                 if let ast::ExprKind::Ident(sym::println) = callee.kind {
-                    if let [_] = &**args {
+                    if let [arg] = &**args {
+                        let ty = self.typeck(arg, TypeckExpectation::NoExpectation)?;
+                        if ty.print_format().is_none() {
+                            raise::yeet! {
+                                make_diag! {
+                                    Error,
+                                    arg.span,
+                                    E0212,
+                                    ty,
+                                }.emit()
+                            }
+                        }
                         TyKind::Unit
                     } else {
                         raise::yeet! {
                             make_diag! {
                                 Error,
                                 e.span,
-                                "`println` takes exact one argument",
+                                E0213,
                             }.emit()
                         }
                     }
+                } else if let ast::ExprKind::Ident(sym::assert) = callee.kind {
+                    let [cond] = &**args else {
+                        raise::yeet!(make_diag!(Error, e.span, E0234).emit());
+                    };
+                    let expectation = TypeckExpectation::Equals {
+                        ty: TyKind::Bool,
+                        sp: callee.span,
+                    };
+                    self.typeck(cond, expectation)?;
+                    TyKind::Unit
+                } else if let ast::ExprKind::Ident(i) = callee.kind
+                    && let Some(closure) = self.closure_id(i)
+                {
+                    let captures = self.closures[&closure].len();
+                    let arg_types = self.functions[&closure].args.clone();
+                    let takes = arg_types.len() - captures;
+                    if args.len() != takes {
+                        raise::yeet!(make_diag!(Error, e.span, E0243, i, takes, args.len()).emit());
+                    }
+                    for (expr, ty) in args.iter().zip(arg_types) {
+                        self.typeck(expr, ty.into())?;
+                    }
+                    self.functions[&closure].ret
                 } else if let ast::ExprKind::Ident(i) = callee.kind {
                     if let Some(&f) = self.fn_symbols.get(&i) {
                         let arg_types = self.functions[&f].args.clone();
@@ -391,28 +814,143 @@ impl AstLowerer {
                             make_diag! {
                                 Error,
                                 callee.span,
-                                "unresolved function call",
+                                E0214,
                             }.emit()
                         }
                     }
+                } else if let ast::ExprKind::Path(enum_, variant) = callee.kind {
+                    match self.extern_fn(enum_, variant) {
+                        Some(f) => {
+                            let f = f?;
+                            for (expr, &ty) in args.iter().zip(&f.args) {
+                                let expectation = TypeckExpectation::Equals { ty, sp: callee.span };
+                                self.typeck(expr, expectation)?;
+                            }
+                            f.ret
+                        }
+                        None => self.typeck_variant(enum_, variant, args, e.span)?,
+                    }
                 } else {
                     todo!()
                 }
             }
             ast::ExprKind::Group(e, _) => return self.typeck(e, expectation),
-            ast::ExprKind::Return(e, _) => {
-                self.typeck(e, self.current_func_ret_ty.unwrap().into())?;
-                TyKind::Unit
+            ast::ExprKind::Return(value, kw) => {
+                let Some(ret) = self.current_func_ret_ty else {
+                    raise::yeet!(make_diag!(Error, *kw, E0242).emit());
+                };
+                let expectation = ret.into();
+                match value {
+                    Some(value) => {
+                        self.typeck(value, expectation)?;
+                    }
+                    None => expectation.check(TyKind::Unit, *kw)?,
+                }
+                TyKind::Never
+            }
+            ast::ExprKind::Array(elems) => {
+                // `let a: [i32; 0] = [];` is the only way to know the type of `[]`.
+                let expected = match expectation {
+                    TypeckExpectation::Equals {
+                        ty: TyKind::Array(elem, _),
+                        sp,
+                    } => TypeckExpectation::Equals { ty: *elem, sp },
+                    _ => TypeckExpectation::NoExpectation,
+                };
+                let elem = match (elems.first(), expected) {
+                    (Some(first), _) => {
+                        let elem = self.typeck(first, expected)?;
+                        for rest in &elems[1..] {
+                            self.typeck(
+                                rest,
+                                TypeckExpectation::Equals {
+                                    ty: elem,
+                                    sp: first.span,
+                                },
+                            )?;
+                        }
+                        elem
+                    }
+                    (None, TypeckExpectation::Equals { ty, .. }) => ty,
+                    (None, _) => return Err(make_diag!(Error, e.span, E0215).emit()),
+                };
+                // codegen has no value to store for `unit`.
+                if elem == TyKind::Unit {
+                    return Err(make_diag!(Error, e.span, E0244).emit());
+                }
+                TyKind::array(elem, elems.len() as u32)
+            }
+            ast::ExprKind::Tuple(elems) => {
+                let expected = match expectation {
+                    TypeckExpectation::Equals {
+                        ty: TyKind::Tuple(tys),
+                        sp,
+                    } if tys.len() == elems.len() => Some((tys, sp)),
+                    _ => None,
+                };
+                let tys: Vec<_> = elems
+                    .iter()
+                    .enumerate()
+                    .map(|(i, elem)| {
+                        let expectation = match expected {
+                            Some((tys, sp)) => TypeckExpectation::Equals { ty: tys[i], sp },
+                            None => TypeckExpectation::NoExpectation,
+                        };
+                        match self.typeck(elem, expectation)? {
+                            TyKind::Unit => Err(make_diag!(Error, elem.span, E0244).emit()),
+                            ty => Ok(ty),
+                        }
+                    })
+                    .collect::<Result<_, _>>()?;
+                TyKind::tuple(tys)
+            }
+            ast::ExprKind::Index(array, index) => {
+                let array_ty = self.typeck(array, TypeckExpectation::NoExpectation)?;
+                let Some(elem) = array_ty.element() else {
+                    return Err(make_diag!(Error, array.span, E0216, array_ty).emit());
+                };
+                self.typeck(
+                    index,
+                    TypeckExpectation::Equals {
+                        ty: TyKind::I32,
+                        sp: e.span,
+                    },
+                )?;
+                elem
+            }
+            ast::ExprKind::Struct { name, fields } => {
+                let Some(&def) = self.structs.get(&name.symbol) else {
+                    raise::yeet!(make_diag!(Error, name.span, E0217, name.symbol).emit());
+                };
+                self.typeck_fields(def, fields, e.span)?;
+                TyKind::Struct(def)
+            }
+            ast::ExprKind::Field(base, field) => {
+                let ty = self.typeck(base, TypeckExpectation::NoExpectation)?;
+                let Some((_, field_ty)) = ty.field(field.symbol) else {
+                    raise::yeet!(make_diag!(Error, field.span, E0219, field.symbol, ty).emit());
+                };
+                field_ty
             }
         };
 
         expectation.check(ty, e.span)?;
         Ok(ty)
     }
+    /// The function `f` of the library `krate`, or `None` if no library is
+    /// called `krate`.
+    fn extern_fn(&self, krate: Ident, f: Ident) -> Option<Result<ExternFn, ErrorReported>> {
+        let lib = self.externs.iter().find(|lib| lib.name == krate.symbol)?;
+        Some(
+            lib.function(f.symbol)
+                .cloned()
+                .ok_or_else(|| make_diag!(Error, f.span, E0237, f.symbol, krate.symbol).emit()),
+        )
+    }
     fn resolve(&mut self, sym: Symbol) -> Result<Resolution, ErrorReported> {
         Ok(if let Some(decl) = self.scoped_syms.get(&sym) {
             Resolution::Local(decl.id)
-        } else if sym == sym::println {
+        } else if sym == sym::println || sym == sym::assert {
             Resolution::Builtin(sym)
         } else if let Some(decl) = self.fn_symbols.get(&sym) {
             Resolution::Fn(*decl)
@@ -447,48 +985,109 @@ impl AstLowerer {
                 let ety = self.typeck(expr, expectation)?;
                 Expr::UnOp(*kind, Box::new(self.lower_expr(expr, expectation)?), ety)
             }
-            ast::ExprKind::Literal(lit) => Expr::Literal(match lit.kind {
-                ast::LiteralKind::Bool(x) => Literal::Bool(x),
-                ast::LiteralKind::Int(x) => Literal::Int(x),
-                ast::LiteralKind::String(x) => Literal::String(x),
-                ast::LiteralKind::Float(x) => Literal::Float(x),
-            }),
-            ast::ExprKind::Ident(symbol) => self.resolve(*symbol).map(Expr::Resolved)?,
+            ast::ExprKind::Cast(expr, _) => {
+                let to = self.typeck(e, expectation)?;
+                let from = self.typeck(expr, TypeckExpectation::NoExpectation)?;
+                let expectation = TypeckExpectation::Equals { ty: from, sp: expr.span };
+                Expr::Cast(Box::new(self.lower_expr(expr, expectation)?), from, to)
+            }
+            ast::ExprKind::Literal(lit) => Expr::Literal(lower_literal(lit)),
+            ast::ExprKind::Ident(symbol) => {
+                if let Some(decl) = self.scoped_syms.get(symbol).cloned() {
+                    if self.closures.contains_key(&decl.id) {
+                        raise::yeet!(make_diag!(Error, e.span, E0240, symbol).emit());
+                    }
+                    self.capture(*symbol, &decl, e.span)?;
+                }
+                self.resolve(*symbol).map(Expr::Resolved)?
+            }
             ast::ExprKind::Block(block) => Expr::Block(self.lower_block(block, expectation)?),
+            ast::ExprKind::Underscore => {
+                self.typeck(e, expectation)?;
+                unreachable!("`_` is rejected by typeck")
+            }
             ast::ExprKind::Assignment { lhs, rhs } => {
-                if let ExprKind::Ident(symbol) = lhs.kind {
+                if let ExprKind::Underscore = lhs.kind {
+                    // `_ = expr` evaluates `expr` and discards its value.
+                    let value = self.lower_expr(rhs, TypeckExpectation::NoExpectation)?;
+                    Expr::Block(Block {
+                        statements: vec![Stmt::Expr(value)],
+                        expr: None,
+                    })
+                } else if let ExprKind::Ident(symbol) = lhs.kind {
+                    if let Some(decl) = self.scoped_syms.get(&symbol)
+                        && !decl.mutable
+                    {
+                        raise::yeet!(make_diag!(Error, lhs.span, E0232, symbol)
+                            .span_note(decl.span, msg!(N0232, symbol))
+                            .emit());
+                    }
+                    if let Some(decl) = self.scoped_syms.get(&symbol).cloned() {
+                        self.capture(symbol, &decl, lhs.span)?;
+                    }
+                    self.typeck(e, expectation)?;
                     Expr::Assign {
                         to: self.resolve(symbol)?,
-                        rvalue: Box::new(self.lower_expr(rhs, expectation)?),
+                        rvalue: Box::new(self.lower_expr(rhs, TypeckExpectation::NoExpectation)?),
                     }
                 } else {
-                    todo!()
+                    raise::yeet!(make_diag!(Error, lhs.span, E0222).emit());
                 }
             }
-            ast::ExprKind::If(ast::ExprIf {
+            ast::ExprKind::If(if_ @ ast::ExprIf {
                 expr,
                 block,
                 else_: None,
-            }) => Expr::If {
-                cond: self
-                    .lower_expr(
-                        expr,
-                        TypeckExpectation::Equals {
-                            ty: TyKind::Bool,
-                            sp: e.span,
-                        },
-                    )
-                    .map(Box::new)?,
-                then: self.lower_block(block, TypeckExpectation::NoExpectation)?,
-            },
-            ast::ExprKind::If(_) => todo!(),
-            ast::ExprKind::While(_) => todo!(),
+            }) => {
+                self.typeck_if(if_, e.span, expectation)?;
+                Expr::If {
+                    cond: self
+                        .lower_expr(expr, TypeckExpectation::NoExpectation)
+                        .map(Box::new)?,
+                    then: self.lower_block(block, TypeckExpectation::NoExpectation)?,
+                    else_: None,
+                }
+            }
+            ast::ExprKind::If(if_ @ ast::ExprIf {
+                expr,
+                block,
+                else_: Some(else_),
+            }) => {
+                self.typeck_if(if_, e.span, expectation)?;
+                let ast::Else::Else(else_) = else_ else {
+                    unreachable!("removed by desugaring")
+                };
+                Expr::If {
+                    cond: self
+                        .lower_expr(expr, TypeckExpectation::NoExpectation)
+                        .map(Box::new)?,
+                    then: self.lower_block(block, expectation)?,
+                    else_: Some(self.lower_block(else_, expectation)?),
+                }
+            }
+            ast::ExprKind::While(ast::ExprWhile { expr, block, .. }) => {
+                self.typeck(e, expectation)?;
+                Expr::While {
+                    cond: self
+                        .lower_expr(expr, TypeckExpectation::NoExpectation)
+                        .map(Box::new)?,
+                    body: self.lower_block(block, TypeckExpectation::NoExpectation)?,
+                }
+            }
+            ast::ExprKind::AssignOp(..) | ast::ExprKind::For(_) => {
+                unreachable!("removed by desugaring")
+            }
             ast::ExprKind::Call { callee, args } => match (&callee.kind, &**args) {
                 (ExprKind::Ident(i), args) => {
+                    if let Some(id) = self.closure_id(*i) {
+                        return self.lower_closure_call(e, id, args, expectation);
+                    }
+                    self.typeck(e, expectation)?;
                     let re = self.resolve(*i)?;
                     let (ret, arg_expectations) = match re {
-                        Resolution::Builtin(sym::println) => (TyKind::Unit, None),
+                        Resolution::Builtin(sym::println | sym::assert) => (TyKind::Unit, None),
                         Resolution::Builtin(_) | Resolution::Local(_) => todo!(),
+                        Resolution::Extern(..) => unreachable!("`resolve` only finds names"),
                         Resolution::Fn(id) => {
                             (self.functions[&id].ret, Some(&self.functions[&id].args))
                         }
@@ -511,29 +1110,193 @@ impl AstLowerer {
                         ret,
                     }
                 }
+                (ExprKind::Path(enum_, variant), args) => match self.extern_fn(*enum_, *variant) {
+                    Some(f) => {
+                        self.typeck(e, expectation)?;
+                        let f = f?;
+                        let args = args
+                            .iter()
+                            .zip(&f.args)
+                            .map(|(expr, &ty)| {
+                                let expectation = TypeckExpectation::Equals { ty, sp: callee.span };
+                                Ok((self.lower_expr(expr, expectation)?, ty))
+                            })
+                            .collect::<Result<_, ErrorReported>>()?;
+                        Expr::Call {
+                            callee: Resolution::Extern(enum_.symbol, variant.symbol),
+                            args,
+                            ret: f.ret,
+                        }
+                    }
+                    None => self.lower_variant(e, *enum_, *variant, args, expectation)?,
+                },
                 _ => todo!(),
             },
+            ast::ExprKind::Path(enum_, variant) => {
+                self.lower_variant(e, *enum_, *variant, &[], expectation)?
+            }
+            ast::ExprKind::Match(match_) => self.lower_match(match_, e.span, expectation)?,
+            ast::ExprKind::Closure(_) => raise::yeet!(make_diag!(Error, e.span, E0239).emit()),
             ast::ExprKind::Group(e, _) => Expr::Group(Box::new(self.lower_expr(e, expectation)?)),
-            ast::ExprKind::Return(e, _) => {
-                let expectation = self.current_func_ret_ty.unwrap().into();
-                Expr::Return(
-                    Box::new(self.lower_expr(e, expectation)?),
-                    self.typeck(e, expectation)?,
+            ast::ExprKind::Array(elems) => {
+                let ty = self.typeck(e, expectation)?;
+                let expectation = TypeckExpectation::Equals {
+                    ty: ty.element().expect("array literals are arrays"),
+                    sp: e.span,
+                };
+                let elems = elems
+                    .iter()
+                    .map(|elem| self.lower_expr(elem, expectation))
+                    .collect::<Result<_, _>>()?;
+                Expr::Array(elems, ty)
+            }
+            ast::ExprKind::Tuple(elems) => {
+                let ty = self.typeck(e, expectation)?;
+                let TyKind::Tuple(tys) = ty else { unreachable!("tuple literals are tuples") };
+                let elems = elems
+                    .iter()
+                    .zip(tys)
+                    .map(|(elem, &ty)| {
+                        let expectation = TypeckExpectation::Equals { ty, sp: elem.span };
+                        self.lower_expr(elem, expectation)
+                    })
+                    .collect::<Result<_, _>>()?;
+                Expr::Tuple(elems, ty)
+            }
+            ast::ExprKind::Index(array, index) => {
+                self.typeck(e, expectation)?;
+                let array_ty = self.typeck(array, TypeckExpectation::NoExpectation)?;
+                let index_expectation = TypeckExpectation::Equals {
+                    ty: TyKind::I32,
+                    sp: e.span,
+                };
+                Expr::Index(
+                    Box::new(self.lower_expr(array, TypeckExpectation::NoExpectation)?),
+                    Box::new(self.lower_expr(index, index_expectation)?),
+                    array_ty,
+                )
+            }
+            ast::ExprKind::Struct { fields, .. } => {
+                let ty = self.typeck(e, expectation)?;
+                let TyKind::Struct(def) = ty else { unreachable!("struct literals are structs") };
+                let fields = fields
+                    .iter()
+                    .map(|field| {
+                        let (index, ty) = def.field(field.name.symbol).expect("checked by typeck");
+                        let expectation = TypeckExpectation::Equals {
+                            ty,
+                            sp: field.name.span,
+                        };
+                        Ok((index, self.lower_expr(&field.value, expectation)?))
+                    })
+                    .collect::<Result<_, ErrorReported>>()?;
+                Expr::Struct(fields, ty)
+            }
+            ast::ExprKind::Field(base, field) => {
+                self.typeck(e, expectation)?;
+                let base_ty = self.typeck(base, TypeckExpectation::NoExpectation)?;
+                let (index, _) = base_ty.field(field.symbol).expect("checked by typeck");
+                Expr::Field(
+                    Box::new(self.lower_expr(base, TypeckExpectation::NoExpectation)?),
+                    index,
+                    base_ty,
                 )
             }
+            ast::ExprKind::Return(value, kw) => {
+                let Some(ret) = self.current_func_ret_ty else {
+                    raise::yeet!(make_diag!(Error, *kw, E0242).emit());
+                };
+                let value = match value {
+                    Some(value) => {
+                        self.typeck(value, ret.into())?;
+                        self.lower_expr(value, ret.into())?
+                    }
+                    None => {
+                        TypeckExpectation::from(ret).check(TyKind::Unit, *kw)?;
+                        Expr::Literal(Literal::Unit)
+                    }
+                };
+                Expr::Return(Box::new(value), ret.kind)
+            }
         })
     }
 
     fn lower_tree(mut self, ast: &ast::Tree) -> Result<HirTree, ErrorReported> {
-        let items = ast.items.iter().map(|item| self.lower_item(item)).collect::<Result<_, _>>()?;
+        let cfg = self.cfg;
+        let items: Vec<_> = ast.items.iter().filter(|item| is_enabled(&item.attrs, cfg)).collect();
+        // types first, so that the signatures can name the ones declared after them.
+        let mut lowered = Vec::with_capacity(items.len());
+        for item in &items {
+            lowered.push(match item.kind {
+                ast::ItemKind::Struct(_) | ast::ItemKind::Enum(_) => Some(self.lower_item(item)?),
+                _ => None,
+            });
+        }
+        for item in &items {
+            if let ast::ItemKind::Fn(f) = &item.kind {
+                self.declare_fn(f)?;
+            }
+        }
+        let items = items
+            .into_iter()
+            .zip(lowered)
+            .map(|(item, lowered)| lowered.map_or_else(|| self.lower_item(item), Ok))
+            .collect::<Result<_, _>>()?;
         Ok(HirTree { items, functions: self.functions })
     }
 }
 
+/// Whether every one of the `#[cfg(...)]` attributes of an item or a statement
+/// is enabled.
+fn is_enabled(attrs: &[ast::Attr], enabled: &[String]) -> bool {
+    attrs.iter().all(|attr| attr.is_enabled(enabled))
+}
+
+/// Whether `e` is a `return`, or a block ending in one.
+fn always_returns(e: &ast::Expr) -> bool {
+    match &e.kind {
+        ast::ExprKind::Return(..) => true,
+        ast::ExprKind::Group(e, _) => always_returns(e),
+        ast::ExprKind::Block(block) => block.expr.as_deref().is_some_and(always_returns),
+        _ => false,
+    }
+}
+
+/// Whether `expr as to` is allowed for an `expr` of type `from`. Casting a
+/// value to its own type does nothing, but is allowed.
+fn is_valid_cast(from: TyKind, to: TyKind) -> bool {
+    use TyKind::*;
+    from == to || matches!((from, to), (I32 | Bool, I32) | (I32, F32) | (F32, I32))
+}
+
+fn lower_literal(lit: &ast::Literal) -> Literal {
+    match lit.kind {
+        ast::LiteralKind::Bool(x) => Literal::Bool(x),
+        ast::LiteralKind::Int(x) => Literal::Int(x),
+        ast::LiteralKind::String(x) => Literal::String(x),
+        ast::LiteralKind::Float(x) => Literal::Float(x),
+    }
+}
+
 fn hir(cx: &dyn Context, id: FileId) -> Result<HirTree, ErrorReported> {
-    AstLowerer::default().lower_tree(&cx.parse(id)?)
+    let lowerer = AstLowerer {
+        cfg: &cx.options().cfg,
+        externs: cx.externs()?,
+        ..AstLowerer::default()
+    };
+    let tree = lowerer.lower_tree(&cx.desugar(id)?)?;
+    for lint in cx.providers().lints {
+        lint.check_hir(cx, &tree);
+    }
+    Ok(tree)
 }
 
 pub fn provide(p: &mut Providers) {
-    *p = Providers { hir, ..*p };
+    *p = Providers {
+        desugar: desugar::desugar,
+        hir,
+        entry_point: entry::entry_point,
+        tests: entry::tests,
+        ..*p
+    };
 }
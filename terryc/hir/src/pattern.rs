@@ -0,0 +1,250 @@
+//! Enums and `match`: type checking of variants and patterns, and checking
+//! that the arms of a `match` are reachable and cover every value.
+
+use terryc_ast::{self as ast, EnumDef, Ty, TyKind, VariantDef};
+use terryc_base::data::FxIndexSet;
+use terryc_base::errors::{make_diag, ErrorReported};
+use terryc_base::hir::*;
+use terryc_base::lex::Ident;
+use terryc_base::Span;
+
+use crate::{lower_literal, AstLowerer, ResolvedDecl, TypeckExpectation};
+
+impl AstLowerer {
+    pub(crate) fn lower_enum(&mut self, e: &ast::ItemEnum) -> Result<Item, ErrorReported> {
+        let mut variants: Vec<VariantDef> = Vec::with_capacity(e.variants.len());
+        for variant in &e.variants {
+            let name = variant.name.symbol;
+            if variants.iter().any(|prev| prev.name == name) {
+                raise::yeet!(make_diag!(Error, variant.name.span, E0218, name).emit());
+            }
+            let fields = variant
+                .fields
+                .iter()
+                .map(|ty| Ok(self.lower_ty(ty)?.kind))
+                .collect::<Result<_, ErrorReported>>()?;
+            variants.push(VariantDef { name, fields });
+        }
+        let def = EnumDef {
+            id: e.id,
+            name: e.name.symbol,
+            variants,
+        }
+        .intern();
+        if self.enums.insert(e.name.symbol, def).is_some()
+            || self.structs.contains_key(&e.name.symbol)
+        {
+            raise::yeet!(make_diag!(Error, e.name.span, E0218, e.name.symbol).emit());
+        }
+        Ok(Item::Enum(def))
+    }
+
+    fn resolve_variant(
+        &self,
+        enum_: Ident,
+        variant: Ident,
+    ) -> Result<(&'static EnumDef, u32), ErrorReported> {
+        let Some(&def) = self.enums.get(&enum_.symbol) else {
+            raise::yeet!(make_diag!(Error, enum_.span, E0217, enum_.symbol).emit());
+        };
+        let Some(index) = def.variant(variant.symbol) else {
+            raise::yeet!(make_diag!(Error, variant.span, E0223, variant.symbol, def.name).emit());
+        };
+        Ok((def, index))
+    }
+
+    /// Checks that `enum_::variant` is given one argument of the right type
+    /// for each of its fields.
+    pub(crate) fn typeck_variant(
+        &mut self,
+        enum_: Ident,
+        variant: Ident,
+        args: &[ast::Expr],
+        sp: Span,
+    ) -> Result<TyKind, ErrorReported> {
+        let (def, index) = self.resolve_variant(enum_, variant)?;
+        let fields = &def.variants[index as usize].fields;
+        if fields.len() != args.len() {
+            let name = format!("{}::{}", def.name, variant.symbol);
+            raise::yeet!(make_diag!(Error, sp, E0224, name, fields.len(), args.len()).emit());
+        }
+        for (arg, &ty) in args.iter().zip(fields) {
+            self.typeck(arg, TypeckExpectation::Equals { ty, sp: variant.span })?;
+        }
+        Ok(TyKind::Enum(def))
+    }
+
+    pub(crate) fn lower_variant(
+        &mut self,
+        e: &ast::Expr,
+        enum_: Ident,
+        variant: Ident,
+        args: &[ast::Expr],
+        expectation: TypeckExpectation<'_>,
+    ) -> Result<Expr, ErrorReported> {
+        let ty = self.typeck(e, expectation)?;
+        let (def, index) = self.resolve_variant(enum_, variant)?;
+        let fields = args
+            .iter()
+            .zip(&def.variants[index as usize].fields)
+            .map(|(arg, &ty)| {
+                self.lower_expr(arg, TypeckExpectation::Equals { ty, sp: variant.span })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Expr::Variant(index, fields, ty))
+    }
+
+    /// The type of a `match`. The first arm that does not return decides it,
+    /// like the `then` block of an `if`.
+    pub(crate) fn typeck_match(
+        &mut self,
+        m: &ast::ExprMatch,
+        expectation: TypeckExpectation<'_>,
+    ) -> Result<TyKind, ErrorReported> {
+        let scrutinee_ty = self.typeck(&m.expr, TypeckExpectation::NoExpectation)?;
+        let mut expectation = expectation;
+        let mut ty = TyKind::Never;
+        for arm in &m.arms {
+            let prev = self.scoped_syms.clone();
+            self.lower_pat(&arm.pat, scrutinee_ty, m.expr.span)?;
+            let arm_ty = self.typeck(&arm.body, expectation);
+            self.scoped_syms = prev;
+            let arm_ty = arm_ty?;
+            if ty == TyKind::Never && arm_ty != TyKind::Never {
+                ty = arm_ty;
+                expectation = TypeckExpectation::Equals { ty, sp: arm.body.span };
+            }
+        }
+        Ok(ty)
+    }
+
+    pub(crate) fn lower_match(
+        &mut self,
+        m: &ast::ExprMatch,
+        sp: Span,
+        expectation: TypeckExpectation<'_>,
+    ) -> Result<Expr, ErrorReported> {
+        let ty = self.typeck_match(m, expectation)?;
+        let scrutinee_ty = self.typeck(&m.expr, TypeckExpectation::NoExpectation)?;
+        let scrutinee = self.lower_expr(&m.expr, TypeckExpectation::NoExpectation)?;
+        let mut arms = Vec::with_capacity(m.arms.len());
+        for (i, arm) in m.arms.iter().enumerate() {
+            let prev = self.scoped_syms.clone();
+            let pat = self.lower_pat(&arm.pat, scrutinee_ty, m.expr.span)?;
+            let expectation = if i == 0 || ty == TyKind::Never {
+                expectation
+            } else {
+                TypeckExpectation::Equals { ty, sp: m.arms[0].body.span }
+            };
+            let body = self.lower_expr(&arm.body, expectation);
+            self.scoped_syms = prev;
+            arms.push(Arm { pat, body: body? });
+        }
+        check_arms(&arms, &m.arms, scrutinee_ty, sp)?;
+        Ok(Expr::Match {
+            scrutinee: Box::new(scrutinee),
+            scrutinee_ty,
+            arms,
+            ty,
+        })
+    }
+
+    /// Checks `pat` against the type of the scrutinee, which is written at
+    /// `sp`, and declares the locals it binds.
+    fn lower_pat(&mut self, pat: &ast::Pat, ty: TyKind, sp: Span) -> Result<Pat, ErrorReported> {
+        Ok(match &pat.kind {
+            ast::PatKind::Wild => Pat::Wild,
+            ast::PatKind::Binding { id, name } => {
+                let decl = ResolvedDecl { id: *id, mutable: false, span: name.span };
+                self.scoped_syms.insert(name.symbol, decl);
+                self.local_tys.insert(*id, Ty { kind: ty, span: sp });
+                Pat::Binding(LocalDecl {
+                    id: *id,
+                    name: *name,
+                    mutable: false,
+                    ty,
+                    initializer: None,
+                })
+            }
+            ast::PatKind::Literal(lit) => {
+                if !matches!(ty, TyKind::Bool | TyKind::I32) {
+                    raise::yeet!(make_diag!(Error, pat.span, E0228, ty).emit());
+                }
+                TypeckExpectation::Equals { ty, sp }.check(lit.kind.ty(), pat.span)?;
+                Pat::Literal(lower_literal(lit))
+            }
+            ast::PatKind::Variant { enum_, variant, fields } => {
+                let (def, index) = self.resolve_variant(*enum_, *variant)?;
+                TypeckExpectation::Equals { ty, sp }.check(TyKind::Enum(def), pat.span)?;
+                let field_tys = &def.variants[index as usize].fields;
+                if fields.len() != field_tys.len() {
+                    let name = format!("{}::{}", def.name, variant.symbol);
+                    raise::yeet!(make_diag!(
+                        Error,
+                        pat.span,
+                        E0224,
+                        name,
+                        field_tys.len(),
+                        fields.len()
+                    )
+                    .emit());
+                }
+                let fields = fields
+                    .iter()
+                    .zip(field_tys)
+                    .map(|(field, &field_ty)| match field.kind {
+                        ast::PatKind::Wild | ast::PatKind::Binding { .. } => {
+                            self.lower_pat(field, field_ty, pat.span)
+                        }
+                        _ => Err(make_diag!(Error, field.span, E0227).emit()),
+                    })
+                    .collect::<Result<_, _>>()?;
+                Pat::Variant(index, fields)
+            }
+        })
+    }
+}
+
+/// Warns about arms that can never be reached, and reports the values no arm
+/// covers.
+fn check_arms(
+    arms: &[Arm],
+    ast_arms: &[ast::Arm],
+    ty: TyKind,
+    sp: Span,
+) -> Result<(), ErrorReported> {
+    let mut covered = FxIndexSet::default();
+    let mut catch_all = false;
+    for (arm, ast_arm) in arms.iter().zip(ast_arms) {
+        let reachable = match arm.pat.switch_value() {
+            _ if catch_all => false,
+            Some(value) => covered.insert(value),
+            None => {
+                catch_all = true;
+                true
+            }
+        };
+        if !reachable {
+            make_diag!(Warning, ast_arm.pat.span, W0226).emit();
+        }
+    }
+    if catch_all {
+        return Ok(());
+    }
+    let missing: Vec<String> = match ty {
+        TyKind::Enum(def) => (0..def.variants.len() as i32)
+            .filter(|index| !covered.contains(index))
+            .map(|index| format!("`{}::{}`", def.name, def.variants[index as usize].name))
+            .collect(),
+        TyKind::Bool => [false, true]
+            .into_iter()
+            .filter(|b| !covered.contains(&(*b as i32)))
+            .map(|b| format!("`{b}`"))
+            .collect(),
+        _ => vec!["`_`".into()],
+    };
+    if missing.is_empty() {
+        return Ok(());
+    }
+    Err(make_diag!(Error, sp, E0225, missing.join(", ")).emit())
+}
@@ -0,0 +1,222 @@
+use terryc_base::ast::ItemKind;
+use terryc_base::errors::{Code, Diagnostic};
+use terryc_base::{Context, GlobalCtxt, Options, Providers};
+
+fn init() {
+    GlobalCtxt::create_and_then(Options::default(), |mut gcx| {
+        let mut providers = Providers::default();
+        terryc_lex::provide(&mut providers);
+        terryc_ast::provide(&mut providers);
+        crate::provide(&mut providers);
+        gcx.set_providers(terryc_base::leak(providers));
+        gcx.capture_diagnostics();
+        gcx
+    });
+}
+
+/// The statements and trailing expression of `body` after desugaring it as
+/// the body of `main`, or the codes of the errors desugaring reported.
+fn desugar(body: &str) -> Result<Vec<String>, Vec<Option<Code>>> {
+    let src = format!("fn main() -> unit {{ {body} }}");
+    let file = GlobalCtxt::with_mut(|gcx| gcx.add_virtual_file("test.terry", src));
+    GlobalCtxt::with(|gcx| match gcx.desugar(file) {
+        Ok(tree) => {
+            let ItemKind::Fn(main) = &tree.items[0].kind else {
+                unreachable!()
+            };
+            let stmts = main.body.stmts.iter().map(|stmt| format!("{stmt:?}"));
+            Ok(stmts
+                .chain(main.body.expr.iter().map(|e| format!("{e:?}")))
+                .collect())
+        }
+        Err(_) => Err(gcx.take_diagnostics().iter().map(|d| d.code).collect()),
+    })
+}
+
+/// The diagnostics of lowering and type checking `src`.
+fn check(src: &str) -> Vec<Diagnostic> {
+    let file = GlobalCtxt::with_mut(|gcx| gcx.add_virtual_file("test.terry", src.into()));
+    GlobalCtxt::with(|gcx| {
+        let _ = gcx.hir(file);
+        gcx.take_diagnostics()
+    })
+}
+
+#[test]
+fn compound_assignments_become_assignments() {
+    init();
+    for (op, kind) in [
+        ("+", "Add"),
+        ("-", "Sub"),
+        ("*", "Mul"),
+        ("/", "Div"),
+        ("%", "Mod"),
+    ] {
+        let stmts = desugar(&format!("let mut a = 6; a {op}= 2 + 1;")).unwrap();
+        let rhs = format!("BinOp({kind}, Ident(a), BinOp(Add, Literal(2), Literal(1)))");
+        assert_eq!(
+            stmts[1],
+            format!("Assignment {{ lhs: Ident(a), rhs: {rhs} }}")
+        );
+    }
+    // anything but a local would be evaluated twice.
+    assert_eq!(
+        desugar("let a = [1]; a[0] += 1;"),
+        Err(vec![Some(Code::E0222)])
+    );
+    assert_eq!(desugar("_ += 1;"), Err(vec![Some(Code::E0222)]));
+}
+
+#[test]
+fn for_loops_become_while_loops() {
+    init();
+    let stmts = desugar("for i in 0..n() { println(i); }").unwrap();
+    assert_eq!(
+        stmts,
+        [
+            "Block({let mut i = Literal(0), let <for end> = Call { callee: Ident(n), args: [] }, \
+             While(ExprWhile { while_: 20..23, expr: BinOp(Less, Ident(i), Ident(<for end>)), \
+             block: {Block({Call { callee: Ident(println), args: [Ident(i)] }}), \
+             Assignment { lhs: Ident(i), rhs: BinOp(Add, Ident(i), Literal(1)) }} })})"
+        ]
+    );
+}
+
+#[test]
+fn empty_arrays_need_a_type() {
+    init();
+    let diagnostics = check("fn main() -> unit { let a = []; }");
+    assert_eq!(diagnostics[0].code, Some(Code::E0215));
+}
+
+#[test]
+fn arrays_cannot_hold_unit() {
+    init();
+    let diagnostics = check("fn f(x: i32) {} fn main() -> unit { let a = [f(1), f(2)]; }");
+    assert_eq!(diagnostics[0].code, Some(Code::E0244));
+}
+
+#[test]
+fn struct_literals_need_every_field() {
+    init();
+    let diagnostics =
+        check("struct Point { x: i32, y: i32 } fn main() -> unit { let p = Point { x: 1 }; }");
+    assert_eq!(diagnostics[0].code, Some(Code::E0220));
+}
+
+#[test]
+fn matches_must_be_exhaustive() {
+    init();
+    let diagnostics = check(
+        "enum Shape { Circle(i32), Rect(i32, i32), Empty } \
+         fn main() -> unit { \
+             let s = Shape::Empty; \
+             match s { Shape::Circle(_) => println(1), Shape::Circle(_) => println(2) } \
+         }",
+    );
+    let codes: Vec<_> = diagnostics.iter().map(|d| d.code).collect();
+    assert_eq!(codes, [Some(Code::W0226), Some(Code::E0225)]);
+    assert!(diagnostics[1]
+        .message
+        .contains("`Shape::Rect`, `Shape::Empty`"));
+}
+
+#[test]
+fn returns_and_tail_expressions_have_the_return_type() {
+    init();
+    let diagnostics = check("fn f(x: i32) -> i32 { if x < 0 { return; } x }");
+    assert_eq!(diagnostics[0].code, Some(Code::E0201));
+
+    let src = "fn f(x: i32) -> i32 { println(x); return x; } fn g(x: i32) -> i32 { let y = x; }";
+    let diagnostics = check(src);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].code, Some(Code::E0201));
+    let span = diagnostics[0].span;
+    assert_eq!(&src[span.lo()..span.hi()], "{ let y = x; }");
+
+    // a branch that returns takes the type of the other one.
+    let src = "fn f(c: bool) -> i32 { let x = if c { return 1; } else { 2 }; x }";
+    assert!(check(src).is_empty());
+    let src = "fn f(c: bool) -> i32 { let x: bool = if c { return 1; } else { 2 }; 3 }";
+    assert_eq!(check(src)[0].code, Some(Code::E0201));
+}
+
+#[test]
+fn strings_can_only_be_added() {
+    init();
+    let diagnostics = check(r#"fn main() -> unit { let x = "a" - "b"; }"#);
+    assert_eq!(diagnostics[0].code, Some(Code::E0201));
+}
+
+#[test]
+fn logical_operators_take_booleans() {
+    init();
+    let diagnostics = check("fn main() -> unit { let a = 1 && true; }");
+    assert_eq!(diagnostics[0].code, Some(Code::E0201));
+}
+
+#[test]
+fn bitwise_operators_take_integers() {
+    init();
+    let diagnostics = check("fn main() -> unit { let x = true & false; }");
+    assert_eq!(diagnostics[0].code, Some(Code::E0201));
+}
+
+#[test]
+fn closures_are_only_bound_and_called() {
+    init();
+    for (src, code) in [
+        (
+            "fn main() { let mut m = 1; let f = || m; f(); }",
+            Code::E0241,
+        ),
+        (
+            "fn main() { let mut m = 1; let f = || { m = 2; }; f(); }",
+            Code::E0241,
+        ),
+        ("fn main() { let f = || 1; println(f); }", Code::E0240),
+        ("fn main() { let f = |x: i32| x; f(); }", Code::E0243),
+        ("fn main() { let f = || { return 1; }; f(); }", Code::E0242),
+        ("fn main() { println(|| 1); }", Code::E0239),
+        ("fn main() { let mut f = || 1; }", Code::E0239),
+    ] {
+        assert_eq!(check(src)[0].code, Some(code), "{src}");
+    }
+}
+
+#[test]
+fn tuples_are_checked_element_by_element() {
+    init();
+    for (src, code) in [
+        (
+            "fn main() -> unit { let t: (i32, bool) = (1, 2); }",
+            Code::E0201,
+        ),
+        (
+            "fn main() -> unit { let t = (1, true); println(t.2); }",
+            Code::E0219,
+        ),
+        (
+            "enum E { A(i32) } fn main() -> unit { println((1, E::A(2))); }",
+            Code::E0212,
+        ),
+        (
+            "fn f(x: i32) {} fn main() -> unit { let t = (f(1), 2); }",
+            Code::E0244,
+        ),
+    ] {
+        assert_eq!(check(src)[0].code, Some(code), "{src}");
+    }
+}
+
+#[test]
+fn only_numbers_and_booleans_are_cast() {
+    init();
+    for src in [
+        "fn main() -> unit { let x = \"1\" as i32; }",
+        "fn main() -> unit { let x = 1 as bool; }",
+        "fn main() -> unit { let x = 1.5 as string; }",
+    ] {
+        assert_eq!(check(src)[0].code, Some(Code::E0209), "{src}");
+    }
+}
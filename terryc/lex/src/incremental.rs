@@ -0,0 +1,120 @@
+//! Relexing only the part of a file that was touched by an edit, so that
+//! editors don't have to lex large files from scratch on every keystroke.
+
+use std::mem;
+use std::ops::Range;
+
+use terryc_base::errors::ErrorReported;
+use terryc_base::lex::{Ident, Token, TokenKind};
+use terryc_base::{FileId, Span};
+
+use crate::Lexer;
+
+/// A change to a file: the text in `range` was replaced with `text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn apply(&self, src: &mut String) {
+        src.replace_range(self.range.clone(), &self.text);
+    }
+
+    /// How far the text after the edit moves.
+    fn delta(&self) -> isize {
+        self.text.len() as isize - self.range.len() as isize
+    }
+}
+
+fn shift(token: &Token, delta: isize) -> Token {
+    let shift_span = |span: Span| {
+        Span::new(
+            (span.lo() as isize + delta) as usize,
+            (span.hi() as isize + delta) as usize,
+            span.file(),
+        )
+    };
+    let kind = match &token.kind {
+        TokenKind::Ident(ident) => TokenKind::Ident(Ident {
+            span: shift_span(ident.span),
+            ..*ident
+        }),
+        TokenKind::Keyword(ident) => TokenKind::Keyword(Ident {
+            span: shift_span(ident.span),
+            ..*ident
+        }),
+        kind => kind.clone(),
+    };
+    Token {
+        kind,
+        span: shift_span(token.span),
+    }
+}
+
+/// Lexes `src`, the text after applying `edit`, reusing the `old` tokens of the
+/// text before the edit wherever the edit could not have changed them.
+///
+/// The lexer carries no state from one token to the next, so lexing restarts at
+/// the end of the last token before the edit and stops as soon as a token after
+/// the edit starts at the same place as one of the old tokens did. Only numbers
+/// look back, at whether a `.` comes before them, so the old token also has to
+/// be of the same kind: `0.1` becomes `0`, `.` and `1` after typing `t.` in
+/// front of it.
+pub fn relex(
+    src: &str,
+    file: FileId,
+    old: &[Token],
+    edit: &TextEdit,
+) -> Result<Vec<Token>, ErrorReported> {
+    // a token that ends right at the edit might be extended by it, so it is
+    // relexed too. the same goes for tokens that need to look at the next character.
+    let unaffected = old.partition_point(|t| t.span.hi() < edit.range.start);
+    let restart = unaffected
+        .checked_sub(1)
+        .map_or(0, |last| old[last].span.hi());
+
+    let delta = edit.delta();
+    let edit_end = edit.range.start + edit.text.len();
+    let mut candidate = old.partition_point(|t| t.span.lo() < edit.range.end);
+
+    let mut tokens = old[..unaffected].to_vec();
+    let mut lexer = Lexer::new(src, file);
+    lexer.current = restart;
+
+    while let Some(token) = lexer.next_token() {
+        let lo = token.span.lo();
+        if lo >= edit_end {
+            while candidate < old.len()
+                && ((old[candidate].span.lo() as isize + delta) as usize) < lo
+            {
+                candidate += 1;
+            }
+            if let Some(old_token) = old.get(candidate)
+                && old_token.kind != TokenKind::Eof
+                && (old_token.span.lo() as isize + delta) as usize == lo
+                && mem::discriminant(&old_token.kind) == mem::discriminant(&token.kind)
+            {
+                tokens.extend(old[candidate..].iter().map(|t| shift(t, delta)));
+                return if lexer.has_errors {
+                    Err(ErrorReported)
+                } else {
+                    Ok(tokens)
+                };
+            }
+        }
+        tokens.push(token);
+    }
+
+    tokens.push(Token {
+        kind: TokenKind::Eof,
+        span: Span::new(src.len(), src.len(), file),
+    });
+
+    if lexer.has_errors {
+        Err(ErrorReported)
+    } else {
+        Ok(tokens)
+    }
+}
@@ -3,14 +3,18 @@
 use std::rc::Rc;
 use std::str::FromStr;
 
-use terryc_base::errors::{DiagnosticBuilder, DiagnosticSeverity, ErrorReported};
-use terryc_base::lex::{ErrorKind, Ident, Token, TokenKind};
+use terryc_base::ast::TotalF64;
+use terryc_base::errors::{make_diag, ErrorReported};
+use terryc_base::lex::{ErrorKind, Ident, Token, TokenKind, TokenTrivia, Trivia, TriviaKind};
 use terryc_base::sym::Symbol;
 use terryc_base::{Context, FileId, Providers, Span};
 use unicode_xid::UnicodeXID;
 
+mod incremental;
 pub mod unescape;
 
+pub use incremental::{relex, TextEdit};
+
 pub struct Lexer<'a> {
     file: FileId,
     src: &'a str,
@@ -18,6 +22,48 @@ pub struct Lexer<'a> {
     start: usize,
     current: usize,
     has_errors: bool,
+    /// Only present when lexing with trivia, see [`Lexer::scan_tokens_with_trivia`].
+    trivia: Option<TriviaCollector>,
+}
+
+#[derive(Default)]
+struct TriviaCollector {
+    /// trivia for each token in `Lexer::tokens`.
+    tokens: Vec<TokenTrivia>,
+    /// leading trivia of the next token.
+    pending: Vec<Trivia>,
+    /// whether we are still on the line of the last token.
+    same_line: bool,
+}
+
+impl TriviaCollector {
+    fn push(&mut self, trivia: Trivia, newline: bool) {
+        let list = match self.tokens.last_mut() {
+            Some(last) if self.same_line && !newline => &mut last.trailing,
+            _ => &mut self.pending,
+        };
+        if newline {
+            self.same_line = false;
+        }
+        match list.last_mut() {
+            Some(prev)
+                if prev.kind == TriviaKind::Whitespace
+                    && trivia.kind == TriviaKind::Whitespace
+                    && prev.span.hi() == trivia.span.lo() =>
+            {
+                prev.span = prev.span.to(trivia.span);
+            }
+            _ => list.push(trivia),
+        }
+    }
+
+    fn token(&mut self) {
+        self.tokens.push(TokenTrivia {
+            leading: std::mem::take(&mut self.pending),
+            trailing: vec![],
+        });
+        self.same_line = true;
+    }
 }
 
 impl<'a> Lexer<'a> {
@@ -29,66 +75,84 @@ impl<'a> Lexer<'a> {
             start: 0,
             current: 0,
             has_errors: false,
+            trivia: None,
         }
     }
 
     fn error(&mut self, kind: ErrorKind, span: Span) {
         self.has_errors = true;
-        DiagnosticBuilder::new(DiagnosticSeverity::Error, &format!("{kind:?}"), span).emit();
+        match kind {
+            ErrorKind::UnexpectedCharacter(c) => make_diag!(Error, span, E0001, c),
+            ErrorKind::UnterminatedString => make_diag!(Error, span, E0002),
+            ErrorKind::UnclosedComment => make_diag!(Error, span, E0003),
+            ErrorKind::InvalidFloat => make_diag!(Error, span, E0004),
+            ErrorKind::InvalidInt => make_diag!(Error, span, E0005),
+            ErrorKind::InvalidDigit(radix) => make_diag!(Error, span, E0010, radix),
+            ErrorKind::NoDigits => make_diag!(Error, span, E0011),
+            ErrorKind::FileTooLarge => make_diag!(Error, span, E0012),
+        }
+        .emit();
     }
 
     fn is_end(&self) -> bool {
         self.current >= self.src.len()
     }
 
-    fn char_at(&self, idx: usize) -> Option<char> {
-        self.src.split_at(idx).1.chars().next()
+    fn peek_byte(&self) -> Option<u8> {
+        self.src.as_bytes().get(self.current).copied()
     }
 
     fn peek(&self) -> Option<char> {
-        self.char_at(self.current)
+        match self.peek_byte()? {
+            b if b.is_ascii() => Some(b as char),
+            _ => self.src[self.current..].chars().next(),
+        }
     }
 
-    /*fn peek2(&self) -> Option<char> {
-        self.char_at(self.current + 1)
-    }*/
-
     fn advance(&mut self) -> Option<char> {
         let c = self.peek();
         self.current += c.map_or(0, char::len_utf8);
         c
     }
 
-    fn eat(&mut self, c: char) -> bool {
-        if self.peek() != Some(c) {
+    /// Consumes the next byte if it is `b`, which must be ASCII.
+    fn eat(&mut self, b: u8) -> bool {
+        if self.peek_byte() != Some(b) {
             return false;
         }
-        self.current += c.len_utf8();
+        self.current += 1;
         true
     }
 
+    /// Skips bytes while `f` returns true. `f` must only accept ASCII bytes
+    /// so that we never stop in the middle of a character.
+    fn skip_while(&mut self, f: impl Fn(u8) -> bool) {
+        let rest = &self.src.as_bytes()[self.current..];
+        self.current += rest.iter().position(|&b| !f(b)).unwrap_or(rest.len());
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        &self.src.as_bytes()[self.current..]
+    }
+
     fn string(&mut self) -> Option<TokenKind> {
-        while let Some(c) = self.peek() {
-            match c {
-                '\\' => {
-                    self.advance();
-                } // escape will ignore one or more chars.
-                '"' => break,
-                _ => {}
+        loop {
+            let Some(idx) = memchr::memchr2(b'"', b'\\', self.rest()) else {
+                self.current = self.src.len();
+                self.error(
+                    ErrorKind::UnterminatedString,
+                    Span::new(self.current, self.current, self.file),
+                );
+                return None;
+            };
+            self.current += idx + 1;
+            if self.src.as_bytes()[self.current - 1] == b'"' {
+                break;
             }
+            // escape will ignore one or more chars.
             self.advance();
         }
 
-        if self.is_end() {
-            self.error(
-                ErrorKind::UnterminatedString,
-                Span::new(self.current, self.current, self.file),
-            );
-            return None;
-        }
-
-        self.advance();
-
         let s = &self.src[self.start + 1..self.current - 1];
         unescape::unescape(s, Span::new(self.start + 1, self.current - 1, self.file))
             .ok()
@@ -98,33 +162,74 @@ impl<'a> Lexer<'a> {
     }
 
     fn number(&mut self) -> Option<TokenKind> {
-        while let Some(c) = self.peek() && c.is_ascii_digit() {
-            self.advance();
-        }
+        let radix = match (&self.src[self.start..self.current], self.peek_byte()) {
+            ("0", Some(b'x')) => 16,
+            ("0", Some(b'o')) => 8,
+            ("0", Some(b'b')) => 2,
+            _ => return self.decimal(),
+        };
+        self.current += 1;
+        self.prefixed_integer(radix)
+    }
 
-        let kind = /*if Some('.') == self.peek()
-            && self.peek2().map(|c| c.is_ascii_digit()).unwrap_or_default()
-        {
-            self.advance();
-            while let Some(c) = self.peek() && c.is_ascii_digit() {
-                self.advance();
-            }
+    /// The rest of `1_000` or `1.5` after the first digit.
+    fn decimal(&mut self) -> Option<TokenKind> {
+        let is_digit = |b: u8| b.is_ascii_digit() || b == b'_';
+        self.skip_while(is_digit);
+        // a dot that is not followed by a digit is a range or a field access.
+        // after a field access it is one too: `t.0.1` is two fields, not `0.1`.
+        let before = &self.src[..self.start];
+        let is_field = before.ends_with('.') && !before.ends_with("..");
+        if !is_field && let [b'.', b, ..] = self.rest() && b.is_ascii_digit() {
+            self.current += 1;
+            self.skip_while(is_digit);
+            let s = self.src[self.start..self.current].replace('_', "");
+            let Ok(num) = f64::from_str(&s) else {
+                self.error(ErrorKind::InvalidFloat, Span::new(self.start, self.current, self.file));
+                return None;
+            };
+            return Some(TokenKind::Decimal(TotalF64(num)));
+        }
+        self.integer(&self.src[self.start..self.current], 10)
+    }
 
-            let s = &self.src[self.start..self.current];
-            let Ok(num) = f64::from_str(s).map_err(|_| self.error(ErrorKind::InvalidFloat)) else { return None };
-            TokenKind::Decimal(num)
-        } else */{
-            let s = &self.src[self.start..self.current];
-            let Ok(num) = u128::from_str(s).map_err(|_| self.error(ErrorKind::InvalidInt, Span::new(self.start, self.current, self.file))) else { return None };
-            TokenKind::Integer(num)
-        };
+    /// The digits of `0x1f`, `0o17` or `0b1010` after the prefix. Letters are
+    /// taken as well, so `0b12` is an invalid digit rather than `0b1` and `2`.
+    fn prefixed_integer(&mut self, radix: u32) -> Option<TokenKind> {
+        let digits_start = self.current;
+        self.skip_while(|b| b.is_ascii_alphanumeric() || b == b'_');
+        let digits = &self.src[digits_start..self.current];
+        let invalid = digits.bytes().position(|b| b != b'_' && !char::from(b).is_digit(radix));
+        if let Some(i) = invalid {
+            let at = digits_start + i;
+            self.error(ErrorKind::InvalidDigit(radix), Span::new(at, at + 1, self.file));
+            return None;
+        }
+        if digits.bytes().all(|b| b == b'_') {
+            self.error(ErrorKind::NoDigits, Span::new(self.start, self.current, self.file));
+            return None;
+        }
+        self.integer(digits, radix)
+    }
 
-        Some(kind)
+    /// Parses the digits of the current literal, which can contain `_`.
+    fn integer(&mut self, digits: &str, radix: u32) -> Option<TokenKind> {
+        match u128::from_str_radix(&digits.replace('_', ""), radix) {
+            Ok(num) => Some(TokenKind::Integer(num)),
+            Err(_) => {
+                self.error(ErrorKind::InvalidInt, Span::new(self.start, self.current, self.file));
+                None
+            }
+        }
     }
 
     fn identifier(&mut self) -> TokenKind {
-        while let Some(c) = self.peek() && c.is_xid_continue() {
-            self.advance();
+        loop {
+            self.skip_while(|b| b.is_ascii_alphanumeric() || b == b'_');
+            match self.peek() {
+                Some(c) if !c.is_ascii() && c.is_xid_continue() => self.current += c.len_utf8(),
+                _ => break,
+            }
         }
 
         let s = &self.src[self.start..self.current];
@@ -137,82 +242,114 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    fn block_comment(&mut self) {
+        let mut nest = 1;
+
+        while nest > 0 {
+            let Some(idx) = memchr::memchr2(b'/', b'*', self.rest()) else {
+                self.current = self.src.len();
+                self.error(
+                    ErrorKind::UnclosedComment,
+                    Span::new(self.current, self.current, self.file),
+                );
+                return;
+            };
+            let b = self.rest()[idx];
+            self.current += idx + 1;
+            if b == b'/' && self.eat(b'*') {
+                nest += 1;
+            } else if b == b'*' && self.eat(b'/') {
+                nest -= 1;
+            }
+        }
+    }
+
     fn scan_token(&mut self) -> Option<TokenKind> {
         use TokenKind::*;
 
-        let c = match self.advance() {
-            Some(c) => c,
-            None => return None,
-        };
-
-        let kind = match c {
-            '(' => LeftParen,
-            ')' => RightParen,
-            '{' => LeftBrace,
-            '}' => RightBrace,
-            ',' => Comma,
-            '.' => Dot,
-            '-' if self.eat('>') => RArrow,
-            '-' => Minus,
-            '+' => Plus,
-            ';' => Semicolon,
-            '*' => Star,
-            ':' => Colon,
-            '!' if self.eat('=') => NotEq,
-            '!' => Not,
-            '=' if self.eat('=') => EqEq,
-            '=' => Eq,
-            '<' if self.eat('=') => LessEq,
-            '<' => Less,
-            '>' if self.eat('=') => GreaterEq,
-            '>' => Greater,
-            '%' => Percent,
-
-            '/' if self.eat('/') => {
-                while let Some(c) = self.peek() && c != '\n' {
-                    self.advance();
-                }
+        let b = self.peek_byte()?;
+        if !b.is_ascii() {
+            let c = self.advance().unwrap();
+            if c.is_xid_start() {
+                return Some(self.identifier());
+            }
+            self.error(
+                ErrorKind::UnexpectedCharacter(c),
+                Span::new(self.current, self.current, self.file),
+            );
+            return None;
+        }
+        self.current += 1;
+
+        let kind = match b {
+            b'(' => LeftParen,
+            b')' => RightParen,
+            b'{' => LeftBrace,
+            b'}' => RightBrace,
+            b'[' => LeftBracket,
+            b']' => RightBracket,
+            b'#' => Pound,
+            b',' => Comma,
+            b'.' if self.eat(b'.') => DotDot,
+            b'.' => Dot,
+            b'-' if self.eat(b'>') => RArrow,
+            b'-' if self.eat(b'=') => MinusEq,
+            b'-' => Minus,
+            b'+' if self.eat(b'=') => PlusEq,
+            b'+' => Plus,
+            b';' => Semicolon,
+            b'*' if self.eat(b'=') => StarEq,
+            b'*' => Star,
+            b':' if self.eat(b':') => ColonColon,
+            b':' => Colon,
+            b'!' if self.eat(b'=') => NotEq,
+            b'!' => Not,
+            b'=' if self.eat(b'=') => EqEq,
+            b'=' if self.eat(b'>') => FatArrow,
+            b'=' => Eq,
+            b'<' if self.eat(b'<') => Shl,
+            b'<' if self.eat(b'=') => LessEq,
+            b'<' => Less,
+            b'>' if self.eat(b'>') => Shr,
+            b'>' if self.eat(b'=') => GreaterEq,
+            b'>' => Greater,
+            b'%' if self.eat(b'=') => PercentEq,
+            b'%' => Percent,
+            b'&' if self.eat(b'&') => AndAnd,
+            b'&' => And,
+            b'|' if self.eat(b'|') => OrOr,
+            b'|' => Or,
+            b'^' => Caret,
+
+            b'/' if self.eat(b'/') => {
+                let rest = self.rest();
+                self.current += memchr::memchr(b'\n', rest).unwrap_or(rest.len());
                 return None;
             }
 
-            '/' if self.eat('*') => {
-                let mut nest = 1;
-
-                while nest > 0 {
-                    if self.is_end() {
-                        self.error(
-                            ErrorKind::UnclosedComment,
-                            Span::new(self.current, self.current, self.file),
-                        );
-                        return None;
-                    }
-                    while let Some(c) = self.peek() {
-                        self.advance();
-                        if c == '/' && self.eat('*') {
-                            nest += 1;
-                        } else if c == '*' && self.eat('/') {
-                            nest -= 1;
-                            break;
-                        }
-                    }
-                }
-
+            b'/' if self.eat(b'*') => {
+                self.block_comment();
                 return None;
             }
 
-            '/' => Slash,
+            b'/' if self.eat(b'=') => SlashEq,
+            b'/' => Slash,
 
-            // ignore whitespace.
-            ' ' | '\r' | '\t' | '\n' => return None,
+            // ignore whitespace. a newline starts a new run so that trivia
+            // can tell which line the whitespace is on.
+            b' ' | b'\r' | b'\t' | b'\n' => {
+                self.skip_while(|b| matches!(b, b' ' | b'\r' | b'\t'));
+                return None;
+            }
 
-            '"' => return self.string(),
+            b'"' => return self.string(),
 
-            c if c.is_ascii_digit() => return self.number(),
-            c if c.is_xid_start() => self.identifier(),
+            b if b.is_ascii_digit() => return self.number(),
+            b if b.is_ascii_alphabetic() || b == b'_' => self.identifier(),
 
-            c => {
+            b => {
                 self.error(
-                    ErrorKind::UnexpectedCharacter(c),
+                    ErrorKind::UnexpectedCharacter(b as char),
                     Span::new(self.current, self.current, self.file),
                 );
                 return None;
@@ -222,18 +359,64 @@ impl<'a> Lexer<'a> {
         Some(kind)
     }
 
-    pub fn scan_tokens(mut self) -> Result<Vec<Token>, ErrorReported> {
+    /// Records the text consumed by `scan_token` as trivia if it did not produce a token.
+    fn trivia(&mut self) {
+        let Some(collector) = &mut self.trivia else { return };
+        let text = &self.src[self.start..self.current];
+        let kind = if text.starts_with("//") {
+            TriviaKind::LineComment
+        } else if text.starts_with("/*") {
+            TriviaKind::BlockComment
+        } else if text.starts_with(char::is_whitespace) {
+            TriviaKind::Whitespace
+        } else {
+            // not trivia but an error which has already been reported.
+            return;
+        };
+        let span = Span::new(self.start, self.current, self.file);
+        collector.push(Trivia { kind, span }, text.contains('\n'));
+    }
+
+    /// Scans the next token, skipping trivia. Returns `None` at the end of the input.
+    fn next_token(&mut self) -> Option<Token> {
         while !self.is_end() {
             self.start = self.current;
-            let Some(kind) = self.scan_token() else { continue };
+            let Some(kind) = self.scan_token() else {
+                self.trivia();
+                continue;
+            };
             let span = Span::new(self.start, self.current, self.file);
-            self.tokens.push(Token { kind, span })
+            if let Some(collector) = &mut self.trivia {
+                collector.token();
+            }
+            return Some(Token { kind, span });
+        }
+        None
+    }
+
+    fn scan(&mut self) {
+        if u32::try_from(self.src.len()).is_err() {
+            self.error(ErrorKind::FileTooLarge, Span::new(0, 0, self.file));
+            return;
+        }
+        // roughly one token every four bytes, to avoid regrowing the
+        // vector over and over again for large files.
+        self.tokens.reserve(self.src.len() / 4);
+        while let Some(token) = self.next_token() {
+            self.tokens.push(token);
         }
 
         self.tokens.push(Token {
             kind: TokenKind::Eof,
             span: Span::new(self.current, self.current, self.file),
         });
+        if let Some(collector) = &mut self.trivia {
+            collector.token();
+        }
+    }
+
+    pub fn scan_tokens(mut self) -> Result<Vec<Token>, ErrorReported> {
+        self.scan();
 
         if self.has_errors {
             Err(ErrorReported)
@@ -241,6 +424,24 @@ impl<'a> Lexer<'a> {
             Ok(self.tokens)
         }
     }
+
+    /// Like [`Lexer::scan_tokens`], but also keeps the whitespace and comments around
+    /// each token. The returned trivia lists line up with the tokens, including `Eof`.
+    ///
+    /// This is slower than the normal mode and only meant for tools that need to
+    /// reproduce the source text, like the formatter.
+    pub fn scan_tokens_with_trivia(
+        mut self,
+    ) -> Result<(Vec<Token>, Vec<TokenTrivia>), ErrorReported> {
+        self.trivia = Some(TriviaCollector::default());
+        self.scan();
+
+        if self.has_errors {
+            Err(ErrorReported)
+        } else {
+            Ok((self.tokens, self.trivia.take().unwrap().tokens))
+        }
+    }
 }
 
 fn lex(cx: &dyn Context, file: FileId) -> Result<Rc<[Token]>, ErrorReported> {
@@ -1,7 +1,7 @@
 use std::borrow::Cow;
 use std::iter;
 
-use terryc_base::errors::{DiagnosticBuilder, DiagnosticSeverity, ErrorReported};
+use terryc_base::errors::{make_diag, ErrorReported};
 use terryc_base::Span;
 
 pub fn unescape(s: &str, sp: Span) -> Result<Cow<'_, str>, ErrorReported> {
@@ -30,50 +30,30 @@ pub fn unescape(s: &str, sp: Span) -> Result<Cow<'_, str>, ErrorReported> {
                             match num {
                                 Ok(num) if num < 0x80 => out.push(num as char),
                                 Ok(_) => {
-                                    DiagnosticBuilder::new(
-                                        DiagnosticSeverity::Error,
-                                        "ASCII escape literal must be in range of [0x00, 0x7F]",
-                                        span,
-                                    )
-                                    .emit();
+                                    make_diag!(Error, span, E0006).emit();
                                     return Err(ErrorReported);
                                 }
                                 _ => {
-                                    DiagnosticBuilder::new(
-                                        DiagnosticSeverity::Error,
-                                        "ASCII escape literal must be a hexadecimal",
-                                        span,
-                                    )
-                                    .emit();
+                                    make_diag!(Error, span, E0007).emit();
                                     return Err(ErrorReported);
                                 }
                             }
                         }
                         (Some((n1, _)), None) => {
-                            DiagnosticBuilder::new(
-                                DiagnosticSeverity::Error,
-                                "ASCII escape literal must be followed by exactly two hexadecimal digits", 
-                                Span::new(sp.lo() + slash, sp.lo() + n1, sp.file()),
-                            ).emit();
+                            let span = Span::new(sp.lo() + slash, sp.lo() + n1, sp.file());
+                            make_diag!(Error, span, E0008).emit();
                             return Err(ErrorReported);
                         }
                         _ => {
-                            DiagnosticBuilder::new(
-                                DiagnosticSeverity::Error,
-                                "ASCII escape literal must be followed by exactly two hexadecimal digits",
-                                Span::new(sp.lo() + slash, sp.lo() + nextidx, sp.file()),
-                            ).emit();
+                            let span = Span::new(sp.lo() + slash, sp.lo() + nextidx, sp.file());
+                            make_diag!(Error, span, E0008).emit();
                             return Err(ErrorReported);
                         }
                     },
 
                     _ => {
-                        DiagnosticBuilder::new(
-                            DiagnosticSeverity::Error,
-                            "unknown escape sequence",
-                            Span::new(sp.lo() + slash, sp.lo() + nextidx, sp.file()),
-                        )
-                        .emit();
+                        let span = Span::new(sp.lo() + slash, sp.lo() + nextidx, sp.file());
+                        make_diag!(Error, span, E0009).emit();
                         return Err(ErrorReported);
                     }
                 }
@@ -0,0 +1,376 @@
+//! Bounds check elimination.
+//!
+//! Tracks the range of values every `i32` local can hold, learning from
+//! constants, additions, copies, the conditions of branches and earlier
+//! asserts. The
+//! ranges of loop counters are widened on back edges to the next constant the
+//! function compares against, so the condition of the loop is what bounds
+//! them. A bounds check whose condition holds for the whole range of its index
+//! becomes a `goto`.
+
+use index_vec::IndexVec;
+use terryc_base::ast::{BinOpKind, TyKind, UnOpKind};
+use terryc_base::data::FxHashMap;
+use terryc_base::hir::Literal;
+use terryc_base::mir::{BasicBlock, Body, Local, Operand, Rvalue, Statement, Terminator};
+
+use crate::BOUNDS_CHECK_MSG;
+
+/// The values from `lo` to `hi`, both included.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Range {
+    lo: i64,
+    hi: i64,
+}
+
+impl Range {
+    const FULL: Range = Range {
+        lo: i32::MIN as i64,
+        hi: i32::MAX as i64,
+    };
+
+    fn hull(self, other: Range) -> Range {
+        Range {
+            lo: self.lo.min(other.lo),
+            hi: self.hi.max(other.hi),
+        }
+    }
+
+    /// `self`, or every value if the result of an operation would wrap.
+    fn or_full(self) -> Range {
+        if self.lo < Range::FULL.lo || self.hi > Range::FULL.hi {
+            Range::FULL
+        } else {
+            self
+        }
+    }
+}
+
+/// What is known about the locals at some point of a function.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+struct State {
+    /// The ranges of the locals that are known, the others can hold any value.
+    ranges: FxHashMap<Local, Range>,
+    /// The locals that hold a copy of another local, which is not a copy
+    /// itself, so that narrowing one narrows all of them.
+    copies: FxHashMap<Local, Local>,
+}
+
+impl State {
+    /// Forgets everything about `local`, which is assigned a new value.
+    fn clobber(&mut self, local: Local) {
+        self.ranges.remove(&local);
+        self.copies.retain(|&copy, &mut of| copy != local && of != local);
+    }
+}
+
+/// A comparison, kept for the boolean local it was assigned to so that the
+/// `assert` or branch on that local can use it.
+type Cond = (BinOpKind, Operand, Operand);
+
+pub fn run(body: &mut Body) {
+    let entry_states = analyze(body);
+    for (bb, state) in entry_states.iter_enumerated() {
+        let Some(mut state) = state.clone() else { continue };
+        let conds = transfer_block(body, bb, &mut state);
+        let data = &mut body.blocks[bb];
+        if let Terminator::Assert { cond: Operand::Copy(cond), msg, target } = &data.terminator
+            && msg.get_str() == BOUNDS_CHECK_MSG
+            && let Some(local) = cond.as_local()
+            && let Some((op, a, b)) = conds.get(&local)
+            && holds(&state, *op, a, b)
+        {
+            data.terminator = Terminator::Goto(*target);
+        }
+    }
+}
+
+/// The state at the start of every reachable block.
+fn analyze(body: &Body) -> IndexVec<BasicBlock, Option<State>> {
+    let rpo = body.reverse_post_order();
+    let mut rpo_index: IndexVec<BasicBlock, usize> =
+        IndexVec::from_vec(vec![usize::MAX; body.blocks.len()]);
+    for (i, &bb) in rpo.iter().enumerate() {
+        rpo_index[bb] = i;
+    }
+    let mut entry_states: IndexVec<BasicBlock, Option<State>> =
+        IndexVec::from_vec(vec![None; body.blocks.len()]);
+    entry_states[rpo[0]] = Some(State::default());
+    let thresholds = thresholds(body);
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &bb in &rpo {
+            let Some(mut state) = entry_states[bb].clone() else { continue };
+            let conds = transfer_block(body, bb, &mut state);
+            for (succ, state) in successors(body, &body.blocks[bb].terminator, state, &conds) {
+                // only loops can keep growing a range, so widen on back edges.
+                let widen = (rpo_index[succ] <= rpo_index[bb]).then_some(&thresholds[..]);
+                changed |= merge(&mut entry_states[succ], state, widen);
+            }
+        }
+    }
+    entry_states
+}
+
+/// The values ranges are widened to: the constants of comparisons and the
+/// values next to them, sorted.
+fn thresholds(body: &Body) -> Vec<i64> {
+    let mut thresholds = vec![];
+    let mut add = |rvalue: &Rvalue| {
+        if let Rvalue::BinaryOp(op, a, b) = rvalue
+            && negate(*op).is_some()
+        {
+            for op in [a, b] {
+                if let Operand::Const(Literal::Int(_)) = op {
+                    let c = range(&State::default(), op).lo;
+                    thresholds.extend([c - 1, c, c + 1]);
+                }
+            }
+        }
+    };
+    for data in &body.blocks {
+        for stmt in &data.statements {
+            if let Statement::Assign(_, rvalue) = stmt {
+                add(rvalue);
+            }
+        }
+        if let Terminator::SwitchInt(rvalue, _) = &data.terminator {
+            add(rvalue);
+        }
+    }
+    thresholds.sort_unstable();
+    thresholds.dedup();
+    thresholds
+}
+
+/// Applies the statements of `bb` to `state`, returning the comparisons that
+/// are still true of the locals they were assigned to at the end.
+fn transfer_block(body: &Body, bb: BasicBlock, state: &mut State) -> FxHashMap<Local, Cond> {
+    let mut conds: FxHashMap<Local, Cond> = FxHashMap::default();
+    for stmt in &body.blocks[bb].statements {
+        let Statement::Assign(place, rvalue) = stmt else { continue };
+        let local = place.local;
+        let reads = |op: &Operand| matches!(op, Operand::Copy(p) if p.local == local);
+        conds.retain(|&cond, (_, a, b)| cond != local && !reads(a) && !reads(b));
+        let range = eval(state, rvalue);
+        state.clobber(local);
+        if place.as_local().is_some() {
+            assign(body, state, local, range);
+        }
+        if let (Some(local), Rvalue::Use(Operand::Copy(of))) = (place.as_local(), rvalue)
+            && let Some(of) = of.as_local()
+            && body.locals[local].ty == TyKind::I32
+        {
+            let of = state.copies.get(&of).copied().unwrap_or(of);
+            state.copies.insert(local, of);
+        }
+        if let (Some(local), Rvalue::BinaryOp(op, a, b)) = (place.as_local(), rvalue)
+            && !reads(a)
+            && !reads(b)
+        {
+            conds.insert(local, (*op, a.clone(), b.clone()));
+        }
+    }
+    conds
+}
+
+/// The blocks control continues to, with what is known on each edge.
+fn successors(
+    body: &Body,
+    terminator: &Terminator,
+    mut state: State,
+    conds: &FxHashMap<Local, Cond>,
+) -> Vec<(BasicBlock, State)> {
+    let cond_of = |rvalue: &Rvalue| match rvalue {
+        Rvalue::BinaryOp(op, a, b) => Some((*op, a.clone(), b.clone())),
+        Rvalue::Use(Operand::Copy(place)) => place.as_local().and_then(|l| conds.get(&l).cloned()),
+        _ => None,
+    };
+    match terminator {
+        Terminator::Goto(target) => vec![(*target, state)],
+        Terminator::Call {
+            destination: (local, target),
+            ..
+        } => {
+            state.clobber(*local);
+            vec![(*target, state)]
+        }
+        Terminator::Assert { cond, target, .. } => {
+            if let Some((op, a, b)) = cond_of(&Rvalue::Use(cond.clone())) {
+                assume(body, &mut state, op, &a, &b);
+            }
+            vec![(*target, state)]
+        }
+        // `if` and `while` switch on a `bool`, which is `1` for `true`.
+        Terminator::SwitchInt(rvalue, targets) if targets.values == [1] => {
+            let [then, else_] = targets.targets[..] else { unreachable!("one value") };
+            let Some((op, a, b)) = cond_of(rvalue) else {
+                return vec![(then, state.clone()), (else_, state)];
+            };
+            let mut else_state = state.clone();
+            assume(body, &mut state, op, &a, &b);
+            if let Some(negated) = negate(op) {
+                assume(body, &mut else_state, negated, &a, &b);
+            }
+            vec![(then, state), (else_, else_state)]
+        }
+        Terminator::SwitchInt(_, targets) => {
+            targets.targets.iter().map(|&bb| (bb, state.clone())).collect()
+        }
+        Terminator::Return(_) | Terminator::Unreachable => vec![],
+        Terminator::ReplacedAfterConstruction => unreachable!(),
+    }
+}
+
+/// Joins `state` into the state at the start of a block, returning whether
+/// that changed. Bounds that grew are widened to the next of the `widen`
+/// thresholds, if given.
+fn merge(entry: &mut Option<State>, state: State, widen: Option<&[i64]>) -> bool {
+    let Some(old) = entry else {
+        *entry = Some(state);
+        return true;
+    };
+    let mut joined = State::default();
+    for (local, &range) in old.ranges.iter() {
+        let Some(&other) = state.ranges.get(local) else { continue };
+        let mut range = range.hull(other);
+        if let Some(thresholds) = widen {
+            if range.lo < old.ranges[local].lo {
+                let below = thresholds.iter().rev().find(|&&t| t <= range.lo);
+                range.lo = below.copied().unwrap_or(Range::FULL.lo).max(Range::FULL.lo);
+            }
+            if range.hi > old.ranges[local].hi {
+                let above = thresholds.iter().find(|&&t| t >= range.hi);
+                range.hi = above.copied().unwrap_or(Range::FULL.hi).min(Range::FULL.hi);
+            }
+        }
+        if range != Range::FULL {
+            joined.ranges.insert(*local, range);
+        }
+    }
+    joined.copies = old.copies.clone();
+    joined
+        .copies
+        .retain(|copy, of| state.copies.get(copy) == Some(of));
+    let changed = joined != *old;
+    *old = joined;
+    changed
+}
+
+fn assign(body: &Body, state: &mut State, local: Local, range: Range) {
+    if body.locals[local].ty == TyKind::I32 && range != Range::FULL {
+        state.ranges.insert(local, range);
+    } else {
+        state.ranges.remove(&local);
+    }
+}
+
+fn range(state: &State, op: &Operand) -> Range {
+    match op {
+        // integer literals wrap around to `i32` like in codegen.
+        Operand::Const(Literal::Int(n)) => {
+            let n = *n as u32 as i32 as i64;
+            Range { lo: n, hi: n }
+        }
+        Operand::Copy(place) => place
+            .as_local()
+            .and_then(|local| state.ranges.get(&local).copied())
+            .unwrap_or(Range::FULL),
+        Operand::Const(_) => Range::FULL,
+    }
+}
+
+fn eval(state: &State, rvalue: &Rvalue) -> Range {
+    match rvalue {
+        Rvalue::Use(op) => range(state, op),
+        Rvalue::BinaryOp(BinOpKind::Add, a, b) => {
+            let (a, b) = (range(state, a), range(state, b));
+            Range {
+                lo: a.lo + b.lo,
+                hi: a.hi + b.hi,
+            }
+            .or_full()
+        }
+        Rvalue::BinaryOp(BinOpKind::Sub, a, b) => {
+            let (a, b) = (range(state, a), range(state, b));
+            Range {
+                lo: a.lo - b.hi,
+                hi: a.hi - b.lo,
+            }
+            .or_full()
+        }
+        Rvalue::UnaryOp(UnOpKind::Minus, a) => {
+            let a = range(state, a);
+            Range { lo: -a.hi, hi: -a.lo }.or_full()
+        }
+        _ => Range::FULL,
+    }
+}
+
+/// The comparison that is true exactly when `op` is false.
+fn negate(op: BinOpKind) -> Option<BinOpKind> {
+    Some(match op {
+        BinOpKind::Less => BinOpKind::GreaterEqual,
+        BinOpKind::LessEqual => BinOpKind::Greater,
+        BinOpKind::Greater => BinOpKind::LessEqual,
+        BinOpKind::GreaterEqual => BinOpKind::Less,
+        BinOpKind::Equal => BinOpKind::NotEqual,
+        BinOpKind::NotEqual => BinOpKind::Equal,
+        _ => return None,
+    })
+}
+
+/// Narrows the ranges of `a` and `b` to the values for which `a op b`.
+fn assume(body: &Body, state: &mut State, op: BinOpKind, a: &Operand, b: &Operand) {
+    let (ra, rb) = (range(state, a), range(state, b));
+    let (ra, rb) = match op {
+        BinOpKind::Less => (
+            Range { hi: ra.hi.min(rb.hi - 1), ..ra },
+            Range { lo: rb.lo.max(ra.lo + 1), ..rb },
+        ),
+        BinOpKind::LessEqual => (
+            Range { hi: ra.hi.min(rb.hi), ..ra },
+            Range { lo: rb.lo.max(ra.lo), ..rb },
+        ),
+        BinOpKind::Greater => return assume(body, state, BinOpKind::Less, b, a),
+        BinOpKind::GreaterEqual => return assume(body, state, BinOpKind::LessEqual, b, a),
+        BinOpKind::Equal => {
+            let both = Range {
+                lo: ra.lo.max(rb.lo),
+                hi: ra.hi.min(rb.hi),
+            };
+            (both, both)
+        }
+        _ => return,
+    };
+    for (op, range) in [(a, ra), (b, rb)] {
+        if let Operand::Copy(place) = op
+            && let Some(local) = place.as_local()
+        {
+            let of = state.copies.get(&local).copied().unwrap_or(local);
+            let copies: Vec<_> = state
+                .copies
+                .iter()
+                .filter(|&(_, &other)| other == of)
+                .map(|(&copy, _)| copy)
+                .collect();
+            for local in copies.into_iter().chain([of]) {
+                assign(body, state, local, range);
+            }
+        }
+    }
+}
+
+/// Whether `a op b` is true for every value `a` and `b` can hold.
+fn holds(state: &State, op: BinOpKind, a: &Operand, b: &Operand) -> bool {
+    let (a, b) = (range(state, a), range(state, b));
+    match op {
+        BinOpKind::Less => a.hi < b.lo,
+        BinOpKind::LessEqual => a.hi <= b.lo,
+        BinOpKind::Greater => a.lo > b.hi,
+        BinOpKind::GreaterEqual => a.lo >= b.hi,
+        _ => false,
+    }
+}
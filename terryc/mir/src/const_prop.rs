@@ -0,0 +1,345 @@
+//! Sparse conditional constant propagation.
+//!
+//! Tracks which blocks are reachable and which locals hold a known constant
+//! at the same time, so a branch on a constant only makes its taken arm
+//! reachable and constants assigned in the other arm never pollute the join.
+//! Afterwards known operands are replaced by constants, constant branches
+//! become `goto`s and unreachable blocks are removed.
+
+use index_vec::IndexVec;
+use terryc_base::ast::{BinOpKind, TotalF64, TyKind, UnOpKind};
+use terryc_base::hir::Literal;
+use terryc_base::mir::{
+    BasicBlock, BasicBlockData, Body, Local, Operand, Place, Rvalue, Statement, Targets, Terminator,
+};
+use terryc_base::sym::Symbol;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Value {
+    /// Not assigned on any path seen so far.
+    Undef,
+    Const(Literal),
+    Varying,
+}
+
+impl Value {
+    fn join(self, other: Value) -> Value {
+        match (self, other) {
+            (Value::Undef, x) | (x, Value::Undef) => x,
+            (Value::Const(a), Value::Const(b)) if a == b => self,
+            _ => Value::Varying,
+        }
+    }
+}
+
+pub(crate) type State = IndexVec<Local, Value>;
+
+pub fn run(body: &mut Body, arg_count: usize) {
+    let mut entry_states = analyze(body, arg_count);
+    for (bb, state) in entry_states.iter_enumerated() {
+        if let Some(state) = state {
+            rewrite(body, bb, state.clone());
+        }
+    }
+    redirect_failing_asserts(body, &mut entry_states);
+    remove_unreachable(body, &entry_states);
+}
+
+fn analyze(body: &Body, arg_count: usize) -> IndexVec<BasicBlock, Option<State>> {
+    let mut entry_states: IndexVec<BasicBlock, Option<State>> =
+        IndexVec::from_vec(vec![None; body.blocks.len()]);
+    let mut start = IndexVec::from_vec(vec![Value::Undef; body.locals.len()]);
+    for arg in start.iter_mut().take(arg_count) {
+        *arg = Value::Varying;
+    }
+    entry_states[BasicBlock::from_usize(0)] = Some(start);
+
+    let mut worklist = vec![BasicBlock::from_usize(0)];
+    while let Some(bb) = worklist.pop() {
+        let mut state = entry_states[bb].clone().unwrap();
+        let data = &body.blocks[bb];
+        for stmt in &data.statements {
+            transfer(&mut state, stmt);
+        }
+        let successors = match &data.terminator {
+            Terminator::SwitchInt(rvalue, targets) => match eval(rvalue, &state) {
+                Value::Const(lit) => vec![switch_target(lit, targets)],
+                // reading an unassigned local is not something we can reason
+                // about, so stay conservative and keep every arm.
+                Value::Undef | Value::Varying => targets.targets.clone(),
+            },
+            Terminator::Call {
+                destination: (local, target),
+                ..
+            } => {
+                state[*local] = Value::Varying;
+                vec![*target]
+            }
+            Terminator::Assert { cond, target, .. } => match operand(cond, &state) {
+                // the assertion always fails
+                Value::Const(Literal::Bool(false)) => vec![],
+                _ => vec![*target],
+            },
+            terminator => terminator.successors(),
+        };
+        for target in successors {
+            let changed = match &mut entry_states[target] {
+                Some(old) => {
+                    let mut changed = false;
+                    for (old, new) in old.iter_mut().zip(&state) {
+                        let joined = old.join(*new);
+                        changed |= joined != *old;
+                        *old = joined;
+                    }
+                    changed
+                }
+                slot @ None => {
+                    *slot = Some(state.clone());
+                    true
+                }
+            };
+            if changed && !worklist.contains(&target) {
+                worklist.push(target);
+            }
+        }
+    }
+    entry_states
+}
+
+fn rewrite(body: &mut Body, bb: BasicBlock, mut state: State) {
+    let data = &mut body.blocks[bb];
+    for stmt in &mut data.statements {
+        if let Statement::Assign(place, rvalue) = stmt {
+            let value = eval(rvalue, &state);
+            fold_rvalue(rvalue, value, &state);
+            assign(&mut state, place, value);
+        } else {
+            transfer(&mut state, stmt);
+        }
+    }
+    match &mut data.terminator {
+        Terminator::SwitchInt(rvalue, targets) => match eval(rvalue, &state) {
+            Value::Const(lit) => data.terminator = Terminator::Goto(switch_target(lit, targets)),
+            value => fold_rvalue(rvalue, value, &state),
+        },
+        Terminator::Call { args, .. } => {
+            for arg in args {
+                let value = eval(arg, &state);
+                fold_rvalue(arg, value, &state);
+            }
+        }
+        Terminator::Assert { cond, target, .. } => match operand(cond, &state) {
+            Value::Const(Literal::Bool(true)) => data.terminator = Terminator::Goto(*target),
+            _ => fold_operand(cond, &state),
+        },
+        Terminator::Return(_) | Terminator::Goto(_) | Terminator::Unreachable => {}
+        Terminator::ReplacedAfterConstruction => unreachable!(),
+    }
+}
+
+/// An assert that always fails keeps its target, which may be unreachable
+/// otherwise. Such asserts continue to a new `unreachable` block instead, so
+/// the code after them can be removed.
+fn redirect_failing_asserts(
+    body: &mut Body,
+    entry_states: &mut IndexVec<BasicBlock, Option<State>>,
+) {
+    let failing: Vec<_> = body
+        .blocks
+        .iter_enumerated()
+        .filter(|&(bb, data)| {
+            entry_states[bb].is_some()
+                && matches!(data.terminator, Terminator::Assert { target, .. }
+                    if entry_states[target].is_none())
+        })
+        .map(|(bb, _)| bb)
+        .collect();
+    if failing.is_empty() {
+        return;
+    }
+    let unreachable = body.blocks.push(BasicBlockData {
+        statements: vec![],
+        terminator: Terminator::Unreachable,
+    });
+    entry_states.push(Some(State::new()));
+    for bb in failing {
+        if let Terminator::Assert { target, .. } = &mut body.blocks[bb].terminator {
+            *target = unreachable;
+        }
+    }
+}
+
+fn remove_unreachable(body: &mut Body, entry_states: &IndexVec<BasicBlock, Option<State>>) {
+    let mut new_index = IndexVec::<BasicBlock, Option<BasicBlock>>::new();
+    let mut next = BasicBlock::from_usize(0);
+    for state in entry_states {
+        new_index.push(state.as_ref().map(|_| {
+            let bb = next;
+            next += 1;
+            bb
+        }));
+    }
+    let map = |bb: &mut BasicBlock| *bb = new_index[*bb].expect("jump to unreachable block");
+
+    let blocks = std::mem::take(&mut body.blocks);
+    for (bb, mut data) in blocks.into_iter_enumerated() {
+        if new_index[bb].is_none() {
+            continue;
+        }
+        data.terminator.successors_mut().into_iter().for_each(map);
+        body.blocks.push(data);
+    }
+}
+
+pub(crate) fn switch_target(lit: Literal, targets: &Targets) -> BasicBlock {
+    let value = match lit {
+        Literal::Bool(b) => b as i32,
+        Literal::Int(i) => i as i32,
+        _ => unreachable!("switch on {lit:?}"),
+    };
+    targets
+        .iter()
+        .find(|&(v, _)| v == value)
+        .map_or_else(|| targets.else_(), |(_, bb)| bb)
+}
+
+fn fold_rvalue(rvalue: &mut Rvalue, value: Value, state: &State) {
+    if let Value::Const(lit) = value {
+        *rvalue = Rvalue::Use(Operand::Const(lit));
+        return;
+    }
+    match rvalue {
+        Rvalue::Use(op) | Rvalue::UnaryOp(_, op) | Rvalue::Cast(op, _) => fold_operand(op, state),
+        Rvalue::BinaryOp(_, lhs, rhs) => {
+            fold_operand(lhs, state);
+            fold_operand(rhs, state);
+        }
+        Rvalue::Aggregate(_, elems) | Rvalue::Variant(_, _, elems) => {
+            elems.iter_mut().for_each(|op| fold_operand(op, state))
+        }
+        Rvalue::Discriminant(_) => {}
+    }
+}
+
+fn fold_operand(op: &mut Operand, state: &State) {
+    if let Value::Const(lit) = operand(op, state) {
+        *op = Operand::Const(lit);
+    }
+}
+
+/// Updates `state` with the effect of running `stmt`.
+pub(crate) fn transfer(state: &mut State, stmt: &Statement) {
+    match stmt {
+        Statement::Assign(place, rvalue) => {
+            let value = eval(rvalue, state);
+            assign(state, place, value);
+        }
+        Statement::StorageLive(local) | Statement::StorageDead(local) => {
+            state[*local] = Value::Undef;
+        }
+    }
+}
+
+/// Records that `value` was stored to `place`.
+fn assign(state: &mut State, place: &Place, value: Value) {
+    state[place.local] = match place.as_local() {
+        Some(_) => value,
+        // only a part of the local changed, we do not track those.
+        None => Value::Varying,
+    };
+}
+
+fn operand(op: &Operand, state: &State) -> Value {
+    match op {
+        Operand::Copy(place) => place
+            .as_local()
+            .map_or(Value::Varying, |local| state[local]),
+        Operand::Const(lit) => Value::Const(*lit),
+    }
+}
+
+pub(crate) fn eval(rvalue: &Rvalue, state: &State) -> Value {
+    match rvalue {
+        Rvalue::Use(op) => operand(op, state),
+        Rvalue::UnaryOp(kind, op) => match operand(op, state) {
+            Value::Const(lit) => fold_unop(*kind, lit).map_or(Value::Varying, Value::Const),
+            value => value,
+        },
+        Rvalue::Cast(op, ty) => match operand(op, state) {
+            Value::Const(lit) => fold_cast(lit, *ty).map_or(Value::Varying, Value::Const),
+            value => value,
+        },
+        Rvalue::BinaryOp(kind, lhs, rhs) => match (operand(lhs, state), operand(rhs, state)) {
+            (Value::Const(a), Value::Const(b)) => {
+                fold_binop(*kind, a, b).map_or(Value::Varying, Value::Const)
+            }
+            (Value::Varying, _) | (_, Value::Varying) => Value::Varying,
+            _ => Value::Undef,
+        },
+        // only scalars are tracked.
+        Rvalue::Aggregate(..) | Rvalue::Variant(..) | Rvalue::Discriminant(_) => Value::Varying,
+    }
+}
+
+// `i32`s are stored as their bit pattern, just like codegen reads them.
+pub(crate) fn int(i: i32) -> Literal {
+    Literal::Int(i as u32 as u128)
+}
+
+fn fold_unop(kind: UnOpKind, lit: Literal) -> Option<Literal> {
+    Some(match (kind, lit) {
+        (UnOpKind::Minus, Literal::Int(i)) => int((i as i32).wrapping_neg()),
+        (UnOpKind::Not, Literal::Bool(b)) => Literal::Bool(!b),
+        _ => return None,
+    })
+}
+
+fn fold_cast(lit: Literal, ty: TyKind) -> Option<Literal> {
+    Some(match (lit, ty) {
+        (Literal::Int(i), TyKind::F32) => Literal::Float(TotalF64(i as i32 as f32 as f64)),
+        // `as` saturates like the `fptosi.sat` that codegen uses.
+        (Literal::Float(f), TyKind::I32) => int(f.0 as f32 as i32),
+        (Literal::Bool(b), TyKind::I32) => int(b as i32),
+        _ => return None,
+    })
+}
+
+fn fold_binop(kind: BinOpKind, lhs: Literal, rhs: Literal) -> Option<Literal> {
+    use BinOpKind::*;
+    let (a, b) = match (lhs, rhs) {
+        (Literal::Int(a), Literal::Int(b)) => (a as i32, b as i32),
+        (Literal::String(a), Literal::String(b)) if kind == Add => {
+            let concat = format!("{}{}", a.get_str(), b.get_str());
+            return Some(Literal::String(Symbol::new(&concat)));
+        }
+        (Literal::Bool(_) | Literal::String(_), _) => {
+            return match kind {
+                Equal => Some(Literal::Bool(lhs == rhs)),
+                NotEqual => Some(Literal::Bool(lhs != rhs)),
+                _ => None,
+            };
+        }
+        _ => return None,
+    };
+    Some(match kind {
+        Add => int(a.wrapping_add(b)),
+        Sub => int(a.wrapping_sub(b)),
+        Mul => int(a.wrapping_mul(b)),
+        // leave division by zero to fail at runtime
+        Div => int(a.checked_div(b)?),
+        Mod => int(a.checked_rem(b)?),
+        Equal => Literal::Bool(a == b),
+        NotEqual => Literal::Bool(a != b),
+        Less => Literal::Bool(a < b),
+        LessEqual => Literal::Bool(a <= b),
+        Greater => Literal::Bool(a > b),
+        GreaterEqual => Literal::Bool(a >= b),
+        BitAnd => int(a & b),
+        BitOr => int(a | b),
+        BitXor => int(a ^ b),
+        // the shift amount is masked to the width, as in codegen
+        Shl => int(a.wrapping_shl(b as u32)),
+        Shr => int(a.wrapping_shr(b as u32)),
+        And | Or => return None,
+    })
+}
@@ -0,0 +1,194 @@
+//! Inlining.
+//!
+//! A call to a small function is replaced by a copy of its body, which saves
+//! the call and lets constant propagation see through it. `#[inline]` raises
+//! the size limit, `#[inline(always)]` lifts it and `#[inline(never)]` keeps
+//! every call. Functions that can end up calling themselves are never inlined,
+//! copying them would never finish.
+
+use terryc_base::ast::TyKind;
+use terryc_base::data::{FxIndexMap, FxIndexSet};
+use terryc_base::hir::{InlineKind, Resolution};
+use terryc_base::mir::{
+    BasicBlock, BasicBlockData, Body, CallGraph, Function, Local, Operand, Place, ProjectionElem,
+    Rvalue, Statement, Terminator,
+};
+use terryc_base::Id;
+
+/// Functions with more assignments and terminators than this are not inlined
+/// unless they ask to be.
+const INLINE_THRESHOLD: usize = 8;
+
+/// Like [`INLINE_THRESHOLD`], for functions marked `#[inline]`.
+const INLINE_HINT_THRESHOLD: usize = 32;
+
+/// Inlines the calls in `functions` that are worth it, and returns the
+/// functions that calls were inlined into.
+pub fn run(graph: &CallGraph, functions: &mut FxIndexMap<Id, Function>) -> FxIndexSet<Id> {
+    let recursive: FxIndexSet<Id> = graph.cycles().into_iter().flatten().collect();
+    let mut inlined_into = FxIndexSet::default();
+    // callees come first, so the calls in their bodies are already inlined
+    // when they are copied.
+    for id in graph.bottom_up() {
+        let mut body = std::mem::take(&mut functions[&id].body);
+        // the blocks of the copies are appended, and were already visited
+        // as part of their own function.
+        for bb in body.blocks.indices() {
+            let Terminator::Call {
+                callee: Resolution::Fn(callee),
+                ..
+            } = body.blocks[bb].terminator
+            else {
+                continue;
+            };
+            if recursive.contains(&callee) || !should_inline(&functions[&callee]) {
+                continue;
+            }
+            inline_call(&mut body, bb, &functions[&callee].body);
+            inlined_into.insert(id);
+        }
+        functions[&id].body = body;
+    }
+    inlined_into
+}
+
+fn should_inline(f: &Function) -> bool {
+    match f.inline {
+        Some(InlineKind::Always) => true,
+        Some(InlineKind::Never) => false,
+        Some(InlineKind::Hint) => size(&f.body) <= INLINE_HINT_THRESHOLD,
+        None => size(&f.body) <= INLINE_THRESHOLD,
+    }
+}
+
+fn size(body: &Body) -> usize {
+    let assignments = |data: &BasicBlockData| {
+        data.statements
+            .iter()
+            .filter(|stmt| matches!(stmt, Statement::Assign(..)))
+            .count()
+    };
+    body.blocks.iter().map(|data| assignments(data) + 1).sum()
+}
+
+/// Replaces the call ending `bb` by a copy of `callee`. The arguments are
+/// assigned to the copies of its parameters, and every `return` assigns the
+/// destination of the call and continues after it.
+fn inline_call(body: &mut Body, bb: BasicBlock, callee: &Body) {
+    let entry = body.blocks.next_idx();
+    let Terminator::Call {
+        args,
+        destination: (dest, target),
+        ..
+    } = std::mem::replace(&mut body.blocks[bb].terminator, Terminator::Goto(entry))
+    else {
+        unreachable!("only calls are inlined")
+    };
+    let renumber = Renumber {
+        locals: body.locals.len(),
+        blocks: entry.index(),
+    };
+    body.locals.extend(callee.locals.iter().cloned());
+
+    // unit values are never stored, see codegen.
+    let is_unit = |local: Local| callee.locals[local].ty == TyKind::Unit;
+    for (arg, rvalue) in args.into_iter().enumerate() {
+        let param = Local::from_usize(arg);
+        if !is_unit(param) {
+            let stmt = Statement::Assign(renumber.local(param).into(), rvalue);
+            body.blocks[bb].statements.push(stmt);
+        }
+    }
+
+    for data in &callee.blocks {
+        let mut data = data.clone();
+        data.statements
+            .iter_mut()
+            .for_each(|stmt| renumber.statement(stmt));
+        if let Terminator::Return(local) = data.terminator {
+            if !is_unit(local) {
+                let value = Rvalue::Use(Operand::Copy(renumber.local(local).into()));
+                data.statements.push(Statement::Assign(dest.into(), value));
+            }
+            data.terminator = Terminator::Goto(target);
+        } else {
+            renumber.terminator(&mut data.terminator);
+        }
+        body.blocks.push(data);
+    }
+}
+
+/// Moves the locals and blocks of a copied body past those of the body it is
+/// copied into.
+struct Renumber {
+    locals: usize,
+    blocks: usize,
+}
+
+impl Renumber {
+    fn local(&self, local: Local) -> Local {
+        local + self.locals
+    }
+
+    fn place(&self, place: &mut Place) {
+        place.local = self.local(place.local);
+        for elem in &mut place.projection {
+            if let ProjectionElem::Index(index) = elem {
+                *index = self.local(*index);
+            }
+        }
+    }
+
+    fn operand(&self, op: &mut Operand) {
+        if let Operand::Copy(place) = op {
+            self.place(place);
+        }
+    }
+
+    fn rvalue(&self, rvalue: &mut Rvalue) {
+        match rvalue {
+            Rvalue::Use(op) | Rvalue::UnaryOp(_, op) | Rvalue::Cast(op, _) => self.operand(op),
+            Rvalue::BinaryOp(_, lhs, rhs) => {
+                self.operand(lhs);
+                self.operand(rhs);
+            }
+            Rvalue::Aggregate(_, ops) | Rvalue::Variant(_, _, ops) => {
+                ops.iter_mut().for_each(|op| self.operand(op));
+            }
+            Rvalue::Discriminant(place) => self.place(place),
+        }
+    }
+
+    fn statement(&self, stmt: &mut Statement) {
+        match stmt {
+            Statement::Assign(place, rvalue) => {
+                self.place(place);
+                self.rvalue(rvalue);
+            }
+            Statement::StorageLive(local) | Statement::StorageDead(local) => {
+                *local = self.local(*local);
+            }
+        }
+    }
+
+    fn terminator(&self, terminator: &mut Terminator) {
+        match terminator {
+            Terminator::Return(local) => *local = self.local(*local),
+            Terminator::SwitchInt(rvalue, _) => self.rvalue(rvalue),
+            Terminator::Call {
+                args,
+                destination: (local, _),
+                ..
+            } => {
+                args.iter_mut().for_each(|arg| self.rvalue(arg));
+                *local = self.local(*local);
+            }
+            Terminator::Assert { cond, .. } => self.operand(cond),
+            Terminator::Goto(_) | Terminator::Unreachable => {}
+            Terminator::ReplacedAfterConstruction => unreachable!(),
+        }
+        for succ in terminator.successors_mut() {
+            *succ += self.blocks;
+        }
+    }
+}
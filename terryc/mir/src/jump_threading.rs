@@ -0,0 +1,118 @@
+//! Jump threading.
+//!
+//! When a block ends in a `SwitchInt` whose condition is already decided on
+//! one of its incoming edges, e.g. the join after `if c { x = 1; }` followed
+//! by `if x == 1`, that edge is sent straight to the right target, taking a
+//! copy of the block's statements along.
+
+use index_vec::IndexVec;
+use terryc_base::ast::TyKind;
+use terryc_base::hir::Literal;
+use terryc_base::mir::{
+    BasicBlock, BasicBlockData, Body, Local, Operand, Rvalue, Statement, Targets, Terminator,
+};
+
+use crate::const_prop::{eval, int, switch_target, transfer, State, Value};
+
+/// Blocks with more statements than this are not duplicated.
+const MAX_DUPLICATED_STATEMENTS: usize = 8;
+
+pub fn run(body: &mut Body) {
+    for pred in body.blocks.indices() {
+        for (edge, target, state) in outgoing_edges(body, pred) {
+            if target == pred {
+                continue;
+            }
+            let Some((statements, dest)) = thread(body, target, state) else { continue };
+            let new_target = if statements.is_empty() {
+                dest
+            } else if let Terminator::Goto(_) = body.blocks[pred].terminator {
+                let data = &mut body.blocks[pred];
+                data.statements.extend(statements);
+                data.terminator = Terminator::Goto(dest);
+                continue;
+            } else {
+                body.blocks.push(BasicBlockData {
+                    statements,
+                    terminator: Terminator::Goto(dest),
+                })
+            };
+            *body.blocks[pred].terminator.successors_mut()[edge] = new_target;
+        }
+    }
+}
+
+/// Where the edge into `bb` leads if the `SwitchInt` at its end is decided
+/// by `state`, along with the statements that have to be run on the way.
+fn thread(body: &Body, bb: BasicBlock, mut state: State) -> Option<(Vec<Statement>, BasicBlock)> {
+    let data = &body.blocks[bb];
+    let Terminator::SwitchInt(cond, targets) = &data.terminator else { return None };
+    if data.statements.len() > MAX_DUPLICATED_STATEMENTS {
+        return None;
+    }
+    for stmt in &data.statements {
+        transfer(&mut state, stmt);
+    }
+    match eval(cond, &state) {
+        Value::Const(lit) => Some((data.statements.clone(), switch_target(lit, targets))),
+        Value::Undef | Value::Varying => None,
+    }
+}
+
+/// The successors of `bb`, with the constants known on the edge to each.
+fn outgoing_edges(body: &Body, bb: BasicBlock) -> Vec<(usize, BasicBlock, State)> {
+    let data = &body.blocks[bb];
+    let mut state: State = IndexVec::from_vec(vec![Value::Varying; body.locals.len()]);
+    for stmt in &data.statements {
+        transfer(&mut state, stmt);
+    }
+    if let Terminator::Call {
+        destination: (local, _),
+        ..
+    } = data.terminator
+    {
+        state[local] = Value::Varying;
+    }
+    let successors = data.terminator.successors();
+    successors
+        .into_iter()
+        .enumerate()
+        .map(|(edge, target)| {
+            let mut state = state.clone();
+            let fact = match &data.terminator {
+                Terminator::SwitchInt(cond, targets) => edge_fact(body, cond, targets, edge),
+                _ => None,
+            };
+            if let Some((local, lit)) = fact {
+                state[local] = Value::Const(lit);
+            }
+            (edge, target, state)
+        })
+        .collect()
+}
+
+/// What taking `edge` says about the local that is switched on.
+fn edge_fact(
+    body: &Body,
+    cond: &Rvalue,
+    targets: &Targets,
+    edge: usize,
+) -> Option<(Local, Literal)> {
+    let Rvalue::Use(Operand::Copy(place)) = cond else { return None };
+    let local = place.as_local()?;
+    let value = if edge == targets.targets.len() - 1 {
+        // `if` switches on `true`, so its `else` edge means `false`.
+        match (body.locals[local].ty, &*targets.values) {
+            (TyKind::Bool, [1]) => 0,
+            _ => return None,
+        }
+    } else {
+        targets.values[edge]
+    };
+    let lit = match body.locals[local].ty {
+        TyKind::Bool => Literal::Bool(value != 0),
+        TyKind::I32 => int(value),
+        _ => return None,
+    };
+    Some((local, lit))
+}
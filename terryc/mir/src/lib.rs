@@ -1,70 +1,146 @@
+mod bounds_checks;
+mod const_prop;
+mod inline;
+mod jump_threading;
+#[cfg(test)]
+mod tests;
+
 use std::rc::Rc;
 
-use terryc_base::ast::TyKind;
-use terryc_base::data::FxHashMap;
-use terryc_base::errors::ErrorReported;
+use terryc_base::ast::{BinOpKind, TyKind};
+use terryc_base::data::{FxIndexMap, FxIndexSet};
+use terryc_base::errors::{make_diag, ErrorReported};
 use terryc_base::hir::{Func, HirTree, ItemFn, Literal, Resolution};
 use terryc_base::mir::{
-    BasicBlockData, Body, Function, Local, LocalData, MirTree, Operand, Rvalue, Statement, Targets,
-    Terminator,
+    BasicBlock, BasicBlockData, Body, CallGraph, CallGraphNode, Function, Local, LocalData, MirTree, Operand,
+    Place, ProjectionElem, Rvalue, Statement, Targets, Terminator,
 };
-use terryc_base::{hir, Context, ContextExt, FileId, Id, Providers};
+use terryc_base::sym::Symbol;
+use terryc_base::{hir, sym, Context, ContextExt, FileId, Id, Providers};
 
 fn mir(cx: &dyn Context, id: FileId) -> Result<MirTree, ErrorReported> {
     let HirTree { functions, items } = cx.hir(id)?;
     let mut info = HirInfo::new(functions);
-    let items = items.iter().map(
-        |hir::Item::Fn(ItemFn {
-             name,
-             id,
-             args,
-             ret,
-             block,
-         })| {
-            info.id_to_local.clear();
-            let mut body = Body::default();
-            for arg in args {
-                let local = body.locals.push(LocalData { ty: arg.ty });
-                info.id_to_local.insert(arg.id, local);
+    lower_items(cx, &items, &mut info);
+    let call_graph = build_call_graph(&info.bodies);
+    if cx.options().optimize {
+        let inlined_into =
+            tracing::debug_span!("inline").in_scope(|| inline::run(&call_graph, &mut info.bodies));
+        // constants passed as arguments can now be propagated into the copies.
+        for id in inlined_into {
+            let f = &mut info.bodies[&id];
+            jump_threading::run(&mut f.body);
+            const_prop::run(&mut f.body, f.args.len());
+            if !cx.options().keep_bounds_checks {
+                bounds_checks::run(&mut f.body);
             }
-            body.blocks.push(new_bb());
-            collect_into(cx, &block.statements, &mut body, &mut info);
-            let ret_place = body.locals.push(LocalData { ty: *ret });
-            if let Some(e) = &block.expr {
-                let rv = expr_to_rvalue(cx, e, &mut body, &mut info);
-                if *ret != TyKind::Unit {
-                    body.expect_last_mut()
-                        .statements
-                        .push(Statement::Assign(ret_place, rv));
-                }
-            }
-            body.expect_last_mut().terminator = Terminator::Return(ret_place);
-            (
-                *id,
-                Function {
-                    body,
-                    name: *name,
-                    args: cx.intern_types(args.iter().map(|arg| arg.ty)),
-                    ret: *ret,
-                },
-            )
-        },
-    );
-    let items = Rc::new(items.collect());
+        }
+    }
+    let tree = MirTree {
+        functions: Rc::new(
+            info.bodies
+                .into_iter()
+                .map(|(id, f)| (id, Rc::new(f)))
+                .collect(),
+        ),
+        call_graph: Rc::new(call_graph),
+    };
+    for lint in cx.providers().lints {
+        tracing::debug_span!("lint", name = lint.name()).in_scope(|| lint.check_mir(cx, &tree));
+    }
+    Ok(tree)
+}
 
-    Ok(MirTree { functions: items })
+/// Builds a body for every function in `items`, including those declared in
+/// modules.
+fn lower_items(cx: &dyn Context, items: &[hir::Item], info: &mut HirInfo) {
+    for item in items {
+        match item {
+            hir::Item::Fn(f) => lower_fn(cx, f, info),
+            hir::Item::Mod { tree, .. } => lower_items(cx, &tree.items, info),
+            hir::Item::Struct(_) | hir::Item::Enum(_) => {}
+        }
+    }
+}
+
+fn lower_fn(
+    cx: &dyn Context,
+    ItemFn {
+        name,
+        id,
+        args,
+        ret,
+        block,
+        test: _,
+        inline,
+    }: &ItemFn,
+    info: &mut HirInfo,
+) {
+    let _span = tracing::debug_span!("lower_fn", %name).entered();
+    // a function declared inside another one gets a body of its own.
+    let outer_locals = std::mem::take(&mut info.id_to_local);
+    let outer_diverged = std::mem::take(&mut info.diverged);
+    let mut body = Body::default();
+    for arg in args {
+        let local = body.locals.push(LocalData::new(arg.ty));
+        info.id_to_local.insert(arg.id, local);
+    }
+    let ret_place = body.locals.push(LocalData::new(*ret));
+    body.return_place = ret_place;
+    body.blocks.push(new_bb());
+    let scope = collect_into(cx, &block.statements, &mut body, info);
+    if let Some(e) = &block.expr {
+        let rv = expr_to_rvalue(cx, e, &mut body, info);
+        // a body like `{ return 1; }` has no value to return at its end.
+        if *ret != TyKind::Unit && !info.diverged.contains(&body.blocks.last_idx()) {
+            body.expect_last_mut()
+                .statements
+                .push(Statement::Assign(ret_place.into(), rv));
+        }
+    }
+    storage_dead(&mut body, scope);
+    body.expect_last_mut().terminator = Terminator::Return(ret_place);
+    if cx.options().optimize {
+        tracing::debug_span!("jump_threading").in_scope(|| jump_threading::run(&mut body));
+        tracing::debug_span!("const_prop").in_scope(|| const_prop::run(&mut body, args.len()));
+        if !cx.options().keep_bounds_checks {
+            tracing::debug_span!("bounds_checks").in_scope(|| bounds_checks::run(&mut body));
+        }
+    }
+    info.id_to_local = outer_locals;
+    info.diverged = outer_diverged;
+    let mut function = Function {
+        body,
+        name: *name,
+        args: cx.intern_types(args.iter().map(|arg| arg.ty)),
+        ret: *ret,
+        inline: *inline,
+    };
+    for pass in cx.providers().mir_passes {
+        tracing::debug_span!("mir_pass").in_scope(|| pass(cx, *id, &mut function));
+    }
+    info.bodies.insert(*id, function);
+    cx.providers().observers.iter().for_each(|o| o.function_lowered(*id));
 }
 
 pub struct HirInfo {
-    pub id_to_local: FxHashMap<Id, Local>,
-    pub id_to_func: FxHashMap<Id, Func>,
+    pub id_to_local: FxIndexMap<Id, Local>,
+    pub id_to_func: FxIndexMap<Id, Func>,
+    /// The blocks of the body being lowered that control never reaches, like
+    /// the one after a `return`. Values computed in them are never used.
+    pub diverged: FxIndexSet<BasicBlock>,
+    /// The functions lowered so far, nested functions before the function
+    /// they are declared in.
+    pub bodies: FxIndexMap<Id, Function>,
 }
 
 impl HirInfo {
-    fn new(id_to_func: FxHashMap<Id, Func>) -> Self {
+    fn new(id_to_func: FxIndexMap<Id, Func>) -> Self {
         Self {
-            id_to_local: FxHashMap::default(),
+            id_to_local: FxIndexMap::default(),
             id_to_func,
+            diverged: FxIndexSet::default(),
+            bodies: FxIndexMap::default(),
         }
     }
 }
@@ -79,37 +155,58 @@ fn new_bb() -> BasicBlockData {
 fn rvalue_to_operand(rvalue: Rvalue, ty: TyKind, b: &mut Body) -> Operand {
     match rvalue {
         Rvalue::Use(operand) => operand,
-        Rvalue::BinaryOp(op, lhs, rhs) => {
-            let local = b.locals.push(LocalData { ty });
-            b.expect_last_mut()
-                .statements
-                .push(Statement::Assign(local, Rvalue::BinaryOp(op, lhs, rhs)));
-            Operand::Copy(local)
-        }
-        Rvalue::UnaryOp(op, operand) => {
-            let local = b.locals.push(LocalData { ty });
+        rvalue => {
+            let local = b.locals.push(LocalData::new(ty));
             b.expect_last_mut()
                 .statements
-                .push(Statement::Assign(local, Rvalue::UnaryOp(op, operand)));
-            Operand::Copy(local)
+                .push(Statement::Assign(local.into(), rvalue));
+            Operand::Copy(local.into())
         }
     }
 }
 
+/// The message of the asserts that check indices.
+const BOUNDS_CHECK_MSG: &str = "index out of bounds";
+
+/// Stops the program unless `0 <= index < len`.
+fn bounds_check(b: &mut Body, index: Local, len: u32) {
+    let msg = Symbol::new(BOUNDS_CHECK_MSG);
+    let index = Operand::Copy(index.into());
+    for (op, bound) in [(BinOpKind::GreaterEqual, 0), (BinOpKind::Less, len)] {
+        let cond = b.locals.push(LocalData::new(TyKind::Bool));
+        let bound = Operand::Const(Literal::Int(bound.into()));
+        b.expect_last_mut().statements.push(Statement::Assign(
+            cond.into(),
+            Rvalue::BinaryOp(op, index.clone(), bound),
+        ));
+        b.expect_last_mut().terminator = Terminator::Assert {
+            cond: Operand::Copy(cond.into()),
+            msg,
+            target: b.blocks.next_idx(),
+        };
+        b.blocks.push(new_bb());
+    }
+}
+
 fn expr_to_rvalue(cx: &dyn Context, expr: &hir::Expr, b: &mut Body, info: &mut HirInfo) -> Rvalue {
     match expr {
-        hir::Expr::Block(block) => {
-            collect_into(cx, &block.statements, b, info);
-            if let Some(e) = &block.expr {
-                expr_to_rvalue(cx, e, b, info)
-            } else {
-                Rvalue::Use(Operand::Const(Literal::Unit))
-            }
+        hir::Expr::Block(block) => block_to_rvalue(cx, block, b, info),
+        hir::Expr::Call { callee: Resolution::Builtin(sym::assert), args, .. } => {
+            let [(cond, ty)] = &args[..] else { unreachable!("checked by typeck") };
+            let cond = expr_to_rvalue(cx, cond, b, info);
+            let cond = rvalue_to_operand(cond, *ty, b);
+            b.expect_last_mut().terminator = Terminator::Assert {
+                cond,
+                msg: Symbol::new("assertion failed"),
+                target: b.blocks.next_idx(),
+            };
+            b.blocks.push(new_bb());
+            Rvalue::Use(Operand::Const(Literal::Unit))
         }
         hir::Expr::Call { callee, args, ret } => {
-            let last = b.blocks.last_idx();
-            let newbb = b.blocks.next_idx();
-            let ret = b.locals.push(LocalData { ty: *ret });
+            let ret = b.locals.push(LocalData::new(*ret));
+            // calls in the arguments end the current block, so only pick the
+            // block to end with this call once they are lowered.
             let (args, types): (_, Vec<_>) = args
                 .iter()
                 .map(|(e, ty)| (expr_to_rvalue(cx, e, b, info), *ty))
@@ -118,56 +215,150 @@ fn expr_to_rvalue(cx: &dyn Context, expr: &hir::Expr, b: &mut Body, info: &mut H
             let term = Terminator::Call {
                 callee: *callee,
                 args,
-                destination: (ret, newbb),
+                destination: (ret, b.blocks.next_idx()),
                 types: cx.intern_types(types),
             };
-            b.blocks[last].terminator = term;
+            b.expect_last_mut().terminator = term;
             b.blocks.push(new_bb());
-            Rvalue::Use(Operand::Copy(ret))
+            Rvalue::Use(Operand::Copy(ret.into()))
         }
-        hir::Expr::If { cond, then } => {
-            let newbb = b.blocks.next_idx();
-            let oldbb = newbb - 1;
-            // write the condition to the current block, performing computations in the statements if necessary.
+        hir::Expr::If { cond, then, else_ } => {
+            // write the condition to the current block, performing computations in the
+            // statements if necessary.
             let condition = expr_to_rvalue(cx, cond, b, info);
-            b.blocks.push(new_bb());
-            collect_into(cx, &then.statements, b, info);
-            if let Some(e) = &then.expr {
-                expr_to_rvalue(cx, e, b, info);
-            }
+            let switch = b.blocks.last_idx();
+            let then_bb = b.blocks.push(new_bb());
+            let then_value = block_to_rvalue(cx, then, b, info);
 
             // N.B. since collection might push new basic blocks we defer setting the `if`
             // terminator until we have figured out the basic blocks for the statements in the `if`.
-            b.blocks[oldbb].terminator = Terminator::SwitchInt(
+            let Some(else_) = else_ else {
+                b.blocks[switch].terminator = Terminator::SwitchInt(
+                    condition,
+                    Targets {
+                        values: vec![1],
+                        targets: vec![then_bb, b.blocks.next_idx()],
+                    },
+                );
+                b.expect_last_mut().terminator = Terminator::Goto(b.blocks.next_idx());
+                b.blocks.push(new_bb());
+                return Rvalue::Use(Operand::Const(Literal::Unit));
+            };
+
+            let then_end = b.blocks.last_idx();
+            let else_bb = b.blocks.push(new_bb());
+            let else_value = block_to_rvalue(cx, else_, b, info);
+            let else_end = b.blocks.last_idx();
+
+            // both branches write their value to the same local, except for
+            // those that diverge and have no value.
+            let values: Vec<_> = [(then_end, then_value), (else_end, else_value)]
+                .into_iter()
+                .filter(|(end, _)| !info.diverged.contains(end))
+                .collect();
+            let ty = values.first().map_or(TyKind::Unit, |(_, value)| rvalue_ty(value, b));
+            let dest = (!ty.is_zero_sized()).then(|| b.locals.push(LocalData::new(ty)));
+            let diverges = values.is_empty();
+            if let Some(dest) = dest {
+                for (end, value) in values {
+                    b.blocks[end]
+                        .statements
+                        .push(Statement::Assign(dest.into(), value));
+                }
+            }
+
+            let join = b.blocks.next_idx();
+            b.blocks[then_end].terminator = Terminator::Goto(join);
+            b.blocks[else_end].terminator = Terminator::Goto(join);
+            b.blocks.push(new_bb());
+            if diverges {
+                info.diverged.insert(join);
+            }
+            b.blocks[switch].terminator = Terminator::SwitchInt(
                 condition,
                 Targets {
                     values: vec![1],
-                    targets: vec![newbb, b.blocks.next_idx()],
+                    targets: vec![then_bb, else_bb],
                 },
             );
-            b.expect_last_mut().terminator = Terminator::Goto(b.blocks.next_idx());
+            Rvalue::Use(dest.map_or(Operand::Const(Literal::Unit), |dest| {
+                Operand::Copy(dest.into())
+            }))
+        }
+        hir::Expr::While { cond, body } => {
+            // the condition gets its own block so the body can jump back to it.
+            let header = b.blocks.next_idx();
+            b.expect_last_mut().terminator = Terminator::Goto(header);
             b.blocks.push(new_bb());
+            let condition = expr_to_rvalue(cx, cond, b, info);
+            // evaluating the condition might have ended the header in a call.
+            let switch = b.blocks.last_idx();
+            let body_bb = b.blocks.push(new_bb());
+            let scope = collect_into(cx, &body.statements, b, info);
+            if let Some(e) = &body.expr {
+                expr_to_rvalue(cx, e, b, info);
+            }
+            storage_dead(b, scope);
+            b.expect_last_mut().terminator = Terminator::Goto(header);
+            let exit = b.blocks.push(new_bb());
+            b.blocks[switch].terminator = Terminator::SwitchInt(
+                condition,
+                Targets {
+                    values: vec![1],
+                    targets: vec![body_bb, exit],
+                },
+            );
             Rvalue::Use(Operand::Const(Literal::Unit))
         }
-        hir::Expr::While { cond: _, body: _ } => todo!(),
         hir::Expr::Assign { to, rvalue } => {
             let local = match to {
                 Resolution::Builtin(_) => todo!(),
                 Resolution::Local(id) => info.id_to_local[id],
-                Resolution::Fn(_) => todo!(),
+                Resolution::Fn(_) | Resolution::Extern(..) => todo!(),
             };
             let op = expr_to_rvalue(cx, rvalue, b, info);
             b.expect_last_mut()
                 .statements
-                .push(Statement::Assign(local, op));
+                .push(Statement::Assign(local.into(), op));
             Rvalue::Use(Operand::Const(Literal::Unit))
         }
         hir::Expr::Literal(lit) => Rvalue::Use(Operand::Const(*lit)),
         hir::Expr::Group(e) => expr_to_rvalue(cx, e, b, info),
         hir::Expr::Resolved(Resolution::Builtin(_)) => todo!(),
-        hir::Expr::Resolved(Resolution::Fn(_id)) => todo!(),
+        hir::Expr::Resolved(Resolution::Fn(_) | Resolution::Extern(..)) => todo!(),
         hir::Expr::Resolved(Resolution::Local(id)) => {
-            Rvalue::Use(Operand::Copy(*info.id_to_local.get(id).unwrap()))
+            Rvalue::Use(Operand::Copy(info.id_to_local[id].into()))
+        }
+        // the right side is only evaluated when the left one does not decide
+        // the result.
+        hir::Expr::BinOp(kind @ (BinOpKind::And | BinOpKind::Or), left, right, _) => {
+            let dest = b.locals.push(LocalData::new(TyKind::Bool));
+            let left = expr_to_rvalue(cx, left, b, info);
+            b.expect_last_mut()
+                .statements
+                .push(Statement::Assign(dest.into(), left));
+            // evaluating the left side might have ended the block in a call.
+            let switch = b.blocks.last_idx();
+            let right_bb = b.blocks.push(new_bb());
+            let right = expr_to_rvalue(cx, right, b, info);
+            b.expect_last_mut()
+                .statements
+                .push(Statement::Assign(dest.into(), right));
+            let join = b.blocks.next_idx();
+            b.expect_last_mut().terminator = Terminator::Goto(join);
+            b.blocks.push(new_bb());
+            let targets = match kind {
+                BinOpKind::And => vec![right_bb, join],
+                _ => vec![join, right_bb],
+            };
+            b.blocks[switch].terminator = Terminator::SwitchInt(
+                Rvalue::Use(Operand::Copy(dest.into())),
+                Targets {
+                    values: vec![1],
+                    targets,
+                },
+            );
+            Rvalue::Use(Operand::Copy(dest.into()))
         }
         hir::Expr::BinOp(kind, e, e2, ety) => {
             let left = expr_to_rvalue(cx, e, b, info);
@@ -184,44 +375,378 @@ fn expr_to_rvalue(cx: &dyn Context, expr: &hir::Expr, b: &mut Body, info: &mut H
             let e = rvalue_to_operand(e, *ety, b);
             Rvalue::UnaryOp(*kind, e)
         }
+        hir::Expr::Cast(e, from, to) => {
+            let e = expr_to_rvalue(cx, e, b, info);
+            if from == to {
+                return e;
+            }
+            Rvalue::Cast(rvalue_to_operand(e, *from, b), *to)
+        }
+        hir::Expr::Array(elems, ty) => {
+            let elem_ty = ty.element().expect("array literals are arrays");
+            let elems = elems
+                .iter()
+                .map(|e| {
+                    let rv = expr_to_rvalue(cx, e, b, info);
+                    rvalue_to_operand(rv, elem_ty, b)
+                })
+                .collect();
+            Rvalue::Aggregate(*ty, elems)
+        }
+        hir::Expr::Tuple(elems, ty) => {
+            let TyKind::Tuple(tys) = ty else { unreachable!("tuple literals are tuples") };
+            let elems = elems
+                .iter()
+                .zip(*tys)
+                .map(|(e, &elem_ty)| {
+                    let rv = expr_to_rvalue(cx, e, b, info);
+                    rvalue_to_operand(rv, elem_ty, b)
+                })
+                .collect();
+            Rvalue::Aggregate(*ty, elems)
+        }
+        hir::Expr::Index(array, index, ty) => {
+            let array = expr_to_rvalue(cx, array, b, info);
+            let Operand::Copy(mut place) = rvalue_to_operand(array, *ty, b) else {
+                unreachable!("arrays are not constants")
+            };
+            // the index is copied so that the value that is checked is the one used.
+            let index_rv = expr_to_rvalue(cx, index, b, info);
+            let index = b.locals.push(LocalData::new(TyKind::I32));
+            b.expect_last_mut()
+                .statements
+                .push(Statement::Assign(index.into(), index_rv));
+            let TyKind::Array(_, len) = *ty else { unreachable!("indexed a non-array") };
+            bounds_check(b, index, len);
+            place.projection.push(ProjectionElem::Index(index));
+            Rvalue::Use(Operand::Copy(place))
+        }
+        hir::Expr::Struct(fields, ty) => {
+            let TyKind::Struct(def) = ty else { unreachable!("struct literals are structs") };
+            // fields are evaluated in the order they are written, but stored
+            // in the order they are declared.
+            let mut operands = vec![None; def.fields.len()];
+            for (index, e) in fields {
+                let rv = expr_to_rvalue(cx, e, b, info);
+                let field_ty = def.fields[*index as usize].1;
+                operands[*index as usize] = Some(rvalue_to_operand(rv, field_ty, b));
+            }
+            let operands = operands
+                .into_iter()
+                .map(|op| op.expect("typeck checks that every field is initialized"))
+                .collect();
+            Rvalue::Aggregate(*ty, operands)
+        }
+        hir::Expr::Field(base, index, ty) => {
+            let base = expr_to_rvalue(cx, base, b, info);
+            let Operand::Copy(mut place) = rvalue_to_operand(base, *ty, b) else {
+                unreachable!("structs and tuples are not constants")
+            };
+            place.projection.push(ProjectionElem::Field(*index));
+            Rvalue::Use(Operand::Copy(place))
+        }
+        hir::Expr::Variant(index, fields, ty) => {
+            let TyKind::Enum(def) = ty else { unreachable!("variants belong to enums") };
+            let fields = fields
+                .iter()
+                .zip(&def.variants[*index as usize].fields)
+                .map(|(e, field_ty)| {
+                    let rv = expr_to_rvalue(cx, e, b, info);
+                    rvalue_to_operand(rv, *field_ty, b)
+                })
+                .collect();
+            Rvalue::Variant(*ty, *index, fields)
+        }
+        hir::Expr::Match {
+            scrutinee,
+            scrutinee_ty,
+            arms,
+            ty,
+        } => lower_match(cx, scrutinee, *scrutinee_ty, arms, *ty, b, info),
         hir::Expr::Return(e, ty) => {
             let rv = expr_to_rvalue(cx, e, b, info);
-            let local = b.locals.push(LocalData { ty: *ty });
+            let ret_place = b.return_place;
+            if *ty != TyKind::Unit {
+                b.expect_last_mut()
+                    .statements
+                    .push(Statement::Assign(ret_place.into(), rv));
+            }
+            b.expect_last_mut().terminator = Terminator::Return(ret_place);
+            // whatever follows the `return` is unreachable.
+            let after = b.blocks.push(new_bb());
+            info.diverged.insert(after);
+            Rvalue::Use(Operand::Const(Literal::Unit))
+        }
+    }
+}
+
+/// Lowers a `match` to a `SwitchInt` on the discriminant of the scrutinee,
+/// or on its value for `bool` and `i32`, with a block for each arm.
+fn lower_match(
+    cx: &dyn Context,
+    scrutinee: &hir::Expr,
+    scrutinee_ty: TyKind,
+    arms: &[hir::Arm],
+    ty: TyKind,
+    b: &mut Body,
+    info: &mut HirInfo,
+) -> Rvalue {
+    // the arms read the fields of the scrutinee, so it is kept in a local.
+    let rv = expr_to_rvalue(cx, scrutinee, b, info);
+    let scrut = b.locals.push(LocalData::new(scrutinee_ty));
+    b.expect_last_mut()
+        .statements
+        .push(Statement::Assign(scrut.into(), rv));
+    let discr = match scrutinee_ty {
+        TyKind::Enum(_) => Rvalue::Discriminant(scrut.into()),
+        _ => Rvalue::Use(Operand::Copy(scrut.into())),
+    };
+    let switch = b.blocks.last_idx();
+    let dest = (!ty.is_zero_sized()).then(|| b.locals.push(LocalData::new(ty)));
+
+    let mut values = vec![];
+    let mut targets = vec![];
+    let mut otherwise = None;
+    let mut arm_ends = vec![];
+    for arm in arms {
+        let arm_bb = b.blocks.push(new_bb());
+        // unreachable arms are lowered too, but nothing jumps to them.
+        if otherwise.is_none() {
+            match arm.pat.switch_value() {
+                Some(value) if !values.contains(&value) => {
+                    values.push(value);
+                    targets.push(arm_bb);
+                }
+                Some(_) => {}
+                None => otherwise = Some(arm_bb),
+            }
+        }
+        let scope = bind_pat(&arm.pat, scrut, b, info);
+        let value = expr_to_rvalue(cx, &arm.body, b, info);
+        let diverged = info.diverged.contains(&b.blocks.last_idx());
+        if let Some(dest) = dest.filter(|_| !diverged) {
             b.expect_last_mut()
                 .statements
-                .push(Statement::Assign(local, rv));
-            b.expect_last_mut().terminator = Terminator::Return(local);
-            b.blocks.push(new_bb());
-            Rvalue::Use(Operand::Const(Literal::Unit))
+                .push(Statement::Assign(dest.into(), value));
         }
+        storage_dead(b, scope);
+        arm_ends.push(b.blocks.last_idx());
+    }
+    // typeck checked that the arms cover every value.
+    let otherwise = otherwise.unwrap_or_else(|| {
+        let bb = b.blocks.push(new_bb());
+        b.blocks[bb].terminator = Terminator::Unreachable;
+        bb
+    });
+    let join = b.blocks.push(new_bb());
+    if arm_ends.iter().all(|end| info.diverged.contains(end)) {
+        info.diverged.insert(join);
+    }
+    for end in arm_ends {
+        b.blocks[end].terminator = Terminator::Goto(join);
+    }
+    targets.push(otherwise);
+    b.blocks[switch].terminator = Terminator::SwitchInt(discr, Targets { values, targets });
+    Rvalue::Use(dest.map_or(Operand::Const(Literal::Unit), |dest| {
+        Operand::Copy(dest.into())
+    }))
+}
+
+/// Declares the locals bound by `pat`, copying them out of `scrut`.
+fn bind_pat(pat: &hir::Pat, scrut: Local, b: &mut Body, info: &mut HirInfo) -> Vec<Local> {
+    let mut bind = |decl: &hir::LocalDecl, place: Place| {
+        let local = b.locals.push(LocalData { ty: decl.ty, mutable: decl.mutable });
+        let statements = &mut b.expect_last_mut().statements;
+        statements.push(Statement::StorageLive(local));
+        statements.push(Statement::Assign(local.into(), Rvalue::Use(Operand::Copy(place))));
+        info.id_to_local.insert(decl.id, local);
+        local
+    };
+    match pat {
+        hir::Pat::Binding(decl) => vec![bind(decl, scrut.into())],
+        hir::Pat::Variant(variant, fields) => fields
+            .iter()
+            .zip(0..)
+            .filter_map(|(field, index)| match field {
+                hir::Pat::Binding(decl) => Some(bind(
+                    decl,
+                    Place {
+                        local: scrut,
+                        projection: vec![ProjectionElem::Variant(*variant, index)],
+                    },
+                )),
+                _ => None,
+            })
+            .collect(),
+        hir::Pat::Wild | hir::Pat::Literal(_) => vec![],
     }
 }
 
-fn collect_into(cx: &dyn Context, hir: &[hir::Stmt], b: &mut Body, info: &mut HirInfo) {
+/// Lowers a block, returning its value.
+fn block_to_rvalue(
+    cx: &dyn Context,
+    block: &hir::Block,
+    b: &mut Body,
+    info: &mut HirInfo,
+) -> Rvalue {
+    let scope = collect_into(cx, &block.statements, b, info);
+    let mut value = if let Some(e) = &block.expr {
+        expr_to_rvalue(cx, e, b, info)
+    } else {
+        Rvalue::Use(Operand::Const(Literal::Unit))
+    };
+    // the value has to be read before the locals it uses go away.
+    let ty = rvalue_ty(&value, b);
+    if ty != TyKind::Unit && reads_any(&value, &scope) {
+        let temp = b.locals.push(LocalData::new(ty));
+        b.expect_last_mut()
+            .statements
+            .push(Statement::Assign(temp.into(), value));
+        value = Rvalue::Use(Operand::Copy(temp.into()));
+    }
+    storage_dead(b, scope);
+    value
+}
+
+/// Lowers the statements of a block, returning the locals they declare.
+fn collect_into(
+    cx: &dyn Context,
+    hir: &[hir::Stmt],
+    b: &mut Body,
+    info: &mut HirInfo,
+) -> Vec<Local> {
+    let mut scope = vec![];
     for stmt in hir {
         match stmt {
             hir::Stmt::Local(hir::LocalDecl {
                 id,
+                mutable,
                 ty,
                 initializer,
+                ..
             }) => {
-                let local = b.locals.push(LocalData { ty: *ty });
+                let local = b.locals.push(LocalData { ty: *ty, mutable: *mutable });
+                scope.push(local);
+                b.expect_last_mut()
+                    .statements
+                    .push(Statement::StorageLive(local));
                 if let Some(init) = initializer {
                     let rv = expr_to_rvalue(cx, init, b, info);
                     b.expect_last_mut()
                         .statements
-                        .push(Statement::Assign(local, rv));
+                        .push(Statement::Assign(local.into(), rv));
                 }
                 info.id_to_local.insert(*id, local);
             }
             hir::Stmt::Expr(e) => {
                 let _ = expr_to_rvalue(cx, e, b, info);
             }
-            hir::Stmt::Item(_) => {}
+            hir::Stmt::Item(item) => lower_items(cx, std::slice::from_ref(item), info),
         }
     }
+    scope
+}
+
+/// Ends the storage of the locals declared in a scope that was just left.
+fn storage_dead(b: &mut Body, scope: Vec<Local>) {
+    let statements = &mut b.expect_last_mut().statements;
+    statements.extend(scope.into_iter().rev().map(Statement::StorageDead));
+}
+
+fn rvalue_ty(rvalue: &Rvalue, b: &Body) -> TyKind {
+    let operand_ty = |op: &Operand| match op {
+        Operand::Copy(place) => b.place_ty(place),
+        Operand::Const(lit) => lit.ty(),
+    };
+    match rvalue {
+        Rvalue::Use(op) | Rvalue::UnaryOp(_, op) => operand_ty(op),
+        Rvalue::Cast(_, ty) => *ty,
+        Rvalue::BinaryOp(kind, lhs, _) => {
+            if kind.is_comparison() {
+                TyKind::Bool
+            } else {
+                operand_ty(lhs)
+            }
+        }
+        Rvalue::Aggregate(ty, _) | Rvalue::Variant(ty, _, _) => *ty,
+        Rvalue::Discriminant(_) => TyKind::I32,
+    }
+}
+
+fn reads_any(rvalue: &Rvalue, locals: &[Local]) -> bool {
+    let reads = |op: &Operand| {
+        let Operand::Copy(place) = op else { return false };
+        locals.contains(&place.local)
+            || place.projection.iter().any(|elem| {
+                matches!(elem, ProjectionElem::Index(index) if locals.contains(index))
+            })
+    };
+    match rvalue {
+        Rvalue::Use(op) | Rvalue::UnaryOp(_, op) | Rvalue::Cast(op, _) => reads(op),
+        Rvalue::BinaryOp(_, lhs, rhs) => reads(lhs) || reads(rhs),
+        Rvalue::Aggregate(_, elems) | Rvalue::Variant(_, _, elems) => elems.iter().any(reads),
+        Rvalue::Discriminant(place) => reads(&Operand::Copy(place.clone())),
+    }
+}
+
+/// The calls between `functions`, read off their call terminators.
+fn build_call_graph(functions: &FxIndexMap<Id, Function>) -> CallGraph {
+    let nodes = functions
+        .iter()
+        .map(|(&id, f)| {
+            let callees = f
+                .body
+                .blocks
+                .iter()
+                .filter_map(|bb| match bb.terminator {
+                    Terminator::Call {
+                        callee: Resolution::Fn(callee),
+                        ..
+                    } => Some(callee),
+                    _ => None,
+                })
+                .collect();
+            (
+                id,
+                CallGraphNode {
+                    name: f.name,
+                    callees,
+                },
+            )
+        })
+        .collect();
+    CallGraph { nodes }
+}
+
+fn call_graph(cx: &dyn Context, id: FileId) -> Result<Rc<CallGraph>, ErrorReported> {
+    let graph = cx.mir(id)?.call_graph;
+
+    if let Some((&main, _)) = graph.nodes.iter().find(|(_, node)| node.name == sym::main) {
+        let mut reachable = graph.reachable_from(main);
+        // tests are called by the test runner.
+        for test in cx.tests(id).iter().flat_map(|tests| tests.iter()) {
+            reachable.extend(graph.reachable_from(test.id));
+        }
+        for (&id, node) in &graph.nodes {
+            if !reachable.contains(&id) {
+                make_diag! {
+                    Warning,
+                    cx.def_span(id).expect("functions are definitions"),
+                    W0301,
+                    node.name,
+                }
+                .emit();
+            }
+        }
+    }
+
+    Ok(graph)
 }
 
 pub fn provide(p: &mut Providers) {
-    *p = Providers { mir, ..*p }
+    *p = Providers {
+        mir,
+        call_graph,
+        ..*p
+    };
 }
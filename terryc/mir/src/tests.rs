@@ -0,0 +1,396 @@
+use terryc_base::ast::{BinOpKind, TyKind};
+use terryc_base::hir::{Literal, Resolution};
+use terryc_base::mir::{
+    BasicBlock, Function, MirTree, Operand, ProjectionElem, Rvalue, Statement, Terminator,
+};
+use terryc_base::sym::Symbol;
+use terryc_base::{Context, GlobalCtxt, Options, Providers};
+
+fn init(optimize: bool) {
+    init_with(Options {
+        optimize,
+        ..Options::default()
+    });
+}
+
+fn init_with(options: Options) {
+    GlobalCtxt::create_and_then(options, |mut gcx| {
+        let mut providers = Providers::default();
+        terryc_lex::provide(&mut providers);
+        terryc_ast::provide(&mut providers);
+        terryc_hir::provide(&mut providers);
+        crate::provide(&mut providers);
+        gcx.set_providers(terryc_base::leak(providers));
+        gcx.capture_diagnostics();
+        gcx
+    });
+}
+
+/// The MIR of `src`, which must not have errors.
+fn mir(src: &str) -> MirTree {
+    let file = GlobalCtxt::with_mut(|gcx| gcx.add_virtual_file("test.terry", src.into()));
+    GlobalCtxt::with(|gcx| match gcx.mir(file) {
+        Ok(mir) => mir,
+        Err(_) => panic!("{:?}", gcx.take_diagnostics()),
+    })
+}
+
+/// The function called `name` in `mir`.
+fn function<'a>(mir: &'a MirTree, name: &str) -> &'a Function {
+    mir.functions
+        .values()
+        .find(|f| f.name.get_str() == name)
+        .unwrap()
+}
+
+/// The number of bounds checks left in `f`.
+fn bounds_checks(f: &Function) -> usize {
+    f.body
+        .blocks
+        .iter()
+        .filter(|bb| {
+            matches!(&bb.terminator, Terminator::Assert { msg, .. }
+                if msg.get_str() == "index out of bounds")
+        })
+        .count()
+}
+
+#[test]
+fn arrays_are_indexed_with_bounds_checks() {
+    init(false);
+    let mir = mir("fn second(a: [i32; 3]) -> i32 { a[1] } \
+                   fn main() -> unit { let a: [i32; 3] = [1, 2, 3]; println(second(a)); }");
+    let second = function(&mir, "second");
+    assert_eq!(second.args[0], TyKind::array(TyKind::I32, 3));
+    let asserts: Vec<_> = second
+        .body
+        .blocks
+        .iter()
+        .filter_map(|bb| match &bb.terminator {
+            Terminator::Assert { msg, .. } => Some(msg.get_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(asserts, ["index out of bounds"; 2]);
+    let reads_element = second
+        .body
+        .blocks
+        .iter()
+        .flat_map(|bb| &bb.statements)
+        .any(|stmt| {
+            matches!(
+                stmt,
+                Statement::Assign(_, Rvalue::Use(Operand::Copy(place)))
+                    if matches!(place.projection[..], [ProjectionElem::Index(_)])
+            )
+        });
+    assert!(reads_element);
+    let entry = &function(&mir, "main").body.blocks[BasicBlock::from_usize(0)];
+    assert!(entry.statements.iter().any(|stmt| {
+        matches!(stmt, Statement::Assign(_, Rvalue::Aggregate(_, elems)) if elems.len() == 3)
+    }));
+}
+
+#[test]
+fn structs_are_built_and_read_through_fields() {
+    init(false);
+    let src = "struct Point { x: i32, y: i32 } \
+               fn sum(p: Point) -> i32 { p.x + p.y } \
+               fn main() -> unit { let p = Point { y: 2, x: 1 }; if p.x < 2 { println(sum(p)); } }";
+    let mir = mir(src);
+    let sum = function(&mir, "sum");
+    let TyKind::Struct(point) = sum.args[0] else {
+        panic!("{:?}", sum.args)
+    };
+    assert_eq!(point.field(Symbol::new("y")), Some((1, TyKind::I32)));
+    let fields: Vec<_> = sum
+        .body
+        .blocks
+        .iter()
+        .flat_map(|bb| &bb.statements)
+        .filter_map(|stmt| match stmt {
+            Statement::Assign(_, Rvalue::BinaryOp(_, Operand::Copy(lhs), Operand::Copy(rhs))) => {
+                Some([lhs.projection.clone(), rhs.projection.clone()])
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        fields,
+        [[[ProjectionElem::Field(0)], [ProjectionElem::Field(1)]]]
+    );
+    let entry = &function(&mir, "main").body.blocks[BasicBlock::from_usize(0)];
+    assert!(entry.statements.iter().any(|stmt| {
+        matches!(stmt, Statement::Assign(_, Rvalue::Aggregate(TyKind::Struct(_), fields))
+            if fields == &[Operand::Const(Literal::Int(1)), Operand::Const(Literal::Int(2))])
+    }));
+}
+
+#[test]
+fn match_switches_on_the_discriminant() {
+    init(false);
+    let src = "enum Shape { Circle(i32), Rect(i32, i32), Empty } \
+               fn area(s: Shape) -> i32 { \
+                   match s { Shape::Circle(r) => 3 * r * r, Shape::Rect(w, h) => w * h, _ => 0 } \
+               } \
+               fn main() -> unit { println(area(Shape::Rect(2, 3))); }";
+    let mir = mir(src);
+    let area = function(&mir, "area");
+    let switch = area.body.blocks.iter().find_map(|bb| match &bb.terminator {
+        Terminator::SwitchInt(Rvalue::Discriminant(_), targets) => Some(targets),
+        _ => None,
+    });
+    assert_eq!(switch.map(|targets| &*targets.values), Some(&[0, 1][..]));
+    let reads_field = |stmt: &Statement| {
+        matches!(stmt, Statement::Assign(_, Rvalue::Use(Operand::Copy(place)))
+            if place.projection == [ProjectionElem::Variant(1, 1)])
+    };
+    assert!(area
+        .body
+        .blocks
+        .iter()
+        .flat_map(|bb| &bb.statements)
+        .any(reads_field));
+}
+
+#[test]
+fn every_return_writes_the_return_place() {
+    init(false);
+    let src = "fn sign(x: i32) -> i32 { if x < 0 { return -1; } if x == 0 { return 0 } 1 } \
+               fn log(x: i32) -> unit { if x < 0 { return; } println(x); } \
+               fn main() -> unit { log(sign(2)); }";
+    let mir = mir(src);
+    let sign = &function(&mir, "sign").body;
+    let returns: Vec<_> = sign
+        .blocks
+        .iter()
+        .filter_map(|bb| match bb.terminator {
+            Terminator::Return(local) => Some(local),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(returns, [sign.return_place; 3]);
+    assert_eq!(sign.return_place.index(), 1);
+}
+
+#[test]
+fn constant_strings_are_concatenated() {
+    init(true);
+    let mir = mir(r#"fn main() -> unit { let name = "terry"; println("hi " + name + "!"); }"#);
+    let body = &function(&mir, "main").body;
+    let Terminator::Call { args, .. } = &body.blocks[BasicBlock::from_usize(0)].terminator else {
+        panic!("{body:?}")
+    };
+    let hi = Literal::String(Symbol::new("hi terry!"));
+    assert_eq!(args, &[Rvalue::Use(Operand::Const(hi))]);
+}
+
+#[test]
+fn logical_operators_short_circuit() {
+    init(false);
+    let mir = mir("fn check(x: i32) -> bool { println(x); x > 0 } \
+                   fn main() -> unit { let a = check(1) && check(2); let b = a || check(3); }");
+    let blocks = &function(&mir, "main").body.blocks;
+    // every call after the first is only reached through a switch.
+    let call_blocks: Vec<_> = blocks
+        .iter_enumerated()
+        .filter(|(_, bb)| matches!(bb.terminator, Terminator::Call { .. }))
+        .map(|(i, _)| i)
+        .collect();
+    assert_eq!(call_blocks.len(), 3);
+    for &callee in &call_blocks[1..] {
+        let switches = blocks.iter().filter(|bb| match &bb.terminator {
+            Terminator::SwitchInt(_, targets) => targets.targets.contains(&callee),
+            _ => false,
+        });
+        assert_eq!(switches.count(), 1);
+    }
+    let logical = |s: &Statement| {
+        matches!(
+            s,
+            Statement::Assign(_, Rvalue::BinaryOp(BinOpKind::And | BinOpKind::Or, ..))
+        )
+    };
+    assert!(!blocks.iter().flat_map(|bb| &bb.statements).any(logical));
+}
+
+#[test]
+fn bitwise_operators_fold() {
+    init(true);
+    let mir = mir("fn main() -> unit { println(((6 & 3) | (1 << 4)) ^ ((-8 >> 1) + (1 << 33))); }");
+    let body = &function(&mir, "main").body;
+    let Terminator::Call { args, .. } = &body.blocks[BasicBlock::from_usize(0)].terminator else {
+        panic!("{body:?}")
+    };
+    assert_eq!(
+        args,
+        &[Rvalue::Use(Operand::Const(Literal::Int(
+            -20i32 as u32 as u128
+        )))]
+    );
+}
+
+#[test]
+fn closures_take_their_captures_as_arguments() {
+    init(false);
+    let mir = mir("fn main() {\n\
+                   let n = 40;\n\
+                   let add = |x: i32| x + n;\n\
+                   let twice = |x: i32| -> i32 { return add(add(x)); };\n\
+                   println(twice(1));\n\
+                   }");
+    let names: Vec<_> = mir.functions.values().map(|f| f.name.to_string()).collect();
+    assert_eq!(names, ["add", "twice", "main"]);
+    // `twice` passes `n` on to `add`.
+    for f in mir.functions.values().take(2) {
+        assert_eq!(f.args.len(), 2, "{}", f.name);
+    }
+    let (&twice, _) = mir.functions.get_index(1).unwrap();
+    let calls: Vec<_> = mir.functions[2]
+        .body
+        .blocks
+        .iter()
+        .filter_map(|bb| match &bb.terminator {
+            Terminator::Call { callee, args, .. } => Some((*callee, args.len())),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(calls[0], (Resolution::Fn(twice), 2));
+    // the body of `twice` returns, so nothing is assigned after it.
+    let unit = Rvalue::Use(Operand::Const(Literal::Unit));
+    let assigns_unit = |stmt: &Statement| matches!(stmt, Statement::Assign(_, rv) if *rv == unit);
+    let blocks = &mir.functions[1].body.blocks;
+    assert!(!blocks
+        .iter()
+        .flat_map(|bb| &bb.statements)
+        .any(assigns_unit));
+}
+
+#[test]
+fn tuples_are_built_and_read_through_fields() {
+    init(false);
+    let src = "fn swap(t: (i32, (bool, f32))) -> ((bool, f32), i32) { (t.1, t.0) } \
+               fn main() -> unit { let t = swap((1, (true, 2.5))); println(t); println(t.0.1); }";
+    let mir = mir(src);
+    let swap = function(&mir, "swap");
+    assert_eq!(swap.args[0].to_string(), "(i32, (bool, f32))");
+    assert_eq!(
+        swap.ret.field(Symbol::new("0")),
+        Some((0, swap.args[0].field_ty(1)))
+    );
+    let statements: Vec<_> = swap
+        .body
+        .blocks
+        .iter()
+        .flat_map(|bb| &bb.statements)
+        .collect();
+    assert!(statements.iter().any(|stmt| {
+        matches!(stmt, Statement::Assign(_, Rvalue::Aggregate(TyKind::Tuple(_), elems))
+            if elems.len() == 2)
+    }));
+    let printed: Vec<_> = function(&mir, "main")
+        .body
+        .blocks
+        .iter()
+        .filter_map(|bb| match &bb.terminator {
+            Terminator::Call { args, .. } => match &args[..] {
+                [Rvalue::Use(Operand::Copy(place))] => Some(&place.projection[..]),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        printed,
+        [
+            &[][..],
+            &[ProjectionElem::Field(0), ProjectionElem::Field(1)]
+        ]
+    );
+}
+
+#[test]
+fn casts_convert_numbers_and_fold() {
+    init(true);
+    let mir = mir("fn half(x: i32) -> f32 { x as f32 / 2.0 } \
+                   fn main() -> unit { println((half(3), 2.9 as i32, true as i32, 7 as i32)); }");
+    let half = function(&mir, "half");
+    let statements: Vec<_> = half
+        .body
+        .blocks
+        .iter()
+        .flat_map(|bb| &bb.statements)
+        .collect();
+    assert!(statements
+        .iter()
+        .any(|stmt| matches!(stmt, Statement::Assign(_, Rvalue::Cast(_, TyKind::F32)))));
+    let elems = function(&mir, "main")
+        .body
+        .blocks
+        .iter()
+        .find_map(|bb| match &bb.terminator {
+            Terminator::Call { args, .. } => match &args[..] {
+                [Rvalue::Aggregate(_, elems)] => Some(elems),
+                _ => None,
+            },
+            _ => None,
+        });
+    let constants = [Literal::Int(2), Literal::Int(1), Literal::Int(7)];
+    assert_eq!(elems.unwrap()[1..], constants.map(Operand::Const));
+}
+
+#[test]
+fn asserts_that_always_fail_end_the_function() {
+    init(true);
+    for src in [
+        "fn main() -> unit { assert(false); println(1); }",
+        "fn main() -> unit { let a: [i32; 2] = [1, 2]; println(a[5]); }",
+    ] {
+        let mir = mir(src);
+        let main = function(&mir, "main");
+        let terminators: Vec<_> = main.body.blocks.iter().map(|bb| &bb.terminator).collect();
+        assert!(
+            matches!(
+                terminators[..],
+                [.., Terminator::Assert { target, .. }, Terminator::Unreachable]
+                    if target.index() == terminators.len() - 1
+            ),
+            "{src}: {terminators:?}"
+        );
+        assert!(
+            !terminators
+                .iter()
+                .any(|t| matches!(t, Terminator::Call { .. })),
+            "{src}: {terminators:?}"
+        );
+    }
+}
+
+const BOUNDS_CHECKED: &str = "fn sum(a: [i32; 4]) -> i32 { \
+        let mut total = 0; let mut i = 0; \
+        while i < 4 { total = total + a[i]; i = i + 1; } total } \
+    fn at(a: [i32; 4], n: i32) -> i32 { a[n] } \
+    fn twice(a: [i32; 4], n: i32) -> i32 { a[n] + a[n] } \
+    fn main() -> unit { let a = [1, 2, 3, 4]; \
+        println(sum(a)); println(at(a, 1)); println(twice(a, 2)); }";
+
+#[test]
+fn bounds_checks_proven_to_pass_are_removed() {
+    init(true);
+    let mir = mir(BOUNDS_CHECKED);
+    assert_eq!(bounds_checks(function(&mir, "sum")), 0);
+    assert_eq!(bounds_checks(function(&mir, "at")), 2);
+    assert_eq!(bounds_checks(function(&mir, "twice")), 2);
+}
+
+#[test]
+fn bounds_checks_can_be_kept() {
+    init_with(Options {
+        optimize: true,
+        keep_bounds_checks: true,
+        ..Options::default()
+    });
+    let mir = mir(BOUNDS_CHECKED);
+    assert_eq!(bounds_checks(function(&mir, "sum")), 2);
+    assert_eq!(bounds_checks(function(&mir, "twice")), 4);
+}
@@ -1,80 +1,464 @@
-#![feature(decl_macro)]
-
-use std::io;
-use std::path::PathBuf;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use clap::ValueEnum;
-use terryc_base::{Context, Providers};
+use terryc_base::errors::{Code, ErrorReported};
+use terryc_base::hir::{TestFn, TestKind};
+use terryc_base::manifest::EXECUTABLE_PATH;
+use terryc_base::render::{ColorChoice, RenderConfig, Style};
+use terryc_base::{Context, FileId, Unpretty};
+use terryc_driver::minimize::{self, Failure};
+use terryc_driver::plugins;
+use terryc_driver::progress::Progress;
+use terryc_driver::Compiler;
+use tracing_flame::{FlameLayer, FlushGuard};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::EnvFilter;
 
 /// The terry compiler
 #[derive(clap::Parser, Debug)]
-#[clap(author, version, about, long_about = None)]
+#[clap(
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
-    file: PathBuf,
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    #[clap(required = true)]
+    file: Option<PathBuf>,
 
     #[clap(long)]
     use_ascii: bool,
 
+    /// When to color diagnostics.
+    #[clap(long, value_enum, default_value_t = Color::Auto)]
+    color: Color,
+
     #[clap(long)]
     dont_print_path: bool,
 
-    #[clap(short, value_enum, default_value_t = Mode::Gen)]
+    /// Do not show how far the build is on stderr.
+    #[clap(short, long)]
+    quiet: bool,
+
+    #[clap(short, long = "emit", value_enum, default_value_t = Mode::Gen)]
     mode: Mode,
+
+    /// Unstable options, like `-Z unpretty=mir`.
+    #[clap(short = 'Z', value_parser = parse_unstable, conflicts_with = "mode")]
+    unstable: Option<Mode>,
+
+    #[clap(short = 'O')]
+    optimize: bool,
+
+    /// Keep the bounds checks that `-O` proves to always pass.
+    #[clap(long)]
+    keep_bounds_checks: bool,
+
+    #[clap(long)]
+    coverage: bool,
+
+    /// Language of diagnostics, like `de`. Untranslated messages are in English.
+    #[clap(long)]
+    locale: Option<String>,
+
+    /// Explain common errors at length, with examples.
+    #[clap(long)]
+    teaching_diagnostics: bool,
+
+    /// Build a library, which does not need a `main` function.
+    #[clap(long)]
+    lib: bool,
+
+    /// Enable `#[cfg(name)]` items, or `#[cfg(name = "value")]` ones with
+    /// `name=value`.
+    #[clap(long = "cfg", value_name = "NAME")]
+    cfg: Vec<String>,
+
+    /// Use a library built with `--lib`, as `name=path/to/out.tmeta`. Its
+    /// functions are called as `name::function(...)`.
+    #[clap(long = "extern", value_name = "NAME=PATH", value_parser = parse_extern)]
+    externs: Vec<(String, PathBuf)>,
 }
 
-macro modes($($name:ident),*$(,)?) {
-    impl From<terryc_base::Mode> for Mode {
-        fn from(m: terryc_base::Mode) -> Self {
-            match m {
-                $(terryc_base::Mode::$name => Self::$name,)*
-            }
+impl Args {
+    /// The options for building `path`, from the flags on the command line.
+    /// Subcommands do not take these flags, so they start from the defaults.
+    fn options(&self, path: PathBuf, cfg: Vec<String>) -> terryc_base::Options {
+        terryc_base::Options {
+            path,
+            use_ascii: self.use_ascii,
+            color: self.color.into(),
+            dont_print_path: self.dont_print_path,
+            mode: self.unstable.unwrap_or(self.mode).into(),
+            optimize: self.optimize,
+            keep_bounds_checks: self.keep_bounds_checks,
+            coverage: self.coverage,
+            locale: self.locale.clone(),
+            teaching_diagnostics: self.teaching_diagnostics,
+            lib: self.lib,
+            test: false,
+            cfg,
+            externs: self.externs.clone(),
         }
     }
+}
 
-    impl From<Mode> for terryc_base::Mode {
-        fn from(m: Mode) -> Self {
-            match m {
-                $(Mode::$name => Self::$name,)*
-            }
-        }
-    }
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Shrinks a file that crashes the compiler into a small reproducer for
+    /// bug reports, written to `<file>.min.terry`.
+    Minimize {
+        file: PathBuf,
+
+        /// Keep a diagnostic, like `E0201`, instead of a crash.
+        #[clap(long)]
+        diagnostic: Option<Code>,
+
+        /// Arguments for `terryc` when checking for a crash, like `-- -O`.
+        #[clap(last = true)]
+        args: Vec<OsString>,
+    },
+    /// Reports the errors and warnings in a file without generating code.
+    Check {
+        file: PathBuf,
+
+        /// Turn on a lint that is compiled in but off by default, like
+        /// `while_true`.
+        #[clap(long = "lint", value_name = "NAME", value_parser = parse_lint)]
+        lints: Vec<String>,
+
+        /// Enable `#[cfg(name)]` items, like for a regular build.
+        #[clap(long = "cfg", value_name = "NAME")]
+        cfg: Vec<String>,
+    },
+    /// Compiles the `#[test]` functions of a file and runs each of them,
+    /// reporting which ones pass.
+    Test {
+        file: PathBuf,
+
+        /// Enable `#[cfg(name)]` items, like for a regular build.
+        #[clap(long = "cfg", value_name = "NAME")]
+        cfg: Vec<String>,
+    },
+    /// Compiles the `#[bench]` functions of a file with optimizations and
+    /// reports how long each takes per run.
+    Bench {
+        file: PathBuf,
+
+        /// Enable `#[cfg(name)]` items, like for a regular build.
+        #[clap(long = "cfg", value_name = "NAME")]
+        cfg: Vec<String>,
+    },
 }
 
+/// What to do with the file. The names of intermediate representations can
+/// also be passed to `-Z unpretty`.
 #[derive(ValueEnum, Clone, Copy, Debug)]
 pub enum Mode {
-    PrintAst,
-    PrintMir,
+    Tokens,
+    #[value(alias = "print-ast")]
+    Ast,
+    Hir,
+    #[value(alias = "print-typed-hir")]
+    HirTyped,
+    #[value(alias = "print-mir")]
+    Mir,
+    MirCfg,
+    CallgraphDot,
     Gen,
 }
 
-modes! {
-    PrintAst,
-    PrintMir,
-    Gen,
+impl From<Mode> for terryc_base::Mode {
+    fn from(m: Mode) -> Self {
+        let unpretty = match m {
+            Mode::Tokens => Unpretty::Tokens,
+            Mode::Ast => Unpretty::Ast,
+            Mode::Hir => Unpretty::Hir,
+            Mode::HirTyped => Unpretty::HirTyped,
+            Mode::Mir => Unpretty::Mir,
+            Mode::MirCfg => Unpretty::MirCfg,
+            Mode::CallgraphDot => return Self::CallgraphDot,
+            Mode::Gen => return Self::Gen,
+        };
+        Self::Unpretty(unpretty)
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Color {
+    /// Color output to a terminal, unless `NO_COLOR` is set or with `--use-ascii`.
+    Auto,
+    Always,
+    Never,
+}
+
+impl From<Color> for ColorChoice {
+    fn from(c: Color) -> Self {
+        match c {
+            Color::Auto => ColorChoice::Auto,
+            Color::Always => ColorChoice::Always,
+            Color::Never => ColorChoice::Never,
+        }
+    }
+}
+
+/// Parses a `-Z` option, of which `unpretty=<ir>` is the only one so far.
+fn parse_unstable(option: &str) -> Result<Mode, String> {
+    let Some(value) = option.strip_prefix("unpretty=") else {
+        return Err(format!("unknown option `{option}`"));
+    };
+    match Mode::from_str(value, false)? {
+        Mode::CallgraphDot | Mode::Gen => {
+            Err(format!("`{value}` is not an intermediate representation"))
+        }
+        mode => Ok(mode),
+    }
+}
+
+fn parse_extern(option: &str) -> Result<(String, PathBuf), String> {
+    match option.split_once('=') {
+        Some((name, path)) if !name.is_empty() => Ok((name.into(), path.into())),
+        _ => Err(format!("expected `name=path`, found `{option}`")),
+    }
+}
+
+fn parse_lint(name: &str) -> Result<String, String> {
+    if plugins::plugin(name).is_some() {
+        return Ok(name.into());
+    }
+    let known: Vec<_> = plugins::PLUGINS.iter().map(|lint| format!("`{}`", lint.name())).collect();
+    Err(format!("unknown lint `{name}`, expected one of {}", known.join(", ")))
+}
+
+/// Logs what the compiler does to stderr as selected by `TERRYC_LOG`, like
+/// `TERRYC_LOG=debug` or `TERRYC_LOG=terryc_mir=debug`. Closing a span logs
+/// how long it was open, which for queries is how long they took.
+///
+/// With `TERRYC_FLAME=<path>`, the time spent in each span is also written to
+/// `path` as folded stacks, which `inferno-flamegraph` turns into a flamegraph.
+fn init_logging() -> Option<FlushGuard<BufWriter<File>>> {
+    let fmt = tracing_subscriber::fmt::layer()
+        .with_writer(io::stderr)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_filter(EnvFilter::from_env("TERRYC_LOG"));
+    let (flame, guard) = match std::env::var_os("TERRYC_FLAME") {
+        Some(path) => {
+            let (layer, guard) =
+                FlameLayer::with_file(path).expect("failed to create the `TERRYC_FLAME` file");
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+    tracing_subscriber::registry().with(fmt).with(flame).init();
+    guard
+}
+
+/// Writes the smallest version of the file that still fails to
+/// `<file>.min.terry`.
+fn minimize(
+    options: terryc_base::Options,
+    diagnostic: Option<Code>,
+    args: Vec<OsString>,
+) -> io::Result<()> {
+    let file = options.path.clone();
+    let source = fs::read_to_string(&file)?;
+    let out = file.with_extension("min.terry");
+    let failure = match diagnostic {
+        Some(code) => Failure::Diagnostic(code),
+        None => {
+            let mut command = vec![std::env::current_exe()?.into_os_string()];
+            command.extend(args);
+            Failure::Crash { command }
+        }
+    };
+    let compiler = Compiler::new(options);
+    let Some(reduced) = minimize::minimize(&compiler, &source, &out, &failure) else {
+        eprintln!("{} does not reproduce the failure", file.display());
+        std::process::exit(1);
+    };
+    fs::write(&out, &reduced)?;
+    eprintln!("wrote {} bytes to {}", reduced.len(), out.display());
+    Ok(())
+}
+
+/// Builds the MIR of the file with the `lints` turned on, exiting with an
+/// error if there are errors.
+fn check(options: terryc_base::Options, lints: Vec<String>) -> io::Result<()> {
+    let mut providers = terryc_driver::providers();
+    for name in &lints {
+        providers.add_lint(plugins::plugin(name).expect("checked by `parse_lint`"));
+    }
+    terryc_base::GlobalCtxt::create_and_then(options, |mut gcx| {
+        gcx.set_providers(terryc_base::leak(providers));
+        gcx
+    });
+    terryc_base::GlobalCtxt::with(|cx| {
+        // the call graph needs the MIR, and reports functions that are never
+        // called.
+        let _ = cx.call_graph(FileId::Main);
+        if cx.has_errors() {
+            std::process::exit(1);
+        }
+    });
+    Ok(())
+}
+
+/// Compiles the `#[test]` and `#[bench]` functions of the file into a runner
+/// at [`EXECUTABLE_PATH`], exiting if that fails.
+fn build_runner(options: terryc_base::Options) -> Rc<[TestFn]> {
+    let options = terryc_base::Options {
+        test: true,
+        ..options
+    };
+    terryc_base::GlobalCtxt::create_and_then(options, |mut gcx| {
+        gcx.set_providers(terryc_base::leak(terryc_driver::providers()));
+        gcx
+    });
+    let tests = terryc_base::GlobalCtxt::with(|cx| {
+        // reports functions that neither `main` nor a test calls
+        let _ = cx.call_graph(FileId::Main);
+        cx.codegen(FileId::Main)?;
+        cx.tests(FileId::Main)
+    });
+    tests.unwrap_or_else(|ErrorReported| std::process::exit(1))
+}
+
+/// Runs the test with `index` in the runner `iterations` times.
+fn run_test(index: usize, iterations: u32) -> io::Result<process::Output> {
+    process::Command::new(Path::new(".").join(EXECUTABLE_PATH))
+        .args([index.to_string(), iterations.to_string()])
+        .output()
+}
+
+/// Builds a test runner and runs every test in a process of its own, since a
+/// failed `assert` stops the whole program.
+fn test(options: terryc_base::Options) -> io::Result<()> {
+    let tests = build_runner(options);
+    let tests: Vec<_> =
+        tests.iter().enumerate().filter(|(_, t)| t.kind == TestKind::Test).collect();
+
+    let render = RenderConfig::stdout();
+    let ok = render.paint("ok", Style::Success);
+    let failed = render.paint("FAILED", Style::Error);
+
+    println!("running {} tests", tests.len());
+    let mut failures = vec![];
+    for &(i, test) in &tests {
+        let output = run_test(i, 1)?;
+        if output.status.success() {
+            println!("test {} ... {ok}", test.name);
+        } else {
+            println!("test {} ... {failed}", test.name);
+            failures.push((&test.name, output));
+        }
+    }
+    for (name, output) in &failures {
+        println!("\n---- {name} ({}) ----", output.status);
+        print!("{}", String::from_utf8_lossy(&output.stdout));
+    }
+    let result = if failures.is_empty() { ok } else { failed };
+    let passed = tests.len() - failures.len();
+    println!("\ntest result: {result}. {passed} passed; {} failed", failures.len());
+    if !failures.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// How long a benchmark runs at least before its time per iteration is
+/// reported.
+const BENCH_TIME: Duration = Duration::from_millis(200);
+
+/// Builds an optimized runner and times every benchmark. The time of a single
+/// iteration is subtracted from longer runs, so that starting the process
+/// is not counted.
+fn bench(options: terryc_base::Options) -> io::Result<()> {
+    let tests = build_runner(terryc_base::Options {
+        optimize: true,
+        ..options
+    });
+    let benches: Vec<_> =
+        tests.iter().enumerate().filter(|(_, t)| t.kind == TestKind::Bench).collect();
+
+    let render = RenderConfig::stdout();
+    let failed_text = render.paint("FAILED", Style::Error);
+
+    println!("running {} benchmarks", benches.len());
+    let mut failed = 0;
+    for &(i, bench) in &benches {
+        let time = |iterations| -> io::Result<Option<Duration>> {
+            let start = Instant::now();
+            let output = run_test(i, iterations)?;
+            Ok(output.status.success().then(|| start.elapsed()))
+        };
+        let Some(base) = time(1)? else {
+            println!("bench {} ... {failed_text}", bench.name);
+            failed += 1;
+            continue;
+        };
+        let mut iterations = 10;
+        let per_iteration = loop {
+            let Some(elapsed) = time(iterations)? else { break None };
+            let elapsed = elapsed.saturating_sub(base);
+            if elapsed >= BENCH_TIME || iterations >= 1_000_000 {
+                break Some(elapsed / (iterations - 1));
+            }
+            iterations *= 10;
+        };
+        match per_iteration {
+            Some(time) => {
+                println!("bench {} ... {} ns/iter ({iterations} runs)", bench.name, time.as_nanos())
+            }
+            None => {
+                println!("bench {} ... {failed_text}", bench.name);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        println!("\n{failed} benchmarks failed");
+        std::process::exit(1);
+    }
+    Ok(())
 }
 
 fn main() -> io::Result<()> {
-    let m: Args = clap::Parser::parse();
+    let mut m: Args = clap::Parser::parse();
+    // flushes the flamegraph data when dropped.
+    let _flame = init_logging();
 
-    let mut providers = Providers::default();
-    terryc_lex::provide(&mut providers);
-    terryc_ast::provide(&mut providers);
-    terryc_mir::provide(&mut providers);
-    terryc_hir::provide(&mut providers);
-    terryc_codegen::provide(&mut providers);
+    match m.command.take() {
+        Some(Command::Minimize { file, diagnostic, args }) => {
+            return minimize(m.options(file, vec![]), diagnostic, args)
+        }
+        Some(Command::Check { file, lints, cfg }) => return check(m.options(file, cfg), lints),
+        Some(Command::Test { file, cfg }) => return test(m.options(file, cfg)),
+        Some(Command::Bench { file, cfg }) => return bench(m.options(file, cfg)),
+        None => {}
+    }
 
-    terryc_base::GlobalCtxt::create_and_then(
-        terryc_base::Options {
-            path: m.file,
-            use_ascii: m.use_ascii,
-            dont_print_path: m.dont_print_path,
-            mode: m.mode.into(),
-        },
-        |mut gcx| {
-            gcx.set_providers(terryc_base::leak(providers));
-            gcx
-        },
-    );
+    let file = m.file.take().expect("required without a subcommand");
+    let cfg = std::mem::take(&mut m.cfg);
+    terryc_base::GlobalCtxt::create_and_then(m.options(file, cfg), |mut gcx| {
+        let mut providers = terryc_driver::providers();
+        if !m.quiet && io::stderr().is_terminal() {
+            providers.add_observer(Progress::default());
+        }
+        gcx.set_providers(terryc_base::leak(providers));
+        gcx
+    });
 
     terryc_base::run();
 
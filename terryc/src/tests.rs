@@ -1 +1,1330 @@
+use std::cell::RefCell;
+use std::path::Path;
 
+use terryc_ast::Parser;
+use terryc_base::ast::{TotalF64, TyKind};
+use terryc_base::errors::{Code, DiagnosticSeverity};
+use terryc_base::hir::{typed_dump, Item, Literal, Resolution, TestKind};
+use terryc_base::lex::{TokenKind, Trivia, TriviaKind};
+use terryc_base::manifest::{ArtifactKind, Manifest};
+use terryc_base::metadata::Metadata;
+use terryc_base::mir::{BasicBlock, Operand, Rvalue, Statement, Terminator};
+use terryc_base::render::{ColorChoice, RenderConfig, Style};
+use terryc_base::rewrite::SourceRewriter;
+use terryc_base::sym::{self, Symbol};
+use terryc_base::{
+    Context, DefKind, FileId, FileLoader, FileLocator, GlobalCtxt, Id, IdMaker, Mode, Observer,
+    Options, Providers, Span, Unpretty,
+};
+use terryc_driver::minimize::{self, Failure};
+use terryc_driver::plugins;
+use terryc_driver::progress::Progress;
+use terryc_driver::{Artifact, Compiler, Stage};
+use terryc_lex::{relex, Lexer, TextEdit};
+
+fn options() -> Options {
+    Options {
+        use_ascii: true,
+        color: ColorChoice::Never,
+        dont_print_path: true,
+        path: "test.terry".into(),
+        mode: Mode::Gen,
+        optimize: false,
+        keep_bounds_checks: false,
+        coverage: false,
+        locale: None,
+        teaching_diagnostics: false,
+        lib: false,
+        test: false,
+        cfg: vec![],
+        externs: vec![],
+    }
+}
+
+fn init() {
+    GlobalCtxt::create_and_then(options(), |mut gcx| {
+        let mut providers = Providers::default();
+        terryc_lex::provide(&mut providers);
+        terryc_ast::provide(&mut providers);
+        gcx.set_providers(terryc_base::leak(providers));
+        gcx
+    });
+}
+
+fn compiler() -> Compiler {
+    Compiler::new(options())
+}
+
+fn kinds(trivia: &[Trivia]) -> Vec<TriviaKind> {
+    trivia.iter().map(|t| t.kind).collect()
+}
+
+fn parse_expr(src: &str) -> String {
+    let tokens = Lexer::new(src, FileId::Main).scan_tokens().unwrap();
+    GlobalCtxt::with(|cx| {
+        let expr = Parser::new_with_tokens(cx, FileId::Main, &tokens).parse_expr();
+        format!("{:?}", expr.unwrap())
+    })
+}
+
+#[test]
+fn lexes_tokens() {
+    use TokenKind::*;
+
+    init();
+    let src = "fn 答案() -> i32 { /* /* nested */ */ x += \"a\\\"\"; 12 } // end";
+    let tokens = Lexer::new(src, FileId::Main).scan_tokens().unwrap();
+    let kinds: Vec<_> = tokens.iter().map(|t| &t.kind).collect();
+    assert!(matches!(
+        kinds[..],
+        [
+            Keyword(_),
+            Ident(name),
+            LeftParen,
+            RightParen,
+            RArrow,
+            Ident(_),
+            LeftBrace,
+            Ident(_),
+            PlusEq,
+            String(s),
+            Semicolon,
+            Integer(12),
+            RightBrace,
+            Eof,
+        ] if name.symbol.get_str() == "答案" && s.get_str() == "a\""
+    ));
+}
+
+#[test]
+fn integer_literals_have_bases_and_underscores() {
+    let compiler = compiler();
+    let src = "0x1F 0o17 0b1010 1_000 0x_ff_ 1_0.2_5";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let Some(Artifact::Tokens(tokens)) = compiler.run(FileId::Main, Stage::Lex).artifact else {
+        panic!("expected tokens")
+    };
+    let kinds: Vec<_> = tokens.iter().map(|t| t.kind.clone()).collect();
+    let expected = [31, 15, 10, 1000, 255].map(TokenKind::Integer);
+    assert_eq!(kinds[..5], expected);
+    assert_eq!(kinds[5], TokenKind::Decimal(TotalF64(10.25)));
+
+    let cases = [
+        ("0b102", Code::E0010, 4..5),
+        ("0x", Code::E0011, 0..2),
+        ("0o_", Code::E0011, 0..3),
+        (
+            "0x1_0000_0000_0000_0000_0000_0000_0000_0000",
+            Code::E0005,
+            0..43,
+        ),
+    ];
+    for (src, code, range) in cases {
+        compiler.set_file_contents(FileId::Main, src.into());
+        let output = compiler.run(FileId::Main, Stage::Lex);
+        assert_eq!(output.diagnostics[0].code, Some(code), "{src}");
+        assert_eq!(
+            output.diagnostics[0].span,
+            Span::new(range.start, range.end, FileId::Main)
+        );
+    }
+}
+
+#[test]
+fn trivia_is_attached_to_tokens() {
+    init();
+    let src = "// hi\nlet x = 1; /* one */\n  x";
+    let (tokens, trivia) = Lexer::new(src, FileId::Main)
+        .scan_tokens_with_trivia()
+        .unwrap();
+    assert_eq!(tokens.len(), trivia.len());
+
+    assert_eq!(
+        kinds(&trivia[0].leading),
+        [TriviaKind::LineComment, TriviaKind::Whitespace]
+    );
+    // `;` keeps the comment on its line, the newline and indentation go to `x`.
+    assert_eq!(tokens[4].kind, TokenKind::Semicolon);
+    assert_eq!(
+        kinds(&trivia[4].trailing),
+        [TriviaKind::Whitespace, TriviaKind::BlockComment]
+    );
+    assert_eq!(kinds(&trivia[5].leading), [TriviaKind::Whitespace]);
+    assert_eq!(trivia[5].leading[0].span.lo(), src.find("*/").unwrap() + 2);
+
+    let text: String = tokens
+        .iter()
+        .zip(&trivia)
+        .flat_map(|(token, trivia)| {
+            trivia
+                .leading
+                .iter()
+                .map(|t| t.span)
+                .chain([token.span])
+                .chain(trivia.trailing.iter().map(|t| t.span))
+        })
+        .map(|span| &src[span.lo()..span.hi()])
+        .collect();
+    assert_eq!(text, src);
+}
+
+#[test]
+fn syntax_tree_is_usable_without_a_compiler() {
+    use terryc_syntax::ast::TyKind;
+    use terryc_syntax::sym::Symbol;
+    use terryc_syntax::Interners;
+
+    let interners = terryc_base::leak(Interners::fresh());
+    interners.install().unwrap();
+    assert!(interners.install().is_err());
+    let name = Symbol::new("grid");
+    assert_eq!(name, Symbol::new("grid"));
+    let ty = TyKind::array(TyKind::array(TyKind::Named(name), 3), 2);
+    assert_eq!(ty.to_string(), "[[grid; 3]; 2]");
+}
+
+#[test]
+fn relexing_matches_full_lexing() {
+    init();
+    let src = "fn main() -> unit {\n    let x = 1; // one\n    println(x);\n}\n";
+    let edits = [
+        (src.find('1').unwrap(), 0, "2"),
+        (src.find("let").unwrap(), 0, "// "),
+        (src.find("// one").unwrap(), 6, "/* one */"),
+        (src.find("println").unwrap(), 7, "print"),
+        (src.find("x)").unwrap() + 1, 0, " +"),
+        (src.find("let").unwrap(), 10, ""),
+        (0, 0, "\n"),
+        (src.len(), 0, "fn f() {}"),
+    ];
+    let old = Lexer::new(src, FileId::Main).scan_tokens().unwrap();
+    for (start, len, text) in edits {
+        let edit = TextEdit {
+            range: start..start + len,
+            text: text.into(),
+        };
+        let mut new = src.to_owned();
+        edit.apply(&mut new);
+        let tokens = Lexer::new(&new, FileId::Main).scan_tokens().unwrap();
+        let relexed = relex(&new, FileId::Main, &old, &edit).unwrap();
+        assert_eq!(relexed, tokens, "{new:?}");
+    }
+}
+
+#[test]
+fn binary_operator_precedence() {
+    init();
+    assert_eq!(
+        parse_expr("1 || 2 && 3 == 4"),
+        "BinOp(Or, Literal(1), BinOp(And, Literal(2), BinOp(Equal, Literal(3), Literal(4))))"
+    );
+    assert_eq!(
+        parse_expr("1 | 2 ^ 3 & 4"),
+        "BinOp(BitOr, Literal(1), BinOp(BitXor, Literal(2), BinOp(BitAnd, Literal(3), Literal(4))))"
+    );
+    assert_eq!(
+        parse_expr("1 - 2 - 3 % 4"),
+        "BinOp(Sub, BinOp(Sub, Literal(1), Literal(2)), BinOp(Mod, Literal(3), Literal(4)))"
+    );
+    assert_eq!(
+        parse_expr("(1 << 2) + -3 as i32"),
+        "BinOp(Add, BinOp(Shl, Literal(1), Literal(2)), Cast(UnOp(Minus, Literal(3)), I32))"
+    );
+}
+
+#[test]
+fn source_rewriter_rejects_overlaps() {
+    let src = "let x = a + b;";
+    let span = |s: &str| {
+        let lo = src.find(s).unwrap();
+        Span::new(lo, lo + s.len(), FileId::Main)
+    };
+    let mut rewriter = SourceRewriter::new(FileId::Main);
+    rewriter.replace(span("a + b"), "sum").unwrap();
+    rewriter.insert_before(span("a + b"), "(").unwrap();
+    rewriter.insert_before(span("a + b"), "0 + ").unwrap();
+    rewriter.insert_after(span("a + b"), ")").unwrap();
+    rewriter.replace(span("x"), "y").unwrap();
+    let overlap = rewriter.remove(span("b")).unwrap_err();
+    assert_eq!((overlap.existing, overlap.new), (span("a + b"), span("b")));
+    assert!(rewriter.insert_after(span("a"), "!").is_err());
+    assert_eq!(rewriter.rewrite(src), "let y = (0 + sum);");
+}
+
+#[test]
+fn file_overrides_replace_disk_contents() {
+    init();
+    let file = GlobalCtxt::with_mut(|gcx| gcx.add_virtual_file("virtual.terry", "let x".into()));
+    let count = || GlobalCtxt::with(|cx| cx.lex(file).unwrap().len());
+    assert_eq!(count(), 3);
+
+    GlobalCtxt::with_mut(|gcx| gcx.set_file_contents(file, "let x = 1;".into()));
+    assert_eq!(count(), 6);
+    GlobalCtxt::with(|cx| assert!(cx.file_path(file).ends_with("virtual.terry")));
+}
+
+#[test]
+fn files_are_reloaded_from_disk() {
+    let path = std::env::temp_dir().join(format!("terryc-reload-{}.terry", std::process::id()));
+    std::fs::write(&path, "let x").unwrap();
+    let compiler = compiler();
+    let file = GlobalCtxt::with(|cx| cx.locate(FileLocator::Unresolved(path.clone())));
+    let output = compiler.run(file, Stage::Lex);
+    assert!(matches!(output.artifact, Some(Artifact::Tokens(tokens)) if tokens.len() == 3));
+
+    std::fs::write(&path, "let x = 1;").unwrap();
+    let output = compiler.run(file, Stage::Lex);
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(output.artifact, Some(Artifact::Tokens(tokens)) if tokens.len() == 6));
+}
+
+#[test]
+fn errors_are_counted() {
+    init();
+    let file = GlobalCtxt::with_mut(|gcx| {
+        gcx.add_virtual_file("unfinished.terry", "fn main() -> unit { 1 +".into())
+    });
+    GlobalCtxt::with(|gcx| {
+        assert!(!gcx.has_errors());
+        assert!(gcx.parse(file).is_err());
+        assert!(gcx.has_errors());
+        gcx.take_diagnostics();
+        assert_eq!((gcx.error_count(), gcx.warning_count()), (0, 0));
+    });
+}
+
+#[test]
+fn definitions_have_a_span_and_kind() {
+    init();
+    let src = "fn f(a: i32) -> i32 { let b = a; b }";
+    let file = GlobalCtxt::with_mut(|gcx| gcx.add_virtual_file("defs.terry", src.into()));
+    let broken = GlobalCtxt::with_mut(|gcx| gcx.add_virtual_file("broken.terry", "fn".into()));
+    GlobalCtxt::with(|cx| {
+        let mut defs: Vec<_> = IdMaker::new(file)
+            .take(64)
+            .filter_map(|id| Some((cx.def_span(id)?, cx.def_kind(id)?)))
+            .collect();
+        defs.sort_by_key(|(span, _)| span.lo());
+        let names: Vec<_> = defs
+            .iter()
+            .map(|(span, _)| &src[span.lo()..span.hi()])
+            .collect();
+        let kinds: Vec<_> = defs.iter().map(|&(_, kind)| kind).collect();
+        assert_eq!(names, ["f", "a", "b"]);
+        assert_eq!(kinds, [DefKind::Fn, DefKind::Arg, DefKind::Local]);
+        // expressions have ids too, but are not definitions.
+        assert!(IdMaker::new(file)
+            .take(64)
+            .any(|id| cx.def_span(id).is_none()));
+        assert_eq!(cx.def_kind(IdMaker::new(broken).make()), None);
+    });
+}
+
+struct SingleFile(&'static str);
+
+impl FileLoader for SingleFile {
+    fn load(&self, path: &Path) -> Option<String> {
+        (path == Path::new("test.terry")).then(|| self.0.to_owned())
+    }
+}
+
+#[test]
+fn custom_file_loader() {
+    let compiler = compiler();
+    compiler.set_file_loader(SingleFile("fn main() -> unit {}"));
+    let output = compiler.run(FileId::Main, Stage::Hir);
+    assert!(matches!(output.artifact, Some(Artifact::Hir(_))));
+}
+
+#[test]
+fn strings_can_be_compared() {
+    let compiler = compiler();
+    let src = r#"fn main() -> unit { let same = "a" == "b"; let other = 1 != 2; }"#;
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Mir);
+    assert!(output.diagnostics.is_empty(), "{:?}", output.diagnostics);
+    assert!(matches!(output.artifact, Some(Artifact::Mir(_))));
+}
+
+#[test]
+fn let_types_are_inferred_from_values_and_assignments() {
+    let compiler = compiler();
+    let src = "fn main() -> unit { let mut a; a = 2; let b = a + 1; println(b); }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Mir);
+    assert!(output.diagnostics.is_empty(), "{:?}", output.diagnostics);
+
+    let src = "fn main() -> unit { let mut a = 1; a = true; }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Hir);
+    let [diag] = &*output.diagnostics else {
+        panic!("{:?}", output.diagnostics)
+    };
+    assert_eq!(diag.code, Some(Code::E0201));
+    let span = |s: &str| {
+        let lo = src.find(s).unwrap();
+        Span::new(lo, lo + s.len(), FileId::Main)
+    };
+    assert_eq!(diag.span, span("true"));
+    assert_eq!(diag.span_notes[0].0, span("1"));
+
+    for (src, code) in [
+        (
+            "fn main() -> unit { let mut a; println(a); a = 1; }",
+            Code::E0238,
+        ),
+        ("fn main() -> unit { let a; }", Code::E0204),
+    ] {
+        compiler.set_file_contents(FileId::Main, src.into());
+        let output = compiler.run(FileId::Main, Stage::Hir);
+        assert_eq!(output.diagnostics[0].code, Some(code), "{src}");
+    }
+}
+
+#[test]
+fn functions_without_an_arrow_return_unit() {
+    let compiler = compiler();
+    let src = "fn f(x: i32) { if x > 0 { return; } println(x); } fn main() { f(1); }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Hir);
+    assert!(output.diagnostics.is_empty(), "{:?}", output.diagnostics);
+    let Some(Artifact::Hir(hir)) = output.artifact else {
+        panic!("expected HIR")
+    };
+    let Item::Fn(f) = &hir.items[0] else { panic!() };
+    assert_eq!(f.ret, TyKind::Unit);
+
+    for (src, ret) in [
+        ("fn f() { return 1; } fn main() {}", ")"),
+        ("fn f() -> i32 { return true; } fn main() {}", "i32"),
+        ("fn f() -> i32 { true } fn main() {}", "i32"),
+    ] {
+        compiler.set_file_contents(FileId::Main, src.into());
+        let output = compiler.run(FileId::Main, Stage::Hir);
+        assert_eq!(output.diagnostics[0].code, Some(Code::E0201), "{src}");
+        let lo = src.find(ret).unwrap();
+        let span = Span::new(lo, lo + ret.len(), FileId::Main);
+        assert_eq!(output.diagnostics[0].span_notes[0].0, span, "{src}");
+    }
+}
+
+#[test]
+fn println_rejects_unprintable_values() {
+    let compiler = compiler();
+    let src = "fn f() -> unit {} fn main() -> unit { println(true); println(f()); }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Hir);
+    assert!(output.artifact.is_none());
+    assert_eq!(output.diagnostics.len(), 1);
+    assert_eq!(output.diagnostics[0].message, "`unit` cannot be printed");
+}
+
+#[test]
+fn unused_values_are_linted() {
+    let compiler = compiler();
+    let src = "fn f() -> i32 { 1 } fn main() -> unit { let a = 1; a + 2; (a); _ = a * 2; f(); }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Mir);
+    assert!(matches!(output.artifact, Some(Artifact::Mir(_))));
+    let spans: Vec<_> = output
+        .diagnostics
+        .iter()
+        .inspect(|d| assert_eq!(d.severity, DiagnosticSeverity::Warning))
+        .map(|d| &src[d.span.lo()..d.span.hi()])
+        .collect();
+    assert_eq!(spans, ["a + 2", "a"]);
+}
+
+#[test]
+fn constants_propagate_through_dead_branches() {
+    let compiler = Compiler::new(Options {
+        optimize: true,
+        ..options()
+    });
+    let src = r#"fn main() -> unit {
+        let debug = false;
+        let mut x = 2;
+        if debug {
+            x = 5;
+            println("debug");
+        }
+        println(x * 3);
+    }"#;
+    compiler.set_file_contents(FileId::Main, src.into());
+    let Some(Artifact::Mir(mir)) = compiler.run(FileId::Main, Stage::Mir).artifact else {
+        panic!("expected MIR")
+    };
+    let body = &mir.functions.values().next().unwrap().body;
+    let terminators: Vec<_> = body.blocks.iter().map(|bb| &bb.terminator).collect();
+    let calls: Vec<_> = terminators
+        .iter()
+        .filter_map(|t| match t {
+            Terminator::Call { args, .. } => Some(args),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(calls, [&vec![Rvalue::Use(Operand::Const(Literal::Int(6)))]]);
+    assert!(!terminators
+        .iter()
+        .any(|t| matches!(t, Terminator::SwitchInt(..))));
+}
+
+#[test]
+fn jumps_are_threaded_through_decided_branches() {
+    let compiler = Compiler::new(Options {
+        optimize: true,
+        ..options()
+    });
+    let src = r#"fn f(c: i32) -> unit {
+        let mut x = 0;
+        if c > 0 {
+            x = 1;
+        }
+        if x == 1 {
+            println("one");
+        }
+    }
+    fn main() -> unit {
+        f(1);
+    }"#;
+    compiler.set_file_contents(FileId::Main, src.into());
+    let Some(Artifact::Mir(mir)) = compiler.run(FileId::Main, Stage::Mir).artifact else {
+        panic!("expected MIR")
+    };
+    let body = &mir.functions.values().next().unwrap().body;
+    let switches = body
+        .blocks
+        .iter()
+        .filter(|bb| matches!(bb.terminator, Terminator::SwitchInt(..)))
+        .count();
+    assert_eq!(switches, 1, "{body:#?}");
+}
+
+#[test]
+fn call_graph_orders_callees_first() {
+    let compiler = compiler();
+    let src = "fn b() -> unit {} fn a() -> unit { b(); a(); }\n\
+               fn c() -> unit { b(); } fn main() -> unit { a(); }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let graph = GlobalCtxt::with(|cx| cx.call_graph(FileId::Main)).unwrap();
+    let order: Vec<_> = graph
+        .bottom_up()
+        .iter()
+        .map(|id| graph.nodes[id].name.to_string())
+        .collect();
+    assert_eq!(order, ["b", "a", "c", "main"]);
+
+    let dead = GlobalCtxt::with(GlobalCtxt::take_diagnostics);
+    assert_eq!(dead.len(), 1);
+    assert_eq!(dead[0].severity, DiagnosticSeverity::Warning);
+    assert_eq!(dead[0].message, "function `c` is never called");
+}
+
+#[test]
+fn call_graph_finds_recursion() {
+    let compiler = compiler();
+    let src = "fn leaf() -> unit {} fn fact(x: i32) -> i32 { leaf(); fact(x - 1) }\n\
+               fn main() -> unit { fact(3); }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let graph = GlobalCtxt::with(|cx| cx.call_graph(FileId::Main)).unwrap();
+    let [leaf, fact, main] = [0, 1, 2].map(|i| *graph.nodes.get_index(i).unwrap().0);
+    assert_eq!(graph.cycles(), [vec![fact]]);
+    assert!(graph.is_recursive(fact));
+    assert!(!graph.is_recursive(leaf) && !graph.is_recursive(main));
+}
+
+#[test]
+fn small_functions_are_inlined_unless_marked_never() {
+    let compiler = Compiler::new(Options {
+        optimize: true,
+        ..options()
+    });
+    let src = "fn double(x: i32) -> i32 { x * 2 }\n\
+               #[inline(never)] fn triple(x: i32) -> i32 { x * 3 }\n\
+               fn main() -> unit { println(double(21)); println(triple(1)); }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let Some(Artifact::Mir(mir)) = compiler.run(FileId::Main, Stage::Mir).artifact else {
+        panic!("expected MIR")
+    };
+    let (&triple, _) = mir.functions.get_index(1).unwrap();
+    let body = &mir.functions[2].body;
+    let calls: Vec<_> = body
+        .blocks
+        .iter()
+        .filter_map(|bb| match &bb.terminator {
+            Terminator::Call { callee, args, .. } => Some((*callee, args.clone())),
+            _ => None,
+        })
+        .collect();
+    let println = Resolution::Builtin(sym::println);
+    assert_eq!(
+        calls[0],
+        (println, vec![Rvalue::Use(Operand::Const(Literal::Int(42)))])
+    );
+    assert_eq!(calls[1].0, Resolution::Fn(triple));
+    // the call graph is still the one of the source.
+    assert_eq!(mir.call_graph.nodes[2].callees.len(), 2);
+}
+
+#[test]
+fn storage_statements_mark_scopes() {
+    let compiler = compiler();
+    let src = "fn main() -> unit { let a = 1; { let b = a; println(b); } }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Mir);
+    let Some(Artifact::Mir(mir)) = output.artifact else {
+        panic!("{:?}", output.diagnostics)
+    };
+    let body = &mir.functions.values().next().unwrap().body;
+    let statements: Vec<_> = body
+        .blocks
+        .iter()
+        .flat_map(|bb| &bb.statements)
+        .map(|stmt| format!("{stmt:?}"))
+        .collect();
+    assert_eq!(
+        statements,
+        [
+            "StorageLive(_1)",
+            "_1 = Use(Const(Int(1)))",
+            "StorageLive(_2)",
+            "_2 = Use(Copy(_1))",
+            "StorageDead(_2)",
+            "StorageDead(_1)",
+        ]
+    );
+}
+
+#[test]
+fn dominators_of_an_if() {
+    let compiler = compiler();
+    let src = "fn main() -> unit { let c = 1; if c > 0 { println(1); } println(2); }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Mir);
+    let Some(Artifact::Mir(mir)) = output.artifact else {
+        panic!("{:?}", output.diagnostics)
+    };
+    let body = &mir.functions.values().next().unwrap().body;
+    let entry = BasicBlock::from_usize(0);
+    let rpo = body.reverse_post_order();
+    assert_eq!(rpo[0], entry);
+    assert_eq!(rpo.len(), body.blocks.len());
+
+    let dominators = body.dominators();
+    assert!(rpo.iter().all(|&bb| dominators.dominates(entry, bb)));
+    assert_eq!(dominators.immediate_dominator(entry), None);
+    let preds = body.predecessors();
+    // the block after the `if` is reached both from the then-block and from
+    // the switch skipping it, so only the switch dominates it.
+    let join = preds
+        .iter_enumerated()
+        .find(|(_, p)| p.len() == 2)
+        .unwrap()
+        .0;
+    let switch = body
+        .blocks
+        .indices()
+        .find(|&bb| matches!(body.blocks[bb].terminator, Terminator::SwitchInt(..)))
+        .unwrap();
+    assert_eq!(dominators.immediate_dominator(join), Some(switch));
+    for &pred in &preds[join] {
+        assert!(dominators.dominates(switch, pred));
+    }
+}
+
+#[test]
+fn while_loops_jump_back_to_their_condition() {
+    let compiler = compiler();
+    let src = "fn main() -> unit { let mut i = 0; while i < 3 { println(i); i = i + 1; } }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Mir);
+    let Some(Artifact::Mir(mir)) = output.artifact else {
+        panic!("{:?}", output.diagnostics)
+    };
+    let body = &mir.functions.values().next().unwrap().body;
+    let header = body
+        .blocks
+        .indices()
+        .find(|&bb| matches!(body.blocks[bb].terminator, Terminator::SwitchInt(..)))
+        .unwrap();
+    let Terminator::SwitchInt(_, targets) = &body.blocks[header].terminator else {
+        unreachable!()
+    };
+    let (loop_body, exit) = (targets.targets[0], targets.else_());
+    let dominators = body.dominators();
+    // the end of the body jumps back to the header, which dominates it.
+    let back_edges: Vec<_> = body.predecessors()[header]
+        .iter()
+        .copied()
+        .filter(|&pred| dominators.dominates(header, pred))
+        .collect();
+    assert_eq!(back_edges.len(), 1);
+    assert!(dominators.dominates(loop_body, back_edges[0]));
+    assert!(matches!(
+        body.blocks[exit].terminator,
+        Terminator::Return(_)
+    ));
+
+    let src = "fn main() -> unit { while 1 { } }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Mir);
+    assert!(output.artifact.is_none());
+}
+
+#[test]
+fn typed_hir_dump() {
+    let compiler = compiler();
+    let src = "fn f(x: i32) -> i32 { x * 2 } fn main() -> unit { let a = 1 > 0; println(f(3)); }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Hir);
+    let Some(Artifact::Hir(hir)) = output.artifact else {
+        panic!("{:?}", output.diagnostics)
+    };
+    assert_eq!(
+        typed_dump(&hir),
+        "\
+fn f(x: i32) -> i32
+    binary *: i32
+        local x: i32
+        literal 2: i32
+fn main() -> unit
+    let a: bool
+        binary >: bool
+            literal 1: i32
+            literal 0: i32
+    call println: unit
+        call f: i32
+            literal 3: i32
+"
+    );
+}
+
+#[test]
+fn nested_functions_get_their_own_body() {
+    let compiler = compiler();
+    let src = "fn main() -> unit {
+        let a = 1;
+        fn double(x: i32) -> i32 { x * 2 }
+        println(double(a));
+    }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Mir);
+    let Some(Artifact::Mir(mir)) = output.artifact else {
+        panic!("{:?}", output.diagnostics)
+    };
+    let names: Vec<_> = mir.functions.values().map(|f| f.name.get_str()).collect();
+    assert_eq!(names, ["double", "main"]);
+    let main = &mir.functions[1].body;
+    let double = *mir.functions.keys().next().unwrap();
+    assert!(main.blocks.iter().any(|bb| matches!(
+        bb.terminator,
+        Terminator::Call {
+            callee: Resolution::Fn(callee),
+            ..
+        } if callee == double
+    )));
+}
+
+#[test]
+fn unpretty_renders_the_mir_cfg() {
+    let compiler = compiler();
+    let src = "fn main() -> unit { println(1); }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let dot = GlobalCtxt::with(|cx| Unpretty::MirCfg.render(cx, FileId::Main)).unwrap();
+    assert_eq!(
+        dot,
+        r#"digraph mir {
+    subgraph cluster_0 {
+        label="main";
+        f0_bb0 [shape=box, label="bb0\n_1 = Builtin(println)([Use(Const(Int(1)))]); goto bb1\n"];
+        f0_bb1 [shape=box, label="bb1\nreturn _0\n"];
+        f0_bb0 -> f0_bb1;
+    }
+}
+"#
+    );
+}
+
+#[test]
+fn else_branches_join() {
+    let compiler = compiler();
+    let src = "fn sign(x: i32) -> i32 { if x > 0 { 1 } else if x < 0 { -1 } else { 0 } }
+        fn main() -> unit { println(sign(-5)); }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Mir);
+    let Some(Artifact::Mir(mir)) = output.artifact else {
+        panic!("{:?}", output.diagnostics)
+    };
+    let body = &mir.functions[0].body;
+    let switches: Vec<_> = body
+        .blocks
+        .indices()
+        .filter(|&bb| matches!(body.blocks[bb].terminator, Terminator::SwitchInt(..)))
+        .collect();
+    assert_eq!(switches.len(), 2);
+    // every branch ends up in the block that returns, through the joins.
+    let dominators = body.dominators();
+    let Terminator::Return(ret) = body.blocks.last().unwrap().terminator else {
+        panic!()
+    };
+    let exit = body.blocks.last_idx();
+    assert_eq!(dominators.immediate_dominator(exit), Some(switches[0]));
+    let returned = body.blocks[exit]
+        .statements
+        .iter()
+        .any(|stmt| matches!(stmt, Statement::Assign(place, _) if place.as_local() == Some(ret)));
+    assert!(returned);
+
+    let src = "fn main() -> unit { let a = if 1 > 0 { 1 } else { true }; }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Mir);
+    assert!(output.artifact.is_none());
+    assert_eq!(output.diagnostics[0].message, "mismatched types");
+}
+
+#[test]
+fn plugins_add_passes_and_lints() {
+    use terryc_base::errors::make_diag;
+    use terryc_base::mir::{Function, MirTree};
+
+    fn drop_storage_markers(_: &dyn Context, _: Id, f: &mut Function) {
+        for data in f.body.blocks.iter_mut() {
+            data.statements
+                .retain(|stmt| matches!(stmt, Statement::Assign(..)));
+        }
+    }
+
+    fn branches(cx: &dyn Context, tree: &MirTree) {
+        for (id, f) in &*tree.functions {
+            if f.body.blocks.len() > 1 {
+                make_diag! {
+                    Warning,
+                    cx.def_span(*id).unwrap(),
+                    "function `{}` branches",
+                    f.name,
+                }
+                .emit();
+            }
+        }
+    }
+
+    let mut providers = terryc_driver::providers();
+    providers.add_mir_pass(drop_storage_markers);
+    providers.add_lint(branches);
+    let compiler = Compiler::with_providers(options(), providers);
+    let src = "fn f(x: i32) -> i32 { let y = x; y } \
+               fn main() -> unit { if f(1) > 0 { f(2); } }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Mir);
+    let Some(Artifact::Mir(mir)) = output.artifact else {
+        panic!("expected MIR, got {:?}", output.diagnostics)
+    };
+    let mut statements = mir
+        .functions
+        .values()
+        .flat_map(|f| &f.body.blocks)
+        .flat_map(|data| &data.statements);
+    assert!(statements.all(|stmt| matches!(stmt, Statement::Assign(..))));
+    let messages: Vec<_> = output.diagnostics.iter().map(|d| &*d.message).collect();
+    assert_eq!(messages, ["function `main` branches"]);
+}
+
+#[test]
+fn plugin_lints_visit_the_hir() {
+    let mut providers = terryc_driver::providers();
+    providers.add_lint(plugins::plugin("while_true").unwrap());
+    let compiler = Compiler::with_providers(options(), providers);
+    let src = "fn spin() -> unit { let mut i = 0; while i < 3 { i = i + 1; } while true {} }\n\
+               fn main() -> unit { spin(); }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Mir);
+    let [diag] = &*output.diagnostics else {
+        panic!("{:?}", output.diagnostics)
+    };
+    assert_eq!(diag.message, "`spin` loops with `while true`");
+    assert_eq!(diag.severity, DiagnosticSeverity::Warning);
+    let lo = src.find("spin").unwrap();
+    assert_eq!(diag.span, Span::new(lo, lo + 4, FileId::Main));
+}
+
+#[test]
+fn float_literals_reach_mir() {
+    let compiler = compiler();
+    let tokens = Lexer::new("1.5 1..2 3.", FileId::Main)
+        .scan_tokens()
+        .unwrap();
+    let kinds: Vec<_> = tokens.iter().map(|t| &t.kind).collect();
+    assert!(matches!(
+        kinds[..],
+        [
+            TokenKind::Decimal(TotalF64(f)),
+            TokenKind::Integer(1),
+            TokenKind::DotDot,
+            TokenKind::Integer(2),
+            TokenKind::Integer(3),
+            TokenKind::Dot,
+            TokenKind::Eof,
+        ] if *f == 1.5
+    ));
+
+    let src = "fn half(x: f32) -> f32 { x / 2.0 } fn main() -> unit { println(half(-0.5)); }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Mir);
+    assert!(output.diagnostics.is_empty(), "{:?}", output.diagnostics);
+    let Some(Artifact::Mir(mir)) = output.artifact else {
+        panic!()
+    };
+    let half = mir
+        .functions
+        .values()
+        .find(|f| f.name.get_str() == "half")
+        .unwrap();
+    assert_eq!(half.ret, TyKind::F32);
+    let two = Operand::Const(Literal::Float(TotalF64(2.0)));
+    let divides_by_two = |stmt: &Statement| match stmt {
+        Statement::Assign(_, Rvalue::BinaryOp(_, _, rhs)) => *rhs == two,
+        _ => false,
+    };
+    assert!(half
+        .body
+        .blocks
+        .iter()
+        .flat_map(|data| &data.statements)
+        .any(divides_by_two));
+}
+
+#[test]
+fn manifest_lists_artifacts_with_hashes() {
+    let dir = std::env::temp_dir().join(format!("terryc-manifest-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let exe = dir.join("out");
+    std::fs::write(&exe, "abc").unwrap();
+
+    let mut manifest = Manifest::default();
+    manifest.add(&exe, ArtifactKind::Executable);
+    manifest.add(dir.join("lcov.info"), ArtifactKind::CoverageReport);
+    let json = manifest.to_json(&options()).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(json.contains("\"path\": \"test.terry\""), "{json}");
+    let sha256 = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    assert!(json.contains(&format!(
+        "\"kind\": \"executable\", \"sha256\": \"{sha256}\""
+    )));
+    assert!(json.contains("\"kind\": \"coverage-report\", \"sha256\": null"));
+    assert_eq!(manifest.path(), dir.join("terryc-output.json"));
+}
+
+#[test]
+fn extern_functions_are_called_through_metadata() {
+    let text = "terry library 1\nobject out.o\nfn add(i32, i32) -> i32\n";
+    let dir = std::env::temp_dir().join(format!("terryc-extern-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("out.tmeta");
+    std::fs::write(&path, text).unwrap();
+    let compiler = Compiler::new(Options {
+        externs: vec![("math".into(), path)],
+        ..options()
+    });
+
+    let src = "fn add(a: i32, b: i32) -> i32 { a + b } fn first(a: [i32; 1]) -> i32 { a[0] }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let Some(Artifact::Hir(hir)) = compiler.run(FileId::Main, Stage::Hir).artifact else {
+        panic!("expected HIR")
+    };
+    // arrays cannot be passed to another program.
+    assert_eq!(Metadata::of_library(&hir, "out.o").to_text(), text);
+
+    let src = "fn main() -> unit { println(math::add(1, 2)); }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Mir);
+    let src = "fn main() -> unit { math::sub(1, 2); }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let missing = compiler.run(FileId::Main, Stage::Hir);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let Some(Artifact::Mir(mir)) = output.artifact else {
+        panic!("expected MIR")
+    };
+    let body = &mir.functions[0].body;
+    let Terminator::Call { callee, .. } = body.blocks[0].terminator else {
+        panic!()
+    };
+    assert_eq!(
+        callee,
+        Resolution::Extern(Symbol::new("math"), Symbol::new("add"))
+    );
+    assert_eq!(missing.diagnostics[0].code, Some(Code::E0237));
+}
+
+#[test]
+fn diagnostics_are_translated() {
+    let compiler = Compiler::new(Options {
+        locale: Some("de_DE.UTF-8".into()),
+        ..options()
+    });
+    let src = "fn main() -> unit { let a: i32 = true; }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Hir);
+    let mismatch = &output.diagnostics[0];
+    assert_eq!(mismatch.code, Some(Code::E0201));
+    assert_eq!(mismatch.message, "nicht übereinstimmende Typen");
+    assert_eq!(
+        mismatch.note.as_deref(),
+        Some("`i32` erwartet, `bool` gefunden")
+    );
+
+    // the English message is used when there is no translation.
+    assert_eq!(Code::E0201.template("xx"), "mismatched types");
+}
+
+#[test]
+fn bools_are_switched_on_as_integers() {
+    let compiler = compiler();
+    let src = "fn not(b: bool) -> bool { if b { false } else { true } } \
+               fn main() -> unit { println(not(1 < 2)); }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Mir);
+    assert!(output.diagnostics.is_empty(), "{:?}", output.diagnostics);
+    let Some(Artifact::Mir(mir)) = output.artifact else {
+        panic!()
+    };
+    let not = mir
+        .functions
+        .values()
+        .find(|f| f.name.get_str() == "not")
+        .unwrap();
+    assert_eq!((&*not.args, not.ret), (&[TyKind::Bool][..], TyKind::Bool));
+    let entry = &not.body.blocks[BasicBlock::from_usize(0)];
+    let Terminator::SwitchInt(_, targets) = &entry.terminator else {
+        panic!()
+    };
+    assert_eq!(targets.values, [1]);
+
+    let src = "fn main() -> unit { if 1 { } }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Hir);
+    assert_eq!(output.diagnostics[0].message, "mismatched types");
+}
+
+#[test]
+fn teaching_diagnostics_explain_errors() {
+    let compiler = Compiler::new(Options {
+        teaching_diagnostics: true,
+        ..options()
+    });
+    let src = "fn main() -> unit { let a: i32 = true; }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Hir);
+    let mismatch = &output.diagnostics[0];
+    assert_eq!(mismatch.message, "mismatched types");
+    assert!(mismatch.help.unwrap().contains("`let x: bool = 1;`"));
+    assert!(Code::E0211
+        .teaching("de")
+        .unwrap()
+        .starts_with("Namen müssen"));
+
+    // errors without an explanation are unchanged.
+    assert_eq!(Code::E0213.teaching("en"), None);
+}
+
+#[test]
+fn minimizer_keeps_only_what_reproduces_the_diagnostic() {
+    let compiler = compiler();
+    let src = "fn helper() -> i32 { 1 + 2 }
+fn main() -> unit {
+    println(helper());
+    let a = 4 * 5;
+    println(1 + missing * 2);
+}";
+    let failure = Failure::Diagnostic(Code::E0211);
+    let path = Path::new("test.min.terry");
+    let reduced = minimize::minimize(&compiler, src, path, &failure).unwrap();
+    let reduced = reduced.split_whitespace().collect::<Vec<_>>().join(" ");
+    assert_eq!(reduced, "fn main() -> unit { println(missing); }");
+
+    let failure = Failure::Diagnostic(Code::E0201);
+    assert_eq!(minimize::minimize(&compiler, src, path, &failure), None);
+}
+
+#[test]
+fn entry_point_must_be_a_single_main() {
+    let compiler = compiler();
+    let entry_point = || {
+        let result = GlobalCtxt::with(|cx| cx.entry_point(FileId::Main));
+        let codes: Vec<_> = GlobalCtxt::with(GlobalCtxt::take_diagnostics)
+            .into_iter()
+            .map(|d| d.code)
+            .collect();
+        (result.is_ok(), codes)
+    };
+    compiler.set_file_contents(
+        FileId::Main,
+        "fn f() -> unit { fn main() -> unit {} }".into(),
+    );
+    assert_eq!(entry_point(), (false, vec![Some(Code::E0229)]));
+
+    compiler.set_file_contents(FileId::Main, "fn main(x: i32) -> i32 { x }".into());
+    assert_eq!(entry_point(), (false, vec![Some(Code::E0231)]));
+
+    compiler.add_file("util/mod.terry", "fn main() -> unit {}".into());
+    compiler.set_file_contents(FileId::Main, "mod util; fn main() -> unit {}".into());
+    assert_eq!(entry_point(), (false, vec![Some(Code::E0230)]));
+
+    compiler.set_file_contents(FileId::Main, "mod util;".into());
+    assert_eq!(entry_point(), (true, vec![]));
+}
+
+#[test]
+fn cfg_attributes_leave_out_disabled_code() {
+    let compiler = Compiler::new(Options {
+        cfg: vec!["debug".into(), "os=\"linux\"".into()],
+        ..options()
+    });
+    let src = "#[cfg(debug)] fn log(x: i32) -> unit { println(x); } \
+               #[cfg(release)] fn log(x: i32) -> unit {} \
+               fn main() -> unit { \
+                   #[cfg(os = \"linux\")] let x = 1; \
+                   #[cfg(os = \"windows\")] let x = true; \
+                   #[cfg(release)] undefined(); \
+                   log(x); \
+               }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Hir);
+    assert!(output.diagnostics.is_empty(), "{:?}", output.diagnostics);
+    let Some(Artifact::Hir(hir)) = output.artifact else {
+        panic!()
+    };
+    assert_eq!(hir.items.len(), 2);
+    let Item::Fn(main) = &hir.items[1] else {
+        panic!()
+    };
+    assert_eq!(main.block.statements.len(), 2);
+}
+
+#[test]
+fn attributes_are_checked_against_the_known_ones() {
+    let compiler = compiler();
+    let src = "#[inline(always)] fn f() -> unit {} #[test] fn main() -> unit {}";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Parse);
+    assert!(output.diagnostics.is_empty(), "{:?}", output.diagnostics);
+
+    let cases = [
+        ("fn main() -> unit { #[inline] let x = 1; }", Code::E0115),
+        ("#[deprecated] fn main() -> unit {}", Code::E0114),
+        ("#[test(x)] fn main() -> unit {}", Code::E0116),
+        ("#[inline] struct S {}", Code::E0115),
+    ];
+    let mut notes = vec![];
+    for (src, code) in cases {
+        compiler.set_file_contents(FileId::Main, src.into());
+        let output = compiler.run(FileId::Main, Stage::Parse);
+        assert_eq!(output.diagnostics[0].code, Some(code), "{src}");
+        notes.push(output.diagnostics[0].note.clone().unwrap());
+    }
+    assert_eq!(notes[3], "it can only be used on a function");
+}
+
+#[test]
+fn test_functions_are_collected_and_assert_lowers_to_a_check() {
+    let compiler = compiler();
+    let src = "fn double(x: i32) -> i32 { x * 2 } \
+               #[test] fn doubles() -> unit { assert(double(2) == 4); } \
+               #[bench] fn doubling() -> unit { let x = double(3); } \
+               fn main() -> unit {}";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Mir);
+    let Some(Artifact::Mir(mir)) = output.artifact else {
+        panic!("expected MIR")
+    };
+    let tests = GlobalCtxt::with(|cx| cx.tests(FileId::Main)).unwrap();
+    let kinds: Vec<_> = tests.iter().map(|t| (t.name.as_str(), t.kind)).collect();
+    assert_eq!(
+        kinds,
+        [("doubles", TestKind::Test), ("doubling", TestKind::Bench)]
+    );
+    let body = &mir.functions[&tests[0].id].body;
+    assert!(body
+        .blocks
+        .iter()
+        .any(|bb| matches!(bb.terminator, Terminator::Assert { .. })));
+    // `double` is only called by a test, which still counts as a use.
+    let unused = GlobalCtxt::with(|cx| {
+        cx.call_graph(FileId::Main).unwrap();
+        cx.take_diagnostics()
+    });
+    assert!(unused.is_empty(), "{unused:?}");
+
+    let src = "#[test] fn takes(x: i32) -> unit {} fn main() -> unit { assert(1); }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Hir);
+    assert_eq!(output.diagnostics[0].code, Some(Code::E0201));
+
+    let src = "#[test] fn takes(x: i32) -> unit {} fn main() -> unit {}";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let diagnostics = GlobalCtxt::with(|cx| {
+        assert!(cx.tests(FileId::Main).is_err());
+        cx.take_diagnostics()
+    });
+    assert_eq!(diagnostics[0].code, Some(Code::E0233));
+    let note = diagnostics[0].note.as_deref();
+    assert_eq!(
+        note,
+        Some("expected `fn takes() -> unit`, found `fn takes(i32) -> unit`")
+    );
+}
+
+#[test]
+fn only_mutable_locals_can_be_assigned() {
+    let compiler = compiler();
+    let src = "fn main() -> unit { let mut a = 1; a += 1; let b = a; b = 2; }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Hir);
+    let error = &output.diagnostics[0];
+    assert_eq!(error.code, Some(Code::E0232));
+    assert_eq!(&src[error.span.lo()..error.span.hi()], "b");
+    assert_eq!(error.span_notes[0].0.lo(), src.find("b =").unwrap());
+
+    let src = "fn main() -> unit { let mut a = 1; a = 2; let b = a; println(b); }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let Some(Artifact::Mir(mir)) = compiler.run(FileId::Main, Stage::Mir).artifact else {
+        panic!("expected MIR")
+    };
+    let locals = &mir.functions.values().next().unwrap().body.locals;
+    let mutable: Vec<_> = locals.iter().map(|local| local.mutable).collect();
+    assert_eq!(mutable, [false, true, false, false]);
+}
+
+#[test]
+fn mutually_recursive_functions_are_resolved_in_any_order() {
+    let compiler = Compiler::new(Options {
+        optimize: true,
+        ..options()
+    });
+    let src = "fn main() -> unit { println(is_even(10)); }\n\
+               fn is_even(n: i32) -> bool { if n == 0 { true } else { is_odd(n - 1) } }\n\
+               fn is_odd(n: i32) -> bool { if n == 0 { false } else { is_even(n - 1) } }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let output = compiler.run(FileId::Main, Stage::Mir);
+    assert!(output.diagnostics.is_empty(), "{:?}", output.diagnostics);
+    let Some(Artifact::Mir(mir)) = output.artifact else {
+        panic!("expected MIR")
+    };
+    let [main, is_even, is_odd] = [0, 1, 2].map(|i| *mir.functions.get_index(i).unwrap().0);
+    assert_eq!(mir.call_graph.cycles(), [vec![is_even, is_odd]]);
+    assert!(!mir.call_graph.is_recursive(main));
+}
+
+#[test]
+fn render_config_follows_color_and_ascii_options() {
+    let never = RenderConfig::new(&options(), true);
+    assert!(never.ascii && !never.color);
+    assert_eq!(never.paint("ok", Style::Success), "ok");
+    let always = RenderConfig::new(
+        &Options {
+            color: ColorChoice::Always,
+            ..options()
+        },
+        false,
+    );
+    assert!(always.color);
+    assert_eq!(always.paint("ok", Style::Success), "\x1b[32mok\x1b[0m");
+    // `--use-ascii` is for plain output, which has no colors either.
+    let auto = RenderConfig::new(
+        &Options {
+            color: ColorChoice::Auto,
+            ..options()
+        },
+        true,
+    );
+    assert!(!auto.color);
+
+    let text = "the quick brown fox jumps over the lazy dog";
+    assert_eq!(always.width, None);
+    assert_eq!(always.wrap(text, 2), text);
+    let narrow = RenderConfig {
+        width: Some(24),
+        ..never
+    };
+    assert_eq!(
+        narrow.wrap(text, 2),
+        "the quick brown fox\n  jumps over the lazy\n  dog"
+    );
+}
+
+#[test]
+fn observers_hear_about_queries_and_functions() {
+    struct Recorder(&'static RefCell<Vec<String>>);
+
+    impl Observer for Recorder {
+        fn query_started(&self, query: &'static str, _file: FileId) {
+            self.0.borrow_mut().push(query.to_string());
+        }
+
+        fn function_lowered(&self, _id: Id) {
+            self.0.borrow_mut().push("function".to_string());
+        }
+    }
+
+    let events = terryc_base::leak(RefCell::new(Vec::new()));
+    let mut providers = terryc_driver::providers();
+    providers.add_observer(Recorder(events));
+    let compiler = Compiler::with_providers(options(), providers);
+    let src = "fn f() -> i32 { 1 } fn main() -> unit { f(); }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    compiler.run(FileId::Main, Stage::Mir);
+    assert_eq!(events.borrow().first().map(|e| &**e), Some("mir"));
+    assert_eq!(events.borrow().iter().filter(|e| *e == "lex").count(), 1);
+    assert_eq!(
+        events.borrow().iter().filter(|e| *e == "function").count(),
+        2
+    );
+
+    // cached queries do not start again.
+    events.borrow_mut().clear();
+    compiler.run(FileId::Main, Stage::Mir);
+    assert!(events.borrow().is_empty());
+
+    let progress = Progress::default();
+    progress.query_started("lex", FileId::Main);
+    progress.query_started("parse", FileId::Main);
+    assert_eq!(progress.status(), "1 file parsed");
+    let mut ids = IdMaker::new(FileId::Main);
+    progress.function_lowered(ids.make());
+    progress.function_lowered(ids.make());
+    progress.query_started("codegen", FileId::Main);
+    assert_eq!(
+        progress.status(),
+        "1 file parsed, 2 functions lowered, 1 object file generated"
+    );
+}
+
+#[test]
+fn types_are_interned() {
+    let compiler = compiler();
+    let src = "fn id(t: (i32, bool)) -> (i32, bool) { t } fn main() -> unit { id((1, true)); }";
+    compiler.set_file_contents(FileId::Main, src.into());
+    let Some(Artifact::Mir(first)) = compiler.run(FileId::Main, Stage::Mir).artifact else {
+        panic!()
+    };
+    let pair = TyKind::tuple([TyKind::I32, TyKind::Bool]);
+    let TyKind::Tuple(elems) = pair else { panic!() };
+    let TyKind::Tuple(again) = TyKind::tuple([TyKind::I32, TyKind::Bool]) else {
+        panic!()
+    };
+    assert!(std::ptr::eq(elems, again));
+    let id = first
+        .functions
+        .values()
+        .find(|f| f.name.get_str() == "id")
+        .unwrap();
+    assert_eq!(id.ret, pair);
+
+    // an edit that lowers to the same bodies gives the same MIR.
+    compiler.set_file_contents(FileId::Main, format!("{src}\n"));
+    let Some(Artifact::Mir(second)) = compiler.run(FileId::Main, Stage::Mir).artifact else {
+        panic!()
+    };
+    assert_eq!(first, second);
+}
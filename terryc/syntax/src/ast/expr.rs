@@ -1,11 +1,12 @@
 use std::fmt;
 use std::hash::Hash;
 
-use super::{Block, TyKind};
+use super::{Block, FnArg, Pat, Ty, TyKind};
+use crate::lex::Ident;
 use crate::sym::Symbol;
-use crate::Span;
+use crate::{Id, Span};
 
-#[derive(PartialEq, Eq, Hash)]
+#[derive(PartialEq, Eq, Hash, Clone)]
 pub struct Expr {
     pub kind: ExprKind,
     pub span: Span,
@@ -24,6 +25,13 @@ pub enum BinOpKind {
     Mul,
     Div,
     Mod,
+    And,
+    Or,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
 }
 
 impl BinOpKind {
@@ -40,8 +48,28 @@ impl BinOpKind {
             BinOpKind::Mul => "*",
             BinOpKind::Div => "/",
             BinOpKind::Mod => "%",
+            BinOpKind::And => "&&",
+            BinOpKind::Or => "||",
+            BinOpKind::BitAnd => "&",
+            BinOpKind::BitOr => "|",
+            BinOpKind::BitXor => "^",
+            BinOpKind::Shl => "<<",
+            BinOpKind::Shr => ">>",
         }
     }
+
+    /// Whether this operator compares its operands, producing a `bool`.
+    pub fn is_comparison(self) -> bool {
+        matches!(
+            self,
+            BinOpKind::Equal
+                | BinOpKind::NotEqual
+                | BinOpKind::Less
+                | BinOpKind::LessEqual
+                | BinOpKind::Greater
+                | BinOpKind::GreaterEqual
+        )
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -87,7 +115,7 @@ impl Hash for TotalF64 {
     }
 }
 
-#[derive(PartialEq, Eq, Debug, Hash)]
+#[derive(PartialEq, Eq, Debug, Hash, Clone)]
 pub enum LiteralKind {
     Int(u128),
     String(Symbol),
@@ -117,7 +145,7 @@ impl fmt::Display for LiteralKind {
     }
 }
 
-#[derive(PartialEq, Eq, Hash)]
+#[derive(PartialEq, Eq, Hash, Clone)]
 pub struct Literal {
     pub kind: LiteralKind,
 }
@@ -134,39 +162,105 @@ impl fmt::Debug for Literal {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum Else {
     ElseIf(Box<ExprIf>, Span),
     Else(Block),
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct ExprIf {
     pub expr: Box<Expr>,
     pub block: Block,
     pub else_: Option<Else>,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct ExprWhile {
     pub while_: Span,
     pub expr: Box<Expr>,
     pub block: Block,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+/// `for binding in start..end { block }`
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct ExprFor {
+    pub for_: Span,
+    pub id: Id,
+    pub binding: Ident,
+    pub start: Box<Expr>,
+    /// Id of the hidden local that holds the value of `end`.
+    pub end_id: Id,
+    pub end: Box<Expr>,
+    pub block: Block,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum ExprKind {
     BinOp(BinOpKind, Box<Expr>, Box<Expr>),
     UnOp(UnOpKind, Box<Expr>),
+    /// `expr as ty`
+    Cast(Box<Expr>, Ty),
     Group(Box<Expr>, Span),
     Literal(Literal),
     Ident(Symbol),
+    /// `_`, only valid on the left-hand side of an assignment
+    Underscore,
     Block(Block),
-    Return(Box<Expr>, Span),
+    /// `return value`, or a bare `return` which returns `unit`. The span is
+    /// the `return` keyword.
+    Return(Option<Box<Expr>>, Span),
     Assignment { lhs: Box<Expr>, rhs: Box<Expr> },
+    /// `lhs op= rhs`
+    AssignOp(BinOpKind, Box<Expr>, Box<Expr>),
     If(ExprIf),
     While(ExprWhile),
+    For(ExprFor),
     Call { callee: Box<Expr>, args: Vec<Expr> },
+    /// `[a, b, c]`
+    Array(Vec<Expr>),
+    /// `(a, b)`, or `(a,)` with a single element
+    Tuple(Vec<Expr>),
+    /// `array[index]`
+    Index(Box<Expr>, Box<Expr>),
+    /// `Name { field: value, ... }`
+    Struct { name: Ident, fields: Vec<ExprField> },
+    /// `expr.field`, or `expr.0` for the elements of a tuple
+    Field(Box<Expr>, Ident),
+    /// `Enum::Variant`
+    Path(Ident, Ident),
+    Match(ExprMatch),
+    Closure(ExprClosure),
+}
+
+/// `|args| body`, or `|args| -> ty { ... }`
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct ExprClosure {
+    /// Id of the function the closure is lowered to.
+    pub id: Id,
+    pub args: Vec<FnArg>,
+    pub ret: Option<Ty>,
+    pub body: Box<Expr>,
+}
+
+/// `match expr { pat => body, ... }`
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct ExprMatch {
+    pub expr: Box<Expr>,
+    pub arms: Vec<Arm>,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Arm {
+    pub pat: Pat,
+    pub body: Expr,
+}
+
+/// `field: value` in a struct literal.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct ExprField {
+    pub name: Ident,
+    pub value: Expr,
 }
 
 impl fmt::Debug for Expr {
@@ -180,15 +274,27 @@ impl ExprKind {
         match self {
             ExprKind::BinOp(_, _, _) => false,
             ExprKind::UnOp(_, _) => false,
+            ExprKind::Cast(_, _) => false,
             ExprKind::Literal(_) => false,
             ExprKind::Ident(_) => false,
+            ExprKind::Underscore => false,
             ExprKind::Assignment { .. } => false,
+            ExprKind::AssignOp(..) => false,
             ExprKind::Call { .. } => false,
+            ExprKind::Array(_) => false,
+            ExprKind::Tuple(_) => false,
+            ExprKind::Index(_, _) => false,
+            ExprKind::Struct { .. } => false,
+            ExprKind::Field(_, _) => false,
+            ExprKind::Path(_, _) => false,
             ExprKind::Group(_, _) => false,
             ExprKind::Return(_, _) => false,
+            ExprKind::Closure(_) => false,
             ExprKind::Block(_) => true,
             ExprKind::If(_) => true,
             ExprKind::While { .. } => true,
+            ExprKind::For(_) => true,
+            ExprKind::Match(_) => true,
         }
     }
 }
@@ -0,0 +1,136 @@
+use std::fmt;
+
+use super::{Block, Ty, Tree};
+use crate::lex::Ident;
+use crate::sym::{self, Symbol};
+use crate::{Id, Span};
+
+#[derive(PartialEq, Eq, Hash, Clone)]
+pub struct Item {
+    pub kind: ItemKind,
+    /// The attributes in front of the item.
+    pub attrs: Vec<Attr>,
+    pub span: Span,
+}
+
+impl fmt::Debug for Item {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Item").field("kind", &self.kind).finish()
+    }
+}
+
+/// `#[name]` or `#[name(args)]` in front of an item or a statement. The
+/// parser only keeps the attributes it knows about.
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct Attr {
+    pub name: Ident,
+    pub args: Vec<AttrArg>,
+    pub span: Span,
+}
+
+/// `name` or `name = "value"`
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub struct AttrArg {
+    pub name: Ident,
+    pub value: Option<Symbol>,
+}
+
+impl Attr {
+    /// Whether the code under the attribute is compiled: `#[cfg(name)]` and
+    /// `#[cfg(name = "value")]` are enabled by `--cfg name` and
+    /// `--cfg name=value`, every other attribute always is.
+    pub fn is_enabled(&self, enabled: &[String]) -> bool {
+        if self.name.symbol != sym::cfg {
+            return true;
+        }
+        let [arg] = &self.args[..] else { return false };
+        let name = arg.name.symbol.get_str();
+        let value = arg.value.as_ref().map(Symbol::get_str);
+        enabled.iter().any(|cfg| match cfg.split_once('=') {
+            Some((n, v)) => n == name && value == Some(v.trim_matches('"')),
+            None => cfg == name && value.is_none(),
+        })
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct ItemFn {
+    pub name: Ident,
+    pub id: Id,
+    pub args: Vec<FnArg>,
+    pub ret: Ty,
+    pub body: Block,
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct FnArg {
+    pub id: Id,
+    pub name: Ident,
+    pub ty: Ty,
+}
+
+/// `struct Name { field: ty, ... }`
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct ItemStruct {
+    pub name: Ident,
+    pub id: Id,
+    pub fields: Vec<FieldDef>,
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct FieldDef {
+    pub name: Ident,
+    pub ty: Ty,
+}
+
+/// `enum Name { Variant, Variant(ty, ...), ... }`
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct ItemEnum {
+    pub name: Ident,
+    pub id: Id,
+    pub variants: Vec<Variant>,
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct Variant {
+    pub name: Ident,
+    pub fields: Vec<Ty>,
+}
+
+#[derive(PartialEq, Eq, Hash, Clone)]
+pub enum ItemKind {
+    Fn(ItemFn),
+    Struct(ItemStruct),
+    Enum(ItemEnum),
+    Mod { name: Ident, tree: Tree },
+}
+
+impl fmt::Debug for ItemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fn(ItemFn { name, id: _, args, ret, body }) => {
+                write!(f, "fn {name}(")?;
+                for FnArg { name, ty, id: _ } in args {
+                    write!(f, "{name}: {ty:?},")?;
+                }
+                write!(f, ") -> {ret:?} ")?;
+                body.fmt(f)
+            }
+            Self::Struct(ItemStruct { name, id: _, fields }) => {
+                write!(f, "struct {name} {{ ")?;
+                for FieldDef { name, ty } in fields {
+                    write!(f, "{name}: {ty:?}, ")?;
+                }
+                write!(f, "}} ")
+            }
+            Self::Enum(ItemEnum { name, id: _, variants }) => {
+                write!(f, "enum {name} {{ ")?;
+                for Variant { name, fields } in variants {
+                    write!(f, "{name}{fields:?}, ")?;
+                }
+                write!(f, "}} ")
+            }
+            Self::Mod { name, tree } => write!(f, "mod {name} {{ {tree:?} }} ")
+        }
+    }
+}
@@ -12,6 +12,9 @@ pub use item::*;
 mod ty;
 pub use ty::*;
 
+mod pat;
+pub use pat::*;
+
 #[derive(PartialEq, Eq, Clone, Debug, Hash)]
 pub struct Tree {
     pub items: Rc<[Item]>,
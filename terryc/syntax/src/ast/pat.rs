@@ -0,0 +1,24 @@
+use super::Literal;
+use crate::lex::Ident;
+use crate::{Id, Span};
+
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub struct Pat {
+    pub kind: PatKind,
+    pub span: Span,
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub enum PatKind {
+    /// `_`
+    Wild,
+    /// A name, bound to the matched value.
+    Binding { id: Id, name: Ident },
+    Literal(Literal),
+    /// `Enum::Variant(pat, ...)`
+    Variant {
+        enum_: Ident,
+        variant: Ident,
+        fields: Vec<Pat>,
+    },
+}
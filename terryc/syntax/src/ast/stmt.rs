@@ -1,15 +1,19 @@
 use std::fmt;
 
-use super::{Expr, Item, ItemFn, ItemKind, Ty, TyKind};
+use super::{Attr, Expr, Item, Ty};
 use crate::lex::Ident;
 use crate::{Id, Span};
 
-#[derive(PartialEq, Eq, Hash)]
+#[derive(PartialEq, Eq, Hash, Clone)]
 pub struct Stmt {
     pub kind: StmtKind,
+    /// The attributes in front of the statement.
+    pub attrs: Vec<Attr>,
+    /// Does not include the semicolon after the statement.
+    pub span: Span,
 }
 
-#[derive(PartialEq, Eq, Hash)]
+#[derive(PartialEq, Eq, Hash, Clone)]
 pub struct Block {
     pub stmts: Vec<Stmt>,
     /// Optional trailing expression
@@ -23,11 +27,13 @@ pub struct Function {
     pub body: Block,
 }
 
-#[derive(PartialEq, Eq, Hash)]
+#[derive(PartialEq, Eq, Hash, Clone)]
 pub enum StmtKind {
     Expr(Expr),
     Let {
         id: Id,
+        /// Declared with `let mut`, so it can be assigned to.
+        mutable: bool,
         user_ty: Option<Ty>,
         name: Ident,
         value: Option<Expr>,
@@ -41,11 +47,12 @@ impl fmt::Debug for StmtKind {
             StmtKind::Expr(expr) => expr.fmt(f),
             StmtKind::Let {
                 name,
+                mutable,
                 user_ty,
                 value,
                 id: _,
             } => {
-                write!(f, "let {name}")?;
+                write!(f, "let {}{name}", if *mutable { "mut " } else { "" })?;
                 if let Some(Ty { kind, .. }) = user_ty {
                     write!(f, ": {kind}")?;
                 }
@@ -55,7 +62,7 @@ impl fmt::Debug for StmtKind {
                 Ok(())
             }
             StmtKind::Item(Item {
-                kind,
+                kind, ..
             }) => kind.fmt(f)
         }
     }
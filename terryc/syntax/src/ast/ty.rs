@@ -0,0 +1,249 @@
+use std::hash::{Hash, Hasher};
+use std::{fmt, mem, ptr};
+
+use crate::sym::Symbol;
+use crate::{Id, Interners, Span};
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Ty {
+    pub kind: TyKind,
+    pub span: Span,
+}
+
+impl fmt::Debug for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.kind.fmt(f)
+    }
+}
+
+impl fmt::Display for TyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TyKind::Bool => f.write_str("bool"),
+            TyKind::F32 => f.write_str("f32"),
+            TyKind::I32 => f.write_str("i32"),
+            TyKind::Unit => f.write_str("unit"),
+            TyKind::Never => f.write_str("!"),
+            TyKind::String => f.write_str("string"),
+            TyKind::Array(elem, len) => write!(f, "[{elem}; {len}]"),
+            TyKind::Tuple([elem]) => write!(f, "({elem},)"),
+            TyKind::Tuple(elems) => {
+                f.write_str("(")?;
+                for (i, elem) in elems.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{elem}")?;
+                }
+                f.write_str(")")
+            }
+            TyKind::Named(name) => write!(f, "{name}"),
+            TyKind::Struct(def) => write!(f, "{}", def.name),
+            TyKind::Enum(def) => write!(f, "{}", def.name),
+        }
+    }
+}
+
+/// Types are compared by the address of their parts, which are interned.
+#[derive(Debug, Clone, Copy)]
+pub enum TyKind {
+    I32,
+    F32,
+    Unit,
+    /// The type of `return`, which has no value. It fits wherever another
+    /// type is expected, so `if c { return 1; } else { 2 }` is an `i32`.
+    Never,
+    Bool,
+    String,
+    /// `[elem; len]`
+    Array(&'static TyKind, u32),
+    /// `(a, b)`, or `(a,)` with a single element.
+    Tuple(&'static [TyKind]),
+    /// A type written as a name, before it is resolved to a struct when
+    /// lowering to HIR.
+    Named(Symbol),
+    Struct(&'static StructDef),
+    Enum(&'static EnumDef),
+}
+
+impl PartialEq for TyKind {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (TyKind::Array(a, a_len), TyKind::Array(b, b_len)) => {
+                ptr::eq(*a, *b) && a_len == b_len
+            }
+            (TyKind::Tuple(a), TyKind::Tuple(b)) => ptr::eq(*a, *b),
+            (TyKind::Named(a), TyKind::Named(b)) => a == b,
+            (TyKind::Struct(a), TyKind::Struct(b)) => ptr::eq(*a, *b),
+            (TyKind::Enum(a), TyKind::Enum(b)) => ptr::eq(*a, *b),
+            _ => mem::discriminant(self) == mem::discriminant(other),
+        }
+    }
+}
+
+impl Eq for TyKind {}
+
+impl Hash for TyKind {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+            TyKind::Array(elem, len) => {
+                ptr::hash(*elem, state);
+                len.hash(state);
+            }
+            TyKind::Tuple(elems) => ptr::hash(*elems, state),
+            TyKind::Named(name) => name.hash(state),
+            TyKind::Struct(def) => ptr::hash(*def, state),
+            TyKind::Enum(def) => ptr::hash(*def, state),
+            TyKind::I32
+            | TyKind::F32
+            | TyKind::Unit
+            | TyKind::Never
+            | TyKind::Bool
+            | TyKind::String => {}
+        }
+    }
+}
+
+/// A struct declaration, with its fields in declaration order.
+#[derive(PartialEq, Eq, Hash)]
+pub struct StructDef {
+    pub id: Id,
+    pub name: Symbol,
+    pub fields: Vec<(Symbol, TyKind)>,
+}
+
+impl StructDef {
+    pub fn intern(self) -> &'static StructDef {
+        Interners::get().structs.alloc(self)
+    }
+
+    /// The index and type of the field called `name`.
+    pub fn field(&self, name: Symbol) -> Option<(u32, TyKind)> {
+        let index = self.fields.iter().position(|&(field, _)| field == name)?;
+        Some((index as u32, self.fields[index].1))
+    }
+}
+
+impl fmt::Debug for StructDef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// An enum declaration, with its variants in declaration order. The index of
+/// a variant is its discriminant.
+#[derive(PartialEq, Eq, Hash)]
+pub struct EnumDef {
+    pub id: Id,
+    pub name: Symbol,
+    pub variants: Vec<VariantDef>,
+}
+
+#[derive(PartialEq, Eq, Hash, Debug)]
+pub struct VariantDef {
+    pub name: Symbol,
+    pub fields: Vec<TyKind>,
+}
+
+impl EnumDef {
+    pub fn intern(self) -> &'static EnumDef {
+        Interners::get().enums.alloc(self)
+    }
+
+    /// The index of the variant called `name`.
+    pub fn variant(&self, name: Symbol) -> Option<u32> {
+        let index = self.variants.iter().position(|variant| variant.name == name)?;
+        Some(index as u32)
+    }
+}
+
+impl fmt::Debug for EnumDef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl TyKind {
+    /// `[elem; len]`, with `elem` interned.
+    pub fn array(elem: TyKind, len: u32) -> TyKind {
+        TyKind::Array(Interners::get().types.alloc(elem), len)
+    }
+
+    /// `(elems..)`, with `elems` interned.
+    pub fn tuple(elems: impl IntoIterator<Item = TyKind>) -> TyKind {
+        TyKind::Tuple(Interners::get().types.alloc_slice(elems))
+    }
+
+    /// The index and type of the field called `name`, which is a number like
+    /// `0` for tuples.
+    pub fn field(self, name: Symbol) -> Option<(u32, TyKind)> {
+        match self {
+            TyKind::Struct(def) => def.field(name),
+            TyKind::Tuple(elems) => {
+                let index: u32 = name.get_str().parse().ok()?;
+                Some((index, *elems.get(index as usize)?))
+            }
+            _ => None,
+        }
+    }
+
+    /// The type of the field at `index` of a struct or a tuple.
+    pub fn field_ty(self, index: u32) -> TyKind {
+        match self {
+            TyKind::Struct(def) => def.fields[index as usize].1,
+            TyKind::Tuple(elems) => elems[index as usize],
+            _ => unreachable!("field of a non-struct"),
+        }
+    }
+
+    /// Whether values of this type take no memory, which is the case for
+    /// `unit` and `!`.
+    pub fn is_zero_sized(self) -> bool {
+        matches!(self, TyKind::Unit | TyKind::Never)
+    }
+
+    /// The type of the elements if this is an array.
+    pub fn element(self) -> Option<TyKind> {
+        match self {
+            TyKind::Array(elem, _) => Some(*elem),
+            _ => None,
+        }
+    }
+
+    /// How `println` prints values of this type, as a `printf` format.
+    /// Booleans are printed as the strings `true` and `false`, tuples like
+    /// `(1, true)` and arrays like `[1, 2]` with a conversion for every
+    /// element, and structs like `Point { x: 1, y: 2 }`. Enums are printed as
+    /// the name of their variant, if none of their variants have fields.
+    /// Returns `None` if values of this type cannot be printed.
+    pub fn print_format(self) -> Option<String> {
+        match self {
+            TyKind::I32 => Some("%d".into()),
+            TyKind::F32 => Some("%f".into()),
+            TyKind::String | TyKind::Bool => Some("%s".into()),
+            TyKind::Tuple([elem]) => Some(format!("({},)", elem.print_format()?)),
+            TyKind::Tuple(elems) => {
+                let elems: Option<Vec<_>> = elems.iter().map(|elem| elem.print_format()).collect();
+                Some(format!("({})", elems?.join(", ")))
+            }
+            TyKind::Array(elem, len) => {
+                let elem = elem.print_format()?;
+                Some(format!("[{}]", vec![elem; len as usize].join(", ")))
+            }
+            TyKind::Struct(def) if def.fields.is_empty() => Some(def.name.to_string()),
+            TyKind::Struct(def) => {
+                let fields: Option<Vec<_>> = def
+                    .fields
+                    .iter()
+                    .map(|(name, ty)| Some(format!("{name}: {}", ty.print_format()?)))
+                    .collect();
+                Some(format!("{} {{ {} }}", def.name, fields?.join(", ")))
+            }
+            TyKind::Enum(def) if def.variants.iter().all(|v| v.fields.is_empty()) => {
+                Some("%s".into())
+            }
+            TyKind::Unit | TyKind::Never | TyKind::Named(_) | TyKind::Enum(_) => None,
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::cell::RefCell;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::{fmt, ptr};
+
+use rustc_hash::FxHashSet;
+
+/// An arena that allocates every distinct value once. Values from the same
+/// arena are equal exactly if they are at the same address, so they can be
+/// compared and hashed like pointers, with [`Interned`].
+pub struct UniqueArena<T: 'static> {
+    arena: typed_arena::Arena<T>,
+    values: RefCell<FxHashSet<&'static T>>,
+    slices: RefCell<FxHashSet<&'static [T]>>,
+}
+
+impl<T> Default for UniqueArena<T> {
+    fn default() -> Self {
+        Self {
+            arena: Default::default(),
+            values: Default::default(),
+            slices: Default::default(),
+        }
+    }
+}
+
+impl<T: Eq + Hash> UniqueArena<T> {
+    /// `value`, or the value equal to it that was allocated before.
+    pub fn alloc(&'static self, value: T) -> &'static T {
+        if let Some(&interned) = self.values.borrow().get(&value) {
+            return interned;
+        }
+        let interned = &*self.arena.alloc(value);
+        self.values.borrow_mut().insert(interned);
+        interned
+    }
+
+    /// Like [`UniqueArena::alloc`], for a list of values.
+    pub fn alloc_slice(&'static self, values: impl IntoIterator<Item = T>) -> &'static [T] {
+        let values: Vec<T> = values.into_iter().collect();
+        if let Some(&interned) = self.slices.borrow().get(&*values) {
+            return interned;
+        }
+        let interned = &*self.arena.alloc_extend(values);
+        self.slices.borrow_mut().insert(interned);
+        interned
+    }
+
+    /// Like [`UniqueArena::alloc_slice`], compared by address.
+    pub fn intern_slice(&'static self, values: impl IntoIterator<Item = T>) -> Interned<[T]> {
+        Interned::new_unchecked(self.alloc_slice(values))
+    }
+}
+
+/// A value from a [`UniqueArena`], compared and hashed by its address.
+pub struct Interned<T: ?Sized + 'static>(&'static T);
+
+impl<T: ?Sized> Interned<T> {
+    /// Only values from a [`UniqueArena`] may be wrapped, otherwise equal
+    /// values would compare as different.
+    pub(crate) fn new_unchecked(value: &'static T) -> Self {
+        Interned(value)
+    }
+}
+
+impl<T: ?Sized> Clone for Interned<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for Interned<T> {}
+
+impl<T: ?Sized> Deref for Interned<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.0
+    }
+}
+
+impl<T: ?Sized> PartialEq for Interned<T> {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(self.0, other.0)
+    }
+}
+
+impl<T: ?Sized> Eq for Interned<T> {}
+
+impl<T: ?Sized> Hash for Interned<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        ptr::hash(self.0, state)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Interned<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
@@ -0,0 +1,203 @@
+use std::fmt;
+use std::hash::Hash;
+
+use crate::ast::TotalF64;
+use crate::sym::Symbol;
+use crate::{FileId, Span};
+
+#[derive(Clone, Copy)]
+pub struct Ident {
+    pub span: Span,
+    pub symbol: Symbol,
+}
+
+impl PartialEq for Ident {
+    fn eq(&self, other: &Self) -> bool {
+        self.symbol.eq(&other.symbol)
+    }
+}
+
+impl Eq for Ident {}
+
+impl fmt::Debug for Ident {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.symbol.fmt(f)
+    }
+}
+
+impl fmt::Display for Ident {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.symbol.fmt(f)
+    }
+}
+
+impl Hash for Ident {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.symbol.hash(state);
+    }
+}
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    UnexpectedCharacter(char),
+    UnterminatedString,
+    UnclosedComment,
+    InvalidFloat,
+    /// Too large for a `u128`.
+    InvalidInt,
+    /// A digit that is too large for the base of the literal, like `2` in
+    /// `0b12`.
+    InvalidDigit(u32),
+    /// `0x`, `0o` or `0b` without digits after it.
+    NoDigits,
+    /// Offsets into the file do not fit in a [`Span`](crate::Span).
+    FileTooLarge,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Hash)]
+pub enum TokenKind {
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
+    LeftBracket,
+    RightBracket,
+    Pound,
+    RArrow,
+    Comma,
+    Colon,
+    ColonColon,
+    Dot,
+    DotDot,
+    Minus,
+    MinusEq,
+    Plus,
+    PlusEq,
+    Semicolon,
+    Star,
+    StarEq,
+    Not,
+    NotEq,
+    Eq,
+    EqEq,
+    FatArrow,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+    Slash,
+    SlashEq,
+    Percent,
+    PercentEq,
+    And,
+    AndAnd,
+    Or,
+    OrOr,
+    Caret,
+    Shl,
+    Shr,
+    String(Symbol),
+    Integer(u128),
+    Decimal(TotalF64),
+    Keyword(Ident),
+    Ident(Ident),
+    Eof,
+}
+
+/// Renders the token as it is written in the source, for diagnostics.
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use TokenKind::*;
+        let text = match self {
+            LeftParen => "(",
+            RightParen => ")",
+            LeftBrace => "{",
+            RightBrace => "}",
+            LeftBracket => "[",
+            RightBracket => "]",
+            Pound => "#",
+            RArrow => "->",
+            Comma => ",",
+            Colon => ":",
+            ColonColon => "::",
+            Dot => ".",
+            DotDot => "..",
+            Minus => "-",
+            MinusEq => "-=",
+            Plus => "+",
+            PlusEq => "+=",
+            Semicolon => ";",
+            Star => "*",
+            StarEq => "*=",
+            Not => "!",
+            NotEq => "!=",
+            Eq => "=",
+            EqEq => "==",
+            FatArrow => "=>",
+            Greater => ">",
+            GreaterEq => ">=",
+            Less => "<",
+            LessEq => "<=",
+            Slash => "/",
+            SlashEq => "/=",
+            Percent => "%",
+            PercentEq => "%=",
+            And => "&",
+            AndAnd => "&&",
+            Or => "|",
+            OrOr => "||",
+            Caret => "^",
+            Shl => "<<",
+            Shr => ">>",
+            String(s) => return write!(f, "{:?}", s.get_str()),
+            Integer(n) => return n.fmt(f),
+            Decimal(x) => return x.0.fmt(f),
+            Keyword(ident) | Ident(ident) => return ident.fmt(f),
+            Eof => "<eof>",
+        };
+        f.write_str(text)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TriviaKind {
+    Whitespace,
+    LineComment,
+    BlockComment,
+}
+
+/// Source text that carries no meaning for the parser.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub span: Span,
+}
+
+/// The trivia surrounding a token. Trivia after a token up to the end of its line
+/// is trailing trivia, everything else belongs to the next token as leading trivia.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct TokenTrivia {
+    pub leading: Vec<Trivia>,
+    pub trailing: Vec<Trivia>,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+impl Token {
+    pub fn dummy() -> Self {
+        Token {
+            kind: TokenKind::Dot,
+            span: Span::new(0, 0, FileId::Main),
+        }
+    }
+}
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.kind.fmt(f)
+    }
+}
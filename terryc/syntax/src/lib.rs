@@ -0,0 +1,74 @@
+//! The tokens and the syntax tree of terry, with the spans, ids and symbols
+//! they are made of.
+//!
+//! This crate does not depend on the rest of the compiler, so formatters,
+//! linters and codemods can be built on it without pulling in salsa or LLVM.
+//! Its version follows semver: anything removed or changed in a way that
+//! breaks users bumps the minor version while it is `0.x`.
+//!
+//! Symbols and some types are interned. A thread has to [install]
+//! interners before it creates any of them; the compiler does so when its
+//! context is created. Interned types are allocated once, so they are
+//! compared by address.
+//!
+//! [install]: Interners::install
+
+use std::cell::Cell;
+use std::fmt;
+
+pub mod ast;
+mod intern;
+pub mod lex;
+pub mod sym;
+mod span;
+#[cfg(test)]
+mod tests;
+
+pub use intern::{Interned, UniqueArena};
+pub use span::{FileId, Id, IdMaker, Span};
+
+thread_local! {
+    static INTERNERS: Cell<Option<&'static Interners>> = const { Cell::new(None) };
+}
+
+/// Where symbols, struct and enum definitions and array element types live.
+pub struct Interners {
+    pub symbols: sym::Interner,
+    pub types: UniqueArena<ast::TyKind>,
+    pub structs: UniqueArena<ast::StructDef>,
+    pub enums: UniqueArena<ast::EnumDef>,
+}
+
+impl fmt::Debug for Interners {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Interners").finish_non_exhaustive()
+    }
+}
+
+impl Interners {
+    pub fn fresh() -> Self {
+        Self {
+            symbols: sym::Interner::fresh(),
+            types: Default::default(),
+            structs: Default::default(),
+            enums: Default::default(),
+        }
+    }
+
+    /// Makes these the interners of the current thread. Fails with the
+    /// installed ones if there already are some.
+    pub fn install(&'static self) -> Result<(), &'static Interners> {
+        INTERNERS.with(|cell| match cell.get() {
+            Some(installed) => Err(installed),
+            None => {
+                cell.set(Some(self));
+                Ok(())
+            }
+        })
+    }
+
+    /// The interners of the current thread.
+    pub fn get() -> &'static Interners {
+        INTERNERS.with(|cell| cell.get().expect("no interners installed on this thread"))
+    }
+}
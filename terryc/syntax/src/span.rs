@@ -0,0 +1,95 @@
+use std::fmt;
+
+/// A source file. The paths of the files are kept by whoever reads them, for
+/// the compiler that is `Context::file_path`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FileId {
+    Main,
+    Other(u32),
+}
+
+/// Identifies a definition. Ids are handed out in source order by the parser
+/// of the file that contains the definition, so they stay the same as long as
+/// the file is parsed the same way.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct Id {
+    file: FileId,
+    index: u32,
+}
+
+impl Id {
+    pub const fn file(self) -> FileId {
+        self.file
+    }
+}
+
+pub struct IdMaker {
+    file: FileId,
+    curr: u32,
+}
+
+impl IdMaker {
+    pub fn new(file: FileId) -> Self {
+        Self { file, curr: 0 }
+    }
+
+    pub fn make(&mut self) -> Id {
+        let id = Id {
+            file: self.file,
+            index: self.curr,
+        };
+        self.curr += 1;
+        id
+    }
+}
+
+impl Iterator for IdMaker {
+    type Item = Id;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.make())
+    }
+}
+
+// N.B. offsets are stored as `u32` to keep tokens small, which limits
+// source files to 4 GiB. The lexer rejects larger files.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    lo: u32,
+    hi: u32,
+    file: FileId,
+}
+
+impl Span {
+    pub fn new(lo: usize, hi: usize, file: FileId) -> Self {
+        let offset = |x| u32::try_from(x).expect("source files are smaller than 4 GiB");
+        Self {
+            lo: offset(lo),
+            hi: offset(hi),
+            file,
+        }
+    }
+
+    pub const fn lo(&self) -> usize {
+        self.lo as usize
+    }
+
+    pub const fn hi(&self) -> usize {
+        self.hi as usize
+    }
+
+    pub const fn file(&self) -> FileId {
+        self.file
+    }
+
+    pub fn to(self, other: Span) -> Span {
+        assert_eq!(self.file, other.file);
+        Span::new(self.lo().min(other.lo()), self.hi().max(other.hi()), self.file)
+    }
+}
+
+impl fmt::Debug for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (self.lo..self.hi).fmt(f)
+    }
+}
@@ -60,16 +60,22 @@ macro_rules! define_symbols {
 define_symbols! {
     Keywords {
         Struct: "struct",
+        Enum: "enum",
+        Match: "match",
         If: "if",
         Else: "else",
         Mod: "mod",
         Fn: "fn",
         Let: "let",
+        Mut: "mut",
         For: "for",
         While: "while",
         Return: "return",
         True: "true",
         False: "false",
+        In: "in",
+        As: "as",
+        Underscore: "_",
     }
 
     Symbols {
@@ -80,6 +86,13 @@ define_symbols! {
         unit, // TODO remove and replace with `()`
         bool,
         string,
+        cfg,
+        inline,
+        test,
+        always,
+        never,
+        assert,
+        bench,
     }
 }
 
@@ -89,7 +102,7 @@ pub mod kw {
 
 pub use sym_generated::*;
 
-use crate::{Context, GlobalCtxt};
+use crate::Interners;
 
 #[derive(PartialEq, Eq)]
 struct InternerInner {
@@ -139,11 +152,11 @@ pub struct Symbol(usize);
 
 impl Symbol {
     pub fn new(s: &str) -> Self {
-        GlobalCtxt::with(|gcx| gcx.interners().symbols.intern(s))
+        Interners::get().symbols.intern(s)
     }
 
     pub fn get_str(&self) -> &str {
-        GlobalCtxt::with(|gcx| gcx.interners().symbols.get_str(self))
+        Interners::get().symbols.get_str(self)
     }
 
     pub fn is_keyword(self) -> bool {
@@ -171,9 +184,12 @@ impl Interner {
     }
 
     fn intern(&self, s: &str) -> Symbol {
-        self.inner
-            .borrow_mut()
-            .intern(Box::leak(s.to_owned().into_boxed_str()))
+        let mut inner = self.inner.borrow_mut();
+        // only leak the string if it has not been interned yet.
+        if let Some(&sym) = inner.names.get(s) {
+            return sym;
+        }
+        inner.intern(Box::leak(s.to_owned().into_boxed_str()))
     }
 
     fn get_str<'a>(&self, s: &'a Symbol) -> &'a str {
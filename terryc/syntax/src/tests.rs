@@ -0,0 +1,72 @@
+use crate::ast::{EnumDef, StructDef, TyKind, VariantDef};
+use crate::sym::Symbol;
+use crate::{FileId, IdMaker, Interners};
+
+#[test]
+fn print_formats() {
+    Interners::install(Box::leak(Box::new(Interners::fresh()))).unwrap();
+    let mut ids = IdMaker::new(FileId::Main);
+
+    let point = StructDef {
+        id: ids.make(),
+        name: Symbol::new("Point"),
+        fields: vec![
+            (Symbol::new("x"), TyKind::I32),
+            (Symbol::new("y"), TyKind::F32),
+        ],
+    }
+    .intern();
+    let empty = StructDef {
+        id: ids.make(),
+        name: Symbol::new("Empty"),
+        fields: vec![],
+    }
+    .intern();
+    let variant = |name, fields| VariantDef {
+        name: Symbol::new(name),
+        fields,
+    };
+    let color = EnumDef {
+        id: ids.make(),
+        name: Symbol::new("Color"),
+        variants: vec![variant("Red", vec![]), variant("Green", vec![])],
+    }
+    .intern();
+    let option = EnumDef {
+        id: ids.make(),
+        name: Symbol::new("Option"),
+        variants: vec![variant("None", vec![]), variant("Some", vec![TyKind::I32])],
+    }
+    .intern();
+
+    for (ty, format) in [
+        (TyKind::tuple([TyKind::I32]), "(%d,)"),
+        (
+            TyKind::tuple([TyKind::tuple([TyKind::Bool, TyKind::F32]), TyKind::I32]),
+            "((%s, %f), %d)",
+        ),
+        (TyKind::array(TyKind::I32, 3), "[%d, %d, %d]"),
+        (TyKind::array(TyKind::Bool, 0), "[]"),
+        (
+            TyKind::array(TyKind::array(TyKind::I32, 1), 2),
+            "[[%d], [%d]]",
+        ),
+        (TyKind::Struct(point), "Point { x: %d, y: %f }"),
+        (TyKind::Struct(empty), "Empty"),
+        (TyKind::Enum(color), "%s"),
+        (
+            TyKind::tuple([TyKind::Enum(color), TyKind::array(TyKind::String, 1)]),
+            "(%s, [%s])",
+        ),
+    ] {
+        assert_eq!(ty.print_format().as_deref(), Some(format), "{ty:?}");
+    }
+    for ty in [
+        TyKind::Unit,
+        TyKind::Enum(option),
+        TyKind::array(TyKind::Unit, 2),
+        TyKind::tuple([TyKind::I32, TyKind::Enum(option)]),
+    ] {
+        assert_eq!(ty.print_format(), None, "{ty:?}");
+    }
+}
@@ -0,0 +1,218 @@
+//! The lexer as it was before it was rewritten to scan bytes, kept as the
+//! baseline for `cargo xtask bench`. It walks the source one `char` at a time
+//! and interns every identifier through a fresh `Symbol::new`.
+//!
+//! Only the parts that produce tokens are kept: errors are counted instead of
+//! reported, and trivia is not collected.
+
+use std::str::FromStr;
+
+use terryc_base::lex::{Ident, Token, TokenKind};
+use terryc_base::sym::Symbol;
+use terryc_base::{FileId, Span};
+use terryc_lex::unescape;
+use unicode_xid::UnicodeXID;
+
+pub struct Lexer<'a> {
+    file: FileId,
+    src: &'a str,
+    tokens: Vec<Token>,
+    start: usize,
+    current: usize,
+    has_errors: bool,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(src: &'a str, file: FileId) -> Self {
+        Self {
+            file,
+            src,
+            tokens: Vec::new(),
+            start: 0,
+            current: 0,
+            has_errors: false,
+        }
+    }
+
+    fn is_end(&self) -> bool {
+        self.current >= self.src.len()
+    }
+
+    fn char_at(&self, idx: usize) -> Option<char> {
+        self.src.split_at(idx).1.chars().next()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.char_at(self.current)
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.current += c.map_or(0, char::len_utf8);
+        c
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() != Some(c) {
+            return false;
+        }
+        self.current += c.len_utf8();
+        true
+    }
+
+    fn string(&mut self) -> Option<TokenKind> {
+        while let Some(c) = self.peek() {
+            match c {
+                '\\' => {
+                    self.advance();
+                } // escape will ignore one or more chars.
+                '"' => break,
+                _ => {}
+            }
+            self.advance();
+        }
+
+        if self.is_end() {
+            self.has_errors = true;
+            return None;
+        }
+
+        self.advance();
+
+        let s = &self.src[self.start + 1..self.current - 1];
+        unescape::unescape(s, Span::new(self.start + 1, self.current - 1, self.file))
+            .ok()
+            .as_deref()
+            .map(Symbol::new)
+            .map(TokenKind::String)
+    }
+
+    fn number(&mut self) -> Option<TokenKind> {
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.advance();
+        }
+
+        let s = &self.src[self.start..self.current];
+        let Ok(num) = u128::from_str(s) else {
+            self.has_errors = true;
+            return None;
+        };
+        Some(TokenKind::Integer(num))
+    }
+
+    fn identifier(&mut self) -> TokenKind {
+        while self.peek().is_some_and(|c| c.is_xid_continue()) {
+            self.advance();
+        }
+
+        let s = &self.src[self.start..self.current];
+        let symbol = Symbol::new(s);
+        let span = Span::new(self.start, self.current, self.file);
+        if symbol.is_keyword() {
+            TokenKind::Keyword(Ident { symbol, span })
+        } else {
+            TokenKind::Ident(Ident { symbol, span })
+        }
+    }
+
+    fn scan_token(&mut self) -> Option<TokenKind> {
+        use TokenKind::*;
+
+        let c = self.advance()?;
+
+        let kind = match c {
+            '(' => LeftParen,
+            ')' => RightParen,
+            '{' => LeftBrace,
+            '}' => RightBrace,
+            ',' => Comma,
+            '.' if self.eat('.') => DotDot,
+            '.' => Dot,
+            '-' if self.eat('>') => RArrow,
+            '-' if self.eat('=') => MinusEq,
+            '-' => Minus,
+            '+' if self.eat('=') => PlusEq,
+            '+' => Plus,
+            ';' => Semicolon,
+            '*' if self.eat('=') => StarEq,
+            '*' => Star,
+            ':' => Colon,
+            '!' if self.eat('=') => NotEq,
+            '!' => Not,
+            '=' if self.eat('=') => EqEq,
+            '=' => Eq,
+            '<' if self.eat('=') => LessEq,
+            '<' => Less,
+            '>' if self.eat('=') => GreaterEq,
+            '>' => Greater,
+            '%' if self.eat('=') => PercentEq,
+            '%' => Percent,
+
+            '/' if self.eat('/') => {
+                while self.peek().is_some_and(|c| c != '\n') {
+                    self.advance();
+                }
+                return None;
+            }
+
+            '/' if self.eat('*') => {
+                let mut nest = 1;
+
+                while nest > 0 {
+                    if self.is_end() {
+                        self.has_errors = true;
+                        return None;
+                    }
+                    while let Some(c) = self.peek() {
+                        self.advance();
+                        if c == '/' && self.eat('*') {
+                            nest += 1;
+                        } else if c == '*' && self.eat('/') {
+                            nest -= 1;
+                            break;
+                        }
+                    }
+                }
+
+                return None;
+            }
+
+            '/' if self.eat('=') => SlashEq,
+            '/' => Slash,
+
+            // ignore whitespace.
+            ' ' | '\r' | '\t' | '\n' => return None,
+
+            '"' => return self.string(),
+
+            c if c.is_ascii_digit() => return self.number(),
+            c if c.is_xid_start() => self.identifier(),
+
+            _ => {
+                self.has_errors = true;
+                return None;
+            }
+        };
+
+        Some(kind)
+    }
+
+    /// The tokens of the source, or `None` if it has errors.
+    pub fn scan_tokens(mut self) -> Option<Vec<Token>> {
+        while !self.is_end() {
+            self.start = self.current;
+            let Some(kind) = self.scan_token() else {
+                continue;
+            };
+            let span = Span::new(self.start, self.current, self.file);
+            self.tokens.push(Token { kind, span });
+        }
+
+        self.tokens.push(Token {
+            kind: TokenKind::Eof,
+            span: Span::new(self.current, self.current, self.file),
+        });
+
+        (!self.has_errors).then_some(self.tokens)
+    }
+}
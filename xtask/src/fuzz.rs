@@ -0,0 +1,354 @@
+//! Differential testing: generates random well-typed programs and checks
+//! that they print the same output when compiled with and without `-O`.
+//!
+//! Generated programs never divide by zero or index out of bounds, and their
+//! loops are short, so any difference is a miscompilation by one of the
+//! pipelines. Failing programs are written to `fuzz-failures/<seed>.terry`.
+
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{build_terryc, Result};
+
+/// How many programs to try when no count is given.
+const DEFAULT_RUNS: u64 = 100;
+const FAILURES_DIR: &str = "fuzz-failures";
+
+/// `cargo xtask fuzz [runs] [seed]`
+pub fn fuzz(mut args: impl Iterator<Item = String>) -> Result {
+    let runs = args.next().map_or(Ok(DEFAULT_RUNS), |runs| runs.parse())?;
+    let seed = match args.next() {
+        Some(seed) => seed.parse()?,
+        None => SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos() as u64,
+    };
+    let terryc = build_terryc()?;
+
+    let mut failures = 0;
+    for seed in seed..seed + runs {
+        let program = Generator::new(seed).program();
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("fuzz.terry");
+        fs::write(&file, &program)?;
+        let unoptimized = run(&terryc, &file, &[])?;
+        let optimized = run(&terryc, &file, &["-O"])?;
+        if unoptimized != optimized {
+            failures += 1;
+            fs::create_dir_all(FAILURES_DIR)?;
+            let path = Path::new(FAILURES_DIR).join(format!("{seed}.terry"));
+            fs::write(&path, &program)?;
+            eprintln!("seed {seed}: output differs with `-O`, see {path:?}");
+            eprintln!("without `-O`:\n{unoptimized}\nwith `-O`:\n{optimized}");
+        }
+        print!(".");
+    }
+    println!();
+    if failures > 0 {
+        return Err(format!("{failures} of {runs} programs behaved differently").into());
+    }
+    println!("{runs} programs behaved the same (seeds {seed}..{})", seed + runs);
+    Ok(())
+}
+
+/// Compiles and runs `file`, returning what it printed and how it exited.
+fn run(terryc: &Path, file: &Path, flags: &[&str]) -> Result<String> {
+    let dir = file.parent().expect("the program is in a temporary directory");
+    let compile = Command::new(terryc)
+        .args(["--use-ascii", "--dont-print-path"])
+        .args(flags)
+        .arg(file)
+        .current_dir(dir)
+        .output()?;
+    if !compile.status.success() {
+        let stderr = String::from_utf8_lossy(&compile.stderr);
+        let program = fs::read_to_string(file)?;
+        return Err(format!("generated an invalid program:\n{program}\n{stderr}").into());
+    }
+    let Output { status, stdout, .. } = Command::new(dir.join("out")).output()?;
+    Ok(format!("{}{status}", String::from_utf8_lossy(&stdout)))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Ty {
+    I32,
+    Bool,
+}
+
+struct Function {
+    name: String,
+    args: usize,
+}
+
+struct Generator {
+    /// xorshift64 state, never zero.
+    state: u64,
+    /// The variables in scope, innermost scope last, and whether they can be
+    /// assigned to. Arguments cannot, and neither can loop counters, so that
+    /// every loop ends.
+    scopes: Vec<Vec<(String, Ty, bool)>>,
+    /// Arrays of `i32` in scope, with their lengths.
+    arrays: Vec<Vec<(String, u32)>>,
+    functions: Vec<Function>,
+    next_var: usize,
+    depth: usize,
+}
+
+impl Generator {
+    fn new(seed: u64) -> Self {
+        Generator {
+            state: seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1,
+            scopes: vec![],
+            arrays: vec![],
+            functions: vec![],
+            next_var: 0,
+            depth: 0,
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// A number in `0..n`.
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.below(100) < percent
+    }
+
+    fn program(mut self) -> String {
+        let mut out = String::new();
+        for i in 0..self.below(4) {
+            let args = self.below(3) as usize;
+            let function = self.function(&format!("f{i}"), args);
+            out.push_str(&function);
+            self.functions.push(Function { name: format!("f{i}"), args });
+        }
+        out.push_str("fn main() -> unit {\n");
+        self.scopes.push(vec![]);
+        self.arrays.push(vec![]);
+        for _ in 0..8 + self.below(8) {
+            self.stmt(&mut out, 1);
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn function(&mut self, name: &str, args: usize) -> String {
+        let params: Vec<_> = (0..args).map(|i| format!("a{i}")).collect();
+        self.scopes = vec![params.iter().map(|p| (p.clone(), Ty::I32, false)).collect()];
+        self.arrays = vec![vec![]];
+        let mut out = String::new();
+        let params: Vec<_> = params.iter().map(|p| format!("{p}: i32")).collect();
+        writeln!(out, "fn {name}({}) -> i32 {{", params.join(", ")).unwrap();
+        for _ in 0..self.below(4) {
+            self.stmt(&mut out, 1);
+        }
+        // a value in parentheses after an `if` or `for` would be parsed as a
+        // call of it, so the value gets a name first.
+        let value = self.expr(Ty::I32);
+        writeln!(out, "    let ret = {value};\n    ret\n}}").unwrap();
+        self.scopes.clear();
+        self.arrays.clear();
+        out
+    }
+
+    fn fresh(&mut self, prefix: &str) -> String {
+        self.next_var += 1;
+        format!("{prefix}{}", self.next_var)
+    }
+
+    fn ty(&mut self) -> Ty {
+        if self.chance(70) { Ty::I32 } else { Ty::Bool }
+    }
+
+    fn stmt(&mut self, out: &mut String, indent: usize) {
+        let pad = "    ".repeat(indent);
+        // nested blocks get fewer statements, so programs stay small.
+        let nested = indent < 3;
+        match self.below(10) {
+            0..=2 => {
+                let ty = self.ty();
+                let value = self.expr(ty);
+                let name = self.fresh("v");
+                let mutable = self.chance(50);
+                let mut_ = if mutable { "mut " } else { "" };
+                writeln!(out, "{pad}let {mut_}{name} = {value};").unwrap();
+                self.scopes.last_mut().unwrap().push((name, ty, mutable));
+            }
+            3 => {
+                let len = 1 + self.below(4) as u32;
+                let elems: Vec<_> = (0..len).map(|_| self.expr(Ty::I32)).collect();
+                let name = self.fresh("arr");
+                writeln!(out, "{pad}let {name} = [{}];", elems.join(", ")).unwrap();
+                self.arrays.last_mut().unwrap().push((name, len));
+            }
+            4 if self.scopes.iter().flatten().any(|&(_, _, assignable)| assignable) => {
+                let (name, ty) = self.pick_var(None, true).unwrap();
+                let value = self.expr(ty);
+                writeln!(out, "{pad}{name} = {value};").unwrap();
+            }
+            5 if nested => {
+                let cond = self.expr(Ty::Bool);
+                writeln!(out, "{pad}if {cond} {{").unwrap();
+                self.block(out, indent + 1);
+                if self.chance(50) {
+                    writeln!(out, "{pad}}} else {{").unwrap();
+                    self.block(out, indent + 1);
+                }
+                writeln!(out, "{pad}}}").unwrap();
+            }
+            6 if nested => {
+                let var = self.fresh("i");
+                let end = self.below(5);
+                writeln!(out, "{pad}for {var} in 0..{end} {{").unwrap();
+                self.scopes.push(vec![(var, Ty::I32, false)]);
+                self.arrays.push(vec![]);
+                for _ in 0..1 + self.below(3) {
+                    self.stmt(out, indent + 1);
+                }
+                self.scopes.pop();
+                self.arrays.pop();
+                writeln!(out, "{pad}}}").unwrap();
+            }
+            _ => {
+                let ty = self.ty();
+                let value = self.expr(ty);
+                writeln!(out, "{pad}println({value});").unwrap();
+            }
+        }
+    }
+
+    fn block(&mut self, out: &mut String, indent: usize) {
+        self.scopes.push(vec![]);
+        self.arrays.push(vec![]);
+        for _ in 0..1 + self.below(3) {
+            self.stmt(out, indent);
+        }
+        self.scopes.pop();
+        self.arrays.pop();
+    }
+
+    /// A variable of type `ty`, or of any type for `None`.
+    fn pick_var(&mut self, ty: Option<Ty>, assign: bool) -> Option<(String, Ty)> {
+        let vars: Vec<_> = self
+            .scopes
+            .iter()
+            .flatten()
+            .filter(|&&(_, var_ty, assignable)| {
+                (ty.is_none() || ty == Some(var_ty)) && (assignable || !assign)
+            })
+            .map(|(name, ty, _)| (name.clone(), *ty))
+            .collect();
+        if vars.is_empty() {
+            return None;
+        }
+        let index = self.below(vars.len() as u64) as usize;
+        Some(vars[index].clone())
+    }
+
+    fn expr(&mut self, ty: Ty) -> String {
+        self.depth += 1;
+        // deep expressions only get leaves, so that generation ends.
+        let choice = if self.depth > 3 { self.below(2) } else { self.below(10) };
+        let expr = match ty {
+            Ty::I32 => self.int_expr(choice),
+            Ty::Bool => self.bool_expr(choice),
+        };
+        self.depth -= 1;
+        expr
+    }
+
+    /// A number below 100, written in any base the lexer accepts.
+    fn int_literal(&mut self) -> String {
+        let value = self.below(100);
+        match self.below(4) {
+            0 => format!("{value:#x}"),
+            1 => format!("{value:#o}"),
+            2 => format!("{value:#b}"),
+            _ => value.to_string(),
+        }
+    }
+
+    fn int_expr(&mut self, choice: u64) -> String {
+        match choice {
+            0 => self.int_literal(),
+            1 => match self.pick_var(Some(Ty::I32), false) {
+                Some((name, _)) => name,
+                None => self.below(100).to_string(),
+            },
+            2 | 3 => {
+                // shift amounts are masked, so shifting too far is not an error.
+                let ops = ["+", "-", "*", "&", "|", "^", "<<", ">>"];
+                let op = ops[self.below(ops.len() as u64) as usize];
+                format!("({} {op} {})", self.expr(Ty::I32), self.expr(Ty::I32))
+            }
+            // dividing by a positive constant can neither trap nor overflow.
+            4 => {
+                let op = ["/", "%"][self.below(2) as usize];
+                format!("({} {op} {})", self.expr(Ty::I32), 1 + self.below(9))
+            }
+            5 => format!("(-{})", self.expr(Ty::I32)),
+            6 if !self.functions.is_empty() => {
+                let index = self.below(self.functions.len() as u64) as usize;
+                let args = self.functions[index].args;
+                let args: Vec<_> = (0..args).map(|_| self.expr(Ty::I32)).collect();
+                format!("{}({})", self.functions[index].name, args.join(", "))
+            }
+            7 => {
+                let cond = self.expr(Ty::Bool);
+                let then = self.expr(Ty::I32);
+                let else_ = self.expr(Ty::I32);
+                format!("if {cond} {{ {then} }} else {{ {else_} }}")
+            }
+            8 => {
+                let scrutinee = self.expr(Ty::I32);
+                let arms: Vec<_> = (0..1 + self.below(3))
+                    .map(|value| format!("{value} => {}", self.expr(Ty::I32)))
+                    .collect();
+                let rest = self.expr(Ty::I32);
+                format!("match {scrutinee} {{ {}, _ => {rest} }}", arms.join(", "))
+            }
+            _ => {
+                let arrays: Vec<_> = self.arrays.iter().flatten().cloned().collect();
+                if arrays.is_empty() {
+                    return self.below(100).to_string();
+                }
+                let (name, len) = &arrays[self.below(arrays.len() as u64) as usize];
+                format!("{name}[{}]", self.below(u64::from(*len)))
+            }
+        }
+    }
+
+    fn bool_expr(&mut self, choice: u64) -> String {
+        match choice {
+            0 => ["true", "false"][self.below(2) as usize].into(),
+            1 => match self.pick_var(Some(Ty::Bool), false) {
+                Some((name, _)) => name,
+                None => "true".into(),
+            },
+            2..=5 => {
+                let op = ["<", "<=", ">", ">=", "==", "!="][self.below(6) as usize];
+                format!("({} {op} {})", self.expr(Ty::I32), self.expr(Ty::I32))
+            }
+            6 => format!("(!{})", self.expr(Ty::Bool)),
+            7 => {
+                let op = ["==", "&&", "||"][self.below(3) as usize];
+                format!("({} {op} {})", self.expr(Ty::Bool), self.expr(Ty::Bool))
+            }
+            _ => {
+                let cond = self.expr(Ty::Bool);
+                let then = self.expr(Ty::Bool);
+                let else_ = self.expr(Ty::Bool);
+                format!("if {cond} {{ {then} }} else {{ {else_} }}")
+            }
+        }
+    }
+}
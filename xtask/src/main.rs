@@ -2,23 +2,34 @@
 use std::env::{self, args, current_dir};
 use std::error::Error;
 use std::ffi::OsStr;
-use std::fs::{self, remove_file};
+use std::fs;
+use std::hint::black_box;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::{Duration, Instant};
+
+use terryc_base::render::ColorChoice;
+use terryc_base::{FileId, GlobalCtxt, Mode, Options};
+use terryc_lex::Lexer;
+
+mod char_lexer;
+mod fuzz;
 
 type Result<T = (), E = Box<dyn Error>> = std::result::Result<T, E>;
 
 fn main() -> Result {
     match args().nth(1).as_deref() {
         Some("test") => test(),
+        Some("bench") => bench(),
+        Some("fuzz") => fuzz::fuzz(args().skip(2)),
         Some(cmd) => panic!("invalid command: {cmd}"),
         None => panic!("no subcommand given"),
     }
 }
 
-fn test() -> Result {
-    let cargo = cargo();
-    Command::new(&cargo)
+/// Builds `terryc` in release mode, returning the path of the binary.
+fn build_terryc() -> Result<PathBuf> {
+    Command::new(cargo())
         .arg("build")
         .arg("--release")
         .arg("-p")
@@ -29,11 +40,13 @@ fn test() -> Result {
     let terryc = current_dir()?.join("target/release/terryc");
 
     assert!(terryc.exists());
+    Ok(terryc)
+}
+
+fn test() -> Result {
+    let terryc = build_terryc()?;
 
     for file in walkdir::WalkDir::new("uitests") {
-        if Path::new("./out").exists() {
-            remove_file("./out")?;
-        }
         let file = file?;
         if !file.file_type().is_file() {
             continue;
@@ -64,18 +77,13 @@ fn test() -> Result {
             }
             Ok("gen")
         })()?;
+        // the artifacts and the manifest go into the tempdir, not the repo.
         let dir = tempfile::tempdir()?;
         let mut cmd = Command::new(&terryc);
         cmd.args(["--use-ascii", "--dont-print-path"]);
-        if run {
-            cmd.arg(path.canonicalize()?);
-        } else {
-            cmd.arg(path);
-        }
+        cmd.arg(path.canonicalize()?);
         cmd.args(["-m", mode]);
-        if run {
-            cmd.current_dir(&dir);
-        }
+        cmd.current_dir(&dir);
         println!("{:?}", dir.path());
 
         let output = cmd.output()?;
@@ -102,8 +110,9 @@ fn test() -> Result {
                 panic!();
             }
         }
-        if run && Path::new("./out").exists() {
-            let output = Command::new("./out").output()?;
+        let exe = dir.path().join("out");
+        if run && exe.exists() {
+            let output = Command::new(exe).output()?;
             output.status.exit_ok()?;
             if !output.stdout.is_empty() {
                 let output_str = String::from_utf8_lossy(&output.stdout);
@@ -129,6 +138,85 @@ fn test() -> Result {
     Ok(())
 }
 
+/// Measures the throughput of the lexer on a generated file of a few megabytes,
+/// next to the char-based lexer it replaced.
+fn bench() -> Result {
+    if cfg!(debug_assertions) {
+        // timings of a debug build are meaningless.
+        Command::new(cargo())
+            .args(["run", "--release", "-p", "xtask", "--", "bench"])
+            .status()?
+            .exit_ok()?;
+        return Ok(());
+    }
+
+    const SNIPPET: &str = r#"// sums up some numbers
+fn compute_總和(x: i32, y: i32) -> i32 {
+    /* a /* nested */ comment */
+    let total = x * 31 + y % 7;
+    if total >= 1000 {
+        println("a \"big\" number\n");
+    }
+    total
+}
+"#;
+    let src = SNIPPET.repeat(8 * 1024 * 1024 / SNIPPET.len());
+
+    GlobalCtxt::create_and_then(
+        Options {
+            use_ascii: true,
+            color: ColorChoice::Never,
+            dont_print_path: true,
+            path: "bench.terry".into(),
+            mode: Mode::Gen,
+            optimize: false,
+            keep_bounds_checks: false,
+            coverage: false,
+            locale: None,
+            teaching_diagnostics: false,
+            lib: false,
+            test: false,
+            cfg: vec![],
+            externs: vec![],
+        },
+        |gcx| gcx,
+    );
+
+    let tokens = Lexer::new(&src, FileId::Main)
+        .scan_tokens()
+        .map_err(|_| "failed to lex the benchmark input")?;
+    if char_lexer::Lexer::new(&src, FileId::Main).scan_tokens() != Some(tokens) {
+        return Err("the old and the new lexer disagree on the benchmark input".into());
+    }
+
+    let runs = 10;
+    let old = best_of(runs, || {
+        char_lexer::Lexer::new(&src, FileId::Main).scan_tokens()
+    });
+    let new = best_of(runs, || Lexer::new(&src, FileId::Main).scan_tokens());
+
+    let mib = src.len() as f64 / (1024.0 * 1024.0);
+    for (name, time) in [("char lexer", old), ("lexer", new)] {
+        println!(
+            "{name}: {mib:.1} MiB in {time:?} (best of {runs} runs), {:.1} MiB/s",
+            mib / time.as_secs_f64()
+        );
+    }
+    println!("speedup: {:.2}x", old.as_secs_f64() / new.as_secs_f64());
+
+    Ok(())
+}
+
+fn best_of<T>(runs: usize, mut f: impl FnMut() -> T) -> Duration {
+    let mut best = Duration::MAX;
+    for _ in 0..runs {
+        let start = Instant::now();
+        black_box(f());
+        best = best.min(start.elapsed());
+    }
+    best
+}
+
 fn cargo() -> PathBuf {
     env::var("CARGO")
         .as_deref()